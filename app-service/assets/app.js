@@ -10,6 +10,7 @@ logoutLink.addEventListener("click", (e) => {
     fetch(url, {
         method: 'POST',
         credentials: 'include', // This will include cookies in the request
+        headers: { 'X-CSRF-Token': getCookie('csrf_token') },
     }).then(response => {
         if (response.ok) {
             loginLink.style.display = "block";
@@ -41,4 +42,12 @@ logoutLink.addEventListener("click", (e) => {
             protectImg.src = "/assets/default.jpg";
         }
     });
-})();
\ No newline at end of file
+})();
+
+// The auth service only accepts cookie-authenticated requests that repeat its CSRF cookie in a header
+function getCookie(name) {
+    const cookie = document.cookie
+        .split('; ')
+        .find(cookie => cookie.startsWith(name + '='));
+    return cookie ? decodeURIComponent(cookie.substring(name.length + 1)) : '';
+}
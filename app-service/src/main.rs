@@ -1,22 +1,19 @@
 use std::env;
 
 use askama::Template;
+use auth_middleware::{AuthLayer, AuthenticatedUser, RemoteValidator, TokenValidator};
 use axum::{
-    http::StatusCode,
     response::{Html, IntoResponse},
     routing::get,
     Json, Router,
 };
-use axum_extra::extract::CookieJar;
+use secrecy::Secret;
 use serde::Serialize;
 use tower_http::services::ServeDir;
 
 #[tokio::main]
 async fn main() {
-    let app = Router::new()
-        .nest_service("/assets", ServeDir::new("assets"))
-        .route("/", get(root))
-        .route("/protected", get(protected));
+    let app = app(token_validator());
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:8000").await.unwrap();
 
@@ -24,6 +21,31 @@ async fn main() {
     axum::serve(listener, app).await.unwrap();
 }
 
+fn app(validator: impl TokenValidator + 'static) -> Router {
+    Router::new()
+        .route("/protected", get(protected))
+        .route_layer(AuthLayer::new(validator))
+        .nest_service("/assets", ServeDir::new("assets"))
+        .route("/", get(root))
+}
+
+// Tokens are introspected by the auth service, so logouts take effect immediately
+fn token_validator() -> RemoteValidator {
+    let auth_hostname = env::var("AUTH_SERVICE_HOST_NAME").unwrap_or("0.0.0.0".to_owned());
+    let validator = RemoteValidator::new(format!("http://{}:3000/verify-token", auth_hostname));
+
+    // The auth service can be configured to only verify tokens for known clients
+    match (
+        env::var("AUTH_SERVICE_CLIENT_ID"),
+        env::var("AUTH_SERVICE_CLIENT_SECRET"),
+    ) {
+        (Ok(client_id), Ok(client_secret)) if !client_id.is_empty() => {
+            validator.with_client_credentials(client_id, Secret::new(client_secret))
+        }
+        _ => validator,
+    }
+}
+
 #[derive(Template)]
 #[template(path = "index.html")]
 struct IndexTemplate {
@@ -46,43 +68,60 @@ async fn root() -> impl IntoResponse {
     Html(template.render().unwrap())
 }
 
-async fn protected(jar: CookieJar) -> impl IntoResponse {
-    let jwt_cookie = match jar.get("jwt") {
-        Some(cookie) => cookie,
-        None => {
-            return StatusCode::UNAUTHORIZED.into_response();
-        }
-    };
+async fn protected(_user: AuthenticatedUser) -> impl IntoResponse {
+    Json(ProtectedRouteResponse {
+        img_url: "https://i.ibb.co/YP90j68/Light-Live-Bootcamp-Certificate.png".to_owned(),
+    })
+}
 
-    let api_client = reqwest::Client::builder().build().unwrap();
+#[derive(Serialize)]
+pub struct ProtectedRouteResponse {
+    pub img_url: String,
+}
 
-    let verify_token_body = serde_json::json!({
-        "token": &jwt_cookie.value(),
-    });
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{header, Request, StatusCode},
+        routing::post,
+    };
+    use tower::ServiceExt;
+    use super::*;
 
-    let auth_hostname = env::var("AUTH_SERVICE_HOST_NAME").unwrap_or("0.0.0.0".to_owned());
-    let url = format!("http://{}:3000/verify-token", auth_hostname);
+    // Stands in for the auth service, introspecting every token as one a user granted to
+    // an OAuth client
+    async fn spawn_auth_service() -> String {
+        let router = Router::new().route(
+            "/verify-token",
+            post(|| async {
+                Json(serde_json::json!({
+                    "active": true,
+                    "sub": "test@example.com",
+                    "scope": "openid email",
+                    "client_id": "client",
+                }))
+            }),
+        );
 
-    let response = match api_client.post(&url).json(&verify_token_body).send().await {
-        Ok(response) => response,
-        Err(_) => {
-            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
-        }
-    };
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
 
-    match response.status() {
-        reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::BAD_REQUEST => {
-            StatusCode::UNAUTHORIZED.into_response()
-        }
-        reqwest::StatusCode::OK => Json(ProtectedRouteResponse {
-            img_url: "https://i.ibb.co/YP90j68/Light-Live-Bootcamp-Certificate.png".to_owned(),
-        })
-        .into_response(),
-        _ => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        format!("http://{}/verify-token", address)
     }
-}
 
-#[derive(Serialize)]
-pub struct ProtectedRouteResponse {
-    pub img_url: String,
+    #[tokio::test]
+    async fn test_protected_rejects_client_tokens() {
+        let validator = RemoteValidator::new(spawn_auth_service().await);
+
+        let request = Request::builder()
+            .uri("/protected")
+            .header(header::AUTHORIZATION, "Bearer client-token")
+            .body(Body::empty())
+            .unwrap();
+        let response = app(validator).oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
 }
@@ -0,0 +1,68 @@
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum AuthError {
+    #[error("Missing auth token")]
+    MissingToken,
+    #[error("Invalid auth token")]
+    InvalidToken,
+    #[error("Missing required role: {0}")]
+    MissingRole(String),
+    // The user has to authenticate again, as their last authentication is older than the
+    // allowed number of seconds
+    #[error("Reauthentication required")]
+    ReauthenticationRequired(u64),
+    #[error("Unexpected error: {0}")]
+    UnexpectedError(String),
+}
+
+impl PartialEq for AuthError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::MissingToken, Self::MissingToken)
+                | (Self::InvalidToken, Self::InvalidToken)
+                | (Self::MissingRole(_), Self::MissingRole(_))
+                | (Self::ReauthenticationRequired(_), Self::ReauthenticationRequired(_))
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+// Responses use the same `{"error": ...}` body as the auth service. Requests without a token
+// get a 401 with a bearer challenge (RFC 6750), so clients know to authenticate.
+impl IntoResponse for AuthError {
+    fn into_response(self) -> Response {
+        let (status, error_message) = match &self {
+            AuthError::MissingToken => (StatusCode::UNAUTHORIZED, "Missing auth token"),
+            AuthError::InvalidToken => (StatusCode::UNAUTHORIZED, "Invalid auth token"),
+            AuthError::MissingRole(_) => (StatusCode::FORBIDDEN, "Insufficient permissions"),
+            AuthError::ReauthenticationRequired(_) => (StatusCode::UNAUTHORIZED, "Reauthentication required"),
+            AuthError::UnexpectedError(_) => {
+                tracing::error!("{}", self);
+                (StatusCode::INTERNAL_SERVER_ERROR, "Unexpected error")
+            }
+        };
+
+        let body = Json(serde_json::json!({
+            "error": error_message,
+        }));
+
+        let challenge = match self {
+            AuthError::MissingToken => "Bearer".to_owned(),
+            // Tells clients how recent the authentication has to be (RFC 9470)
+            AuthError::ReauthenticationRequired(max_age) => format!(
+                "Bearer error=\"insufficient_user_authentication\", max_age={}",
+                max_age
+            ),
+            _ => return (status, body).into_response(),
+        };
+
+        (status, [(header::WWW_AUTHENTICATE, challenge)], body).into_response()
+    }
+}
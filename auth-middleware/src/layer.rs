@@ -0,0 +1,279 @@
+use std::{
+    convert::Infallible,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use axum::{
+    extract::Request,
+    http::HeaderMap,
+    response::{IntoResponse, Response},
+};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{extract_token, AuthError, AuthenticatedUser, TokenValidator, DEFAULT_COOKIE_NAME};
+
+// Rejects requests without a valid token and passes the caller on to handlers
+// as an `AuthenticatedUser`. Apply it with `Router::route_layer`, so requests to
+// unknown routes still get a 404.
+#[derive(Clone)]
+pub struct AuthLayer {
+    validator: Arc<dyn TokenValidator>,
+    cookie_name: String,
+    required_roles: Vec<String>,
+    max_auth_age_seconds: Option<u64>,
+    accepted_client_scopes: Vec<String>,
+}
+
+impl AuthLayer {
+    pub fn new(validator: impl TokenValidator + 'static) -> Self {
+        Self {
+            validator: Arc::new(validator),
+            cookie_name: DEFAULT_COOKIE_NAME.to_owned(),
+            required_roles: vec![],
+            max_auth_age_seconds: None,
+            accepted_client_scopes: vec![],
+        }
+    }
+
+    pub fn with_cookie_name(mut self, cookie_name: impl Into<String>) -> Self {
+        self.cookie_name = cookie_name.into();
+        self
+    }
+
+    // Only let users through that were granted the role. Can be called more than once
+    // to require several roles.
+    pub fn require_role(mut self, role: impl Into<String>) -> Self {
+        self.required_roles.push(role.into());
+        self
+    }
+
+    // Only let users through that authenticated within the last `max_age_seconds`, for
+    // sensitive routes where a stolen token shouldn't be enough
+    pub fn require_recent_auth(mut self, max_age_seconds: u64) -> Self {
+        self.max_auth_age_seconds = Some(max_age_seconds);
+        self
+    }
+
+    // Also let through tokens issued to clients, such as service tokens and the access tokens
+    // of OAuth clients, when they were granted the scope. Without it, only tokens users got
+    // for themselves are accepted.
+    pub fn accept_client_scope(mut self, scope: impl Into<String>) -> Self {
+        self.accepted_client_scopes.push(scope.into());
+        self
+    }
+
+    async fn authenticate(&self, headers: &HeaderMap) -> Result<AuthenticatedUser, AuthError> {
+        let token = extract_token(headers, &self.cookie_name).ok_or(AuthError::MissingToken)?;
+        let user = self.validator.validate(&token).await?;
+
+        if user.client_id.is_some() && !self.accepted_client_scopes.iter().any(|scope| user.has_scope(scope)) {
+            return Err(AuthError::InvalidToken);
+        }
+
+        if let Some(role) = self.required_roles.iter().find(|role| !user.has_role(role)) {
+            return Err(AuthError::MissingRole(role.clone()));
+        }
+
+        if let Some(max_age) = self.max_auth_age_seconds {
+            if !user.authenticated_within(max_age) {
+                return Err(AuthError::ReauthenticationRequired(max_age));
+            }
+        }
+
+        Ok(user)
+    }
+}
+
+impl<S> Layer<S> for AuthLayer {
+    type Service = AuthService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AuthService {
+            inner,
+            auth: self.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct AuthService<S> {
+    inner: S,
+    auth: AuthLayer,
+}
+
+impl<S> Service<Request> for AuthService<S>
+where
+    S: Service<Request, Response = Response, Error = Infallible> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Response, Infallible>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request) -> Self::Future {
+        // Take the service that was driven to readiness and leave a fresh clone behind
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let auth = self.auth.clone();
+
+        Box::pin(async move {
+            match auth.authenticate(request.headers()).await {
+                Ok(user) => {
+                    request.extensions_mut().insert(user);
+                    inner.call(request).await
+                }
+                Err(e) => Ok(e.into_response()),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::{header, StatusCode}, routing::get, Router};
+    use secrecy::{ExposeSecret, Secret};
+    use tower::ServiceExt;
+    use super::*;
+
+    // Accepts "admin" and "user" tokens, granting a role of the same name. "stale" tokens
+    // belong to a user who authenticated an hour ago, "client" tokens were issued to an
+    // OAuth client with the "openid" scope.
+    struct StubValidator;
+
+    #[async_trait::async_trait]
+    impl TokenValidator for StubValidator {
+        async fn validate(&self, token: &Secret<String>) -> Result<AuthenticatedUser, AuthError> {
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs() as usize;
+
+            let (role, auth_time, client_id) = match token.expose_secret().as_str() {
+                role @ ("admin" | "user") => (role, now, None),
+                "stale" => ("user", now - 3600, None),
+                "client" => ("user", now, Some("client".to_owned())),
+                _ => return Err(AuthError::InvalidToken),
+            };
+
+            Ok(AuthenticatedUser {
+                subject: format!("{}@example.com", role),
+                roles: vec![role.to_owned()],
+                scopes: vec!["openid".to_owned()],
+                token_id: None,
+                session_id: None,
+                expires_at: None,
+                client_id,
+                auth_time: Some(auth_time),
+                amr: vec!["pwd".to_owned()],
+            })
+        }
+    }
+
+    fn router(layer: AuthLayer) -> Router {
+        Router::new()
+            .route("/", get(|user: AuthenticatedUser| async move { user.subject }))
+            .route_layer(layer)
+    }
+
+    async fn status(router: Router, authorization: Option<&str>) -> StatusCode {
+        let mut request = Request::builder().uri("/");
+        if let Some(value) = authorization {
+            request = request.header(header::AUTHORIZATION, value);
+        }
+        let response = router
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        response.status()
+    }
+
+    #[tokio::test]
+    async fn test_auth_layer() {
+        let router = router(AuthLayer::new(StubValidator));
+
+        assert_eq!(status(router.clone(), None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(router.clone(), Some("Bearer invalid")).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(router, Some("Bearer user")).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_auth_layer_challenges_requests_without_token() {
+        let router = router(AuthLayer::new(StubValidator));
+
+        let request = Request::builder().uri("/").body(Body::empty()).unwrap();
+        let response = router.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(response.headers()[header::WWW_AUTHENTICATE], "Bearer");
+    }
+
+    #[tokio::test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "AuthLayer is not applied to the route")]
+    async fn test_extractor_without_auth_layer() {
+        let router = Router::new().route("/", get(|user: AuthenticatedUser| async move { user.subject }));
+
+        let request = Request::builder().uri("/").body(Body::empty()).unwrap();
+        router.oneshot(request).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_auth_layer_with_required_role() {
+        let router = router(AuthLayer::new(StubValidator).require_role("admin"));
+
+        assert_eq!(status(router.clone(), Some("Bearer user")).await, StatusCode::FORBIDDEN);
+        assert_eq!(status(router, Some("Bearer admin")).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_auth_layer_with_recent_auth() {
+        let router = router(AuthLayer::new(StubValidator).require_recent_auth(300));
+
+        let request = Request::builder()
+            .uri("/")
+            .header(header::AUTHORIZATION, "Bearer stale")
+            .body(Body::empty())
+            .unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            response.headers()[header::WWW_AUTHENTICATE],
+            "Bearer error=\"insufficient_user_authentication\", max_age=300"
+        );
+        assert_eq!(status(router, Some("Bearer user")).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_auth_layer_with_client_tokens() {
+        let rejected = router(AuthLayer::new(StubValidator));
+        assert_eq!(status(rejected, Some("Bearer client")).await, StatusCode::UNAUTHORIZED);
+
+        let other_scope = router(AuthLayer::new(StubValidator).accept_client_scope("profile"));
+        assert_eq!(status(other_scope, Some("Bearer client")).await, StatusCode::UNAUTHORIZED);
+
+        let accepted = router(AuthLayer::new(StubValidator).accept_client_scope("openid"));
+        assert_eq!(status(accepted, Some("Bearer client")).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_auth_layer_reads_cookie() {
+        let router = router(AuthLayer::new(StubValidator).with_cookie_name("token"));
+
+        let request = Request::builder()
+            .uri("/")
+            .header(header::COOKIE, "token=user")
+            .body(Body::empty())
+            .unwrap();
+        let response = router.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,27 @@
+// Guards axum routes with the tokens issued by the auth service.
+//
+// `AuthLayer` reads the token of each request, validates it locally or against the
+// auth service and checks the required roles. Handlers behind it receive the caller
+// through the `AuthenticatedUser` extractor:
+//
+//     let router = Router::new()
+//         .route("/protected", get(protected))
+//         .route_layer(AuthLayer::new(RemoteValidator::new(verify_token_url)));
+//
+//     async fn protected(user: AuthenticatedUser) -> impl IntoResponse { ... }
+
+mod error;
+mod layer;
+mod token;
+mod user;
+mod validator;
+
+// Re-export items from submodules
+pub use error::*;
+pub use layer::*;
+pub use token::*;
+pub use user::*;
+pub use validator::*;
+
+// Name of the cookie the auth service stores the JWT auth token in
+pub const DEFAULT_COOKIE_NAME: &str = "jwt";
@@ -0,0 +1,66 @@
+use axum::http::{header, HeaderMap};
+use axum_extra::extract::CookieJar;
+use secrecy::Secret;
+
+// Read the auth token of a request. A bearer token in the Authorization header
+// takes precedence over the auth cookie, so a stale cookie can't shadow the token
+// a client sends explicitly.
+pub fn extract_token(headers: &HeaderMap, cookie_name: &str) -> Option<Secret<String>> {
+    bearer_token(headers).or_else(|| {
+        CookieJar::from_headers(headers)
+            .get(cookie_name)
+            .map(|cookie| Secret::new(cookie.value().to_owned()))
+    })
+}
+
+// Read the token of an `Authorization: Bearer <token>` header. Other schemes are ignored.
+pub fn bearer_token(headers: &HeaderMap) -> Option<Secret<String>> {
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+
+    if !scheme.eq_ignore_ascii_case("Bearer") {
+        return None;
+    }
+
+    Some(Secret::new(token.trim().to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::ExposeSecret;
+    use super::*;
+
+    #[test]
+    fn test_extract_token_prefers_bearer_header() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::COOKIE, "jwt=cookie_token".parse().unwrap());
+
+        let token = extract_token(&headers, "jwt").unwrap();
+        assert_eq!(token.expose_secret(), "cookie_token");
+
+        headers.insert(header::AUTHORIZATION, "Bearer header_token".parse().unwrap());
+        let token = extract_token(&headers, "jwt").unwrap();
+        assert_eq!(token.expose_secret(), "header_token");
+    }
+
+    #[test]
+    fn test_extract_token_uses_cookie_name() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::COOKIE, "session=cookie_token".parse().unwrap());
+
+        assert!(extract_token(&headers, "jwt").is_none());
+        assert!(extract_token(&headers, "session").is_some());
+    }
+
+    #[test]
+    fn test_bearer_token_ignores_other_schemes() {
+        let mut headers = HeaderMap::new();
+        assert!(bearer_token(&headers).is_none());
+
+        headers.insert(header::AUTHORIZATION, "Basic dXNlcjpwYXNz".parse().unwrap());
+        assert!(bearer_token(&headers).is_none());
+
+        headers.insert(header::AUTHORIZATION, "bearer token".parse().unwrap());
+        assert_eq!(bearer_token(&headers).unwrap().expose_secret(), "token");
+    }
+}
@@ -0,0 +1,122 @@
+use axum::{async_trait, extract::FromRequestParts, http::request::Parts};
+use serde::{Deserialize, Serialize};
+
+use crate::AuthError;
+
+// The caller of a request that passed `AuthLayer`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuthenticatedUser {
+    // Email address of the user, or the client id of a service, from the `sub` claim
+    pub subject: String,
+    pub roles: Vec<String>,
+    pub scopes: Vec<String>,
+    // The `jti` claim, used to revoke this particular token
+    pub token_id: Option<String>,
+    // The `sid` claim. Only known when tokens are validated by the auth service itself.
+    pub session_id: Option<String>,
+    pub expires_at: Option<usize>,
+    // The client the token was issued to, from the `client_id` claim of service tokens or
+    // the `azp` claim of tokens users granted an OAuth client. `AuthLayer` rejects these
+    // tokens unless a client scope is accepted.
+    pub client_id: Option<String>,
+    // The `auth_time` claim, when the user last proved who they are
+    #[serde(default)]
+    pub auth_time: Option<usize>,
+    // The `amr` claim, how the user proved it, e.g. `pwd` or `otp`
+    #[serde(default)]
+    pub amr: Vec<String>,
+}
+
+impl AuthenticatedUser {
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.iter().any(|r| r == role)
+    }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|s| s == scope)
+    }
+
+    // Whether the caller is a backend service rather than a user
+    pub fn is_service(&self) -> bool {
+        self.client_id.as_ref().is_some_and(|client_id| *client_id == self.subject)
+    }
+
+    // Whether the user proved who they are within the last `max_age_seconds`. Callers
+    // whose tokens don't say when never did.
+    pub fn authenticated_within(&self, max_age_seconds: u64) -> bool {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+
+        self.auth_time
+            .is_some_and(|auth_time| now.saturating_sub(auth_time as u64) <= max_age_seconds)
+    }
+}
+
+// Split a space-delimited `scope` claim
+pub(crate) fn parse_scopes(scope: Option<&str>) -> Vec<String> {
+    scope
+        .map(|scope| scope.split_whitespace().map(str::to_owned).collect())
+        .unwrap_or_default()
+}
+
+// The user is put in the request extensions by `AuthLayer`, so the extractor
+// only works on routes the layer is applied to. Using it on any other route is a bug:
+// debug builds panic, release builds answer with a 500 rather than let the request through.
+#[async_trait]
+impl<S> FromRequestParts<S> for AuthenticatedUser
+where
+    S: Send + Sync,
+{
+    type Rejection = AuthError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<AuthenticatedUser>()
+            .cloned()
+            .ok_or_else(|| {
+                if cfg!(debug_assertions) {
+                    panic!("AuthLayer is not applied to the route");
+                }
+                AuthError::UnexpectedError("AuthLayer is not applied to the route".to_owned())
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_authenticated_within() {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as usize;
+
+        let user = |auth_time| AuthenticatedUser {
+            subject: "test@example.com".to_owned(),
+            roles: vec![],
+            scopes: vec![],
+            token_id: None,
+            session_id: None,
+            expires_at: None,
+            client_id: None,
+            auth_time,
+            amr: vec![],
+        };
+
+        assert!(user(Some(now - 60)).authenticated_within(300));
+        assert!(!user(Some(now - 600)).authenticated_within(300));
+        assert!(!user(None).authenticated_within(300));
+    }
+
+    #[test]
+    fn test_parse_scopes() {
+        assert_eq!(parse_scopes(Some("read write")), vec!["read", "write"]);
+        assert!(parse_scopes(Some("")).is_empty());
+        assert!(parse_scopes(None).is_empty());
+    }
+}
@@ -32,6 +32,16 @@ signupLoginLink.addEventListener("click", (e) => {
 
 // -----------------------------------------------------
 
+// Users sent here by an application signing them in are sent back to finish authorizing it
+function continueAuthorization() {
+    const returnTo = new URLSearchParams(window.location.search).get("return_to");
+    if (returnTo !== null && returnTo.startsWith("/authorize?")) {
+        window.location.href = returnTo;
+        return true;
+    }
+    return false;
+}
+
 const loginForm = document.getElementById("login-form");
 const loginButton = document.getElementById("login-form-submit");
 const loginErrAlter = document.getElementById("login-err-alert");
@@ -66,6 +76,9 @@ loginButton.addEventListener("click", (e) => {
             loginForm.email.value = "";
             loginForm.password.value = "";
             loginErrAlter.style.display = "none";
+            if (continueAuthorization()) {
+                return;
+            }
             alert("You have successfully logged in.");
         } else {
             response.json().then(data => {
@@ -145,6 +158,9 @@ TwoFAButton.addEventListener("click", (e) => {
             TwoFAForm.email_code.value = "";
             TwoFAForm.login_attempt_id.value = "";
             TwoFAErrAlter.style.display = "none";
+            if (continueAuthorization()) {
+                return;
+            }
             alert("You have successfully logged in.");
             loginSection.style.display = "block";
             twoFASection.style.display = "none";
@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS api_keys(
+   id TEXT NOT NULL PRIMARY KEY,
+   email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE,
+   name TEXT NOT NULL,
+   key_hash TEXT NOT NULL UNIQUE,
+   scopes TEXT[] NOT NULL DEFAULT '{}',
+   created_at BIGINT NOT NULL,
+   expires_at BIGINT,
+   last_used_at BIGINT
+);
+
+CREATE INDEX IF NOT EXISTS api_keys_email_idx ON api_keys(email);
\ No newline at end of file
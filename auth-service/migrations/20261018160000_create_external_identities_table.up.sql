@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS external_identities(
+   provider TEXT NOT NULL,
+   subject TEXT NOT NULL,
+   email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE,
+   PRIMARY KEY (provider, subject)
+);
\ No newline at end of file
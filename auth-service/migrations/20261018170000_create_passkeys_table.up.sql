@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS passkeys(
+   id TEXT NOT NULL PRIMARY KEY,
+   email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE,
+   name TEXT NOT NULL,
+   public_key BYTEA NOT NULL,
+   sign_count BIGINT NOT NULL DEFAULT 0,
+   created_at BIGINT NOT NULL,
+   last_used_at BIGINT
+);
+
+CREATE INDEX IF NOT EXISTS passkeys_email_idx ON passkeys(email);
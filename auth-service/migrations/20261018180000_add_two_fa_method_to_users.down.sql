@@ -0,0 +1 @@
+ALTER TABLE users DROP COLUMN IF EXISTS two_fa_method;
\ No newline at end of file
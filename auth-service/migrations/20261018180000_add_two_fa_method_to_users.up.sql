@@ -0,0 +1 @@
+ALTER TABLE users ADD COLUMN IF NOT EXISTS two_fa_method TEXT NOT NULL DEFAULT 'email';
\ No newline at end of file
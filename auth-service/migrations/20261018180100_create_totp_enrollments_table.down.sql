@@ -0,0 +1 @@
+DROP TABLE IF EXISTS totp_enrollments;
\ No newline at end of file
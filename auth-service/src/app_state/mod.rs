@@ -1,34 +1,200 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 use tokio::sync::RwLock;
 
-use crate::domain::{data_stores::{BannedTokenStore, TwoFACodeStore, UserStore}, EmailClient};
+use crate::domain::{
+    data_stores::{
+        ApiKeyStore, AuthorizationCodeStore, BannedTokenStore, ExternalIdentityStore,
+        MagicLinkStore, OAuthClientStore, OidcLoginStore, PasskeyChallengeStore, PasskeyStore,
+        PhoneVerificationStore, RecoveryCodeStore, RefreshTokenStore, SessionStore,
+        SessionVersionStore, TotpStore, TrustedDeviceStore, TwoFACodeStore, UserStore,
+    },
+    ClientCredentials, EmailClient, SmsClient,
+};
+use crate::services::{
+    data_stores::{
+        HashmapApiKeyStore, HashmapAuthorizationCodeStore, HashmapExternalIdentityStore,
+        HashmapMagicLinkStore, HashmapOAuthClientStore, HashmapOidcLoginStore,
+        HashmapPasskeyChallengeStore, HashmapPasskeyStore, HashmapPhoneVerificationStore,
+        HashmapRecoveryCodeStore, HashmapTotpStore, HashmapTrustedDeviceStore, MockSmsClient,
+    },
+    oidc_client::OidcClient,
+};
 
 // Using a type alias to improve readability!
 pub type UserStoreType = Arc<RwLock<dyn UserStore + Send + Sync>>;
 pub type BannedTokenStoreType = Arc<RwLock<dyn BannedTokenStore + Send + Sync>>;
+pub type RefreshTokenStoreType = Arc<RwLock<dyn RefreshTokenStore + Send + Sync>>;
+pub type SessionStoreType = Arc<RwLock<dyn SessionStore + Send + Sync>>;
+pub type SessionVersionStoreType = Arc<RwLock<dyn SessionVersionStore + Send + Sync>>;
 pub type TwoFACodeStoreType = Arc<RwLock<dyn TwoFACodeStore + Send + Sync>>;
+pub type OAuthClientStoreType = Arc<RwLock<dyn OAuthClientStore + Send + Sync>>;
+pub type AuthorizationCodeStoreType = Arc<RwLock<dyn AuthorizationCodeStore + Send + Sync>>;
+pub type ApiKeyStoreType = Arc<RwLock<dyn ApiKeyStore + Send + Sync>>;
+pub type OidcLoginStoreType = Arc<RwLock<dyn OidcLoginStore + Send + Sync>>;
+pub type ExternalIdentityStoreType = Arc<RwLock<dyn ExternalIdentityStore + Send + Sync>>;
+pub type PasskeyStoreType = Arc<RwLock<dyn PasskeyStore + Send + Sync>>;
+pub type PasskeyChallengeStoreType = Arc<RwLock<dyn PasskeyChallengeStore + Send + Sync>>;
+pub type TotpStoreType = Arc<RwLock<dyn TotpStore + Send + Sync>>;
+pub type RecoveryCodeStoreType = Arc<RwLock<dyn RecoveryCodeStore + Send + Sync>>;
+pub type PhoneVerificationStoreType = Arc<RwLock<dyn PhoneVerificationStore + Send + Sync>>;
+pub type MagicLinkStoreType = Arc<RwLock<dyn MagicLinkStore + Send + Sync>>;
+pub type TrustedDeviceStoreType = Arc<RwLock<dyn TrustedDeviceStore + Send + Sync>>;
 pub type EmailClientType = Arc<dyn EmailClient + Send + Sync>;
+pub type SmsClientType = Arc<dyn SmsClient + Send + Sync>;
 
 #[derive(Clone)]
 pub struct AppState {
     pub user_store: UserStoreType,
     pub banned_token_store: BannedTokenStoreType,
+    pub refresh_token_store: RefreshTokenStoreType,
+    pub session_version_store: SessionVersionStoreType,
+    pub session_store: SessionStoreType,
     pub two_fa_code_store: TwoFACodeStoreType,
-    pub email_client: EmailClientType
+    pub email_client: EmailClientType,
+    // Used when acting as an OAuth 2.0 / OpenID Connect provider. In-memory unless configured.
+    pub oauth_client_store: OAuthClientStoreType,
+    pub authorization_code_store: AuthorizationCodeStoreType,
+    // Personal API keys users create for scripts. In-memory unless configured.
+    pub api_key_store: ApiKeyStoreType,
+    // External OpenID Connect providers users can log in with, by name
+    pub oidc_providers: Arc<HashMap<String, OidcClient>>,
+    pub oidc_login_store: OidcLoginStoreType,
+    pub external_identity_store: ExternalIdentityStoreType,
+    // Passkeys users log in with, and the challenges of ceremonies in progress. In-memory unless configured.
+    pub passkey_store: PasskeyStoreType,
+    pub passkey_challenge_store: PasskeyChallengeStoreType,
+    // Authenticator apps users enrolled for 2FA. In-memory unless configured.
+    pub totp_store: TotpStoreType,
+    // Hashed codes users fall back on when they lost their second factor. In-memory unless configured.
+    pub recovery_code_store: RecoveryCodeStoreType,
+    // Texts 2FA codes to users who chose SMS. Only logs them unless configured.
+    pub sms_client: SmsClientType,
+    // Phone numbers waiting for the user to confirm the texted code. In-memory unless configured.
+    pub phone_verification_store: PhoneVerificationStoreType,
+    // Login links emailed to users, until they are clicked. In-memory unless configured.
+    pub magic_link_store: MagicLinkStoreType,
+    // Browsers users chose to skip 2FA on. In-memory unless configured.
+    pub trusted_device_store: TrustedDeviceStoreType,
+    // When set, clients must authenticate with these credentials to verify tokens
+    pub introspection_client: Option<ClientCredentials>,
+    // Skip CSRF checks for requests that carry a bearer token
+    pub csrf_exempt_bearer: bool,
 }
 
 impl AppState {
     pub fn new(
         user_store: UserStoreType,
         banned_token_store: BannedTokenStoreType,
+        refresh_token_store: RefreshTokenStoreType,
+        session_version_store: SessionVersionStoreType,
+        session_store: SessionStoreType,
         two_fa_code_store: TwoFACodeStoreType,
         email_client: EmailClientType,
     ) -> Self {
         Self { 
             user_store,
             banned_token_store,
+            refresh_token_store,
+            session_version_store,
+            session_store,
             two_fa_code_store,
             email_client,
+            oauth_client_store: Arc::new(RwLock::new(HashmapOAuthClientStore::default())),
+            authorization_code_store: Arc::new(RwLock::new(HashmapAuthorizationCodeStore::default())),
+            api_key_store: Arc::new(RwLock::new(HashmapApiKeyStore::default())),
+            oidc_providers: Arc::new(HashMap::new()),
+            oidc_login_store: Arc::new(RwLock::new(HashmapOidcLoginStore::default())),
+            external_identity_store: Arc::new(RwLock::new(HashmapExternalIdentityStore::default())),
+            passkey_store: Arc::new(RwLock::new(HashmapPasskeyStore::default())),
+            passkey_challenge_store: Arc::new(RwLock::new(HashmapPasskeyChallengeStore::default())),
+            totp_store: Arc::new(RwLock::new(HashmapTotpStore::default())),
+            recovery_code_store: Arc::new(RwLock::new(HashmapRecoveryCodeStore::default())),
+            sms_client: Arc::new(MockSmsClient),
+            phone_verification_store: Arc::new(RwLock::new(HashmapPhoneVerificationStore::default())),
+            magic_link_store: Arc::new(RwLock::new(HashmapMagicLinkStore::default())),
+            trusted_device_store: Arc::new(RwLock::new(HashmapTrustedDeviceStore::default())),
+            introspection_client: None,
+            csrf_exempt_bearer: true,
         }
     }
+
+    pub fn with_oauth_stores(
+        mut self,
+        oauth_client_store: OAuthClientStoreType,
+        authorization_code_store: AuthorizationCodeStoreType,
+    ) -> Self {
+        self.oauth_client_store = oauth_client_store;
+        self.authorization_code_store = authorization_code_store;
+        self
+    }
+
+    pub fn with_api_key_store(mut self, api_key_store: ApiKeyStoreType) -> Self {
+        self.api_key_store = api_key_store;
+        self
+    }
+
+    pub fn with_oidc_provider(mut self, client: OidcClient) -> Self {
+        Arc::make_mut(&mut self.oidc_providers).insert(client.name().to_owned(), client);
+        self
+    }
+
+    pub fn with_oidc_stores(
+        mut self,
+        oidc_login_store: OidcLoginStoreType,
+        external_identity_store: ExternalIdentityStoreType,
+    ) -> Self {
+        self.oidc_login_store = oidc_login_store;
+        self.external_identity_store = external_identity_store;
+        self
+    }
+
+    pub fn with_passkey_stores(
+        mut self,
+        passkey_store: PasskeyStoreType,
+        passkey_challenge_store: PasskeyChallengeStoreType,
+    ) -> Self {
+        self.passkey_store = passkey_store;
+        self.passkey_challenge_store = passkey_challenge_store;
+        self
+    }
+
+    pub fn with_totp_store(mut self, totp_store: TotpStoreType) -> Self {
+        self.totp_store = totp_store;
+        self
+    }
+
+    pub fn with_recovery_code_store(mut self, recovery_code_store: RecoveryCodeStoreType) -> Self {
+        self.recovery_code_store = recovery_code_store;
+        self
+    }
+
+    pub fn with_sms(
+        mut self,
+        sms_client: SmsClientType,
+        phone_verification_store: PhoneVerificationStoreType,
+    ) -> Self {
+        self.sms_client = sms_client;
+        self.phone_verification_store = phone_verification_store;
+        self
+    }
+
+    pub fn with_magic_link_store(mut self, magic_link_store: MagicLinkStoreType) -> Self {
+        self.magic_link_store = magic_link_store;
+        self
+    }
+
+    pub fn with_trusted_device_store(mut self, trusted_device_store: TrustedDeviceStoreType) -> Self {
+        self.trusted_device_store = trusted_device_store;
+        self
+    }
+
+    pub fn with_introspection_client(mut self, client: ClientCredentials) -> Self {
+        self.introspection_client = Some(client);
+        self
+    }
+
+    pub fn with_csrf_exempt_bearer(mut self, exempt: bool) -> Self {
+        self.csrf_exempt_bearer = exempt;
+        self
+    }
 }
\ No newline at end of file
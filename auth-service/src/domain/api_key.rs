@@ -0,0 +1,179 @@
+use chrono::Utc;
+use color_eyre::eyre::{eyre, Result};
+use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
+
+use super::{
+    oauth::{is_scope_token, random_string},
+    Email,
+};
+
+// Tells API keys apart from JWTs, and makes leaked keys easy to spot
+pub const API_KEY_PREFIX: &str = "ak_";
+
+// Long-lived credential a user creates for scripts that can't go through the login flow.
+// Only its hash is stored, so the key itself is shown once when it is created.
+#[derive(Debug, Clone)]
+pub struct ApiKey(Secret<String>);
+
+impl PartialEq for ApiKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.expose_secret() == other.0.expose_secret()
+    }
+}
+
+impl ApiKey {
+    pub fn parse(key: Secret<String>) -> Result<Self> {
+        let is_valid = key
+            .expose_secret()
+            .strip_prefix(API_KEY_PREFIX)
+            .is_some_and(|secret| {
+                secret.len() == API_KEY_SECRET_LENGTH
+                    && secret.chars().all(|c| c.is_ascii_alphanumeric())
+            });
+
+        if is_valid {
+            Ok(Self(key))
+        } else {
+            Err(eyre!("Invalid API key"))
+        }
+    }
+
+    // Whether a bearer token looks like an API key rather than a JWT
+    pub fn is_api_key(token: &Secret<String>) -> bool {
+        token.expose_secret().starts_with(API_KEY_PREFIX)
+    }
+
+    // Unlike passwords, keys are long random strings, so a fast hash is enough to keep
+    // them safe. That lets keys be looked up by their hash on every request.
+    pub fn hash(&self) -> String {
+        format!("{:x}", Sha256::digest(self.0.expose_secret().as_bytes()))
+    }
+}
+
+impl Default for ApiKey {
+    fn default() -> Self {
+        Self(Secret::new(format!(
+            "{}{}",
+            API_KEY_PREFIX,
+            random_string(API_KEY_SECRET_LENGTH)
+        )))
+    }
+}
+
+impl AsRef<Secret<String>> for ApiKey {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.0
+    }
+}
+
+// What is known about an API key, everything but the key itself
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiKeyEntry {
+    pub id: String,
+    pub email: Email,
+    pub name: String,
+    // Requests made with the key are limited to these scopes
+    pub scopes: Vec<String>,
+    pub created_at: i64,
+    // Keys without an expiry last until they are revoked
+    pub expires_at: Option<i64>,
+    pub last_used_at: Option<i64>,
+}
+
+impl ApiKeyEntry {
+    pub fn new(
+        email: Email,
+        name: String,
+        scopes: Vec<String>,
+        expires_in: Option<i64>,
+    ) -> Result<Self> {
+        if name.trim().is_empty() {
+            return Err(eyre!("API key name must not be empty"));
+        }
+
+        if let Some(scope) = scopes.iter().find(|scope| !is_scope_token(scope)) {
+            return Err(eyre!("Invalid scope: {}", scope));
+        }
+
+        let now = Utc::now().timestamp();
+
+        let expires_at = match expires_in {
+            Some(expires_in) if expires_in > 0 => Some(now + expires_in),
+            Some(_) => return Err(eyre!("API key expiry must be in the future")),
+            None => None,
+        };
+
+        Ok(Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            email,
+            name,
+            scopes,
+            created_at: now,
+            expires_at,
+            last_used_at: None,
+        })
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= Utc::now().timestamp())
+    }
+}
+
+const API_KEY_SECRET_LENGTH: usize = 40;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn email() -> Email {
+        Email::parse(Secret::new("test@example.com".to_owned())).unwrap()
+    }
+
+    #[test]
+    fn test_generated_api_key_parses() {
+        let key = ApiKey::default();
+        assert!(ApiKey::is_api_key(key.as_ref()));
+
+        let parsed = ApiKey::parse(key.as_ref().clone()).unwrap();
+        assert_eq!(parsed, key);
+        assert_eq!(parsed.hash(), key.hash());
+        assert_ne!(ApiKey::default().hash(), key.hash());
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed_api_keys() {
+        let keys = [
+            "".to_owned(),
+            "ak_short".to_owned(),
+            format!("xx_{}", "a".repeat(API_KEY_SECRET_LENGTH)),
+            format!("ak_{}!", "a".repeat(API_KEY_SECRET_LENGTH - 1)),
+        ];
+
+        for key in keys {
+            assert!(ApiKey::parse(Secret::new(key)).is_err());
+        }
+    }
+
+    #[test]
+    fn test_api_key_entry_validation() {
+        let entry = ApiKeyEntry::new(email(), "ci".to_owned(), vec!["read".to_owned()], Some(60)).unwrap();
+        assert!(!entry.is_expired());
+        assert_eq!(entry.expires_at, Some(entry.created_at + 60));
+        assert_eq!(entry.last_used_at, None);
+
+        assert!(ApiKeyEntry::new(email(), " ".to_owned(), vec![], None).is_err());
+        assert!(ApiKeyEntry::new(email(), "ci".to_owned(), vec!["bad\"scope".to_owned()], None).is_err());
+        assert!(ApiKeyEntry::new(email(), "ci".to_owned(), vec![], Some(0)).is_err());
+    }
+
+    #[test]
+    fn test_api_key_entry_expiry() {
+        let mut entry = ApiKeyEntry::new(email(), "ci".to_owned(), vec![], None).unwrap();
+        assert!(!entry.is_expired());
+
+        entry.expires_at = Some(Utc::now().timestamp() - 1);
+        assert!(entry.is_expired());
+    }
+}
@@ -0,0 +1,92 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use color_eyre::eyre::{eyre, Context, ContextCompat, Result};
+use secrecy::{ExposeSecret, Secret};
+
+// Credentials a client authenticates itself with, using HTTP Basic authentication
+#[derive(Debug, Clone)]
+pub struct ClientCredentials {
+    pub client_id: String,
+    pub client_secret: Secret<String>,
+}
+
+impl ClientCredentials {
+    pub fn new(client_id: String, client_secret: Secret<String>) -> Self {
+        Self { client_id, client_secret }
+    }
+
+    // Parse the value of an `Authorization: Basic <base64(client_id:client_secret)>` header
+    pub fn parse_basic_auth(header: &str) -> Result<Self> {
+        let encoded = header
+            .strip_prefix("Basic ")
+            .wrap_err("Authorization header is not using the Basic scheme")?;
+
+        let decoded = STANDARD
+            .decode(encoded.trim())
+            .wrap_err("Failed to decode Basic credentials")?;
+        let decoded = String::from_utf8(decoded).wrap_err("Basic credentials are not valid UTF-8")?;
+
+        let (client_id, client_secret) = decoded
+            .split_once(':')
+            .wrap_err("Basic credentials are missing a client secret")?;
+
+        if client_id.is_empty() {
+            return Err(eyre!("Basic credentials are missing a client id"));
+        }
+
+        Ok(Self::new(client_id.to_owned(), Secret::new(client_secret.to_owned())))
+    }
+
+    // Secrets are compared in constant time so their content can't be guessed from response times
+    pub fn matches(&self, other: &ClientCredentials) -> bool {
+        let secret = self.client_secret.expose_secret().as_bytes();
+        let other_secret = other.client_secret.expose_secret().as_bytes();
+
+        self.client_id == other.client_id
+            && secret.len() == other_secret.len()
+            && secret
+                .iter()
+                .zip(other_secret)
+                .fold(0, |acc, (a, b)| acc | (a ^ b))
+                == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn credentials(client_id: &str, client_secret: &str) -> ClientCredentials {
+        ClientCredentials::new(client_id.to_owned(), Secret::new(client_secret.to_owned()))
+    }
+
+    #[test]
+    fn test_parse_basic_auth() {
+        let header = format!("Basic {}", STANDARD.encode("app-service:s3cr3t:with:colons"));
+        let parsed = ClientCredentials::parse_basic_auth(&header).unwrap();
+        assert_eq!(parsed.client_id, "app-service");
+        assert_eq!(parsed.client_secret.expose_secret(), "s3cr3t:with:colons");
+    }
+
+    #[test]
+    fn test_parse_basic_auth_rejects_invalid_headers() {
+        let test_cases = [
+            format!("Bearer {}", STANDARD.encode("app-service:secret")),
+            "Basic not-base64!".to_owned(),
+            format!("Basic {}", STANDARD.encode("app-service")),
+            format!("Basic {}", STANDARD.encode(":secret")),
+        ];
+
+        for header in test_cases {
+            assert!(ClientCredentials::parse_basic_auth(&header).is_err(), "{}", header);
+        }
+    }
+
+    #[test]
+    fn test_matches() {
+        let expected = credentials("app-service", "secret");
+        assert!(credentials("app-service", "secret").matches(&expected));
+        assert!(!credentials("app-service", "secreT").matches(&expected));
+        assert!(!credentials("app-service", "secret2").matches(&expected));
+        assert!(!credentials("other-service", "secret").matches(&expected));
+    }
+}
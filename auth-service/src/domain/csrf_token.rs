@@ -0,0 +1,70 @@
+use rand::{distributions::Alphanumeric, Rng};
+use secrecy::{ExposeSecret, Secret};
+
+const CSRF_TOKEN_LENGTH: usize = 32;
+
+// Random token a browser repeats in a header to prove a request was made by a script
+// of the site, not by a cross-site form
+#[derive(Debug, Clone)]
+pub struct CsrfToken(Secret<String>);
+
+impl CsrfToken {
+    pub fn new(token: Secret<String>) -> Self {
+        Self(token)
+    }
+
+    // Tokens are compared in constant time so their content can't be guessed from response times
+    pub fn matches(&self, other: &CsrfToken) -> bool {
+        let token = self.0.expose_secret().as_bytes();
+        let other_token = other.0.expose_secret().as_bytes();
+
+        !token.is_empty()
+            && token.len() == other_token.len()
+            && token
+                .iter()
+                .zip(other_token)
+                .fold(0, |acc, (a, b)| acc | (a ^ b))
+                == 0
+    }
+}
+
+impl Default for CsrfToken {
+    fn default() -> Self {
+        let token: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(CSRF_TOKEN_LENGTH)
+            .map(char::from)
+            .collect();
+        Self(Secret::new(token))
+    }
+}
+
+impl AsRef<Secret<String>> for CsrfToken {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(value: &str) -> CsrfToken {
+        CsrfToken::new(Secret::new(value.to_owned()))
+    }
+
+    #[test]
+    fn test_default_tokens_are_random() {
+        let token = CsrfToken::default();
+        assert_eq!(token.as_ref().expose_secret().len(), CSRF_TOKEN_LENGTH);
+        assert!(!token.matches(&CsrfToken::default()));
+    }
+
+    #[test]
+    fn test_matches() {
+        assert!(token("token").matches(&token("token")));
+        assert!(!token("token").matches(&token("other")));
+        assert!(!token("token").matches(&token("token2")));
+        assert!(!token("").matches(&token("")));
+    }
+}
@@ -4,9 +4,16 @@ use color_eyre::eyre::{
     Report,
     Result
 };
-use rand::Rng;
+use rand::{distributions::Alphanumeric, Rng};
 use secrecy::{ExposeSecret, Secret};
-use super::{Email, Password, User};
+use sha2::{Digest, Sha256};
+use std::hash::{Hash, Hasher};
+use super::{
+    ApiKey, ApiKeyEntry, AuthMethod, AuthorizationCode, AuthorizationCodeEntry, ClientSecret, Email,
+    ExternalIdentity, MagicLink, MagicLinkToken, OAuthClient, OidcLogin, OidcState, Passkey,
+    PasskeyChallenge, Password, PhoneNumber, RecoveryCode, Session, TotpEnrollment,
+    TrustedDevice, TwoFAMethod, User,
+};
 use thiserror::Error;
 
 #[async_trait::async_trait]
@@ -14,6 +21,11 @@ pub trait UserStore {
     async fn add_user(&mut self, user: User) -> Result<(), UserStoreError>;
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError>;
     async fn validate_user(&self, email: &Email, password: &Password) -> Result<(), UserStoreError>;
+    async fn update_password(&mut self, email: &Email, password: Password) -> Result<(), UserStoreError>;
+    // Switch the user's second factor to the given method, turning on 2FA if needed
+    async fn update_two_fa_method(&mut self, email: &Email, method: TwoFAMethod) -> Result<(), UserStoreError>;
+    // Only called with numbers the user proved they receive texts at
+    async fn update_phone_number(&mut self, email: &Email, phone_number: PhoneNumber) -> Result<(), UserStoreError>;
 }
 
 #[derive(Debug, Error)]
@@ -40,10 +52,12 @@ impl PartialEq for UserStoreError {
     }
 }
 
+// Revoked tokens are identified by their `jti` claim and only need to be
+// remembered until their `exp` claim, after which they are rejected anyway.
 #[async_trait::async_trait]
 pub trait BannedTokenStore {
-    async fn add_token(&mut self, token: Secret<String>) -> Result<(), BannedTokenStoreError>;
-    async fn contains_token(&self, token: &Secret<String>) -> Result<bool, BannedTokenStoreError>;
+    async fn add_token(&mut self, jti: String, exp: usize) -> Result<(), BannedTokenStoreError>;
+    async fn contains_token(&self, jti: &str) -> Result<bool, BannedTokenStoreError>;
 }
 
 #[derive(Debug, Error)]
@@ -52,9 +66,467 @@ pub enum BannedTokenStoreError {
     UnexpectedError(#[source] Report),
 }
 
+#[async_trait::async_trait]
+pub trait RefreshTokenStore {
+    async fn add_token(
+        &mut self,
+        token: RefreshToken,
+        entry: RefreshTokenEntry,
+    ) -> Result<(), RefreshTokenStoreError>;
+
+    async fn get_token(
+        &self,
+        token: &RefreshToken,
+    ) -> Result<RefreshTokenEntry, RefreshTokenStoreError>;
+
+    async fn mark_token_used(&mut self, token: &RefreshToken) -> Result<(), RefreshTokenStoreError>;
+
+    async fn revoke_family(&mut self, family_id: &str) -> Result<(), RefreshTokenStoreError>;
+
+    async fn is_family_revoked(&self, family_id: &str) -> Result<bool, RefreshTokenStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum RefreshTokenStoreError {
+    #[error("Refresh token not found")]
+    TokenNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for RefreshTokenStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::TokenNotFound, Self::TokenNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+// Every auth token carries the session version its user had when it was issued.
+// Incrementing the version invalidates all previously issued tokens, on every device.
+#[async_trait::async_trait]
+pub trait SessionVersionStore {
+    async fn get_version(&self, email: &Email) -> Result<u64, SessionVersionStoreError>;
+    async fn increment_version(&mut self, email: &Email) -> Result<u64, SessionVersionStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum SessionVersionStoreError {
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+#[async_trait::async_trait]
+pub trait SessionStore {
+    async fn add_session(&mut self, session: Session) -> Result<(), SessionStoreError>;
+    async fn get_session(&self, id: &str) -> Result<Session, SessionStoreError>;
+    async fn get_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError>;
+    // Record that the session is still in use, returning it
+    async fn touch_session(&mut self, id: &str) -> Result<Session, SessionStoreError>;
+    // Record that the user of the session just proved who they are again
+    async fn record_authentication(&mut self, id: &str, amr: Vec<AuthMethod>) -> Result<Session, SessionStoreError>;
+    // Count a failed attempt to reauthenticate, returning how many there were in a row
+    async fn record_failed_reauthentication(&mut self, id: &str) -> Result<u32, SessionStoreError>;
+    async fn remove_session(&mut self, email: &Email, id: &str) -> Result<(), SessionStoreError>;
+    async fn remove_sessions(&mut self, email: &Email) -> Result<(), SessionStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum SessionStoreError {
+    #[error("Session not found")]
+    SessionNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for SessionStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::SessionNotFound, Self::SessionNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[async_trait::async_trait]
+pub trait OAuthClientStore {
+    // Confidential clients are registered with a secret, public clients without one
+    async fn add_client(
+        &mut self,
+        client: OAuthClient,
+        client_secret: Option<ClientSecret>,
+    ) -> Result<(), OAuthClientStoreError>;
+
+    async fn get_client(&self, client_id: &str) -> Result<OAuthClient, OAuthClientStoreError>;
+
+    async fn validate_client_secret(
+        &self,
+        client_id: &str,
+        client_secret: &ClientSecret,
+    ) -> Result<(), OAuthClientStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum OAuthClientStoreError {
+    #[error("Client already exists")]
+    ClientAlreadyExists,
+    #[error("Client not found")]
+    ClientNotFound,
+    #[error("Invalid client credentials")]
+    InvalidCredentials,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for OAuthClientStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::ClientAlreadyExists, Self::ClientAlreadyExists)
+                | (Self::ClientNotFound, Self::ClientNotFound)
+                | (Self::InvalidCredentials, Self::InvalidCredentials)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+// Authorization codes are short-lived and can only be redeemed once
+#[async_trait::async_trait]
+pub trait AuthorizationCodeStore {
+    async fn add_code(
+        &mut self,
+        code: AuthorizationCode,
+        entry: AuthorizationCodeEntry,
+    ) -> Result<(), AuthorizationCodeStoreError>;
+
+    // Remove the code and return what it was issued for
+    async fn take_code(
+        &mut self,
+        code: &AuthorizationCode,
+    ) -> Result<AuthorizationCodeEntry, AuthorizationCodeStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum AuthorizationCodeStoreError {
+    #[error("Authorization code not found")]
+    CodeNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for AuthorizationCodeStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::CodeNotFound, Self::CodeNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+// API keys are looked up by the hash of the key, which is all that is stored
+#[async_trait::async_trait]
+pub trait ApiKeyStore {
+    async fn add_key(&mut self, key: &ApiKey, entry: ApiKeyEntry) -> Result<(), ApiKeyStoreError>;
+    async fn get_key(&self, key: &ApiKey) -> Result<ApiKeyEntry, ApiKeyStoreError>;
+    async fn get_keys(&self, email: &Email) -> Result<Vec<ApiKeyEntry>, ApiKeyStoreError>;
+    // Record that the key was just used
+    async fn touch_key(&mut self, id: &str) -> Result<(), ApiKeyStoreError>;
+    async fn remove_key(&mut self, email: &Email, id: &str) -> Result<(), ApiKeyStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum ApiKeyStoreError {
+    #[error("API key not found")]
+    KeyNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for ApiKeyStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::KeyNotFound, Self::KeyNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+// Logins with external providers are short-lived and can only be completed once
+#[async_trait::async_trait]
+pub trait OidcLoginStore {
+    async fn add_login(&mut self, state: OidcState, login: OidcLogin) -> Result<(), OidcLoginStoreError>;
+
+    // Remove the login and return it
+    async fn take_login(&mut self, state: &OidcState) -> Result<OidcLogin, OidcLoginStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum OidcLoginStoreError {
+    #[error("Login not found")]
+    LoginNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for OidcLoginStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::LoginNotFound, Self::LoginNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+// Login links sent by email, until they are clicked or expire
+#[async_trait::async_trait]
+pub trait MagicLinkStore {
+    async fn add_link(&mut self, token: MagicLinkToken, link: MagicLink) -> Result<(), MagicLinkStoreError>;
+
+    // Remove the link and return it, so each link logs in once
+    async fn take_link(&mut self, token: &MagicLinkToken) -> Result<MagicLink, MagicLinkStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum MagicLinkStoreError {
+    #[error("Link not found")]
+    LinkNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for MagicLinkStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::LinkNotFound, Self::LinkNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+// Browsers users trusted to skip 2FA on
+#[async_trait::async_trait]
+pub trait TrustedDeviceStore {
+    async fn add_device(&mut self, device: TrustedDevice) -> Result<(), TrustedDeviceStoreError>;
+    async fn get_device(&self, id: &str) -> Result<TrustedDevice, TrustedDeviceStoreError>;
+    async fn get_devices(&self, email: &Email) -> Result<Vec<TrustedDevice>, TrustedDeviceStoreError>;
+    async fn remove_device(&mut self, email: &Email, id: &str) -> Result<(), TrustedDeviceStoreError>;
+    async fn remove_devices(&mut self, email: &Email) -> Result<(), TrustedDeviceStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum TrustedDeviceStoreError {
+    #[error("Device not found")]
+    DeviceNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for TrustedDeviceStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::DeviceNotFound, Self::DeviceNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[async_trait::async_trait]
+pub trait ExternalIdentityStore {
+    async fn add_identity(&mut self, identity: ExternalIdentity) -> Result<(), ExternalIdentityStoreError>;
+
+    async fn get_identity(
+        &self,
+        provider: &str,
+        subject: &str,
+    ) -> Result<ExternalIdentity, ExternalIdentityStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum ExternalIdentityStoreError {
+    #[error("Identity already linked")]
+    IdentityAlreadyExists,
+    #[error("Identity not found")]
+    IdentityNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for ExternalIdentityStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::IdentityAlreadyExists, Self::IdentityAlreadyExists)
+                | (Self::IdentityNotFound, Self::IdentityNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[async_trait::async_trait]
+pub trait PasskeyStore {
+    async fn add_passkey(&mut self, passkey: Passkey) -> Result<(), PasskeyStoreError>;
+    async fn get_passkey(&self, id: &str) -> Result<Passkey, PasskeyStoreError>;
+    async fn get_passkeys(&self, email: &Email) -> Result<Vec<Passkey>, PasskeyStoreError>;
+    // Record a successful login with the passkey
+    async fn update_sign_count(&mut self, id: &str, sign_count: u32) -> Result<(), PasskeyStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum PasskeyStoreError {
+    #[error("Passkey already registered")]
+    PasskeyAlreadyExists,
+    #[error("Passkey not found")]
+    PasskeyNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for PasskeyStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::PasskeyAlreadyExists, Self::PasskeyAlreadyExists)
+                | (Self::PasskeyNotFound, Self::PasskeyNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+// Passkey challenges are short-lived and can only be answered once
+#[async_trait::async_trait]
+pub trait PasskeyChallengeStore {
+    async fn add_challenge(&mut self, challenge: PasskeyChallenge) -> Result<(), PasskeyChallengeStoreError>;
+
+    // Remove the challenge and return it
+    async fn take_challenge(&mut self, challenge: &str) -> Result<PasskeyChallenge, PasskeyChallengeStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum PasskeyChallengeStoreError {
+    #[error("Challenge not found")]
+    ChallengeNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for PasskeyChallengeStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::ChallengeNotFound, Self::ChallengeNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+// Authenticator apps users enrolled for 2FA. Replacing an enrollment is only allowed
+// until it is confirmed.
+#[async_trait::async_trait]
+pub trait TotpStore {
+    async fn add_enrollment(&mut self, enrollment: TotpEnrollment) -> Result<(), TotpStoreError>;
+    async fn get_enrollment(&self, email: &Email) -> Result<TotpEnrollment, TotpStoreError>;
+    // Confirm the enrollment with the time step of its first code
+    async fn confirm_enrollment(&mut self, email: &Email, step: u64) -> Result<(), TotpStoreError>;
+    // Record that a code was used, failing if a code of the same or a later step already was
+    async fn use_step(&mut self, email: &Email, step: u64) -> Result<(), TotpStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum TotpStoreError {
+    #[error("TOTP already enabled")]
+    TotpAlreadyEnabled,
+    #[error("TOTP enrollment not found")]
+    EnrollmentNotFound,
+    #[error("TOTP code already used")]
+    CodeAlreadyUsed,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for TotpStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::TotpAlreadyEnabled, Self::TotpAlreadyEnabled)
+                | (Self::EnrollmentNotFound, Self::EnrollmentNotFound)
+                | (Self::CodeAlreadyUsed, Self::CodeAlreadyUsed)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+// Only hashes of recovery codes are kept. Generating a new set replaces the old one.
+#[async_trait::async_trait]
+pub trait RecoveryCodeStore {
+    async fn replace_codes(&mut self, email: &Email, codes: &[RecoveryCode]) -> Result<(), RecoveryCodeStoreError>;
+    // Remove the code, failing if it isn't one of the user's unused codes
+    async fn use_code(&mut self, email: &Email, code: &RecoveryCode) -> Result<(), RecoveryCodeStoreError>;
+    async fn count_codes(&self, email: &Email) -> Result<usize, RecoveryCodeStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum RecoveryCodeStoreError {
+    #[error("Recovery code not found")]
+    CodeNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for RecoveryCodeStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::CodeNotFound, Self::CodeNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+// Phone numbers users are adding for SMS 2FA, until they confirm the code texted to them
+#[async_trait::async_trait]
+pub trait PhoneVerificationStore {
+    async fn add_verification(
+        &mut self,
+        email: Email,
+        phone_number: PhoneNumber,
+        code: TwoFACode,
+    ) -> Result<(), PhoneVerificationStoreError>;
+    async fn get_verification(&self, email: &Email) -> Result<(PhoneNumber, TwoFACode), PhoneVerificationStoreError>;
+    async fn remove_verification(&mut self, email: &Email) -> Result<(), PhoneVerificationStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum PhoneVerificationStoreError {
+    #[error("Verification not found")]
+    VerificationNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for PhoneVerificationStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::VerificationNotFound, Self::VerificationNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+// Pending logins are keyed by their attempt id, so logins of the same user from several devices
+// don't replace each other's codes
 #[async_trait::async_trait]
 pub trait TwoFACodeStore {
     
+    // Once the user has too many logins waiting for a code, the oldest one is dropped
     async fn add_code(
         &mut self, 
         email: Email, 
@@ -64,13 +536,28 @@ pub trait TwoFACodeStore {
 
     async fn remove_code(
         &mut self,
-        email: &Email
+        login_attempt_id: &LoginAttemptId,
     ) -> Result<(), TwoFACodeStoreError>;
 
     async fn get_code(
         &self,
-        email: &Email,
-    ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError>;
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(Email, TwoFACode), TwoFACodeStoreError>;
+
+    // Counts a wrong code against the login attempt. Once too many were wrong the code is
+    // removed and `TooManyAttempts` is returned, so the user has to log in again.
+    async fn record_failed_attempt(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(), TwoFACodeStoreError>;
+
+    // Replaces the code of the login attempt with a fresh one. Codes can only be resent a few
+    // times per attempt, and not again until the cooldown since the last one passed.
+    async fn resend_code(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+        code: TwoFACode,
+    ) -> Result<(), TwoFACodeStoreError>;
 
 }
 
@@ -78,17 +565,28 @@ pub trait TwoFACodeStore {
 pub enum TwoFACodeStoreError {
     #[error("Login Attempt ID not found")]
     LoginAttemptIdNotFound,
+    #[error("Too many failed attempts")]
+    TooManyAttempts,
+    #[error("Code was resent too recently, retry in {0} seconds")]
+    ResendTooSoon(u64),
+    #[error("Too many resends")]
+    TooManyResends,
     #[error("Unexpected error")]
     UnexpectedError(#[source] Report),
 }
 
 impl PartialEq for TwoFACodeStoreError {
     fn eq(&self, other: &Self) -> bool {
-        matches!(
-            (self, other),
-            (Self::LoginAttemptIdNotFound, Self::LoginAttemptIdNotFound)
-                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
-        )
+        match (self, other) {
+            (Self::ResendTooSoon(a), Self::ResendTooSoon(b)) => a == b,
+            _ => matches!(
+                (self, other),
+                (Self::LoginAttemptIdNotFound, Self::LoginAttemptIdNotFound)
+                    | (Self::TooManyAttempts, Self::TooManyAttempts)
+                    | (Self::TooManyResends, Self::TooManyResends)
+                    | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+            ),
+        }
     }
 }
 
@@ -101,9 +599,17 @@ impl PartialEq for LoginAttemptId {
     }
 }
 
+impl Hash for LoginAttemptId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.expose_secret().hash(state);
+    }
+}
+
+impl Eq for LoginAttemptId {}
+
 impl LoginAttemptId {
     pub fn parse(id: Secret<String>) -> Result<Self> {
-        let parse_id = uuid::Uuid::parse_str(&id.expose_secret())
+        let parse_id = uuid::Uuid::parse_str(id.expose_secret())
             .wrap_err("Invalid login attempt id")?;
         Ok(Self(Secret::new(parse_id.to_string())))
     }
@@ -131,13 +637,11 @@ impl PartialEq for TwoFACode {
 }
 
 impl TwoFACode {
+    // Codes from authenticator apps can start with zeros, so any six digits are accepted
     pub fn parse(code: Secret<String>) -> Result<Self> {
-        let code_as_u32 = code
-            .expose_secret()
-            .parse::<u32>()
-            .wrap_err("Invalid 2FA code")?;
+        let code_str = code.expose_secret();
 
-         if (100_000..=999_999).contains(&code_as_u32) {
+         if code_str.len() == 6 && code_str.bytes().all(|b| b.is_ascii_digit()) {
             Ok(Self(code))
          } else {
             Err(eyre!("Invalid 2FA code"))
@@ -157,4 +661,83 @@ impl AsRef<Secret<String>> for TwoFACode {
     fn as_ref(&self) -> &Secret<String> {
         &self.0
     }
-}
\ No newline at end of file
+}
+
+// Opaque, long-lived token that can be traded for a new JWT auth token
+#[derive(Debug, Clone)]
+pub struct RefreshToken(Secret<String>);
+
+impl PartialEq for RefreshToken {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.expose_secret() == other.0.expose_secret()
+    }
+}
+
+impl RefreshToken {
+    pub fn parse(token: Secret<String>) -> Result<Self> {
+        let is_valid = token.expose_secret().len() == REFRESH_TOKEN_LENGTH
+            && token.expose_secret().chars().all(|c| c.is_ascii_alphanumeric());
+
+        if is_valid {
+            Ok(Self(token))
+        } else {
+            Err(eyre!("Invalid refresh token"))
+        }
+    }
+
+    // Stores look tokens up by their hash, like API keys, so whoever can read a store can't
+    // use the tokens in it
+    pub fn hash(&self) -> String {
+        format!("{:x}", Sha256::digest(self.0.expose_secret().as_bytes()))
+    }
+}
+
+impl Default for RefreshToken {
+    fn default() -> Self {
+        let token: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(REFRESH_TOKEN_LENGTH)
+            .map(char::from)
+            .collect();
+        Self(Secret::new(token))
+    }
+}
+
+impl AsRef<Secret<String>> for RefreshToken {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.0
+    }
+}
+
+const REFRESH_TOKEN_LENGTH: usize = 64;
+
+// Every refresh token belongs to a family that starts with a session, so the family
+// shares the session's id. Rotating a token keeps the family, so replaying an old
+// token can revoke all of its descendants.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RefreshTokenEntry {
+    pub email: Email,
+    pub family_id: String,
+    pub used: bool,
+    pub session_version: u64,
+}
+
+impl RefreshTokenEntry {
+    pub fn new(email: Email, session_id: String, session_version: u64) -> Self {
+        Self {
+            email,
+            family_id: session_id,
+            used: false,
+            session_version,
+        }
+    }
+
+    pub fn rotate(&self) -> Self {
+        Self {
+            email: self.email.clone(),
+            family_id: self.family_id.clone(),
+            used: false,
+            session_version: self.session_version,
+        }
+    }
+}
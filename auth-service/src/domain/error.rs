@@ -3,16 +3,99 @@ use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum AuthAPIError {
+    #[error("API key not found")]
+    ApiKeyNotFound,
+    #[error("External account not linked")]
+    ExternalAccountNotLinked,
+    #[error("External login failed")]
+    ExternalLoginFailed,
     #[error("Incorrect credentials")]
     IncorrectCredentials,
     #[error("Invalid credentials")]
     InvalidCredentials,
+    #[error("Invalid client credentials")]
+    InvalidClientCredentials,
+    #[error("Invalid API key request")]
+    InvalidApiKeyRequest,
+    #[error("Invalid CSRF token")]
+    InvalidCsrfToken,
+    #[error("Invalid magic link")]
+    InvalidMagicLink,
+    #[error("Invalid passkey")]
+    InvalidPasskey,
+    #[error("Invalid phone number")]
+    InvalidPhoneNumber,
+    #[error("Invalid redirect URI")]
+    InvalidRedirectUri,
+    #[error("Invalid scope")]
+    InvalidScope,
     #[error("Invalid token")]
     InvalidToken,
+    #[error("Invalid TOTP code")]
+    InvalidTotpCode,
+    #[error("Invalid verification code")]
+    InvalidVerificationCode,
     #[error("Missing token")]
     MissingToken,
+    #[error("Phone verification not found")]
+    PhoneVerificationNotFound,
+    #[error("Session not found")]
+    SessionNotFound,
+    #[error("TOTP already enabled")]
+    TotpAlreadyEnabled,
+    #[error("TOTP enrollment not found")]
+    TotpEnrollmentNotFound,
+    #[error("Trusted device not found")]
+    TrustedDeviceNotFound,
+    #[error("Too many failed 2FA attempts")]
+    TwoFAAttemptsExceeded,
+    #[error("2FA codes of authenticator apps can't be resent")]
+    TwoFAResendNotSupported,
+    #[error("2FA code was resent too recently")]
+    TwoFAResendTooSoon(u64),
+    #[error("Too many 2FA code resends")]
+    TwoFAResendsExceeded,
     #[error("Unexpected error")]
     UnexpectedError(#[source] Report),
+    #[error("Unknown identity provider")]
+    UnknownIdentityProvider,
     #[error("User already exists")]
     UserAlreadyExists,
-}
\ No newline at end of file
+}
+
+// Errors of the OAuth 2.0 endpoints, which have their own response format (RFC 6749 section 5.2)
+#[derive(Debug, Error)]
+pub enum OAuthError {
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+    #[error("Invalid client")]
+    InvalidClient,
+    #[error("Invalid grant")]
+    InvalidGrant,
+    #[error("Invalid scope: {0}")]
+    InvalidScope(String),
+    #[error("Unauthorized client")]
+    UnauthorizedClient,
+    #[error("Unsupported grant type")]
+    UnsupportedGrantType,
+    #[error("Unsupported response type")]
+    UnsupportedResponseType,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl OAuthError {
+    // The `error` code clients act on
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidRequest(_) => "invalid_request",
+            Self::InvalidClient => "invalid_client",
+            Self::InvalidGrant => "invalid_grant",
+            Self::InvalidScope(_) => "invalid_scope",
+            Self::UnauthorizedClient => "unauthorized_client",
+            Self::UnsupportedGrantType => "unsupported_grant_type",
+            Self::UnsupportedResponseType => "unsupported_response_type",
+            Self::UnexpectedError(_) => "server_error",
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use chrono::Utc;
+use color_eyre::eyre::{eyre, Result};
+use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
+
+use super::{oauth::random_string, sign_value, verify_signed_value, Email};
+
+// Identifies a login link. The links users get carry it signed, so forged tokens are
+// rejected before the store is asked.
+#[derive(Debug, Clone)]
+pub struct MagicLinkToken(Secret<String>);
+
+impl PartialEq for MagicLinkToken {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.expose_secret() == other.0.expose_secret()
+    }
+}
+
+impl MagicLinkToken {
+    pub fn parse(token: Secret<String>) -> Result<Self> {
+        if is_random_string(token.expose_secret(), MAGIC_LINK_TOKEN_LENGTH) {
+            Ok(Self(token))
+        } else {
+            Err(eyre!("Invalid magic link token"))
+        }
+    }
+
+    // `<token>.<signature>`, the form the token takes in links
+    pub fn sign(&self, key: &[u8]) -> Secret<String> {
+        Secret::new(sign_value(self.0.expose_secret(), key))
+    }
+
+    pub fn verify_signed(signed: Secret<String>, key: &[u8]) -> Result<Self> {
+        let token = verify_signed_value(signed.expose_secret(), key)?;
+
+        Self::parse(Secret::new(token.to_owned()))
+    }
+}
+
+impl Default for MagicLinkToken {
+    fn default() -> Self {
+        Self(Secret::new(random_string(MAGIC_LINK_TOKEN_LENGTH)))
+    }
+}
+
+impl AsRef<Secret<String>> for MagicLinkToken {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.0
+    }
+}
+
+// Random value kept in a cookie of the browser that asked for the link. The link only logs
+// in that browser, so a leaked or forwarded link is useless elsewhere.
+#[derive(Debug, Clone)]
+pub struct BrowserBinding(Secret<String>);
+
+impl BrowserBinding {
+    pub fn parse(binding: Secret<String>) -> Result<Self> {
+        if is_random_string(binding.expose_secret(), BROWSER_BINDING_LENGTH) {
+            Ok(Self(binding))
+        } else {
+            Err(eyre!("Invalid browser binding"))
+        }
+    }
+
+    // Only the hash is stored, so the store can't be used to forge the cookie
+    pub fn hash(&self) -> String {
+        format!("{:x}", Sha256::digest(self.0.expose_secret().as_bytes()))
+    }
+}
+
+impl Default for BrowserBinding {
+    fn default() -> Self {
+        Self(Secret::new(random_string(BROWSER_BINDING_LENGTH)))
+    }
+}
+
+impl AsRef<Secret<String>> for BrowserBinding {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.0
+    }
+}
+
+// A login link that was sent but not clicked yet
+#[derive(Debug, Clone, PartialEq)]
+pub struct MagicLink {
+    pub email: Email,
+    // Hash of the `BrowserBinding` of the browser that asked for the link
+    pub browser_binding: String,
+    // Unix timestamp
+    pub expires_at: i64,
+}
+
+impl MagicLink {
+    pub fn new(email: Email, binding: &BrowserBinding, ttl_seconds: i64) -> Self {
+        Self {
+            email,
+            browser_binding: binding.hash(),
+            expires_at: Utc::now().timestamp() + ttl_seconds,
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= Utc::now().timestamp()
+    }
+
+    pub fn is_bound_to(&self, binding: &BrowserBinding) -> bool {
+        self.browser_binding == binding.hash()
+    }
+}
+
+const MAGIC_LINK_TOKEN_LENGTH: usize = 32;
+const BROWSER_BINDING_LENGTH: usize = 32;
+
+fn is_random_string(value: &str, length: usize) -> bool {
+    value.len() == length && value.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &[u8] = b"magic-link-test-key";
+
+    #[test]
+    fn should_verify_signed_tokens() {
+        let token = MagicLinkToken::default();
+        let signed = token.sign(KEY);
+
+        assert_eq!(MagicLinkToken::verify_signed(signed, KEY).unwrap(), token);
+    }
+
+    #[test]
+    fn should_reject_forged_tokens() {
+        let token = MagicLinkToken::default();
+        let signed = token.sign(KEY);
+        let (_, signature) = signed.expose_secret().split_once('.').unwrap();
+        let other = MagicLinkToken::default();
+
+        let forged = [
+            token.as_ref().expose_secret().to_owned(),
+            format!("{}.{}", other.as_ref().expose_secret(), signature),
+            format!("{}.{}", token.as_ref().expose_secret(), "zz"),
+            signed.expose_secret()[..signed.expose_secret().len() - 2].to_owned(),
+        ];
+
+        for signed in forged {
+            assert!(MagicLinkToken::verify_signed(Secret::new(signed.clone()), KEY).is_err(), "{}", signed);
+        }
+        assert!(MagicLinkToken::verify_signed(token.sign(b"other-key"), KEY).is_err());
+    }
+
+    #[test]
+    fn should_bind_links_to_the_browser() {
+        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+        let binding = BrowserBinding::default();
+        let link = MagicLink::new(email, &binding, 60);
+
+        assert!(link.is_bound_to(&binding));
+        assert!(!link.is_bound_to(&BrowserBinding::default()));
+        assert!(!link.is_expired());
+    }
+}
@@ -1,13 +1,41 @@
+pub mod api_key;
+pub mod client_credentials;
+pub mod csrf_token;
 pub mod data_stores;
 pub mod email;
 pub mod email_client;
 pub mod error;
+pub mod magic_link;
+pub mod oauth;
+pub mod oidc;
+pub mod passkey;
 pub mod password;
+pub mod phone_number;
+pub mod recovery_code;
+pub mod session;
+pub mod signed_value;
+pub mod sms_client;
+pub mod totp;
+pub mod trusted_device;
 pub mod user;
 
+pub use api_key::*;
+pub use client_credentials::*;
+pub use csrf_token::*;
 pub use data_stores::*;
 pub use email::*;
 pub use email_client::*;
 pub use error::*;
+pub use magic_link::*;
+pub use oauth::*;
+pub use oidc::*;
+pub use passkey::*;
 pub use password::*;
+pub use phone_number::*;
+pub use recovery_code::*;
+pub use session::*;
+pub use signed_value::*;
+pub use sms_client::*;
+pub use totp::*;
+pub use trusted_device::*;
 pub use user::*;
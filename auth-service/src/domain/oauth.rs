@@ -0,0 +1,329 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use color_eyre::eyre::{eyre, Result};
+use rand::{distributions::Alphanumeric, Rng};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::Email;
+
+// Scopes clients can request. `openid` asks for an ID token, `email` for the user's
+// email address in it.
+pub const SUPPORTED_SCOPES: [&str; 2] = ["openid", "email"];
+
+// Scope that lets a service client verify tokens at /verify-token
+pub const INTROSPECT_SCOPE: &str = "introspect";
+
+// An application that signs its users in with this service
+#[derive(Debug, Clone, PartialEq)]
+pub struct OAuthClient {
+    pub client_id: String,
+    pub name: String,
+    // Authorization codes are only ever sent to one of these URIs
+    pub redirect_uris: Vec<String>,
+    // Confidential clients, such as server-side apps, authenticate with a secret when trading
+    // codes for tokens. Public clients, such as single-page apps, can't keep one and rely on PKCE.
+    pub confidential: bool,
+    // Scopes a service client may be granted with the client credentials grant. Clients
+    // without any can only sign users in.
+    pub scopes: Vec<String>,
+}
+
+impl OAuthClient {
+    pub fn new(name: String, redirect_uris: Vec<String>, confidential: bool) -> Result<Self> {
+        for uri in &redirect_uris {
+            let parsed = url::Url::parse(uri).map_err(|_| eyre!("Invalid redirect URI: {}", uri))?;
+            if parsed.fragment().is_some() {
+                return Err(eyre!("Redirect URIs must not contain a fragment: {}", uri));
+            }
+        }
+
+        if redirect_uris.is_empty() {
+            return Err(eyre!("At least one redirect URI is required"));
+        }
+
+        Ok(Self {
+            client_id: uuid::Uuid::new_v4().to_string(),
+            name,
+            redirect_uris,
+            confidential,
+            scopes: vec![],
+        })
+    }
+
+    // A backend service that gets tokens for itself rather than for a user. Service clients
+    // always authenticate with a secret.
+    pub fn service(name: String, scopes: Vec<String>) -> Result<Self> {
+        if scopes.is_empty() {
+            return Err(eyre!("At least one scope is required"));
+        }
+
+        if let Some(scope) = scopes.iter().find(|scope| !is_scope_token(scope)) {
+            return Err(eyre!("Invalid scope: {}", scope));
+        }
+
+        Ok(Self {
+            client_id: uuid::Uuid::new_v4().to_string(),
+            name,
+            redirect_uris: vec![],
+            confidential: true,
+            scopes,
+        })
+    }
+
+    // Redirect URIs are compared exactly, so codes can't be sent to a path or query
+    // the client didn't register
+    pub fn allows_redirect_uri(&self, redirect_uri: &str) -> bool {
+        self.redirect_uris.iter().any(|uri| uri == redirect_uri)
+    }
+
+    pub fn allows_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|s| s == scope)
+    }
+
+    // Check the scopes requested with the client credentials grant, defaulting to all
+    // the scopes the client was registered with
+    pub fn grant_scope(&self, scope: Option<&str>) -> Result<String> {
+        let scope = match scope {
+            Some(scope) => scope.split_whitespace().collect::<Vec<_>>(),
+            None => self.scopes.iter().map(String::as_str).collect(),
+        };
+
+        if let Some(scope) = scope.iter().find(|scope| !self.allows_scope(scope)) {
+            return Err(eyre!("Scope not allowed for client: {}", scope));
+        }
+
+        Ok(scope.join(" "))
+    }
+}
+
+// Secret of a confidential client, shown once when the client is registered
+#[derive(Debug, Clone)]
+pub struct ClientSecret(Secret<String>);
+
+impl ClientSecret {
+    pub fn new(secret: Secret<String>) -> Self {
+        Self(secret)
+    }
+}
+
+impl Default for ClientSecret {
+    fn default() -> Self {
+        Self(Secret::new(random_string(CLIENT_SECRET_LENGTH)))
+    }
+}
+
+impl AsRef<Secret<String>> for ClientSecret {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.0
+    }
+}
+
+// Single-use code a client trades for tokens at the token endpoint
+#[derive(Debug, Clone)]
+pub struct AuthorizationCode(Secret<String>);
+
+impl PartialEq for AuthorizationCode {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.expose_secret() == other.0.expose_secret()
+    }
+}
+
+impl AuthorizationCode {
+    pub fn parse(code: Secret<String>) -> Result<Self> {
+        let is_valid = code.expose_secret().len() == AUTHORIZATION_CODE_LENGTH
+            && code.expose_secret().chars().all(|c| c.is_ascii_alphanumeric());
+
+        if is_valid {
+            Ok(Self(code))
+        } else {
+            Err(eyre!("Invalid authorization code"))
+        }
+    }
+}
+
+impl Default for AuthorizationCode {
+    fn default() -> Self {
+        Self(Secret::new(random_string(AUTHORIZATION_CODE_LENGTH)))
+    }
+}
+
+impl AsRef<Secret<String>> for AuthorizationCode {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.0
+    }
+}
+
+// What the user consented to when the code was issued, checked again when it's redeemed
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthorizationCodeEntry {
+    pub client_id: String,
+    pub redirect_uri: String,
+    pub email: Email,
+    pub scope: String,
+    pub nonce: Option<String>,
+    pub code_challenge: CodeChallenge,
+    // When the user logged in, as a Unix timestamp
+    pub auth_time: i64,
+}
+
+// PKCE (RFC 7636) binds a code to the client that requested it. Only the S256 method is
+// supported, as the plain method offers no protection if the request is intercepted.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CodeChallenge(String);
+
+impl CodeChallenge {
+    pub fn parse(challenge: String, method: Option<&str>) -> Result<Self> {
+        if method != Some("S256") {
+            return Err(eyre!("code_challenge_method must be S256"));
+        }
+
+        // A base64url encoded SHA-256 hash is always 43 characters long
+        let is_valid = challenge.len() == 43
+            && challenge
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+        if is_valid {
+            Ok(Self(challenge))
+        } else {
+            Err(eyre!("Invalid code_challenge"))
+        }
+    }
+
+    pub fn verify(&self, code_verifier: &Secret<String>) -> bool {
+        let verifier = code_verifier.expose_secret();
+
+        // RFC 7636 section 4.1
+        let is_valid_verifier = (43..=128).contains(&verifier.len())
+            && verifier
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-._~".contains(c));
+
+        is_valid_verifier && URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes())) == self.0
+    }
+}
+
+impl AsRef<str> for CodeChallenge {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// Check a space-delimited scope parameter, defaulting to `openid`
+pub fn parse_scope(scope: Option<&str>) -> Result<String> {
+    let scope = scope.unwrap_or("openid");
+
+    if let Some(unsupported) = scope
+        .split_whitespace()
+        .find(|scope| !SUPPORTED_SCOPES.contains(scope))
+    {
+        return Err(eyre!("Unsupported scope: {}", unsupported));
+    }
+
+    Ok(scope.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+// RFC 6749 section 3.3: printable ASCII, except for spaces, double quotes and backslashes
+pub(crate) fn is_scope_token(scope: &str) -> bool {
+    !scope.is_empty()
+        && scope
+            .chars()
+            .all(|c| c.is_ascii_graphic() && c != '"' && c != '\\')
+}
+
+const AUTHORIZATION_CODE_LENGTH: usize = 32;
+const CLIENT_SECRET_LENGTH: usize = 48;
+
+pub(crate) fn random_string(length: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(length)
+        .map(char::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Example from RFC 7636 appendix B
+    const VERIFIER: &str = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+    const CHALLENGE: &str = "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM";
+
+    #[test]
+    fn test_code_challenge_verify() {
+        let challenge = CodeChallenge::parse(CHALLENGE.to_owned(), Some("S256")).unwrap();
+
+        assert!(challenge.verify(&Secret::new(VERIFIER.to_owned())));
+        assert!(!challenge.verify(&Secret::new(VERIFIER.replace('d', "e"))));
+        assert!(!challenge.verify(&Secret::new("short".to_owned())));
+    }
+
+    #[test]
+    fn test_code_challenge_requires_s256() {
+        assert!(CodeChallenge::parse(CHALLENGE.to_owned(), Some("plain")).is_err());
+        assert!(CodeChallenge::parse(CHALLENGE.to_owned(), None).is_err());
+        assert!(CodeChallenge::parse("too_short".to_owned(), Some("S256")).is_err());
+    }
+
+    #[test]
+    fn test_oauth_client_redirect_uris() {
+        let client = OAuthClient::new(
+            "app".to_owned(),
+            vec!["https://app.example.com/callback".to_owned()],
+            false,
+        )
+        .unwrap();
+
+        assert!(client.allows_redirect_uri("https://app.example.com/callback"));
+        assert!(!client.allows_redirect_uri("https://app.example.com/callback/"));
+        assert!(!client.allows_redirect_uri("https://app.example.com/callback?next=/"));
+        assert!(!client.allows_redirect_uri("https://evil.example.com/callback"));
+    }
+
+    #[test]
+    fn test_oauth_client_rejects_invalid_redirect_uris() {
+        let test_cases = [
+            vec![],
+            vec!["not a uri".to_owned()],
+            vec!["https://app.example.com/callback#fragment".to_owned()],
+        ];
+
+        for redirect_uris in test_cases {
+            assert!(OAuthClient::new("app".to_owned(), redirect_uris, false).is_err());
+        }
+    }
+
+    #[test]
+    fn test_service_client_scopes() {
+        let client = OAuthClient::service(
+            "reports".to_owned(),
+            vec!["reports:read".to_owned(), INTROSPECT_SCOPE.to_owned()],
+        )
+        .unwrap();
+
+        assert!(client.confidential);
+        assert!(client.redirect_uris.is_empty());
+        assert_eq!(client.grant_scope(None).unwrap(), "reports:read introspect");
+        assert_eq!(client.grant_scope(Some("reports:read")).unwrap(), "reports:read");
+        assert!(client.grant_scope(Some("reports:read reports:write")).is_err());
+
+        assert!(OAuthClient::service("reports".to_owned(), vec![]).is_err());
+        assert!(OAuthClient::service("reports".to_owned(), vec!["two words".to_owned()]).is_err());
+    }
+
+    #[test]
+    fn test_parse_scope() {
+        assert_eq!(parse_scope(None).unwrap(), "openid");
+        assert_eq!(parse_scope(Some("openid  email")).unwrap(), "openid email");
+        assert!(parse_scope(Some("openid admin")).is_err());
+    }
+
+    #[test]
+    fn test_authorization_code_parse() {
+        let code = AuthorizationCode::default();
+        assert!(AuthorizationCode::parse(code.as_ref().clone()).is_ok());
+        assert!(AuthorizationCode::parse(Secret::new("invalid".to_owned())).is_err());
+    }
+}
@@ -0,0 +1,195 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use color_eyre::eyre::{eyre, Context, Result};
+use secrecy::{ExposeSecret, Secret};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use super::{oauth::random_string, Email};
+
+// Users have this long to log in at an external provider
+pub const OIDC_LOGIN_TTL_SECONDS: u64 = 600;
+
+// An external OpenID Connect provider users can log in with, such as Google or a
+// corporate identity provider
+#[derive(Debug, Clone, Deserialize)]
+pub struct OidcProviderConfig {
+    // Identifies the provider in the login and callback URLs
+    pub name: String,
+    // The provider's metadata is discovered from its issuer URL
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: Secret<String>,
+    #[serde(default = "default_scopes")]
+    pub scopes: Vec<String>,
+}
+
+impl OidcProviderConfig {
+    // Load the providers listed in a JSON manifest
+    pub fn from_manifest(path: &str) -> Result<Vec<Self>> {
+        let manifest = std::fs::read_to_string(path)
+            .wrap_err(format!("Failed to read OIDC provider manifest: {}", path))?;
+        let providers: Vec<Self> = serde_json::from_str(&manifest)
+            .wrap_err("Failed to parse OIDC provider manifest")?;
+
+        for provider in &providers {
+            provider.validate()?;
+        }
+
+        Ok(providers)
+    }
+
+    fn validate(&self) -> Result<()> {
+        let is_valid_name = !self.name.is_empty()
+            && self
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+        if !is_valid_name {
+            return Err(eyre!("Invalid OIDC provider name: {}", self.name));
+        }
+
+        url::Url::parse(&self.issuer)
+            .wrap_err(format!("Invalid issuer of OIDC provider {}", self.name))?;
+
+        if !self.scopes.iter().any(|scope| scope == "openid") {
+            return Err(eyre!("Scopes of OIDC provider {} must include openid", self.name));
+        }
+
+        Ok(())
+    }
+}
+
+fn default_scopes() -> Vec<String> {
+    vec!["openid".to_owned(), "email".to_owned()]
+}
+
+// Ties the provider's callback to the login the browser started, so logins can't be
+// completed in someone else's browser
+#[derive(Debug, Clone)]
+pub struct OidcState(Secret<String>);
+
+impl PartialEq for OidcState {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.expose_secret() == other.0.expose_secret()
+    }
+}
+
+impl OidcState {
+    pub fn parse(state: Secret<String>) -> Result<Self> {
+        let is_valid = state.expose_secret().len() == OIDC_STATE_LENGTH
+            && state.expose_secret().chars().all(|c| c.is_ascii_alphanumeric());
+
+        if is_valid {
+            Ok(Self(state))
+        } else {
+            Err(eyre!("Invalid OIDC state"))
+        }
+    }
+}
+
+impl Default for OidcState {
+    fn default() -> Self {
+        Self(Secret::new(random_string(OIDC_STATE_LENGTH)))
+    }
+}
+
+impl AsRef<Secret<String>> for OidcState {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.0
+    }
+}
+
+// A login with an external provider that was started but not completed yet
+#[derive(Debug, Clone)]
+pub struct OidcLogin {
+    pub provider: String,
+    // Must come back in the ID token, so a token issued for another login is rejected
+    pub nonce: String,
+    // PKCE verifier of the authorization code the provider will return
+    pub code_verifier: Secret<String>,
+    // Where to send the user once they are logged in
+    pub return_to: Option<String>,
+    // The logged in user who asked to link the provider's account to theirs, if any
+    pub link_to: Option<Email>,
+}
+
+impl OidcLogin {
+    pub fn new(provider: String, return_to: Option<String>) -> Self {
+        Self {
+            provider,
+            nonce: random_string(OIDC_NONCE_LENGTH),
+            code_verifier: Secret::new(random_string(CODE_VERIFIER_LENGTH)),
+            return_to,
+            link_to: None,
+        }
+    }
+
+    // S256 challenge of the code verifier (RFC 7636 section 4.2)
+    pub fn code_challenge(&self) -> String {
+        URL_SAFE_NO_PAD.encode(Sha256::digest(self.code_verifier.expose_secret().as_bytes()))
+    }
+}
+
+// A user's account at an external provider, linked to a user of this service
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExternalIdentity {
+    pub provider: String,
+    // The `sub` claim of the provider's ID tokens, which never changes for an account
+    pub subject: String,
+    pub email: Email,
+}
+
+const OIDC_STATE_LENGTH: usize = 32;
+const OIDC_NONCE_LENGTH: usize = 32;
+const CODE_VERIFIER_LENGTH: usize = 64;
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::CodeChallenge;
+    use super::*;
+
+    #[test]
+    fn test_generated_state_parses() {
+        let state = OidcState::default();
+        assert_eq!(OidcState::parse(state.as_ref().clone()).unwrap(), state);
+        assert_ne!(OidcState::default(), state);
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed_state() {
+        assert!(OidcState::parse(Secret::new("".to_owned())).is_err());
+        assert!(OidcState::parse(Secret::new("short".to_owned())).is_err());
+        assert!(OidcState::parse(Secret::new(format!("{}!", "a".repeat(31)))).is_err());
+    }
+
+    #[test]
+    fn test_provider_config_validation() {
+        let config: OidcProviderConfig = serde_json::from_value(serde_json::json!({
+            "name": "corp",
+            "issuer": "https://login.example.com",
+            "client_id": "client",
+            "client_secret": "secret",
+        }))
+        .unwrap();
+        assert_eq!(config.scopes, vec!["openid", "email"]);
+        assert!(config.validate().is_ok());
+
+        let invalid_configs = [
+            OidcProviderConfig { name: "corp/../".to_owned(), ..config.clone() },
+            OidcProviderConfig { issuer: "not a url".to_owned(), ..config.clone() },
+            OidcProviderConfig { scopes: vec!["email".to_owned()], ..config.clone() },
+        ];
+
+        for config in invalid_configs {
+            assert!(config.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn test_code_challenge_matches_verifier() {
+        let login = OidcLogin::new("corp".to_owned(), None);
+        let challenge = CodeChallenge::parse(login.code_challenge(), Some("S256")).unwrap();
+        assert!(challenge.verify(&login.code_verifier));
+    }
+}
@@ -0,0 +1,423 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ciborium::value::Value;
+use color_eyre::eyre::{eyre, Context, Result};
+use p256::ecdsa::{signature::Verifier, Signature, VerifyingKey};
+use rand::RngCore;
+use secrecy::ExposeSecret;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use super::Email;
+
+// Users have this long to complete a passkey ceremony
+pub const PASSKEY_CHALLENGE_TTL_SECONDS: u64 = 300;
+
+// The site passkeys are bound to (WebAuthn section 5.4.2)
+#[derive(Debug, Clone)]
+pub struct RelyingParty {
+    // Domain passkeys are scoped to, such as `example.com`
+    pub id: String,
+    pub name: String,
+    // Origin of the pages that run the ceremonies, such as `https://example.com`
+    pub origin: String,
+}
+
+// A passkey registered by a user
+#[derive(Debug, Clone, PartialEq)]
+pub struct Passkey {
+    // Base64url encoded credential ID chosen by the authenticator
+    pub id: String,
+    pub email: Email,
+    pub name: String,
+    // SEC1 encoded P-256 public key
+    pub public_key: Vec<u8>,
+    // Number of signatures the authenticator reports having made, used to detect cloned keys
+    pub sign_count: u32,
+    pub created_at: i64,
+    pub last_used_at: Option<i64>,
+}
+
+// What a passkey challenge was issued for
+#[derive(Debug, Clone, PartialEq)]
+pub enum PasskeyCeremony {
+    Registration(Email),
+    // Second factor after the user's password was checked
+    SecondFactor(Email),
+    // Passwordless login with a passkey stored on the authenticator
+    Login,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PasskeyChallenge {
+    // Base64url encoded random bytes the authenticator signs
+    pub challenge: String,
+    pub ceremony: PasskeyCeremony,
+}
+
+impl PasskeyChallenge {
+    pub fn new(ceremony: PasskeyCeremony) -> Self {
+        let mut bytes = [0u8; CHALLENGE_LENGTH];
+        rand::thread_rng().fill_bytes(&mut bytes);
+
+        Self {
+            challenge: URL_SAFE_NO_PAD.encode(bytes),
+            ceremony,
+        }
+    }
+}
+
+// The client data the browser passes to the authenticator (WebAuthn section 5.8.1)
+#[derive(Debug)]
+pub struct ClientData {
+    pub challenge: String,
+    hash: [u8; 32],
+}
+
+#[derive(Deserialize)]
+struct CollectedClientData {
+    #[serde(rename = "type")]
+    ceremony_type: String,
+    challenge: String,
+    origin: String,
+}
+
+impl ClientData {
+    pub fn parse(client_data_json: &[u8], ceremony_type: &str, rp: &RelyingParty) -> Result<Self> {
+        let client_data: CollectedClientData = serde_json::from_slice(client_data_json)
+            .wrap_err("Invalid client data")?;
+
+        if client_data.ceremony_type != ceremony_type {
+            return Err(eyre!("Unexpected ceremony type: {}", client_data.ceremony_type));
+        }
+
+        // Stops phishing sites from relaying a ceremony
+        if client_data.origin != rp.origin {
+            return Err(eyre!("Unexpected origin: {}", client_data.origin));
+        }
+
+        Ok(Self {
+            challenge: client_data.challenge,
+            hash: Sha256::digest(client_data_json).into(),
+        })
+    }
+}
+
+// A newly created passkey, as returned by the authenticator
+#[derive(Debug)]
+pub struct NewCredential {
+    pub id: String,
+    pub public_key: Vec<u8>,
+    pub sign_count: u32,
+}
+
+// Check the authenticator's response to a registration challenge (WebAuthn section 7.1). Only
+// ES256 keys are accepted, and attestation statements are not checked as none are requested.
+pub fn verify_registration(
+    rp: &RelyingParty,
+    attestation_object: &[u8],
+    require_user_verification: bool,
+) -> Result<NewCredential> {
+    let attestation: Value = ciborium::de::from_reader(attestation_object)
+        .wrap_err("Invalid attestation object")?;
+
+    let auth_data = attestation
+        .as_map()
+        .and_then(|map| text_field(map, "authData"))
+        .and_then(Value::as_bytes)
+        .ok_or(eyre!("Attestation object has no authenticator data"))?;
+
+    let auth_data = AuthenticatorData::parse(auth_data)?;
+    auth_data.verify(rp, require_user_verification)?;
+
+    let (credential_id, public_key) = auth_data
+        .attested_credential
+        .ok_or(eyre!("Authenticator data has no credential"))?;
+
+    Ok(NewCredential {
+        id: URL_SAFE_NO_PAD.encode(credential_id),
+        public_key: parse_public_key(&public_key)?,
+        sign_count: auth_data.sign_count,
+    })
+}
+
+// Check the authenticator's response to an authentication challenge (WebAuthn section 7.2)
+// and return the new sign count
+pub fn verify_assertion(
+    rp: &RelyingParty,
+    passkey: &Passkey,
+    client_data: &ClientData,
+    authenticator_data: &[u8],
+    signature: &[u8],
+    require_user_verification: bool,
+) -> Result<u32> {
+    let auth_data = AuthenticatorData::parse(authenticator_data)?;
+    auth_data.verify(rp, require_user_verification)?;
+
+    let verifying_key = VerifyingKey::from_sec1_bytes(&passkey.public_key)
+        .wrap_err("Invalid stored public key")?;
+    let signature = Signature::from_der(signature).wrap_err("Invalid signature encoding")?;
+
+    let signed_data = [authenticator_data, &client_data.hash].concat();
+    verifying_key
+        .verify(&signed_data, &signature)
+        .wrap_err("Invalid signature")?;
+
+    // Authenticators that don't count signatures always report 0, any other count must
+    // go up or the key may have been cloned
+    let counts_signatures = auth_data.sign_count != 0 || passkey.sign_count != 0;
+    if counts_signatures && auth_data.sign_count <= passkey.sign_count {
+        return Err(eyre!("Sign count did not increase"));
+    }
+
+    Ok(auth_data.sign_count)
+}
+
+// Opaque identifier of the user given to authenticators, which must not contain the email
+pub fn user_handle(email: &Email) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(email.as_ref().expose_secret().as_bytes()))
+}
+
+// WebAuthn section 6.1
+struct AuthenticatorData {
+    rp_id_hash: [u8; 32],
+    flags: u8,
+    sign_count: u32,
+    // Credential ID and COSE encoded public key
+    attested_credential: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+impl AuthenticatorData {
+    fn parse(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 37 {
+            return Err(eyre!("Authenticator data is too short"));
+        }
+
+        let rp_id_hash = bytes[..32].try_into()?;
+        let flags = bytes[32];
+        let sign_count = u32::from_be_bytes(bytes[33..37].try_into()?);
+
+        let attested_credential = if flags & FLAG_ATTESTED_CREDENTIAL != 0 {
+            // AAGUID (16 bytes), credential ID length (2 bytes), credential ID, public key
+            let data = &bytes[37..];
+            let length = data
+                .get(16..18)
+                .map(|length| u16::from_be_bytes([length[0], length[1]]) as usize)
+                .ok_or(eyre!("Attested credential data is too short"))?;
+            let credential_id = data
+                .get(18..18 + length)
+                .ok_or(eyre!("Attested credential data is too short"))?;
+            let public_key = &data[18 + length..];
+            Some((credential_id.to_vec(), public_key.to_vec()))
+        } else {
+            None
+        };
+
+        Ok(Self { rp_id_hash, flags, sign_count, attested_credential })
+    }
+
+    fn verify(&self, rp: &RelyingParty, require_user_verification: bool) -> Result<()> {
+        if self.rp_id_hash != <[u8; 32]>::from(Sha256::digest(rp.id.as_bytes())) {
+            return Err(eyre!("Credential is scoped to another relying party"));
+        }
+
+        if self.flags & FLAG_USER_PRESENT == 0 {
+            return Err(eyre!("User was not present"));
+        }
+
+        if require_user_verification && self.flags & FLAG_USER_VERIFIED == 0 {
+            return Err(eyre!("User was not verified"));
+        }
+
+        Ok(())
+    }
+}
+
+// Convert an ES256 COSE key (RFC 9053 section 7.1.1) to a SEC1 encoded public key
+fn parse_public_key(cose_key: &[u8]) -> Result<Vec<u8>> {
+    let key: Value = ciborium::de::from_reader(cose_key).wrap_err("Invalid COSE key")?;
+    let key = key.as_map().ok_or(eyre!("COSE key is not a map"))?;
+
+    let integer = |label| int_field(key, label).and_then(Value::as_integer).map(i128::from);
+    let is_es256 = integer(COSE_KEY_TYPE) == Some(COSE_KEY_TYPE_EC2)
+        && integer(COSE_ALGORITHM) == Some(COSE_ALGORITHM_ES256)
+        && integer(COSE_CURVE) == Some(COSE_CURVE_P256);
+
+    if !is_es256 {
+        return Err(eyre!("Only ES256 keys are supported"));
+    }
+
+    let coordinate = |label| {
+        int_field(key, label)
+            .and_then(Value::as_bytes)
+            .filter(|bytes| bytes.len() == 32)
+            .ok_or(eyre!("Invalid COSE key coordinate"))
+    };
+
+    let public_key = [&[0x04][..], coordinate(COSE_X)?, coordinate(COSE_Y)?].concat();
+    VerifyingKey::from_sec1_bytes(&public_key).wrap_err("Public key is not on the curve")?;
+
+    Ok(public_key)
+}
+
+fn text_field<'a>(map: &'a [(Value, Value)], name: &str) -> Option<&'a Value> {
+    map.iter()
+        .find(|(key, _)| key.as_text() == Some(name))
+        .map(|(_, value)| value)
+}
+
+fn int_field(map: &[(Value, Value)], label: i128) -> Option<&Value> {
+    map.iter()
+        .find(|(key, _)| key.as_integer().map(i128::from) == Some(label))
+        .map(|(_, value)| value)
+}
+
+const CHALLENGE_LENGTH: usize = 32;
+
+const FLAG_USER_PRESENT: u8 = 0x01;
+const FLAG_USER_VERIFIED: u8 = 0x04;
+const FLAG_ATTESTED_CREDENTIAL: u8 = 0x40;
+
+const COSE_KEY_TYPE: i128 = 1;
+const COSE_ALGORITHM: i128 = 3;
+const COSE_CURVE: i128 = -1;
+const COSE_X: i128 = -2;
+const COSE_Y: i128 = -3;
+const COSE_KEY_TYPE_EC2: i128 = 2;
+const COSE_ALGORITHM_ES256: i128 = -7;
+const COSE_CURVE_P256: i128 = 1;
+
+#[cfg(test)]
+mod tests {
+    use p256::ecdsa::{signature::Signer, SigningKey};
+    use secrecy::Secret;
+    use super::*;
+
+    fn rp() -> RelyingParty {
+        RelyingParty {
+            id: "localhost".to_owned(),
+            name: "Auth Service".to_owned(),
+            origin: "http://localhost:3000".to_owned(),
+        }
+    }
+
+    fn authenticator_data(rp_id: &str, flags: u8, sign_count: u32) -> Vec<u8> {
+        [&Sha256::digest(rp_id.as_bytes())[..], &[flags], &sign_count.to_be_bytes()].concat()
+    }
+
+    fn client_data(ceremony_type: &str, challenge: &str) -> Vec<u8> {
+        serde_json::to_vec(&serde_json::json!({
+            "type": ceremony_type,
+            "challenge": challenge,
+            "origin": "http://localhost:3000",
+        }))
+        .unwrap()
+    }
+
+    fn attestation_object(key: &SigningKey, credential_id: &[u8], flags: u8) -> Vec<u8> {
+        let point = key.verifying_key().to_encoded_point(false);
+        let cose_key = Value::Map(vec![
+            (Value::from(1), Value::from(2)),
+            (Value::from(3), Value::from(-7)),
+            (Value::from(-1), Value::from(1)),
+            (Value::from(-2), Value::Bytes(point.x().unwrap().to_vec())),
+            (Value::from(-3), Value::Bytes(point.y().unwrap().to_vec())),
+        ]);
+        let mut cose_key_bytes = Vec::new();
+        ciborium::ser::into_writer(&cose_key, &mut cose_key_bytes).unwrap();
+
+        let auth_data = [
+            authenticator_data("localhost", flags | FLAG_ATTESTED_CREDENTIAL, 0),
+            vec![0; 16],
+            (credential_id.len() as u16).to_be_bytes().to_vec(),
+            credential_id.to_vec(),
+            cose_key_bytes,
+        ]
+        .concat();
+
+        let attestation = Value::Map(vec![
+            (Value::from("fmt"), Value::from("none")),
+            (Value::from("attStmt"), Value::Map(vec![])),
+            (Value::from("authData"), Value::Bytes(auth_data)),
+        ]);
+        let mut bytes = Vec::new();
+        ciborium::ser::into_writer(&attestation, &mut bytes).unwrap();
+        bytes
+    }
+
+    fn passkey(key: &SigningKey, sign_count: u32) -> Passkey {
+        Passkey {
+            id: "credential".to_owned(),
+            email: Email::parse(Secret::new("test@example.com".to_owned())).unwrap(),
+            name: "Laptop".to_owned(),
+            public_key: key.verifying_key().to_encoded_point(false).as_bytes().to_vec(),
+            sign_count,
+            created_at: 0,
+            last_used_at: None,
+        }
+    }
+
+    fn sign(key: &SigningKey, auth_data: &[u8], client_data: &[u8]) -> Vec<u8> {
+        let signed_data = [auth_data, &Sha256::digest(client_data)[..]].concat();
+        let signature: Signature = key.sign(&signed_data);
+        signature.to_der().as_bytes().to_vec()
+    }
+
+    #[test]
+    fn test_client_data_checks_type_and_origin() {
+        let data = client_data("webauthn.get", "challenge");
+
+        assert_eq!(ClientData::parse(&data, "webauthn.get", &rp()).unwrap().challenge, "challenge");
+        assert!(ClientData::parse(&data, "webauthn.create", &rp()).is_err());
+
+        let other_origin = RelyingParty { origin: "https://evil.example.com".to_owned(), ..rp() };
+        assert!(ClientData::parse(&data, "webauthn.get", &other_origin).is_err());
+    }
+
+    #[test]
+    fn test_verify_registration() {
+        let key = SigningKey::random(&mut rand::thread_rng());
+
+        let credential =
+            verify_registration(&rp(), &attestation_object(&key, b"credential", FLAG_USER_PRESENT), false)
+                .unwrap();
+
+        assert_eq!(credential.id, URL_SAFE_NO_PAD.encode(b"credential"));
+        assert_eq!(credential.public_key, passkey(&key, 0).public_key);
+
+        let other_rp = RelyingParty { id: "example.com".to_owned(), ..rp() };
+        assert!(verify_registration(&other_rp, &attestation_object(&key, b"credential", FLAG_USER_PRESENT), false).is_err());
+        assert!(verify_registration(&rp(), &attestation_object(&key, b"credential", FLAG_USER_PRESENT), true).is_err());
+    }
+
+    #[test]
+    fn test_verify_assertion() {
+        let key = SigningKey::random(&mut rand::thread_rng());
+        let data = client_data("webauthn.get", "challenge");
+        let client_data = ClientData::parse(&data, "webauthn.get", &rp()).unwrap();
+        let auth_data = authenticator_data("localhost", FLAG_USER_PRESENT | FLAG_USER_VERIFIED, 5);
+        let signature = sign(&key, &auth_data, &data);
+
+        let sign_count =
+            verify_assertion(&rp(), &passkey(&key, 4), &client_data, &auth_data, &signature, true).unwrap();
+        assert_eq!(sign_count, 5);
+
+        let other_key = SigningKey::random(&mut rand::thread_rng());
+        assert!(verify_assertion(&rp(), &passkey(&other_key, 4), &client_data, &auth_data, &signature, true).is_err());
+    }
+
+    #[test]
+    fn test_verify_assertion_rejects_sign_count_that_did_not_increase() {
+        let key = SigningKey::random(&mut rand::thread_rng());
+        let data = client_data("webauthn.get", "challenge");
+        let client_data = ClientData::parse(&data, "webauthn.get", &rp()).unwrap();
+
+        let auth_data = authenticator_data("localhost", FLAG_USER_PRESENT, 5);
+        let signature = sign(&key, &auth_data, &data);
+        assert!(verify_assertion(&rp(), &passkey(&key, 5), &client_data, &auth_data, &signature, false).is_err());
+
+        // Authenticators without a counter always report 0
+        let auth_data = authenticator_data("localhost", FLAG_USER_PRESENT, 0);
+        let signature = sign(&key, &auth_data, &data);
+        assert!(verify_assertion(&rp(), &passkey(&key, 0), &client_data, &auth_data, &signature, false).is_ok());
+    }
+}
@@ -0,0 +1,62 @@
+use color_eyre::eyre::{eyre, Result};
+use secrecy::{ExposeSecret, Secret};
+
+// Phone number in E.164 format, the form SMS providers expect
+#[derive(Debug, Clone)]
+pub struct PhoneNumber(Secret<String>);
+
+impl PartialEq for PhoneNumber {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.expose_secret() == other.0.expose_secret()
+    }
+}
+
+impl PhoneNumber {
+    // Spaces, dashes and parentheses people type for readability are dropped
+    pub fn parse(s: Secret<String>) -> Result<PhoneNumber> {
+        let normalized: String = s
+            .expose_secret()
+            .chars()
+            .filter(|c| !matches!(c, ' ' | '-' | '(' | ')'))
+            .collect();
+
+        let digits = normalized.strip_prefix('+').unwrap_or_default();
+        let valid = (MIN_DIGITS..=MAX_DIGITS).contains(&digits.len())
+            && digits.bytes().all(|b| b.is_ascii_digit())
+            && !digits.starts_with('0');
+
+        if valid {
+            Ok(Self(Secret::new(normalized)))
+        } else {
+            Err(eyre!("{} is not a valid phone number.", s.expose_secret()))
+        }
+    }
+}
+
+impl AsRef<Secret<String>> for PhoneNumber {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.0
+    }
+}
+
+const MIN_DIGITS: usize = 8;
+const MAX_DIGITS: usize = 15;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_parse_international_numbers() {
+        let phone_number = PhoneNumber::parse(Secret::new("+1 (415) 555-2671".to_owned())).unwrap();
+
+        assert_eq!(phone_number.as_ref().expose_secret(), "+14155552671");
+    }
+
+    #[test]
+    fn should_reject_invalid_numbers() {
+        for number in ["", "+", "4155552671", "+0155552671", "+1415555267a", "+1234567", "+1234567890123456"] {
+            assert!(PhoneNumber::parse(Secret::new(number.to_owned())).is_err(), "{}", number);
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use color_eyre::eyre::{eyre, Result};
+use rand::Rng;
+use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
+
+// How many codes users get each time they are generated
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+// Single-use code that stands in for the user's second factor when they lost access to it.
+// Codes are 80 bit random strings, so like API keys they are stored with a fast hash.
+#[derive(Debug, Clone)]
+pub struct RecoveryCode(Secret<String>);
+
+impl PartialEq for RecoveryCode {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.expose_secret() == other.0.expose_secret()
+    }
+}
+
+impl RecoveryCode {
+    // Users may type codes in any case, with or without the dashes
+    pub fn parse(code: Secret<String>) -> Result<Self> {
+        let normalized: String = code
+            .expose_secret()
+            .trim()
+            .chars()
+            .filter(|c| *c != '-')
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+
+        if normalized.len() == RECOVERY_CODE_LENGTH && normalized.bytes().all(|b| ALPHABET.contains(&b)) {
+            Ok(Self(Secret::new(normalized)))
+        } else {
+            Err(eyre!("Invalid recovery code"))
+        }
+    }
+
+    pub fn generate_set() -> Vec<Self> {
+        (0..RECOVERY_CODE_COUNT).map(|_| Self::default()).collect()
+    }
+
+    pub fn hash(&self) -> String {
+        format!("{:x}", Sha256::digest(self.0.expose_secret().as_bytes()))
+    }
+
+    // Groups of four characters, the way codes are shown to users
+    pub fn formatted(&self) -> Secret<String> {
+        let code = self.0.expose_secret();
+        let groups: Vec<&str> = (0..code.len())
+            .step_by(GROUP_LENGTH)
+            .map(|start| &code[start..start + GROUP_LENGTH])
+            .collect();
+        Secret::new(groups.join("-"))
+    }
+}
+
+impl Default for RecoveryCode {
+    fn default() -> Self {
+        let mut rng = rand::thread_rng();
+        let code = (0..RECOVERY_CODE_LENGTH)
+            .map(|_| ALPHABET[rng.gen_range(0..ALPHABET.len())] as char)
+            .collect();
+        Self(Secret::new(code))
+    }
+}
+
+// Lowercase base32, 5 bits per character. It leaves out 0, 1 and 8, which are easily confused
+// with letters.
+const ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyz234567";
+const RECOVERY_CODE_LENGTH: usize = 16;
+const GROUP_LENGTH: usize = 4;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_parse_formatted_codes() {
+        let code = RecoveryCode::default();
+        let formatted = code.formatted();
+
+        assert_eq!(formatted.expose_secret().len(), 19);
+        assert_eq!(RecoveryCode::parse(formatted.clone()).unwrap(), code);
+
+        let uppercase = Secret::new(format!(" {} ", formatted.expose_secret().to_uppercase()));
+        assert_eq!(RecoveryCode::parse(uppercase).unwrap().hash(), code.hash());
+    }
+
+    #[test]
+    fn should_reject_invalid_codes() {
+        for code in ["", "123456", "abcd-efgh-ijkl", "abcd-efgh-ijkl-mno0", "abcd-efgh-ijkl-mnop-qrst"] {
+            assert!(RecoveryCode::parse(Secret::new(code.to_owned())).is_err(), "{}", code);
+        }
+    }
+
+    #[test]
+    fn should_generate_distinct_codes() {
+        let codes = RecoveryCode::generate_set();
+
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        for (i, code) in codes.iter().enumerate() {
+            assert!(codes[i + 1..].iter().all(|other| other != code));
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use super::{Email, TwoFAMethod};
+
+// A device the user is logged in on. A session starts with every successful login
+// and lasts as long as its refresh token family.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Session {
+    pub id: String,
+    pub email: Email,
+    pub ip: String,
+    pub user_agent: Option<String>,
+    pub created_at: i64,
+    pub last_seen: i64,
+    // When the user last proved who they are, at login or by reauthenticating
+    pub auth_time: i64,
+    // How they proved it then
+    pub amr: Vec<AuthMethod>,
+    // Wrong passwords and codes given to reauthenticate since then
+    pub failed_reauthentications: u32,
+}
+
+impl Session {
+    pub fn new(email: Email, ip: String, user_agent: Option<String>, amr: Vec<AuthMethod>) -> Self {
+        let now = Utc::now().timestamp();
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            email,
+            ip,
+            user_agent,
+            created_at: now,
+            last_seen: now,
+            auth_time: now,
+            amr,
+            failed_reauthentications: 0,
+        }
+    }
+
+    // The `amr` claim of the session's tokens. `mfa` is added when the user proved more
+    // than one factor.
+    pub fn amr_claim(&self) -> Vec<String> {
+        let mut amr: Vec<String> = Vec::with_capacity(self.amr.len() + 1);
+        for method in &self.amr {
+            if !amr.iter().any(|value| value == method.as_str()) {
+                amr.push(method.as_str().to_owned());
+            }
+        }
+
+        if amr.len() > 1 {
+            amr.push("mfa".to_owned());
+        }
+
+        amr
+    }
+}
+
+// Ways a user proves who they are, named after their `amr` values (RFC 8176)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AuthMethod {
+    #[serde(rename = "pwd")]
+    Password,
+    // An emailed code or login link, a code from an authenticator app or a recovery code
+    #[serde(rename = "otp")]
+    Otp,
+    // A code texted to the user
+    #[serde(rename = "sms")]
+    Sms,
+    // A passkey, held by the user's device
+    #[serde(rename = "hwk")]
+    Passkey,
+    // A login through an external OpenID Connect provider
+    #[serde(rename = "fed")]
+    External,
+}
+
+impl AuthMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Password => "pwd",
+            Self::Otp => "otp",
+            Self::Sms => "sms",
+            Self::Passkey => "hwk",
+            Self::External => "fed",
+        }
+    }
+}
+
+// How entering a code of the user's 2FA method proves who they are
+impl From<TwoFAMethod> for AuthMethod {
+    fn from(method: TwoFAMethod) -> Self {
+        match method {
+            TwoFAMethod::Email | TwoFAMethod::Totp => Self::Otp,
+            TwoFAMethod::Sms => Self::Sms,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+    use super::*;
+
+    #[test]
+    fn test_amr_claim() {
+        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+        let session = |amr| Session::new(email.clone(), "127.0.0.1".to_owned(), None, amr);
+
+        assert_eq!(session(vec![AuthMethod::Password]).amr_claim(), vec!["pwd"]);
+        assert_eq!(
+            session(vec![AuthMethod::Password, AuthMethod::Sms]).amr_claim(),
+            vec!["pwd", "sms", "mfa"]
+        );
+        // A login link and an emailed code are both proof of the same mailbox
+        assert_eq!(session(vec![AuthMethod::Otp, AuthMethod::Otp]).amr_claim(), vec!["otp"]);
+    }
+}
@@ -0,0 +1,43 @@
+use color_eyre::eyre::{eyre, Result};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+// Values handed to browsers are signed so they can't be forged. The signed form is
+// `<value>.<hex encoded HMAC-SHA256>`.
+pub fn sign_value(value: &str, key: &[u8]) -> String {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(value.as_bytes());
+    let signature: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+
+    format!("{}.{}", value, signature)
+}
+
+// The value, if it was signed with the key
+pub fn verify_signed_value<'a>(signed: &'a str, key: &[u8]) -> Result<&'a str> {
+    let (value, signature) = signed
+        .rsplit_once('.')
+        .ok_or(eyre!("Value is not signed"))?;
+
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key)?;
+    mac.update(value.as_bytes());
+    let signature = hex_decode(signature).ok_or(eyre!("Malformed signature"))?;
+    mac.verify_slice(&signature)
+        .map_err(|_| eyre!("Invalid signature"))?;
+
+    Ok(value)
+}
+
+fn hex_decode(value: &str) -> Option<Vec<u8>> {
+    if !value.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(value.get(i..i + 2)?, 16).ok())
+        .collect()
+}
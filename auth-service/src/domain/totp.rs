@@ -0,0 +1,250 @@
+use aes_gcm::{
+    aead::{Aead, KeyInit, Payload},
+    Aes256Gcm, Nonce,
+};
+use color_eyre::eyre::{eyre, Result};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use secrecy::{ExposeSecret, Secret};
+use sha1::Sha1;
+
+use super::{Email, TwoFACode};
+
+// RFC 6238 defaults, which every authenticator app supports
+pub const TOTP_DIGITS: u32 = 6;
+pub const TOTP_STEP_SECONDS: u64 = 30;
+// Codes from the previous and next time step are accepted to allow for clock drift
+pub const TOTP_ALLOWED_DRIFT_STEPS: u64 = 1;
+
+// Shared secret between the service and the user's authenticator app
+pub struct TotpSecret(Secret<Vec<u8>>);
+
+impl Clone for TotpSecret {
+    fn clone(&self) -> Self {
+        Self(Secret::new(self.0.expose_secret().clone()))
+    }
+}
+
+impl std::fmt::Debug for TotpSecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("TotpSecret([REDACTED])")
+    }
+}
+
+impl PartialEq for TotpSecret {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.expose_secret() == other.0.expose_secret()
+    }
+}
+
+impl TotpSecret {
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Self(Secret::new(bytes))
+    }
+
+    // What users type into their authenticator app when they can't scan the QR code
+    pub fn to_base32(&self) -> Secret<String> {
+        Secret::new(base32::encode(
+            base32::Alphabet::RFC4648 { padding: false },
+            self.0.expose_secret(),
+        ))
+    }
+
+    // Key URI understood by authenticator apps, usually shown as a QR code
+    pub fn provisioning_uri(&self, email: &Email, issuer: &str) -> Secret<String> {
+        let label = format!("{}:{}", issuer, email.as_ref().expose_secret());
+        Secret::new(format!(
+            "otpauth://totp/{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+            encode(&label),
+            self.to_base32().expose_secret(),
+            encode(issuer),
+            TOTP_DIGITS,
+            TOTP_STEP_SECONDS,
+        ))
+    }
+
+    // Returns the time step the code belongs to if it is valid at `now` and newer than the
+    // last step a code was used for, so that each code can only be used once
+    pub fn verify(&self, code: &TwoFACode, now: u64, last_used_step: Option<u64>) -> Option<u64> {
+        let current_step = now / TOTP_STEP_SECONDS;
+        let first_step = current_step.saturating_sub(TOTP_ALLOWED_DRIFT_STEPS);
+
+        (first_step..=current_step + TOTP_ALLOWED_DRIFT_STEPS)
+            .filter(|step| last_used_step < Some(*step))
+            .find(|step| self.code_at(*step) == *code.as_ref().expose_secret())
+    }
+
+    // HOTP value for a time step (RFC 4226 section 5.3)
+    fn code_at(&self, step: u64) -> String {
+        let mut mac = <Hmac<Sha1> as Mac>::new_from_slice(self.0.expose_secret())
+            .expect("HMAC can take a key of any size");
+        mac.update(&step.to_be_bytes());
+        let hash = mac.finalize().into_bytes();
+
+        let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+        let binary = u32::from_be_bytes([hash[offset], hash[offset + 1], hash[offset + 2], hash[offset + 3]])
+            & 0x7fff_ffff;
+
+        format!("{:0width$}", binary % 10u32.pow(TOTP_DIGITS), width = TOTP_DIGITS as usize)
+    }
+}
+
+impl Default for TotpSecret {
+    fn default() -> Self {
+        let mut bytes = vec![0u8; SECRET_LENGTH];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        Self::new(bytes)
+    }
+}
+
+// A user's authenticator app. It only replaces emailed codes once the user confirmed it
+// with a first code.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TotpEnrollment {
+    pub email: Email,
+    pub secret: TotpSecret,
+    pub confirmed: bool,
+    // Time step of the last code that was accepted
+    pub last_used_step: Option<u64>,
+}
+
+impl TotpEnrollment {
+    pub fn new(email: Email, secret: TotpSecret) -> Self {
+        Self { email, secret, confirmed: false, last_used_step: None }
+    }
+}
+
+// Encrypts TOTP secrets before they are stored, since unlike passwords they can't be hashed
+#[derive(Clone)]
+pub struct TotpCipher(Aes256Gcm);
+
+impl TotpCipher {
+    pub fn new(key: &[u8]) -> Result<Self> {
+        Aes256Gcm::new_from_slice(key)
+            .map(Self)
+            .map_err(|_| eyre!("TOTP encryption key must be 32 bytes"))
+    }
+
+    // The nonce is prepended to the ciphertext. The email is authenticated with it, so a
+    // secret can't be moved to another user's row.
+    pub fn encrypt(&self, email: &Email, secret: &TotpSecret) -> Result<Vec<u8>> {
+        let mut nonce = [0u8; NONCE_LENGTH];
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        let payload = Payload {
+            msg: secret.0.expose_secret(),
+            aad: email.as_ref().expose_secret().as_bytes(),
+        };
+        let ciphertext = self
+            .0
+            .encrypt(Nonce::from_slice(&nonce), payload)
+            .map_err(|_| eyre!("Failed to encrypt TOTP secret"))?;
+
+        Ok([&nonce[..], &ciphertext].concat())
+    }
+
+    pub fn decrypt(&self, email: &Email, encrypted: &[u8]) -> Result<TotpSecret> {
+        if encrypted.len() < NONCE_LENGTH {
+            return Err(eyre!("Encrypted TOTP secret is too short"));
+        }
+        let (nonce, ciphertext) = encrypted.split_at(NONCE_LENGTH);
+
+        let payload = Payload {
+            msg: ciphertext,
+            aad: email.as_ref().expose_secret().as_bytes(),
+        };
+        self.0
+            .decrypt(Nonce::from_slice(nonce), payload)
+            .map(TotpSecret::new)
+            .map_err(|_| eyre!("Failed to decrypt TOTP secret"))
+    }
+}
+
+// Percent-encodes a key URI component. Spaces become %20 rather than +, which not every
+// authenticator app decodes.
+fn encode(component: &str) -> String {
+    url::form_urlencoded::byte_serialize(component.as_bytes())
+        .collect::<String>()
+        .replace('+', "%20")
+}
+
+// 160 bits, as recommended by RFC 4226 section 4
+const SECRET_LENGTH: usize = 20;
+const NONCE_LENGTH: usize = 12;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test vectors from RFC 6238 appendix B, truncated to six digits
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    fn code(code: &str) -> TwoFACode {
+        TwoFACode::parse(Secret::new(code.to_owned())).unwrap()
+    }
+
+    fn email() -> Email {
+        Email::parse(Secret::new("user@example.com".to_owned())).unwrap()
+    }
+
+    #[test]
+    fn should_generate_rfc_6238_codes() {
+        let secret = TotpSecret::new(RFC_SECRET.to_vec());
+
+        assert_eq!(secret.code_at(59 / TOTP_STEP_SECONDS), "287082");
+        assert_eq!(secret.code_at(1_111_111_109 / TOTP_STEP_SECONDS), "081804");
+        assert_eq!(secret.code_at(1_234_567_890 / TOTP_STEP_SECONDS), "005924");
+        assert_eq!(secret.code_at(2_000_000_000 / TOTP_STEP_SECONDS), "279037");
+    }
+
+    #[test]
+    fn should_accept_codes_within_allowed_drift() {
+        let secret = TotpSecret::new(RFC_SECRET.to_vec());
+        let now = 1_234_567_890;
+        let step = now / TOTP_STEP_SECONDS;
+
+        assert_eq!(secret.verify(&code("005924"), now, None), Some(step));
+        assert_eq!(secret.verify(&code(&secret.code_at(step - 1)), now, None), Some(step - 1));
+        assert_eq!(secret.verify(&code(&secret.code_at(step + 1)), now, None), Some(step + 1));
+        assert_eq!(secret.verify(&code(&secret.code_at(step - 2)), now, None), None);
+        assert_eq!(secret.verify(&code(&secret.code_at(step + 2)), now, None), None);
+    }
+
+    #[test]
+    fn should_reject_codes_of_used_time_steps() {
+        let secret = TotpSecret::new(RFC_SECRET.to_vec());
+        let now = 1_234_567_890;
+        let step = now / TOTP_STEP_SECONDS;
+
+        assert_eq!(secret.verify(&code("005924"), now, Some(step)), None);
+        assert_eq!(secret.verify(&code(&secret.code_at(step - 1)), now, Some(step - 1)), None);
+        assert_eq!(secret.verify(&code("005924"), now, Some(step - 1)), Some(step));
+    }
+
+    #[test]
+    fn should_build_provisioning_uri() {
+        let secret = TotpSecret::new(RFC_SECRET.to_vec());
+
+        assert_eq!(
+            secret.provisioning_uri(&email(), "Auth Service").expose_secret(),
+            "otpauth://totp/Auth%20Service%3Auser%40example.com\
+             ?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&issuer=Auth%20Service\
+             &algorithm=SHA1&digits=6&period=30"
+        );
+    }
+
+    #[test]
+    fn should_round_trip_encrypted_secrets() {
+        let cipher = TotpCipher::new(&[7u8; 32]).unwrap();
+        let secret = TotpSecret::default();
+
+        let encrypted = cipher.encrypt(&email(), &secret).unwrap();
+
+        assert!(!encrypted.windows(SECRET_LENGTH).any(|window| window == secret.0.expose_secret().as_slice()));
+        assert_eq!(cipher.decrypt(&email(), &encrypted).unwrap(), secret);
+
+        let other_email = Email::parse(Secret::new("other@example.com".to_owned())).unwrap();
+        assert!(cipher.decrypt(&other_email, &encrypted).is_err());
+        assert!(TotpCipher::new(&[7u8; 16]).is_err());
+    }
+}
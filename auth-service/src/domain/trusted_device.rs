@@ -0,0 +1,65 @@
+use chrono::Utc;
+use color_eyre::eyre::Result;
+
+use super::{sign_value, verify_signed_value, Email};
+
+// A browser the user chose to skip 2FA on after verifying it there. The browser keeps the
+// signed id in a cookie, so the device can be revoked by removing it from the store.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrustedDevice {
+    pub id: String,
+    pub email: Email,
+    pub ip: String,
+    pub user_agent: Option<String>,
+    pub created_at: i64,
+    pub expires_at: i64,
+}
+
+impl TrustedDevice {
+    pub fn new(email: Email, ip: String, user_agent: Option<String>, ttl_seconds: i64) -> Self {
+        let now = Utc::now().timestamp();
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            email,
+            ip,
+            user_agent,
+            created_at: now,
+            expires_at: now + ttl_seconds,
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= Utc::now().timestamp()
+    }
+
+    // What the browser keeps in its cookie
+    pub fn cookie_value(&self, key: &[u8]) -> String {
+        sign_value(&self.id, key)
+    }
+
+    // The id of the device a cookie was issued to
+    pub fn id_from_cookie(value: &str, key: &[u8]) -> Result<String> {
+        verify_signed_value(value, key).map(str::to_owned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+    use super::*;
+
+    const KEY: &[u8] = b"trusted-device-test-key";
+
+    #[test]
+    fn should_only_accept_cookies_signed_with_the_key() {
+        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+        let device = TrustedDevice::new(email, "127.0.0.1".to_owned(), None, 60);
+        let cookie = device.cookie_value(KEY);
+
+        assert_eq!(TrustedDevice::id_from_cookie(&cookie, KEY).unwrap(), device.id);
+        assert!(TrustedDevice::id_from_cookie(&cookie, b"other-key").is_err());
+        assert!(TrustedDevice::id_from_cookie(&device.id, KEY).is_err());
+        assert!(TrustedDevice::id_from_cookie(&cookie.replace(&device.id, "other-id"), KEY).is_err());
+        assert!(!device.is_expired());
+    }
+}
@@ -1,14 +1,53 @@
-use crate::domain::{Email, Password};
+use serde::{Deserialize, Serialize};
+
+use crate::domain::{Email, Password, PhoneNumber};
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct User {
     pub email: Email,
     pub password: Password,
     pub requires_2fa: bool,
+    // How the user proves the second factor when `requires_2fa` is set
+    pub two_fa_method: TwoFAMethod,
+    // Only set once the user proved they receive texts at this number
+    pub phone_number: Option<PhoneNumber>,
+    // Roles are granted by administrators and carried in the user's auth tokens
+    pub roles: Vec<String>,
 }
 
 impl User {
     pub fn new(email: Email, password: Password, requires_2fa: bool ) -> User {
-        Self { email, password, requires_2fa }
+        Self { email, password, requires_2fa, two_fa_method: TwoFAMethod::default(), phone_number: None, roles: vec![] }
     }
-}
\ No newline at end of file
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TwoFAMethod {
+    // A code sent through the email client
+    #[default]
+    Email,
+    // A code from an authenticator app (RFC 6238)
+    Totp,
+    // A code texted to the user's verified phone number
+    Sms,
+}
+
+impl TwoFAMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Email => "email",
+            Self::Totp => "totp",
+            Self::Sms => "sms",
+        }
+    }
+
+    pub fn parse(method: &str) -> Option<Self> {
+        match method {
+            "email" => Some(Self::Email),
+            "totp" => Some(Self::Totp),
+            "sms" => Some(Self::Sms),
+            _ => None,
+        }
+    }
+}
@@ -1,16 +1,19 @@
+use auth_middleware::AuthLayer;
 use axum::{
-    http::{Method, StatusCode},
+    http::{header, HeaderName, Method, StatusCode},
     response::{IntoResponse, Response},
-    routing::post,
+    extract::{connect_info::IntoMakeServiceWithConnectInfo, ConnectInfo},
+    middleware::{self, AddExtension},
+    routing::{delete, get, post},
     serve::Serve,
     Json, Router,
 };
-use domain::AuthAPIError;
+use domain::{AuthAPIError, OAuthError};
 use redis::{Client, RedisResult};
 use secrecy::{ExposeSecret, Secret};
 use serde::{Deserialize, Serialize};
 use sqlx::{postgres::PgPoolOptions, PgPool};
-use std::error::Error;
+use std::{error::Error, net::SocketAddr};
 use tower_http::{
     cors::CorsLayer,
     services::ServeDir,
@@ -24,6 +27,11 @@ use utils::tracing::{
 
 use crate::app_state::AppState;
 use crate::routes::*;
+use crate::utils::{
+    auth::StoreTokenValidator,
+    constants::{CSRF_HEADER_NAME, JWT_COOKIE_NAME, REAUTHENTICATION_MAX_AGE_SECONDS},
+    csrf::verify_csrf_token,
+};
 
 
 pub mod app_state;
@@ -32,9 +40,15 @@ pub mod routes;
 pub mod services;
 pub mod utils;
 
+// Connection info is kept so handlers can record the client address of sessions
+type Server = Serve<
+    IntoMakeServiceWithConnectInfo<Router, SocketAddr>,
+    AddExtension<Router, ConnectInfo<SocketAddr>>,
+>;
+
 // This struct encapsulates our application-related logic.
 pub struct Application {
-    server: Serve<Router, Router>,
+    server: Server,
     // address is exposed as a public field, so we have access to it in tests.
     pub address: String,
 }
@@ -48,19 +62,97 @@ impl Application {
         ];
 
         let cors = CorsLayer::new()
-            // Allow GET and POST requests
-            .allow_methods([Method::GET, Method::POST])
+            // Allow GET, POST and DELETE requests
+            .allow_methods([Method::GET, Method::POST, Method::DELETE])
+            // Allow JSON bodies and the CSRF token header
+            .allow_headers([header::CONTENT_TYPE, HeaderName::from_static(CSRF_HEADER_NAME)])
             // Allow cookies to be included in requests
             .allow_credentials(true)
             .allow_origin(allowed_origins);
 
+        // Routes that act on behalf of a logged in user
+        let auth_layer = AuthLayer::new(StoreTokenValidator::new(&app_state))
+            .with_cookie_name(JWT_COOKIE_NAME);
+
+        let authenticated_routes = Router::new()
+            .route("/logout", post(logout))
+            .route("/logout-all", post(logout_all))
+            .route("/reauthenticate", post(reauthenticate))
+            .route("/reauthenticate/send-2fa", post(send_reauthentication_2fa))
+            .route("/sessions", get(get_sessions))
+            .route("/sessions/:id", delete(delete_session))
+            .route("/trusted-devices", get(get_trusted_devices))
+            .route("/api-keys", get(get_api_keys))
+            .route("/api-keys/:id", delete(delete_api_key))
+            .route_layer(auth_layer);
+
+        // Sensitive routes, which users must have authenticated for shortly before, at login
+        // or with `/reauthenticate`
+        let recent_auth_layer = AuthLayer::new(StoreTokenValidator::new(&app_state))
+            .with_cookie_name(JWT_COOKIE_NAME)
+            .require_recent_auth(*REAUTHENTICATION_MAX_AGE_SECONDS as u64);
+
+        let recently_authenticated_routes = Router::new()
+            .route("/change-password", post(change_password))
+            .route("/api-keys", post(create_api_key))
+            .route("/oidc/:provider/link", get(oidc_link))
+            .route("/trusted-devices/:id", delete(delete_trusted_device))
+            .route("/passkeys/register/start", post(start_passkey_registration))
+            .route("/passkeys/register/finish", post(finish_passkey_registration))
+            .route("/2fa/totp/enroll", post(enroll_totp))
+            .route("/2fa/totp/confirm", post(confirm_totp))
+            .route("/2fa/sms/enroll", post(enroll_sms))
+            .route("/2fa/sms/confirm", post(confirm_sms))
+            .route("/2fa/recovery-codes", post(regenerate_recovery_codes))
+            .route_layer(recent_auth_layer);
+
+        // The UserInfo endpoint also serves OAuth clients the user granted the `openid` scope
+        let userinfo_layer = AuthLayer::new(StoreTokenValidator::new(&app_state).accept_client_scope("openid"))
+            .with_cookie_name(JWT_COOKIE_NAME)
+            .accept_client_scope("openid");
+
+        let userinfo_routes = Router::new()
+            .route("/userinfo", get(userinfo))
+            .route_layer(userinfo_layer);
+
+        // Routes only administrators may use
+        let admin_layer = AuthLayer::new(StoreTokenValidator::new(&app_state))
+            .with_cookie_name(JWT_COOKIE_NAME)
+            .require_role("admin");
+
+        let admin_routes = Router::new()
+            .route("/oauth/clients", post(register_oauth_client))
+            .route("/oauth/service-clients", post(register_service_client))
+            .route_layer(admin_layer);
+
+        // Routes that trust the auth or refresh cookie, and so must be protected from
+        // cross-site request forgery
+        let csrf_protected_routes = Router::new()
+            .route("/refresh", post(refresh))
+            .merge(authenticated_routes)
+            .merge(recently_authenticated_routes)
+            .merge(userinfo_routes)
+            .merge(admin_routes)
+            .route_layer(middleware::from_fn_with_state(app_state.clone(), verify_csrf_token));
+
         let router = Router::new()
             .nest_service("/", ServeDir::new("assets"))
             .route("/signup", post(signup))
             .route("/login", post(login))
-            .route("/logout", post(logout))
+            .merge(csrf_protected_routes)
             .route("/verify-2fa", post(verify_2fa))
+            .route("/resend-2fa", post(resend_2fa))
+            .route("/magic-link", post(request_magic_link))
+            .route("/magic-link/verify", get(verify_magic_link))
+            .route("/passkeys/login/start", post(start_passkey_login))
+            .route("/passkeys/login/finish", post(finish_passkey_login))
             .route("/verify-token", post(verify_token))
+            .route("/.well-known/jwks.json", get(jwks))
+            .route("/.well-known/openid-configuration", get(openid_configuration))
+            .route("/authorize", get(authorize))
+            .route("/token", post(token))
+            .route("/oidc/:provider/login", get(oidc_login))
+            .route("/oidc/:provider/callback", get(oidc_callback))
             .with_state(app_state)
             .layer(cors)
             .layer(
@@ -72,7 +164,10 @@ impl Application {
         
         let listener = tokio::net::TcpListener::bind(address).await?;
         let address = listener.local_addr()?.to_string();
-        let server: Serve<Router, Router> = axum::serve(listener, router);
+        let server: Server = axum::serve(
+            listener,
+            router.into_make_service_with_connect_info::<SocketAddr>(),
+        );
 
         // Create a new Application instance and return it
         let app: Application = Application {
@@ -98,23 +193,85 @@ pub struct ErrorResponse {
 impl IntoResponse for AuthAPIError {
     fn into_response(self) -> Response {
         log_error_chain(&self);
+        // Throttled clients are told when to try again
+        let retry_after = match self {
+            AuthAPIError::TwoFAResendTooSoon(seconds) => Some(seconds),
+            _ => None,
+        };
         let (status, error_message) = match self {
+            AuthAPIError::ApiKeyNotFound => (StatusCode::NOT_FOUND, "API key not found"),
+            AuthAPIError::ExternalAccountNotLinked => (StatusCode::CONFLICT, "Log in and link the external account first"),
+            AuthAPIError::ExternalLoginFailed => (StatusCode::UNAUTHORIZED, "External login failed"),
             AuthAPIError::IncorrectCredentials => (StatusCode::UNAUTHORIZED, "Invalid credentials"),
             AuthAPIError::InvalidCredentials => (StatusCode::BAD_REQUEST, "Invalid credentials"),
+            AuthAPIError::InvalidClientCredentials => (StatusCode::UNAUTHORIZED, "Invalid client credentials"),
+            AuthAPIError::InvalidApiKeyRequest => (StatusCode::BAD_REQUEST, "Invalid API key request"),
+            AuthAPIError::InvalidCsrfToken => (StatusCode::FORBIDDEN, "Invalid CSRF token"),
+            AuthAPIError::InvalidMagicLink => (StatusCode::UNAUTHORIZED, "Invalid magic link"),
+            AuthAPIError::InvalidPasskey => (StatusCode::BAD_REQUEST, "Invalid passkey"),
+            AuthAPIError::InvalidPhoneNumber => (StatusCode::BAD_REQUEST, "Invalid phone number"),
+            AuthAPIError::InvalidRedirectUri => (StatusCode::BAD_REQUEST, "Invalid redirect URI"),
+            AuthAPIError::InvalidScope => (StatusCode::BAD_REQUEST, "Invalid scope"),
             AuthAPIError::InvalidToken => (StatusCode::UNAUTHORIZED, "Invalid auth token"),
+            AuthAPIError::InvalidTotpCode => (StatusCode::BAD_REQUEST, "Invalid TOTP code"),
+            AuthAPIError::InvalidVerificationCode => (StatusCode::BAD_REQUEST, "Invalid verification code"),
             AuthAPIError::MissingToken => (StatusCode::BAD_REQUEST, "Missing auth token"),
+            AuthAPIError::PhoneVerificationNotFound => (StatusCode::NOT_FOUND, "Phone verification not found"),
+            AuthAPIError::SessionNotFound => (StatusCode::NOT_FOUND, "Session not found"),
+            AuthAPIError::TotpAlreadyEnabled => (StatusCode::CONFLICT, "TOTP already enabled"),
+            AuthAPIError::TotpEnrollmentNotFound => (StatusCode::NOT_FOUND, "TOTP enrollment not found"),
+            AuthAPIError::TrustedDeviceNotFound => (StatusCode::NOT_FOUND, "Trusted device not found"),
+            AuthAPIError::TwoFAAttemptsExceeded => (StatusCode::UNAUTHORIZED, "Too many failed attempts, log in again"),
+            AuthAPIError::TwoFAResendNotSupported => (StatusCode::BAD_REQUEST, "Codes come from the authenticator app"),
+            AuthAPIError::TwoFAResendTooSoon(_) => (StatusCode::TOO_MANY_REQUESTS, "Code was sent too recently"),
+            AuthAPIError::TwoFAResendsExceeded => (StatusCode::TOO_MANY_REQUESTS, "Too many codes sent, log in again"),
             AuthAPIError::UnexpectedError(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Unexpected error"),
+            AuthAPIError::UnknownIdentityProvider => (StatusCode::NOT_FOUND, "Unknown identity provider"),
             AuthAPIError::UserAlreadyExists => (StatusCode::CONFLICT, "User already exists"),
         };
 
         let body = Json(ErrorResponse { 
             error: error_message.to_string() 
         });
-        (status, body).into_response()
+        match retry_after {
+            Some(seconds) => (status, [(header::RETRY_AFTER, seconds.to_string())], body).into_response(),
+            None => (status, body).into_response(),
+        }
 
     }
 }
 
+#[derive(Deserialize, Serialize)]
+pub struct OAuthErrorResponse {
+    pub error: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_description: Option<String>,
+}
+
+impl IntoResponse for OAuthError {
+    fn into_response(self) -> Response {
+        log_error_chain(&self);
+        let status = match self {
+            OAuthError::InvalidClient => StatusCode::UNAUTHORIZED,
+            OAuthError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_REQUEST,
+        };
+
+        let error_description = match &self {
+            OAuthError::InvalidRequest(description) | OAuthError::InvalidScope(description) => {
+                Some(description.clone())
+            }
+            _ => None,
+        };
+
+        let body = Json(OAuthErrorResponse {
+            error: self.code().to_owned(),
+            error_description,
+        });
+        (status, body).into_response()
+    }
+}
+
 pub async fn get_postgres_pool(url: &Secret<String>) -> Result<PgPool, sqlx::Error> {
     PgPoolOptions::new()
         .max_connections(5)
@@ -4,16 +4,43 @@ use std::sync::Arc;
 use sqlx::PgPool;
 use tokio::sync::RwLock;
 use auth_service::{
-    app_state::AppState,
+    app_state::{AppState, SmsClientType},
     domain::Email,
     get_postgres_pool, get_redis_client,
+    domain::OidcProviderConfig,
     services::data_stores::{
+        PostgresApiKeyStore,
+        PostgresExternalIdentityStore,
+        PostgresOAuthClientStore,
+        PostgresPasskeyStore,
+        PostgresRecoveryCodeStore,
+        PostgresTotpStore,
         PostgresUserStore,
+        RedisAuthorizationCodeStore,
         RedisBannedTokenStore,
+        RedisOidcLoginStore,
+        MockSmsClient,
+        RedisMagicLinkStore,
+        RedisPasskeyChallengeStore,
+        RedisPhoneVerificationStore,
+        RedisRefreshTokenStore,
+        RedisSessionStore,
+        RedisSessionVersionStore,
+        RedisTrustedDeviceStore,
         RedisTwoFACodeStore
     },
-    services::postmark_email_client::PostmarkEmailClient,
-    utils::{constants::{prod, DATABASE_URL, POSTMARK_AUTH_TOKEN, REDIS_HOST_NAME}, tracing::init_tracing},
+    services::{
+        http_sms_client::HttpSmsClient, oidc_client::OidcClient,
+        postmark_email_client::PostmarkEmailClient,
+    },
+    utils::{
+        constants::{
+            prod, CSRF_EXEMPT_BEARER, DATABASE_URL, INTROSPECTION_CLIENT, MAGIC_LINK_KEY,
+            OIDC_PROVIDERS, POSTMARK_AUTH_TOKEN, REDIS_HOST_NAME, SMS_PROVIDER, TOTP_CIPHER,
+            TRUSTED_DEVICE_KEY,
+        },
+        tracing::init_tracing,
+    },
     Application
 };
 
@@ -23,21 +50,63 @@ async fn main() {
     color_eyre::install().expect("Failed to install color_eyre");
     init_tracing().expect("Failed to initialize tracing");
 
+    // Fail at startup rather than on the first login link or trusted device when the
+    // keys are missing
+    lazy_static::initialize(&MAGIC_LINK_KEY);
+    lazy_static::initialize(&TRUSTED_DEVICE_KEY);
+
     let pg_pool = configure_postgresql().await;
-    let user_store = Arc::new(RwLock::new(PostgresUserStore::new(pg_pool)));
+    let user_store = Arc::new(RwLock::new(PostgresUserStore::new(pg_pool.clone())));
+    let oauth_client_store = Arc::new(RwLock::new(PostgresOAuthClientStore::new(pg_pool.clone())));
+    let api_key_store = Arc::new(RwLock::new(PostgresApiKeyStore::new(pg_pool.clone())));
+    let external_identity_store = Arc::new(RwLock::new(PostgresExternalIdentityStore::new(pg_pool.clone())));
+    let passkey_store = Arc::new(RwLock::new(PostgresPasskeyStore::new(pg_pool.clone())));
+    let recovery_code_store = Arc::new(RwLock::new(PostgresRecoveryCodeStore::new(pg_pool.clone())));
+    let totp_store = Arc::new(RwLock::new(PostgresTotpStore::new(pg_pool, TOTP_CIPHER.clone())));
 
     let redis_connection = Arc::new(RwLock::new(configure_redis()));
     let banned_token_store = Arc::new(RwLock::new(RedisBannedTokenStore::new(redis_connection.clone())));
-    let two_fa_code_store = Arc::new(RwLock::new(RedisTwoFACodeStore::new(redis_connection)));
+    let refresh_token_store = Arc::new(RwLock::new(RedisRefreshTokenStore::new(redis_connection.clone())));
+    let session_version_store = Arc::new(RwLock::new(RedisSessionVersionStore::new(redis_connection.clone())));
+    let session_store = Arc::new(RwLock::new(RedisSessionStore::new(redis_connection.clone())));
+    let two_fa_code_store = Arc::new(RwLock::new(RedisTwoFACodeStore::new(redis_connection.clone())));
+    let authorization_code_store = Arc::new(RwLock::new(RedisAuthorizationCodeStore::new(redis_connection.clone())));
+    let oidc_login_store = Arc::new(RwLock::new(RedisOidcLoginStore::new(redis_connection.clone())));
+    let passkey_challenge_store = Arc::new(RwLock::new(RedisPasskeyChallengeStore::new(redis_connection.clone())));
+    let phone_verification_store = Arc::new(RwLock::new(RedisPhoneVerificationStore::new(redis_connection.clone())));
+    let magic_link_store = Arc::new(RwLock::new(RedisMagicLinkStore::new(redis_connection.clone())));
+    let trusted_device_store = Arc::new(RwLock::new(RedisTrustedDeviceStore::new(redis_connection)));
 
     let email_client = Arc::new(configure_postmark_email_client());
+    let sms_client = configure_sms_client();
 
-    let app_state = AppState::new(
+    let mut app_state = AppState::new(
         user_store,
         banned_token_store,
+        refresh_token_store,
+        session_version_store,
+        session_store,
         two_fa_code_store,
         email_client,
-    );
+    )
+    .with_oauth_stores(oauth_client_store, authorization_code_store)
+    .with_api_key_store(api_key_store)
+    .with_oidc_stores(oidc_login_store, external_identity_store)
+    .with_passkey_stores(passkey_store, passkey_challenge_store)
+    .with_totp_store(totp_store)
+    .with_recovery_code_store(recovery_code_store)
+    .with_sms(sms_client, phone_verification_store)
+    .with_magic_link_store(magic_link_store)
+    .with_trusted_device_store(trusted_device_store)
+    .with_csrf_exempt_bearer(*CSRF_EXEMPT_BEARER);
+
+    for config in OIDC_PROVIDERS.iter() {
+        app_state = app_state.with_oidc_provider(configure_oidc_client(config.clone()));
+    }
+
+    if let Some(client) = INTROSPECTION_CLIENT.clone() {
+        app_state = app_state.with_introspection_client(client);
+    }
 
     // Here we are using ip 0.0.0.0 so the service is listening on all the configured network interfaces.
     // This is needed for Docker to work, which we will add later on.
@@ -80,3 +149,31 @@ fn configure_postmark_email_client() -> PostmarkEmailClient{
         http_client,
     )
 }
+
+// Texts are only logged until an SMS provider is configured
+fn configure_sms_client() -> SmsClientType {
+    let Some(config) = SMS_PROVIDER.clone() else {
+        return Arc::new(MockSmsClient);
+    };
+
+    let http_client = Client::builder()
+        .timeout(prod::sms_client::TIMEOUT)
+        .build()
+        .expect("Failed to build HTTP client");
+
+    Arc::new(HttpSmsClient::new(
+        config.base_url,
+        config.sender,
+        config.authorization_token,
+        http_client,
+    ))
+}
+
+fn configure_oidc_client(config: OidcProviderConfig) -> OidcClient {
+    let http_client = Client::builder()
+        .timeout(prod::oidc_client::TIMEOUT)
+        .build()
+        .expect("Failed to build HTTP client");
+
+    OidcClient::new(config, http_client)
+}
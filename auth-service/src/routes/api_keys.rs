@@ -0,0 +1,142 @@
+use auth_middleware::AuthenticatedUser;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{data_stores::ApiKeyStoreError, ApiKey, ApiKeyEntry, AuthAPIError, Email},
+};
+
+#[derive(Debug, Deserialize)]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    // Lifetime of the key in seconds. Keys without one last until they are revoked.
+    #[serde(rename = "expiresIn", default)]
+    pub expires_in: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateApiKeyResponse {
+    // Only returned once, the service keeps a hash of it
+    pub key: String,
+    #[serde(flatten)]
+    pub api_key: ApiKeyResponse,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiKeysResponse {
+    #[serde(rename = "apiKeys")]
+    pub api_keys: Vec<ApiKeyResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiKeyResponse {
+    pub id: String,
+    pub name: String,
+    pub scopes: Vec<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: i64,
+    #[serde(rename = "expiresAt")]
+    pub expires_at: Option<i64>,
+    #[serde(rename = "lastUsedAt")]
+    pub last_used_at: Option<i64>,
+}
+
+impl From<ApiKeyEntry> for ApiKeyResponse {
+    fn from(entry: ApiKeyEntry) -> Self {
+        Self {
+            id: entry.id,
+            name: entry.name,
+            scopes: entry.scopes,
+            created_at: entry.created_at,
+            expires_at: entry.expires_at,
+            last_used_at: entry.last_used_at,
+        }
+    }
+}
+
+#[tracing::instrument(name = "Create API key", skip_all)]
+pub async fn create_api_key(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Json(request): Json<CreateApiKeyRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+
+    let email = key_owner(user)?;
+
+    let entry = ApiKeyEntry::new(email, request.name, request.scopes, request.expires_in)
+        .map_err(|_| AuthAPIError::InvalidApiKeyRequest)?;
+
+    let key = ApiKey::default();
+
+    state
+        .api_key_store
+        .write()
+        .await
+        .add_key(&key, entry.clone())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let response = Json(CreateApiKeyResponse {
+        key: key.as_ref().expose_secret().to_owned(),
+        api_key: ApiKeyResponse::from(entry),
+    });
+
+    Ok((StatusCode::CREATED, response))
+}
+
+#[tracing::instrument(name = "Get API keys", skip_all)]
+pub async fn get_api_keys(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+) -> Result<impl IntoResponse, AuthAPIError> {
+
+    let email = key_owner(user)?;
+
+    let mut api_keys = state
+        .api_key_store
+        .read()
+        .await
+        .get_keys(&email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    // Most recently created first
+    api_keys.sort_by_key(|entry| std::cmp::Reverse(entry.created_at));
+
+    let response = Json(ApiKeysResponse {
+        api_keys: api_keys.into_iter().map(ApiKeyResponse::from).collect(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+// Revoked keys are deleted, so they stop working right away
+#[tracing::instrument(name = "Delete API key", skip_all)]
+pub async fn delete_api_key(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+
+    let email = key_owner(user)?;
+
+    match state.api_key_store.write().await.remove_key(&email, &id).await {
+        Ok(()) => Ok(StatusCode::OK),
+        Err(ApiKeyStoreError::KeyNotFound) => Err(AuthAPIError::ApiKeyNotFound),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+// The user managing their keys
+fn key_owner(user: AuthenticatedUser) -> Result<Email, AuthAPIError> {
+    Email::parse(Secret::new(user.subject)).map_err(|_| AuthAPIError::InvalidToken)
+}
@@ -0,0 +1,207 @@
+use auth_middleware::extract_token;
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, Uri},
+    response::Redirect,
+};
+use secrecy::{ExposeSecret, Secret};
+use serde::Deserialize;
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        data_stores::OAuthClientStoreError, parse_scope, AuthorizationCode, AuthorizationCodeEntry,
+        CodeChallenge, Email, OAuthError,
+    },
+    utils::{
+        auth::{validate_token, ValidatedToken},
+        constants::JWT_COOKIE_NAME,
+    },
+};
+
+// Parameters of an authorization request (RFC 6749 section 4.1.1, with PKCE and the
+// OpenID Connect nonce)
+#[derive(Debug, Deserialize)]
+pub struct AuthorizeRequest {
+    pub response_type: Option<String>,
+    pub client_id: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub scope: Option<String>,
+    pub state: Option<String>,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<String>,
+    pub nonce: Option<String>,
+}
+
+#[tracing::instrument(name = "Authorize", skip_all)]
+pub async fn authorize(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    uri: Uri,
+    Query(request): Query<AuthorizeRequest>,
+) -> Result<Redirect, OAuthError> {
+
+    // Until the redirect URI is known to belong to the client, errors are shown to the
+    // user instead of being sent to a URI an attacker may have picked
+    let client_id = request
+        .client_id
+        .ok_or_else(|| OAuthError::InvalidRequest("client_id is required".to_owned()))?;
+
+    let client = match state.oauth_client_store.read().await.get_client(&client_id).await {
+        Ok(client) => client,
+        Err(OAuthClientStoreError::ClientNotFound) => {
+            return Err(OAuthError::InvalidRequest("Unknown client_id".to_owned()))
+        }
+        Err(e) => return Err(OAuthError::UnexpectedError(e.into())),
+    };
+
+    let redirect_uri = match request.redirect_uri {
+        Some(redirect_uri) if client.allows_redirect_uri(&redirect_uri) => redirect_uri,
+        _ => {
+            return Err(OAuthError::InvalidRequest(
+                "redirect_uri is not registered for the client".to_owned(),
+            ))
+        }
+    };
+
+    let client_state = request.state.as_deref();
+
+    if request.response_type.as_deref() != Some("code") {
+        return redirect_with_error(&redirect_uri, OAuthError::UnsupportedResponseType, client_state);
+    }
+
+    let scope = match parse_scope(request.scope.as_deref()) {
+        Ok(scope) => scope,
+        Err(e) => {
+            return redirect_with_error(&redirect_uri, OAuthError::InvalidScope(e.to_string()), client_state)
+        }
+    };
+
+    let code_challenge = match request
+        .code_challenge
+        .ok_or_else(|| OAuthError::InvalidRequest("code_challenge is required".to_owned()))
+        .and_then(|challenge| {
+            CodeChallenge::parse(challenge, request.code_challenge_method.as_deref())
+                .map_err(|e| OAuthError::InvalidRequest(e.to_string()))
+        }) {
+        Ok(code_challenge) => code_challenge,
+        Err(e) => return redirect_with_error(&redirect_uri, e, client_state),
+    };
+
+    // Users without a session log in first and are then sent back here
+    let claims = match extract_token(&headers, JWT_COOKIE_NAME) {
+        Some(token) => validate_token(
+            &token,
+            state.banned_token_store.clone(),
+            state.session_version_store.clone(),
+            state.session_store.clone(),
+            state.api_key_store.clone(),
+        )
+        .await
+        .and_then(ValidatedToken::into_user)
+        .ok(),
+        None => None,
+    };
+
+    let claims = match claims {
+        Some(claims) => claims,
+        None => return Ok(redirect_to_login(&uri)),
+    };
+
+    let email = Email::parse(Secret::new(claims.sub.clone()))
+        .map_err(OAuthError::UnexpectedError)?;
+
+    // The user authenticated when their session started or they last reauthenticated, which
+    // may be well before the current auth token was issued
+    let auth_time = state
+        .session_store
+        .read()
+        .await
+        .get_sessions(&email)
+        .await
+        .map_err(|e| OAuthError::UnexpectedError(e.into()))?
+        .into_iter()
+        .find(|session| session.id == claims.sid)
+        .map(|session| session.auth_time)
+        .unwrap_or(claims.iat as i64);
+
+    let code = AuthorizationCode::default();
+    let entry = AuthorizationCodeEntry {
+        client_id: client.client_id,
+        redirect_uri: redirect_uri.clone(),
+        email,
+        scope,
+        nonce: request.nonce,
+        code_challenge,
+        auth_time,
+    };
+
+    if let Err(e) = state
+        .authorization_code_store
+        .write()
+        .await
+        .add_code(code.clone(), entry)
+        .await
+    {
+        return redirect_with_error(&redirect_uri, OAuthError::UnexpectedError(e.into()), client_state);
+    }
+
+    redirect_to_client(
+        &redirect_uri,
+        &[("code", code.as_ref().expose_secret())],
+        client_state,
+    )
+}
+
+// Send the user back to the client, passing its state along unchanged
+fn redirect_to_client(
+    redirect_uri: &str,
+    params: &[(&str, &str)],
+    client_state: Option<&str>,
+) -> Result<Redirect, OAuthError> {
+    let mut url = url::Url::parse(redirect_uri)
+        .map_err(|e| OAuthError::UnexpectedError(e.into()))?;
+
+    {
+        let mut query = url.query_pairs_mut();
+        query.extend_pairs(params);
+        if let Some(client_state) = client_state {
+            query.append_pair("state", client_state);
+        }
+    }
+
+    Ok(Redirect::to(url.as_str()))
+}
+
+#[tracing::instrument(name = "Redirect with OAuth error", skip_all)]
+fn redirect_with_error(
+    redirect_uri: &str,
+    error: OAuthError,
+    client_state: Option<&str>,
+) -> Result<Redirect, OAuthError> {
+    tracing::warn!("Authorization request failed: {}", error);
+
+    let mut params = vec![("error", error.code().to_owned())];
+    if let OAuthError::InvalidRequest(description) | OAuthError::InvalidScope(description) = error {
+        params.push(("error_description", description));
+    }
+
+    let params: Vec<(&str, &str)> = params
+        .iter()
+        .map(|(name, value)| (*name, value.as_str()))
+        .collect();
+
+    redirect_to_client(redirect_uri, &params, client_state)
+}
+
+// The login page sends the user back to `return_to` once they are logged in
+fn redirect_to_login(uri: &Uri) -> Redirect {
+    let return_to = uri
+        .path_and_query()
+        .map(|path_and_query| path_and_query.as_str())
+        .unwrap_or("/authorize");
+
+    let return_to: String = url::form_urlencoded::byte_serialize(return_to.as_bytes()).collect();
+
+    Redirect::to(&format!("/?return_to={}", return_to))
+}
@@ -0,0 +1,114 @@
+use std::net::SocketAddr;
+use auth_middleware::AuthenticatedUser;
+use axum::{
+    extract::{ConnectInfo, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use axum_extra::extract::CookieJar;
+use secrecy::Secret;
+use serde::Deserialize;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, AuthMethod, Email, Password, Session},
+    utils::{
+        auth::{start_session, TokenDelivery},
+        constants::{COOKIE_CONFIG, TRUSTED_DEVICE_COOKIE_NAME},
+    },
+};
+
+#[derive(Deserialize)]
+pub struct ChangePasswordRequest {
+    #[serde(rename = "currentPassword")]
+    pub current_password: Secret<String>,
+    #[serde(rename = "newPassword")]
+    pub new_password: Secret<String>,
+    #[serde(default, rename = "tokenDelivery")]
+    pub token_delivery: TokenDelivery,
+}
+
+#[tracing::instrument(name = "Change password", skip_all)]
+pub async fn change_password(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    user: AuthenticatedUser,
+    jar: CookieJar,
+    Json(request): Json<ChangePasswordRequest>,
+) -> (CookieJar, Result<Response, AuthAPIError>) {
+
+    let email = match Email::parse(Secret::new(user.subject)) {
+        Ok(email) => email,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
+    };
+
+    let current_password = match Password::parse(request.current_password) {
+        Ok(password) => password,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
+    };
+
+    let new_password = match Password::parse(request.new_password) {
+        Ok(password) => password,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
+    };
+
+    let mut user_store = state.user_store.write().await;
+
+    if user_store.validate_user(&email, &current_password).await.is_err() {
+        return (jar, Err(AuthAPIError::IncorrectCredentials));
+    }
+
+    if let Err(e) = user_store.update_password(&email, new_password).await {
+        return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+    }
+
+    let user = match user_store.get_user(&email).await {
+        Ok(user) => user,
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    };
+
+    drop(user_store);
+
+    // Sessions started with the old password end everywhere, including this one
+    if let Err(e) = state
+        .session_version_store
+        .write()
+        .await
+        .increment_version(&email)
+        .await
+    {
+        return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+    }
+
+    if let Err(e) = state.session_store.write().await.remove_sessions(&email).await {
+        return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+    }
+
+    // Browsers trusted to skip 2FA have to prove the second factor again
+    if let Err(e) = state.trusted_device_store.write().await.remove_devices(&email).await {
+        return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+    }
+
+    let user_agent = headers
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned);
+
+    // The caller proved they know the new password, so they get a fresh session
+    let session = Session::new(email, addr.ip().to_string(), user_agent, vec![AuthMethod::Password]);
+
+    let tokens = match start_session(session, &user.roles, &state).await {
+        Ok(tokens) => tokens,
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e))),
+    };
+
+    let jar = jar.remove(COOKIE_CONFIG.removal(TRUSTED_DEVICE_COOKIE_NAME));
+
+    match tokens.deliver(request.token_delivery, jar) {
+        (updated_jar, Some(body)) => (updated_jar, Ok((StatusCode::OK, Json(body)).into_response())),
+        (updated_jar, None) => (updated_jar, Ok(StatusCode::OK.into_response())),
+    }
+
+}
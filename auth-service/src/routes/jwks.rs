@@ -0,0 +1,10 @@
+use axum::Json;
+use jsonwebtoken::jwk::JwkSet;
+
+use crate::utils::constants::JWT_KEY_SET;
+
+// Publish the public keys used to sign JWT auth tokens, so other services can validate them
+#[tracing::instrument(name = "JWKS", skip_all)]
+pub async fn jwks() -> Json<JwkSet> {
+    Json(JWT_KEY_SET.jwk_set())
+}
@@ -1,29 +1,39 @@
+use std::net::SocketAddr;
 use axum::{
-    extract::State,
-    http::StatusCode, Json,
+    extract::{ConnectInfo, State},
+    http::{header, HeaderMap, StatusCode}, Json,
     response::IntoResponse
 };
 use axum_extra::extract::CookieJar;
+use color_eyre::eyre::{eyre, Result};
 use secrecy::{ExposeSecret, Secret};
 use serde::{Deserialize, Serialize};
 
 use crate::{
     app_state::AppState,
-    domain::{AuthAPIError, Email, LoginAttemptId, Password, TwoFACode},
-    utils::auth::generate_auth_cookie,
+    domain::{
+        AuthAPIError, AuthMethod, Email, LoginAttemptId, Passkey, PasskeyCeremony, Password, Session,
+        TwoFACode, TwoFAMethod, User,
+    },
+    routes::{is_trusted_device, sms_content, start_passkey_challenge, PasskeyRequestOptions},
+    utils::auth::{start_session, TokenDelivery, TokenResponse},
 };
 
 #[derive(Deserialize)]
 pub struct LoginRequest{
     pub email: Secret<String>,
     pub password: Secret<String>,
+    #[serde(default, rename = "tokenDelivery")]
+    pub token_delivery: TokenDelivery,
 }
 
 #[derive(Debug, Serialize)]
 #[serde(untagged)]
 pub enum LoginResponse {
     RegularAuth,
+    Tokens(TokenResponse),
     TwoFactorAuth(TwoFactorAuthResponse),
+    PasskeyAuth(PasskeyAuthResponse),
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -31,11 +41,29 @@ pub struct TwoFactorAuthResponse {
     pub message: String,
     #[serde(rename = "loginAttemptId")]
     pub login_attempt_id: String,
+    // Where the user finds the code
+    #[serde(rename = "twoFAMethod")]
+    pub two_fa_method: TwoFAMethod,
+}
+
+// Users with passkeys confirm the login with one, or with a code of their 2FA method or a
+// recovery code like other users
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PasskeyAuthResponse {
+    pub message: String,
+    #[serde(rename = "publicKey")]
+    pub public_key: PasskeyRequestOptions,
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: String,
+    #[serde(rename = "twoFAMethod")]
+    pub two_fa_method: TwoFAMethod,
 }
 
 #[tracing::instrument(name = "Login", skip_all)]
 pub async fn login(
     State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     cookie_jar: CookieJar,
     Json(request): Json<LoginRequest>
 ) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
@@ -62,63 +90,161 @@ pub async fn login(
         Err(_) => return (cookie_jar, Err(AuthAPIError::IncorrectCredentials)),
     };
 
+    let user_agent = headers
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned);
+
+    // Browsers the user trusted when verifying their second factor skip it
+    let requires_2fa = match user.requires_2fa {
+        true => match is_trusted_device(&state, &cookie_jar, &user.email).await {
+            Ok(trusted) => !trusted,
+            Err(e) => return (cookie_jar, Err(AuthAPIError::UnexpectedError(e))),
+        },
+        false => false,
+    };
+
     // handle request based on user's 2FA configuration
-    match user.requires_2fa {
-        true  => handle_2fa(&user.email, &state, cookie_jar).await,
-        false => handle_no_2fa(&user.email, cookie_jar).await,
+    match requires_2fa {
+        true  => start_second_factor(&user, &state, cookie_jar).await,
+        false => {
+            let session = Session::new(user.email, addr.ip().to_string(), user_agent, vec![AuthMethod::Password]);
+            handle_no_2fa(session, &user.roles, request.token_delivery, &state, cookie_jar).await
+        }
+    }
+
+}
+
+// Asks users who proved their first factor for the second one, with a passkey if they have one
+pub(crate) async fn start_second_factor(
+    user: &User,
+    state: &AppState,
+    jar: CookieJar
+) -> (CookieJar, Result<(StatusCode, Json<LoginResponse>), AuthAPIError>) {
+
+    let passkeys = match state.passkey_store.read().await.get_passkeys(&user.email).await {
+        Ok(passkeys) => passkeys,
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    };
+
+    match passkeys.is_empty() {
+        false => handle_passkey_2fa(user, &passkeys, state, jar).await,
+        true => handle_2fa(user, state, jar).await,
     }
 
 }
 
 #[tracing::instrument(name = "Handle 2FA flow", skip_all)]
 async fn handle_2fa(
-    email: &Email,
+    user: &User,
     state: &AppState,
     jar: CookieJar
 ) -> (CookieJar, Result<(StatusCode, Json<LoginResponse>), AuthAPIError>) {
     
+    let login_attempt_id = match start_login_attempt(user, state).await {
+        Ok(login_attempt_id) => login_attempt_id,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    let auth_response = TwoFactorAuthResponse {
+        message: String::from("2FA required"),
+        login_attempt_id: login_attempt_id.as_ref().expose_secret().to_owned(),
+        two_fa_method: user.two_fa_method,
+    };
+    let response = Json(LoginResponse::TwoFactorAuth(auth_response));
+
+    (jar, Ok((StatusCode::PARTIAL_CONTENT, response)))
+    
+}
+
+// Starts a login attempt for `/verify-2fa` and sends its code
+async fn start_login_attempt(user: &User, state: &AppState) -> Result<LoginAttemptId, AuthAPIError> {
     let login_attempt_id = LoginAttemptId::default();
+    // Users with an authenticator app never see this code, the store only tracks the attempt
     let two_fa_code = TwoFACode::default();
 
-    if let Err(e) = state
+    state
         .two_fa_code_store
         .write()
         .await
-        .add_code(email.clone(), login_attempt_id.clone(), two_fa_code.clone())
+        .add_code(user.email.clone(), login_attempt_id.clone(), two_fa_code.clone())
         .await
-    {
-        return (jar, Err(AuthAPIError::UnexpectedError(e.into())))
-    }
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
 
-    if let Err(e) = state
-        .email_client
-        .send_email(email, "2FA Code", two_fa_code.as_ref().expose_secret())
+    send_2fa_code(user, &two_fa_code, state)
         .await
-    {
-        return (jar, Err(AuthAPIError::UnexpectedError(e)));
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    Ok(login_attempt_id)
+}
+
+// The code goes out through the user's chosen channel
+pub(crate) async fn send_2fa_code(user: &User, two_fa_code: &TwoFACode, state: &AppState) -> Result<()> {
+    match (user.two_fa_method, &user.phone_number) {
+        (TwoFAMethod::Email, _) => state
+            .email_client
+            .send_email(&user.email, "2FA Code", two_fa_code.as_ref().expose_secret())
+            .await,
+        (TwoFAMethod::Sms, Some(phone_number)) => state
+            .sms_client
+            .send_sms(phone_number, &sms_content(two_fa_code))
+            .await,
+        (TwoFAMethod::Sms, None) => Err(eyre!("User chose SMS 2FA without a verified phone number")),
+        (TwoFAMethod::Totp, _) => Ok(()),
     }
+}
 
+#[tracing::instrument(name = "Handle passkey 2FA flow", skip_all)]
+async fn handle_passkey_2fa(
+    user: &User,
+    passkeys: &[Passkey],
+    state: &AppState,
+    jar: CookieJar
+) -> (CookieJar, Result<(StatusCode, Json<LoginResponse>), AuthAPIError>) {
 
-    let auth_response = TwoFactorAuthResponse {
-        message: String::from("2FA required"),
-        login_attempt_id: login_attempt_id.as_ref().expose_secret().to_owned(),
+    let ceremony = PasskeyCeremony::SecondFactor(user.email.clone());
+    let options = match start_passkey_challenge(state, ceremony, passkeys).await {
+        Ok(options) => options,
+        Err(e) => return (jar, Err(e)),
     };
-    let response = Json(LoginResponse::TwoFactorAuth(auth_response));
+
+    // Users without their passkey at hand fall back to `/verify-2fa`
+    let login_attempt_id = match start_login_attempt(user, state).await {
+        Ok(login_attempt_id) => login_attempt_id,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    let response = Json(LoginResponse::PasskeyAuth(PasskeyAuthResponse {
+        message: String::from("Passkey required"),
+        public_key: options,
+        login_attempt_id: login_attempt_id.as_ref().expose_secret().to_owned(),
+        two_fa_method: user.two_fa_method,
+    }));
 
     (jar, Ok((StatusCode::PARTIAL_CONTENT, response)))
-    
+
 }
 
 #[tracing::instrument(name = "Handle non-2FA flow", skip_all)]
-async fn handle_no_2fa(email: &Email, jar: CookieJar) -> (CookieJar, Result<(StatusCode, Json<LoginResponse>), AuthAPIError>) {
+async fn handle_no_2fa(
+    session: Session,
+    roles: &[String],
+    token_delivery: TokenDelivery,
+    state: &AppState,
+    jar: CookieJar
+) -> (CookieJar, Result<(StatusCode, Json<LoginResponse>), AuthAPIError>) {
     
-    let auth_cookie = match generate_auth_cookie(&email) {
-        Ok(cookie) => cookie,
-        Err(e) => return(jar, Err(AuthAPIError::UnexpectedError(e))),
+    let tokens = match start_session(session, roles, state).await {
+        Ok(tokens) => tokens,
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e))),
     };
 
-    let updated_jar = jar.add(auth_cookie);
+    let (updated_jar, body) = tokens.deliver(token_delivery, jar);
+    let response = match body {
+        Some(body) => LoginResponse::Tokens(body),
+        None => LoginResponse::RegularAuth,
+    };
 
-    (updated_jar, Ok((StatusCode::OK, Json(LoginResponse::RegularAuth))))
+    (updated_jar, Ok((StatusCode::OK, Json(response))))
 
 }
\ No newline at end of file
@@ -1,28 +1,28 @@
+use auth_middleware::AuthenticatedUser;
 use axum::{extract::State, http::StatusCode, response::IntoResponse};
-use axum_extra::extract::{cookie, CookieJar};
+use axum_extra::extract::CookieJar;
 use secrecy::Secret;
 use crate::{
-    domain::AuthAPIError,
-    utils::{auth::validate_token, constants::JWT_COOKIE_NAME},
+    domain::{AuthAPIError, Email, RefreshToken, SessionStoreError},
+    utils::constants::{COOKIE_CONFIG, CSRF_COOKIE_NAME, JWT_COOKIE_NAME, REFRESH_COOKIE_NAME},
 };
 use crate::app_state::AppState;
 
 #[tracing::instrument(name = "Logout", skip_all)]
 pub async fn logout(
     State(state): State<AppState>,
+    user: AuthenticatedUser,
     jar: CookieJar
 ) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
 
-    // Validate cookie
-    let cookie = match jar.get(JWT_COOKIE_NAME) {
-        Some(cookie) => cookie,
-        None => return (jar, Err(AuthAPIError::MissingToken)),
+    // Tokens issued by this service always carry these claims
+    let (Some(jti), Some(exp), Some(session_id)) = (user.token_id, user.expires_at, user.session_id) else {
+        return (jar, Err(AuthAPIError::InvalidToken));
     };
-    // Validate token
-    let token = Secret::new(cookie.value().to_owned());
-    let _ = match validate_token(&token, state.banned_token_store.clone()).await {
-        Ok(claims) => claims,
-        Err(_) => return (jar, Err(AuthAPIError::InvalidToken))
+
+    let email = match Email::parse(Secret::new(user.subject)) {
+        Ok(email) => email,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
     };
 
     // Banned list
@@ -30,13 +30,33 @@ pub async fn logout(
         .banned_token_store
         .write()
         .await
-        .add_token(token.to_owned())
+        .add_token(jti, exp)
         .await
     {
         return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
     }
 
-    let removed_jar = jar.remove(cookie::Cookie::from(JWT_COOKIE_NAME));
+    match state.session_store.write().await.remove_session(&email, &session_id).await {
+        Ok(()) | Err(SessionStoreError::SessionNotFound) => (),
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    }
+
+    // Revoke the refresh token family so the session can't be resumed
+    if let Some(cookie) = jar.get(REFRESH_COOKIE_NAME) {
+        if let Ok(refresh_token) = RefreshToken::parse(Secret::new(cookie.value().to_owned())) {
+            let mut refresh_token_store = state.refresh_token_store.write().await;
+            if let Ok(entry) = refresh_token_store.get_token(&refresh_token).await {
+                if let Err(e) = refresh_token_store.revoke_family(&entry.family_id).await {
+                    return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+                }
+            }
+        }
+    }
+
+    let removed_jar = jar
+        .remove(COOKIE_CONFIG.removal(JWT_COOKIE_NAME))
+        .remove(COOKIE_CONFIG.removal(REFRESH_COOKIE_NAME))
+        .remove(COOKIE_CONFIG.removal(CSRF_COOKIE_NAME));
 
     (removed_jar, Ok(StatusCode::OK))
 
@@ -0,0 +1,46 @@
+use auth_middleware::AuthenticatedUser;
+use axum::{extract::State, http::StatusCode, response::IntoResponse};
+use axum_extra::extract::CookieJar;
+use secrecy::Secret;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email},
+    utils::constants::{COOKIE_CONFIG, CSRF_COOKIE_NAME, JWT_COOKIE_NAME, REFRESH_COOKIE_NAME},
+};
+
+// Invalidate every auth and refresh token of the user, on all devices
+#[tracing::instrument(name = "Logout all", skip_all)]
+pub async fn logout_all(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    jar: CookieJar
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+
+    let email = match Email::parse(Secret::new(user.subject)) {
+        Ok(email) => email,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
+    };
+
+    if let Err(e) = state
+        .session_version_store
+        .write()
+        .await
+        .increment_version(&email)
+        .await
+    {
+        return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+    }
+
+    if let Err(e) = state.session_store.write().await.remove_sessions(&email).await {
+        return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+    }
+
+    let removed_jar = jar
+        .remove(COOKIE_CONFIG.removal(JWT_COOKIE_NAME))
+        .remove(COOKIE_CONFIG.removal(REFRESH_COOKIE_NAME))
+        .remove(COOKIE_CONFIG.removal(CSRF_COOKIE_NAME));
+
+    (removed_jar, Ok(StatusCode::OK))
+
+}
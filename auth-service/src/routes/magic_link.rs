@@ -0,0 +1,183 @@
+use std::net::SocketAddr;
+use axum::{
+    extract::{ConnectInfo, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+    Json,
+};
+use axum_extra::extract::{cookie::SameSite, CookieJar};
+use color_eyre::eyre::{eyre, Result};
+use secrecy::{ExposeSecret, Secret};
+use serde::Deserialize;
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        data_stores::UserStoreError, AuthAPIError, AuthMethod, BrowserBinding, Email, MagicLink,
+        MagicLinkToken, Session,
+    },
+    routes::start_second_factor,
+    utils::{
+        auth::{start_session, TokenDelivery},
+        constants::{
+            COOKIE_CONFIG, MAGIC_LINK_COOKIE_NAME, MAGIC_LINK_KEY, MAGIC_LINK_TTL_SECONDS,
+            PUBLIC_URL,
+        },
+    },
+};
+
+#[derive(Deserialize)]
+pub struct MagicLinkRequest {
+    pub email: Secret<String>,
+}
+
+#[derive(Deserialize)]
+pub struct VerifyMagicLinkRequest {
+    pub token: Option<Secret<String>>,
+}
+
+// Emails a one-time login link. The response is the same whether or not the user exists,
+// so it can't be used to find out who has an account.
+#[tracing::instrument(name = "Request magic link", skip_all)]
+pub async fn request_magic_link(
+    State(state): State<AppState>,
+    cookie_jar: CookieJar,
+    Json(request): Json<MagicLinkRequest>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+
+    let email = match Email::parse(request.email) {
+        Ok(email) => email,
+        Err(_) => return (cookie_jar, Err(AuthAPIError::InvalidCredentials)),
+    };
+
+    // Links requested earlier in this browser keep working
+    let binding = cookie_jar
+        .get(MAGIC_LINK_COOKIE_NAME)
+        .and_then(|cookie| BrowserBinding::parse(Secret::new(cookie.value().to_owned())).ok())
+        .unwrap_or_default();
+
+    let mut cookie = COOKIE_CONFIG.build(
+        MAGIC_LINK_COOKIE_NAME,
+        binding.as_ref().expose_secret().to_owned(),
+        *MAGIC_LINK_TTL_SECONDS,
+    );
+    // Links are opened from the email client, a cross-site navigation strict cookies don't follow
+    if cookie.same_site() == Some(SameSite::Strict) {
+        cookie.set_same_site(SameSite::Lax);
+    }
+    let cookie_jar = cookie_jar.add(cookie);
+
+    match state.user_store.read().await.get_user(&email).await {
+        Ok(_) => {}
+        Err(UserStoreError::UserNotFound) => return (cookie_jar, Ok(StatusCode::OK)),
+        Err(e) => return (cookie_jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    }
+
+    if let Err(e) = send_magic_link(&state, email, &binding).await {
+        return (cookie_jar, Err(AuthAPIError::UnexpectedError(e)));
+    }
+
+    (cookie_jar, Ok(StatusCode::OK))
+}
+
+// Users land here from the link in the email
+#[tracing::instrument(name = "Verify magic link", skip_all)]
+pub async fn verify_magic_link(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    cookie_jar: CookieJar,
+    Query(request): Query<VerifyMagicLinkRequest>,
+) -> (CookieJar, Result<Response, AuthAPIError>) {
+
+    let binding = cookie_jar
+        .get(MAGIC_LINK_COOKIE_NAME)
+        .map(|cookie| cookie.value().to_owned());
+
+    let link = match take_magic_link(&state, request.token, binding).await {
+        Ok(link) => link,
+        Err(e) => {
+            tracing::warn!("Magic link login failed: {:?}", e);
+            return (cookie_jar, Err(AuthAPIError::InvalidMagicLink));
+        }
+    };
+
+    let cookie_jar = cookie_jar.remove(COOKIE_CONFIG.removal(MAGIC_LINK_COOKIE_NAME));
+
+    let user = match state.user_store.read().await.get_user(&link.email).await {
+        Ok(user) => user,
+        Err(UserStoreError::UserNotFound) => return (cookie_jar, Err(AuthAPIError::InvalidMagicLink)),
+        Err(e) => return (cookie_jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    };
+
+    // The link stands in for the password only
+    if user.requires_2fa {
+        let (cookie_jar, result) = start_second_factor(&user, &state, cookie_jar).await;
+        return (cookie_jar, result.map(IntoResponse::into_response));
+    }
+
+    let user_agent = headers
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned);
+
+    let session = Session::new(user.email, addr.ip().to_string(), user_agent, vec![AuthMethod::Otp]);
+    let tokens = match start_session(session, &user.roles, &state).await {
+        Ok(tokens) => tokens,
+        Err(e) => return (cookie_jar, Err(AuthAPIError::UnexpectedError(e))),
+    };
+
+    let (cookie_jar, _) = tokens.deliver(TokenDelivery::Cookie, cookie_jar);
+
+    (cookie_jar, Ok(Redirect::to("/").into_response()))
+}
+
+async fn send_magic_link(state: &AppState, email: Email, binding: &BrowserBinding) -> Result<()> {
+    let token = MagicLinkToken::default();
+    let url = format!(
+        "{}/magic-link/verify?token={}",
+        *PUBLIC_URL,
+        token.sign(MAGIC_LINK_KEY.expose_secret()).expose_secret()
+    );
+
+    state
+        .magic_link_store
+        .write()
+        .await
+        .add_link(token, MagicLink::new(email.clone(), binding, *MAGIC_LINK_TTL_SECONDS))
+        .await?;
+
+    let content = format!(
+        "Use this link to log in: {}\n\nIt works once, in the browser you asked for it in, for the next {} minutes.",
+        url,
+        *MAGIC_LINK_TTL_SECONDS / 60
+    );
+
+    state.email_client.send_email(&email, "Your login link", &content).await
+}
+
+async fn take_magic_link(
+    state: &AppState,
+    token: Option<Secret<String>>,
+    binding: Option<String>,
+) -> Result<MagicLink> {
+
+    let token = MagicLinkToken::verify_signed(
+        token.ok_or(eyre!("Missing token"))?,
+        MAGIC_LINK_KEY.expose_secret(),
+    )?;
+
+    // The link is used up even when it's opened in the wrong browser
+    let link = state.magic_link_store.write().await.take_link(&token).await?;
+
+    let binding = BrowserBinding::parse(Secret::new(binding.ok_or(eyre!("Missing browser binding cookie"))?))?;
+    if !link.is_bound_to(&binding) {
+        return Err(eyre!("Link was requested in another browser"));
+    }
+
+    if link.is_expired() {
+        return Err(eyre!("Link expired"));
+    }
+
+    Ok(link)
+}
@@ -1,12 +1,52 @@
+mod api_keys;
+mod authorize;
+mod change_password;
+mod jwks;
 mod login;
 mod logout;
+mod logout_all;
+mod magic_link;
+mod oauth_clients;
+mod oidc;
+mod openid_configuration;
+mod passkeys;
+mod reauthenticate;
+mod recovery_codes;
+mod refresh;
+mod resend_2fa;
+mod sessions;
 mod signup;
+mod sms;
+mod token;
+mod totp;
+mod trusted_devices;
+mod userinfo;
 mod verify_2fa;
 mod verify_token;
 
 // Re-export items from submodules
+pub use api_keys::*;
+pub use authorize::*;
+pub use change_password::*;
+pub use jwks::*;
 pub use login::*;
 pub use logout::*;
+pub use logout_all::*;
+pub use magic_link::*;
+pub use oauth_clients::*;
+pub use oidc::*;
+pub use openid_configuration::*;
+pub use passkeys::*;
+pub use reauthenticate::*;
+pub use recovery_codes::*;
+pub use refresh::*;
+pub use resend_2fa::*;
+pub use sessions::*;
 pub use signup::*;
+pub use sms::*;
+pub use token::*;
+pub use totp::*;
+pub use trusted_devices::*;
+pub use userinfo::*;
 pub use verify_2fa::*;
 pub use verify_token::*;
\ No newline at end of file
@@ -0,0 +1,92 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, ClientSecret, OAuthClient},
+};
+
+#[derive(Debug, Deserialize)]
+pub struct RegisterClientRequest {
+    pub name: String,
+    #[serde(rename = "redirectUris")]
+    pub redirect_uris: Vec<String>,
+    // Confidential clients get a secret to authenticate with at the token endpoint
+    #[serde(default)]
+    pub confidential: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RegisterClientResponse {
+    #[serde(rename = "clientId")]
+    pub client_id: String,
+    // Only returned once, the service keeps a hash of it
+    #[serde(rename = "clientSecret", skip_serializing_if = "Option::is_none")]
+    pub client_secret: Option<String>,
+}
+
+// Register an application that signs its users in with this service. Restricted to admins.
+#[tracing::instrument(name = "Register OAuth client", skip_all)]
+pub async fn register_oauth_client(
+    State(state): State<AppState>,
+    Json(request): Json<RegisterClientRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+
+    let client = OAuthClient::new(request.name, request.redirect_uris, request.confidential)
+        .map_err(|_| AuthAPIError::InvalidRedirectUri)?;
+
+    let client_secret = client.confidential.then(ClientSecret::default);
+
+    let response = RegisterClientResponse {
+        client_id: client.client_id.clone(),
+        client_secret: client_secret
+            .as_ref()
+            .map(|secret| secret.as_ref().expose_secret().to_owned()),
+    };
+
+    state
+        .oauth_client_store
+        .write()
+        .await
+        .add_client(client, client_secret)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RegisterServiceClientRequest {
+    pub name: String,
+    pub scopes: Vec<String>,
+}
+
+// Register a backend service that gets tokens for itself with the client credentials grant.
+// Restricted to admins.
+#[tracing::instrument(name = "Register service client", skip_all)]
+pub async fn register_service_client(
+    State(state): State<AppState>,
+    Json(request): Json<RegisterServiceClientRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+
+    let client = OAuthClient::service(request.name, request.scopes)
+        .map_err(|_| AuthAPIError::InvalidScope)?;
+
+    let client_secret = ClientSecret::default();
+
+    let response = RegisterClientResponse {
+        client_id: client.client_id.clone(),
+        client_secret: Some(client_secret.as_ref().expose_secret().to_owned()),
+    };
+
+    state
+        .oauth_client_store
+        .write()
+        .await
+        .add_client(client, Some(client_secret))
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok((StatusCode::CREATED, Json(response)))
+}
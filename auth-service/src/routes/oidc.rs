@@ -0,0 +1,299 @@
+use std::net::SocketAddr;
+use auth_middleware::AuthenticatedUser;
+use axum::{
+    extract::{ConnectInfo, Path, Query, State},
+    http::{header, HeaderMap},
+    response::{IntoResponse, Redirect, Response},
+};
+use axum_extra::extract::{cookie::SameSite, CookieJar};
+use color_eyre::eyre::{eyre, Result};
+use secrecy::{ExposeSecret, Secret};
+use serde::Deserialize;
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        data_stores::{ExternalIdentityStoreError, UserStoreError},
+        AuthAPIError, AuthMethod, Email, ExternalIdentity, OidcLogin, OidcState, Password, Session, User,
+        OIDC_LOGIN_TTL_SECONDS,
+    },
+    routes::start_second_factor,
+    services::oidc_client::{ExternalIdTokenClaims, OidcClient},
+    utils::{
+        auth::{start_session, TokenDelivery},
+        constants::{COOKIE_CONFIG, OIDC_STATE_COOKIE_NAME, PUBLIC_URL},
+    },
+};
+
+#[derive(Deserialize)]
+pub struct OidcLoginRequest {
+    pub return_to: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct OidcCallbackRequest {
+    pub code: Option<Secret<String>>,
+    pub state: Option<Secret<String>>,
+    pub error: Option<String>,
+}
+
+// Send the user to log in at an external provider
+#[tracing::instrument(name = "OIDC login", skip_all)]
+pub async fn oidc_login(
+    State(state): State<AppState>,
+    Path(provider): Path<String>,
+    cookie_jar: CookieJar,
+    Query(request): Query<OidcLoginRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+
+    start_oidc_login(&state, provider, None, cookie_jar, request).await
+}
+
+// Send a logged in user to the provider to link their account there to this one. Accounts
+// of users who already exist are only linked this way.
+#[tracing::instrument(name = "OIDC link", skip_all)]
+pub async fn oidc_link(
+    State(state): State<AppState>,
+    Path(provider): Path<String>,
+    user: AuthenticatedUser,
+    cookie_jar: CookieJar,
+    Query(request): Query<OidcLoginRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+
+    let email = Email::parse(Secret::new(user.subject))
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    start_oidc_login(&state, provider, Some(email), cookie_jar, request).await
+}
+
+async fn start_oidc_login(
+    state: &AppState,
+    provider: String,
+    link_to: Option<Email>,
+    cookie_jar: CookieJar,
+    request: OidcLoginRequest,
+) -> Result<(CookieJar, Redirect), AuthAPIError> {
+
+    let client = state
+        .oidc_providers
+        .get(&provider)
+        .ok_or(AuthAPIError::UnknownIdentityProvider)?;
+
+    // Only paths on this site, so the login can't be used to redirect users elsewhere
+    let return_to = request
+        .return_to
+        .filter(|path| path.starts_with('/') && !path.starts_with("//"));
+
+    let oidc_state = OidcState::default();
+    let mut login = OidcLogin::new(provider, return_to);
+    login.link_to = link_to;
+
+    let url = client
+        .authorization_url(&oidc_state, &login, &redirect_uri(client))
+        .await
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    state
+        .oidc_login_store
+        .write()
+        .await
+        .add_login(oidc_state.clone(), login)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let mut cookie = COOKIE_CONFIG.build(
+        OIDC_STATE_COOKIE_NAME,
+        oidc_state.as_ref().expose_secret().to_owned(),
+        OIDC_LOGIN_TTL_SECONDS as i64,
+    );
+    // The provider redirects back with a cross-site navigation, which strict cookies don't follow
+    if cookie.same_site() == Some(SameSite::Strict) {
+        cookie.set_same_site(SameSite::Lax);
+    }
+
+    Ok((cookie_jar.add(cookie), Redirect::to(&url)))
+}
+
+// The provider sends the user back here once they logged in
+#[tracing::instrument(name = "OIDC callback", skip_all)]
+pub async fn oidc_callback(
+    State(state): State<AppState>,
+    Path(provider): Path<String>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    cookie_jar: CookieJar,
+    Query(request): Query<OidcCallbackRequest>,
+) -> (CookieJar, Result<Response, AuthAPIError>) {
+
+    let client = match state.oidc_providers.get(&provider) {
+        Some(client) => client,
+        None => return (cookie_jar, Err(AuthAPIError::UnknownIdentityProvider)),
+    };
+
+    let cookie_state = cookie_jar
+        .get(OIDC_STATE_COOKIE_NAME)
+        .map(|cookie| cookie.value().to_owned());
+    let cookie_jar = cookie_jar.remove(COOKIE_CONFIG.removal(OIDC_STATE_COOKIE_NAME));
+
+    let (claims, login) = match complete_login(&state, client, cookie_state, request).await {
+        Ok(result) => result,
+        Err(e) => {
+            tracing::warn!("External login with {} failed: {:?}", provider, e);
+            return (cookie_jar, Err(AuthAPIError::ExternalLoginFailed));
+        }
+    };
+
+    let linking = login.link_to.is_some();
+    let user = match link_user(&state, &provider, claims, login.link_to).await {
+        Ok(user) => user,
+        Err(e) => return (cookie_jar, Err(e)),
+    };
+
+    let return_to = login.return_to.unwrap_or("/".to_owned());
+
+    // The user who linked the account is already logged in
+    if linking {
+        return (cookie_jar, Ok(Redirect::to(&return_to).into_response()));
+    }
+
+    // The provider stands in for the password only
+    if user.requires_2fa {
+        let (cookie_jar, result) = start_second_factor(&user, &state, cookie_jar).await;
+        return (cookie_jar, result.map(IntoResponse::into_response));
+    }
+
+    let user_agent = headers
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned);
+
+    let session = Session::new(user.email, addr.ip().to_string(), user_agent, vec![AuthMethod::External]);
+    let tokens = match start_session(session, &user.roles, &state).await {
+        Ok(tokens) => tokens,
+        Err(e) => return (cookie_jar, Err(AuthAPIError::UnexpectedError(e))),
+    };
+
+    let (cookie_jar, _) = tokens.deliver(TokenDelivery::Cookie, cookie_jar);
+    (cookie_jar, Ok(Redirect::to(&return_to).into_response()))
+}
+
+async fn complete_login(
+    state: &AppState,
+    client: &OidcClient,
+    cookie_state: Option<String>,
+    request: OidcCallbackRequest,
+) -> Result<(ExternalIdTokenClaims, OidcLogin)> {
+
+    if let Some(error) = request.error {
+        return Err(eyre!("Provider returned an error: {}", error));
+    }
+
+    let oidc_state = OidcState::parse(request.state.ok_or(eyre!("Missing state"))?)?;
+
+    // The state must also come from the browser that started the login
+    if cookie_state.as_deref() != Some(oidc_state.as_ref().expose_secret().as_str()) {
+        return Err(eyre!("State does not match the cookie"));
+    }
+
+    let login = state
+        .oidc_login_store
+        .write()
+        .await
+        .take_login(&oidc_state)
+        .await?;
+
+    if login.provider != client.name() {
+        return Err(eyre!("Login was started with another provider"));
+    }
+
+    let code = request.code.ok_or(eyre!("Missing authorization code"))?;
+    let claims = client
+        .exchange_code(&code, &login, &redirect_uri(client))
+        .await?;
+
+    Ok((claims, login))
+}
+
+// Find the user the external account belongs to. On its first login, the account is linked
+// to the user who asked for it, or to a new user with the provider's verified email.
+async fn link_user(
+    state: &AppState,
+    provider: &str,
+    claims: ExternalIdTokenClaims,
+    link_to: Option<Email>,
+) -> Result<User, AuthAPIError> {
+
+    let mut identity_store = state.external_identity_store.write().await;
+
+    let email = match identity_store.get_identity(provider, &claims.sub).await {
+        // An account linked to one user can't be linked to another
+        Ok(identity) if link_to.as_ref().is_some_and(|email| *email != identity.email) => {
+            return Err(AuthAPIError::ExternalLoginFailed)
+        }
+        Ok(identity) => identity.email,
+        Err(ExternalIdentityStoreError::IdentityNotFound) => {
+            let email = match link_to {
+                Some(email) => email,
+                None => {
+                    // Without a verified email anyone could claim the address
+                    let email = match claims.email {
+                        Some(email) if claims.email_verified => Email::parse(Secret::new(email))
+                            .map_err(|_| AuthAPIError::ExternalLoginFailed)?,
+                        _ => {
+                            tracing::warn!("External login with {} has no verified email", provider);
+                            return Err(AuthAPIError::ExternalLoginFailed);
+                        }
+                    };
+
+                    create_user(state, &email).await?;
+                    email
+                }
+            };
+
+            let identity = ExternalIdentity {
+                provider: provider.to_owned(),
+                subject: claims.sub,
+                email: email.clone(),
+            };
+            identity_store
+                .add_identity(identity)
+                .await
+                .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+            email
+        }
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    state
+        .user_store
+        .read()
+        .await
+        .get_user(&email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))
+}
+
+// Users who only log in with a provider get a random password they never use. Existing
+// users have to prove they own the account and link the provider themselves.
+async fn create_user(state: &AppState, email: &Email) -> Result<(), AuthAPIError> {
+    let mut user_store = state.user_store.write().await;
+
+    match user_store.get_user(email).await {
+        Ok(_) => return Err(AuthAPIError::ExternalAccountNotLinked),
+        Err(UserStoreError::UserNotFound) => {}
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    let password = Password::parse(Secret::new(crate::domain::oauth::random_string(32)))
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    user_store
+        .add_user(User::new(email.clone(), password, false))
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))
+}
+
+fn redirect_uri(client: &OidcClient) -> String {
+    format!("{}/oidc/{}/callback", *PUBLIC_URL, client.name())
+}
@@ -0,0 +1,51 @@
+use axum::Json;
+use jsonwebtoken::Algorithm;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    domain::{AuthAPIError, SUPPORTED_SCOPES},
+    utils::constants::{JWT_ISSUER, JWT_KEY_SET, PUBLIC_URL},
+};
+
+// OpenID Connect discovery document, telling clients where the provider's endpoints are
+// and what it supports
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OpenIdConfiguration {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub userinfo_endpoint: String,
+    pub jwks_uri: String,
+    pub response_types_supported: Vec<String>,
+    pub subject_types_supported: Vec<String>,
+    pub id_token_signing_alg_values_supported: Vec<Algorithm>,
+    pub scopes_supported: Vec<String>,
+    pub token_endpoint_auth_methods_supported: Vec<String>,
+    pub grant_types_supported: Vec<String>,
+    pub code_challenge_methods_supported: Vec<String>,
+}
+
+#[tracing::instrument(name = "OpenID configuration", skip_all)]
+pub async fn openid_configuration() -> Result<Json<OpenIdConfiguration>, AuthAPIError> {
+    let signing_key = JWT_KEY_SET.signing_key().map_err(AuthAPIError::UnexpectedError)?;
+    let to_strings = |values: &[&str]| values.iter().map(|value| value.to_string()).collect();
+
+    Ok(Json(OpenIdConfiguration {
+        issuer: JWT_ISSUER.to_owned(),
+        authorization_endpoint: format!("{}/authorize", *PUBLIC_URL),
+        token_endpoint: format!("{}/token", *PUBLIC_URL),
+        userinfo_endpoint: format!("{}/userinfo", *PUBLIC_URL),
+        jwks_uri: format!("{}/.well-known/jwks.json", *PUBLIC_URL),
+        response_types_supported: to_strings(&["code"]),
+        subject_types_supported: to_strings(&["public"]),
+        id_token_signing_alg_values_supported: vec![signing_key.algorithm()],
+        scopes_supported: to_strings(&SUPPORTED_SCOPES),
+        token_endpoint_auth_methods_supported: to_strings(&[
+            "none",
+            "client_secret_basic",
+            "client_secret_post",
+        ]),
+        grant_types_supported: to_strings(&["authorization_code", "client_credentials"]),
+        code_challenge_methods_supported: to_strings(&["S256"]),
+    }))
+}
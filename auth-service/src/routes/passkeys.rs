@@ -0,0 +1,436 @@
+use std::net::SocketAddr;
+use auth_middleware::AuthenticatedUser;
+use axum::{
+    extract::{ConnectInfo, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use axum_extra::extract::CookieJar;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::Utc;
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        data_stores::{PasskeyChallengeStoreError, PasskeyStoreError},
+        user_handle, verify_assertion, verify_registration, AuthAPIError, AuthMethod, ClientData, Email,
+        Passkey, PasskeyCeremony, PasskeyChallenge, Session, PASSKEY_CHALLENGE_TTL_SECONDS,
+    },
+    utils::{
+        auth::{start_session, TokenDelivery},
+        constants::RELYING_PARTY,
+    },
+};
+
+// Options passed to `navigator.credentials.create()` or `navigator.credentials.get()`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PasskeyOptionsResponse<T> {
+    #[serde(rename = "publicKey")]
+    pub public_key: T,
+}
+
+// WebAuthn section 5.4
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PasskeyCreationOptions {
+    pub challenge: String,
+    pub rp: RelyingPartyEntity,
+    pub user: UserEntity,
+    #[serde(rename = "pubKeyCredParams")]
+    pub pub_key_cred_params: Vec<CredentialParameters>,
+    pub timeout: u64,
+    #[serde(rename = "excludeCredentials")]
+    pub exclude_credentials: Vec<CredentialDescriptor>,
+    #[serde(rename = "authenticatorSelection")]
+    pub authenticator_selection: AuthenticatorSelection,
+    pub attestation: String,
+}
+
+// WebAuthn section 5.5
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PasskeyRequestOptions {
+    pub challenge: String,
+    #[serde(rename = "rpId")]
+    pub rp_id: String,
+    pub timeout: u64,
+    #[serde(rename = "allowCredentials")]
+    pub allow_credentials: Vec<CredentialDescriptor>,
+    #[serde(rename = "userVerification")]
+    pub user_verification: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RelyingPartyEntity {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserEntity {
+    pub id: String,
+    pub name: String,
+    #[serde(rename = "displayName")]
+    pub display_name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CredentialParameters {
+    #[serde(rename = "type")]
+    pub credential_type: String,
+    pub alg: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CredentialDescriptor {
+    #[serde(rename = "type")]
+    pub credential_type: String,
+    pub id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuthenticatorSelection {
+    #[serde(rename = "residentKey")]
+    pub resident_key: String,
+    #[serde(rename = "userVerification")]
+    pub user_verification: String,
+}
+
+// The browser's `PublicKeyCredential`, with binary fields base64url encoded
+#[derive(Debug, Deserialize)]
+pub struct FinishPasskeyRegistrationRequest {
+    pub id: String,
+    pub response: AttestationResponse,
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AttestationResponse {
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    #[serde(rename = "attestationObject")]
+    pub attestation_object: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FinishPasskeyLoginRequest {
+    pub id: String,
+    pub response: AssertionResponse,
+    #[serde(default, rename = "tokenDelivery")]
+    pub token_delivery: TokenDelivery,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AssertionResponse {
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    #[serde(rename = "authenticatorData")]
+    pub authenticator_data: String,
+    pub signature: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PasskeyResponse {
+    pub id: String,
+    pub name: String,
+    #[serde(rename = "createdAt")]
+    pub created_at: i64,
+}
+
+#[tracing::instrument(name = "Start passkey registration", skip_all)]
+pub async fn start_passkey_registration(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+) -> Result<impl IntoResponse, AuthAPIError> {
+
+    let email = passkey_owner(user)?;
+
+    let passkeys = state
+        .passkey_store
+        .read()
+        .await
+        .get_passkeys(&email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let challenge = PasskeyChallenge::new(PasskeyCeremony::Registration(email.clone()));
+    add_challenge(&state, challenge.clone()).await?;
+
+    let name = email.as_ref().expose_secret().to_owned();
+    let options = PasskeyCreationOptions {
+        challenge: challenge.challenge,
+        rp: RelyingPartyEntity {
+            id: RELYING_PARTY.id.clone(),
+            name: RELYING_PARTY.name.clone(),
+        },
+        user: UserEntity {
+            id: user_handle(&email),
+            name: name.clone(),
+            display_name: name,
+        },
+        pub_key_cred_params: vec![CredentialParameters {
+            credential_type: PUBLIC_KEY_TYPE.to_owned(),
+            alg: ES256,
+        }],
+        timeout: PASSKEY_CHALLENGE_TTL_SECONDS * 1000,
+        // Stops users from registering the same authenticator twice
+        exclude_credentials: descriptors(&passkeys),
+        authenticator_selection: AuthenticatorSelection {
+            // Passkeys stored on the authenticator can log in without an email
+            resident_key: "required".to_owned(),
+            user_verification: "preferred".to_owned(),
+        },
+        attestation: "none".to_owned(),
+    };
+
+    Ok((StatusCode::OK, Json(PasskeyOptionsResponse { public_key: options })))
+}
+
+#[tracing::instrument(name = "Finish passkey registration", skip_all)]
+pub async fn finish_passkey_registration(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Json(request): Json<FinishPasskeyRegistrationRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+
+    let email = passkey_owner(user)?;
+
+    let client_data_json = decode(&request.response.client_data_json)?;
+    let attestation_object = decode(&request.response.attestation_object)?;
+
+    let client_data = ClientData::parse(&client_data_json, "webauthn.create", &RELYING_PARTY)
+        .map_err(|_| AuthAPIError::InvalidPasskey)?;
+
+    // The challenge must have been issued to this user
+    let challenge = take_challenge(&state, &client_data).await?;
+    if challenge.ceremony != PasskeyCeremony::Registration(email.clone()) {
+        return Err(AuthAPIError::InvalidPasskey);
+    }
+
+    let credential = verify_registration(&RELYING_PARTY, &attestation_object, false)
+        .map_err(|e| {
+            tracing::warn!("Passkey registration failed: {:?}", e);
+            AuthAPIError::InvalidPasskey
+        })?;
+
+    if credential.id != request.id {
+        return Err(AuthAPIError::InvalidPasskey);
+    }
+
+    let name = request
+        .name
+        .map(|name| name.trim().to_owned())
+        .filter(|name| !name.is_empty())
+        .unwrap_or(DEFAULT_PASSKEY_NAME.to_owned());
+
+    let passkey = Passkey {
+        id: credential.id,
+        email,
+        name,
+        public_key: credential.public_key,
+        sign_count: credential.sign_count,
+        created_at: Utc::now().timestamp(),
+        last_used_at: None,
+    };
+
+    let response = Json(PasskeyResponse {
+        id: passkey.id.clone(),
+        name: passkey.name.clone(),
+        created_at: passkey.created_at,
+    });
+
+    match state.passkey_store.write().await.add_passkey(passkey).await {
+        Ok(()) => Ok((StatusCode::CREATED, response)),
+        Err(PasskeyStoreError::PasskeyAlreadyExists) => Err(AuthAPIError::InvalidPasskey),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+// Passwordless login with a passkey stored on the authenticator
+#[tracing::instrument(name = "Start passkey login", skip_all)]
+pub async fn start_passkey_login(
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+
+    let options = start_passkey_challenge(&state, PasskeyCeremony::Login, &[]).await?;
+
+    Ok((StatusCode::OK, Json(PasskeyOptionsResponse { public_key: options })))
+}
+
+// Completes both passwordless logins and logins that asked for a passkey as second factor
+#[tracing::instrument(name = "Finish passkey login", skip_all)]
+pub async fn finish_passkey_login(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    jar: CookieJar,
+    Json(request): Json<FinishPasskeyLoginRequest>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+
+    let email = match verify_passkey_login(&state, &request).await {
+        Ok(email) => email,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    let user = match state.user_store.read().await.get_user(&email).await {
+        Ok(user) => user,
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    };
+
+    let user_agent = headers
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned);
+
+    let session = Session::new(email, addr.ip().to_string(), user_agent, vec![AuthMethod::Passkey]);
+
+    let tokens = match start_session(session, &user.roles, &state).await {
+        Ok(tokens) => tokens,
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e))),
+    };
+
+    match tokens.deliver(request.token_delivery, jar) {
+        (updated_jar, Some(body)) => (updated_jar, Ok((StatusCode::OK, Json(body)).into_response())),
+        (updated_jar, None) => (updated_jar, Ok(StatusCode::OK.into_response())),
+    }
+}
+
+// Issue a challenge that one of the passkeys must sign, or any passkey of the site when
+// none are given
+pub(crate) async fn start_passkey_challenge(
+    state: &AppState,
+    ceremony: PasskeyCeremony,
+    passkeys: &[Passkey],
+) -> Result<PasskeyRequestOptions, AuthAPIError> {
+
+    // Passwordless logins must prove the user, a second factor only their presence
+    let user_verification = match ceremony {
+        PasskeyCeremony::Login => "required",
+        _ => "preferred",
+    };
+
+    let challenge = PasskeyChallenge::new(ceremony);
+    add_challenge(state, challenge.clone()).await?;
+
+    Ok(PasskeyRequestOptions {
+        challenge: challenge.challenge,
+        rp_id: RELYING_PARTY.id.clone(),
+        timeout: PASSKEY_CHALLENGE_TTL_SECONDS * 1000,
+        allow_credentials: descriptors(passkeys),
+        user_verification: user_verification.to_owned(),
+    })
+}
+
+// Check the assertion and return the email of the user it logs in
+async fn verify_passkey_login(
+    state: &AppState,
+    request: &FinishPasskeyLoginRequest,
+) -> Result<Email, AuthAPIError> {
+
+    let client_data_json = decode(&request.response.client_data_json)?;
+    let authenticator_data = decode(&request.response.authenticator_data)?;
+    let signature = decode(&request.response.signature)?;
+
+    let client_data = ClientData::parse(&client_data_json, "webauthn.get", &RELYING_PARTY)
+        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+    let challenge = match take_challenge(state, &client_data).await {
+        Err(AuthAPIError::InvalidPasskey) => return Err(AuthAPIError::IncorrectCredentials),
+        result => result?,
+    };
+
+    let (expected_email, require_user_verification) = match challenge.ceremony {
+        PasskeyCeremony::SecondFactor(email) => (Some(email), false),
+        PasskeyCeremony::Login => (None, true),
+        PasskeyCeremony::Registration(_) => return Err(AuthAPIError::IncorrectCredentials),
+    };
+
+    let passkey = match state.passkey_store.read().await.get_passkey(&request.id).await {
+        Ok(passkey) => passkey,
+        Err(PasskeyStoreError::PasskeyNotFound) => return Err(AuthAPIError::IncorrectCredentials),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    // A second factor must be one of the passkeys of the user who entered their password
+    if expected_email.is_some_and(|email| email != passkey.email) {
+        return Err(AuthAPIError::IncorrectCredentials);
+    }
+
+    let sign_count = verify_assertion(
+        &RELYING_PARTY,
+        &passkey,
+        &client_data,
+        &authenticator_data,
+        &signature,
+        require_user_verification,
+    )
+    .map_err(|e| {
+        tracing::warn!("Passkey login failed: {:?}", e);
+        AuthAPIError::IncorrectCredentials
+    })?;
+
+    state
+        .passkey_store
+        .write()
+        .await
+        .update_sign_count(&passkey.id, sign_count)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok(passkey.email)
+}
+
+async fn add_challenge(state: &AppState, challenge: PasskeyChallenge) -> Result<(), AuthAPIError> {
+    state
+        .passkey_challenge_store
+        .write()
+        .await
+        .add_challenge(challenge)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))
+}
+
+async fn take_challenge(
+    state: &AppState,
+    client_data: &ClientData,
+) -> Result<PasskeyChallenge, AuthAPIError> {
+    match state
+        .passkey_challenge_store
+        .write()
+        .await
+        .take_challenge(&client_data.challenge)
+        .await
+    {
+        Ok(challenge) => Ok(challenge),
+        Err(PasskeyChallengeStoreError::ChallengeNotFound) => Err(AuthAPIError::InvalidPasskey),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+fn descriptors(passkeys: &[Passkey]) -> Vec<CredentialDescriptor> {
+    passkeys
+        .iter()
+        .map(|passkey| CredentialDescriptor {
+            credential_type: PUBLIC_KEY_TYPE.to_owned(),
+            id: passkey.id.clone(),
+        })
+        .collect()
+}
+
+fn decode(value: &str) -> Result<Vec<u8>, AuthAPIError> {
+    URL_SAFE_NO_PAD
+        .decode(value.trim_end_matches('='))
+        .map_err(|_| AuthAPIError::InvalidCredentials)
+}
+
+// The user managing their passkeys
+fn passkey_owner(user: AuthenticatedUser) -> Result<Email, AuthAPIError> {
+    Email::parse(Secret::new(user.subject)).map_err(|_| AuthAPIError::InvalidToken)
+}
+
+const PUBLIC_KEY_TYPE: &str = "public-key";
+// COSE algorithm identifier of ECDSA with P-256 and SHA-256
+const ES256: i64 = -7;
+const DEFAULT_PASSKEY_NAME: &str = "Passkey";
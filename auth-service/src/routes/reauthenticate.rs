@@ -0,0 +1,242 @@
+use auth_middleware::AuthenticatedUser;
+use axum::{extract::State, http::StatusCode, response::{IntoResponse, Response}, Json};
+use axum_extra::extract::CookieJar;
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        data_stores::SessionStoreError, AuthAPIError, AuthMethod, Email, LoginAttemptId,
+        Password, TwoFACode, TwoFACodeStoreError, TwoFAMethod, User,
+    },
+    routes::{send_2fa_code, verify_totp_code, TwoFactorAuthResponse},
+    utils::{
+        auth::{generate_auth_cookie, generate_auth_token, TokenDelivery},
+        constants::{REAUTHENTICATION_MAX_ATTEMPTS, TOKEN_TTL_SECONDS},
+    },
+};
+
+#[derive(Deserialize)]
+pub struct ReauthenticateRequest {
+    pub password: Option<Secret<String>>,
+    // Only needed for codes sent by `/reauthenticate/send-2fa`
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: Option<Secret<String>>,
+    #[serde(rename = "2FACode")]
+    pub two_fa_code: Option<Secret<String>>,
+    #[serde(default, rename = "tokenDelivery")]
+    pub token_delivery: TokenDelivery,
+}
+
+// Only the auth token is replaced, the session keeps its refresh token
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReauthenticateResponse {
+    #[serde(rename = "accessToken")]
+    pub access_token: String,
+    #[serde(rename = "tokenType")]
+    pub token_type: String,
+    #[serde(rename = "expiresIn")]
+    pub expires_in: i64,
+}
+
+// Logged in users prove who they are again before sensitive operations, with their password,
+// their second factor or both
+#[tracing::instrument(name = "Reauthenticate", skip_all)]
+pub async fn reauthenticate(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    jar: CookieJar,
+    Json(request): Json<ReauthenticateRequest>,
+) -> (CookieJar, Result<Response, AuthAPIError>) {
+
+    let (email, session_id) = match (Email::parse(Secret::new(user.subject)), user.session_id) {
+        (Ok(email), Some(session_id)) => (email, session_id),
+        _ => return (jar, Err(AuthAPIError::InvalidToken)),
+    };
+
+    if request.password.is_none() && request.two_fa_code.is_none() {
+        return (jar, Err(AuthAPIError::InvalidCredentials));
+    }
+
+    let user = match state.user_store.read().await.get_user(&email).await {
+        Ok(user) => user,
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    };
+
+    let mut amr = Vec::new();
+
+    if let Some(password) = request.password {
+        if let Err(e) = verify_password(&state, &email, password).await {
+            return (jar, Err(record_failure(&state, &email, &session_id, e).await));
+        }
+        amr.push(AuthMethod::Password);
+    }
+
+    if let Some(code) = request.two_fa_code {
+        if let Err(e) = verify_second_factor(&state, &user, request.login_attempt_id, code).await {
+            return (jar, Err(record_failure(&state, &email, &session_id, e).await));
+        }
+        amr.push(AuthMethod::from(user.two_fa_method));
+    }
+
+    let session = match state
+        .session_store
+        .write()
+        .await
+        .record_authentication(&session_id, amr)
+        .await
+    {
+        Ok(session) => session,
+        Err(SessionStoreError::SessionNotFound) => return (jar, Err(AuthAPIError::InvalidToken)),
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    };
+
+    let session_version = match state.session_version_store.read().await.get_version(&email).await {
+        Ok(version) => version,
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    };
+
+    match request.token_delivery {
+        TokenDelivery::Cookie => match generate_auth_cookie(&session, &user.roles, session_version) {
+            Ok(cookie) => (jar.add(cookie), Ok(StatusCode::OK.into_response())),
+            Err(e) => (jar, Err(AuthAPIError::UnexpectedError(e))),
+        },
+        TokenDelivery::Body => match generate_auth_token(&session, &user.roles, session_version) {
+            Ok(token) => {
+                let body = Json(ReauthenticateResponse {
+                    access_token: token.expose_secret().to_owned(),
+                    token_type: "Bearer".to_owned(),
+                    expires_in: *TOKEN_TTL_SECONDS,
+                });
+                (jar, Ok((StatusCode::OK, body).into_response()))
+            }
+            Err(e) => (jar, Err(AuthAPIError::UnexpectedError(e))),
+        },
+    }
+
+}
+
+// Sends a code of the user's 2FA method to reauthenticate with. Users with an authenticator
+// app don't need one and can send a code from the app right away.
+#[tracing::instrument(name = "Send reauthentication 2FA code", skip_all)]
+pub async fn send_reauthentication_2fa(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+) -> Result<impl IntoResponse, AuthAPIError> {
+
+    let email = Email::parse(Secret::new(user.subject))
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let user = state
+        .user_store
+        .read()
+        .await
+        .get_user(&email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let login_attempt_id = LoginAttemptId::default();
+    let two_fa_code = TwoFACode::default();
+
+    state
+        .two_fa_code_store
+        .write()
+        .await
+        .add_code(email, login_attempt_id.clone(), two_fa_code.clone())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    send_2fa_code(&user, &two_fa_code, &state)
+        .await
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    let response = Json(TwoFactorAuthResponse {
+        message: String::from("2FA code sent"),
+        login_attempt_id: login_attempt_id.as_ref().expose_secret().to_owned(),
+        two_fa_method: user.two_fa_method,
+    });
+
+    Ok((StatusCode::OK, response))
+
+}
+
+// Wrong passwords and codes count against the session, which is logged out once it gave
+// too many, so whoever holds its token can't guess the user's credentials
+async fn record_failure(state: &AppState, email: &Email, session_id: &str, error: AuthAPIError) -> AuthAPIError {
+    if !matches!(error, AuthAPIError::IncorrectCredentials | AuthAPIError::TwoFAAttemptsExceeded) {
+        return error;
+    }
+
+    let mut session_store = state.session_store.write().await;
+
+    let failures = match session_store.record_failed_reauthentication(session_id).await {
+        Ok(failures) => failures,
+        Err(SessionStoreError::SessionNotFound) => return AuthAPIError::InvalidToken,
+        Err(e) => return AuthAPIError::UnexpectedError(e.into()),
+    };
+
+    if failures < *REAUTHENTICATION_MAX_ATTEMPTS {
+        return error;
+    }
+
+    match session_store.remove_session(email, session_id).await {
+        Ok(()) | Err(SessionStoreError::SessionNotFound) => AuthAPIError::TwoFAAttemptsExceeded,
+        Err(e) => AuthAPIError::UnexpectedError(e.into()),
+    }
+}
+
+async fn verify_password(state: &AppState, email: &Email, password: Secret<String>) -> Result<(), AuthAPIError> {
+    let password = Password::parse(password).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    state
+        .user_store
+        .read()
+        .await
+        .validate_user(email, &password)
+        .await
+        .map_err(|_| AuthAPIError::IncorrectCredentials)
+}
+
+// Codes from an authenticator app are checked directly, emailed and texted ones against
+// the attempt started by `/reauthenticate/send-2fa`
+async fn verify_second_factor(
+    state: &AppState,
+    user: &User,
+    login_attempt_id: Option<Secret<String>>,
+    code: Secret<String>,
+) -> Result<(), AuthAPIError> {
+
+    let code = TwoFACode::parse(code).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    if user.two_fa_method == TwoFAMethod::Totp {
+        return verify_totp_code(state, &user.email, &code).await;
+    }
+
+    let login_attempt_id = login_attempt_id
+        .and_then(|id| LoginAttemptId::parse(id).ok())
+        .ok_or(AuthAPIError::InvalidCredentials)?;
+
+    let mut two_fa_code_store = state.two_fa_code_store.write().await;
+
+    match two_fa_code_store.get_code(&login_attempt_id).await {
+        Ok((email, expected)) if email == user.email && expected == code => {}
+        Ok((email, _)) if email == user.email => {
+            // Wrong codes count against the attempt, so the code can't be brute forced
+            return match two_fa_code_store.record_failed_attempt(&login_attempt_id).await {
+                Ok(()) => Err(AuthAPIError::IncorrectCredentials),
+                Err(TwoFACodeStoreError::TooManyAttempts) => Err(AuthAPIError::TwoFAAttemptsExceeded),
+                Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+            };
+        }
+        Ok(_) | Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => {
+            return Err(AuthAPIError::IncorrectCredentials)
+        }
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    two_fa_code_store
+        .remove_code(&login_attempt_id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))
+}
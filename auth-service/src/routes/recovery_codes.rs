@@ -0,0 +1,91 @@
+use auth_middleware::AuthenticatedUser;
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{data_stores::RecoveryCodeStoreError, AuthAPIError, Email, RecoveryCode},
+};
+
+// The codes are only ever shown in this response
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecoveryCodesResponse {
+    #[serde(rename = "recoveryCodes")]
+    pub recovery_codes: Vec<String>,
+}
+
+// Replaces the user's recovery codes, for when they used up or misplaced the old ones
+#[tracing::instrument(name = "Regenerate recovery codes", skip_all)]
+pub async fn regenerate_recovery_codes(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+) -> Result<impl IntoResponse, AuthAPIError> {
+
+    let email = Email::parse(Secret::new(user.subject)).map_err(|_| AuthAPIError::InvalidToken)?;
+    let recovery_codes = issue_recovery_codes(&state, &email).await?;
+
+    Ok((StatusCode::OK, Json(RecoveryCodesResponse { recovery_codes })))
+}
+
+// Generates a new set of recovery codes and returns them formatted for the user
+pub(crate) async fn issue_recovery_codes(
+    state: &AppState,
+    email: &Email,
+) -> Result<Vec<String>, AuthAPIError> {
+
+    let codes = RecoveryCode::generate_set();
+
+    state
+        .recovery_code_store
+        .write()
+        .await
+        .replace_codes(email, &codes)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok(codes
+        .iter()
+        .map(|code| code.formatted().expose_secret().to_owned())
+        .collect())
+}
+
+// Accepts a recovery code in place of the user's second factor, and lets them know it was used
+pub(crate) async fn use_recovery_code(
+    state: &AppState,
+    email: &Email,
+    code: &RecoveryCode,
+) -> Result<(), AuthAPIError> {
+
+    let mut recovery_code_store = state.recovery_code_store.write().await;
+
+    recovery_code_store.use_code(email, code).await.map_err(|e| match e {
+        RecoveryCodeStoreError::CodeNotFound => AuthAPIError::IncorrectCredentials,
+        e => AuthAPIError::UnexpectedError(e.into()),
+    })?;
+
+    let remaining = recovery_code_store
+        .count_codes(email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    drop(recovery_code_store);
+
+    let content = format!(
+        "A recovery code was just used to log in to your account. You have {} recovery codes left. \
+         If this wasn't you, change your password and generate new recovery codes.",
+        remaining
+    );
+
+    // The code is already used up, so failing the login over the notification would only
+    // lock the user out further
+    if let Err(e) = state
+        .email_client
+        .send_email(email, "Recovery code used", &content)
+        .await
+    {
+        tracing::error!("Failed to send recovery code notification: {:?}", e);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,124 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use axum_extra::extract::CookieJar;
+use secrecy::Secret;
+use serde::Deserialize;
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, RefreshToken, RefreshTokenStoreError, SessionStoreError, UserStoreError,
+    },
+    utils::{
+        auth::{generate_auth_token, SessionTokens, TokenDelivery},
+        constants::REFRESH_COOKIE_NAME,
+    },
+};
+
+#[tracing::instrument(name = "Refresh", skip_all)]
+pub async fn refresh(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    request: Option<Json<RefreshRequest>>,
+) -> (CookieJar, Result<Response, AuthAPIError>) {
+
+    // A refresh token sent in the body takes precedence over the cookie, and the
+    // new tokens are returned the same way the old one was presented
+    let (token, token_delivery) = match (request, jar.get(REFRESH_COOKIE_NAME)) {
+        (Some(Json(request)), _) => (request.refresh_token, TokenDelivery::Body),
+        (None, Some(cookie)) => (Secret::new(cookie.value().to_owned()), TokenDelivery::Cookie),
+        (None, None) => return (jar, Err(AuthAPIError::MissingToken)),
+    };
+
+    let token = match RefreshToken::parse(token) {
+        Ok(token) => token,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
+    };
+
+    let mut refresh_token_store = state.refresh_token_store.write().await;
+
+    let entry = match refresh_token_store.get_token(&token).await {
+        Ok(entry) => entry,
+        Err(RefreshTokenStoreError::TokenNotFound) => return (jar, Err(AuthAPIError::InvalidToken)),
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    };
+
+    // A token that was already rotated is being replayed, so the whole family is compromised
+    // and the session it belongs to is logged out, along with its auth tokens
+    if entry.used {
+        if let Err(e) = refresh_token_store.revoke_family(&entry.family_id).await {
+            return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+        }
+        return match state.session_store.write().await.remove_session(&entry.email, &entry.family_id).await {
+            Ok(()) | Err(SessionStoreError::SessionNotFound) => (jar, Err(AuthAPIError::InvalidToken)),
+            Err(e) => (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+        };
+    }
+
+    match refresh_token_store.is_family_revoked(&entry.family_id).await {
+        Ok(false) => (),
+        Ok(true) => return (jar, Err(AuthAPIError::InvalidToken)),
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    }
+
+    // The user logged out everywhere after this family was started
+    match state.session_version_store.read().await.get_version(&entry.email).await {
+        Ok(version) if entry.session_version < version => return (jar, Err(AuthAPIError::InvalidToken)),
+        Ok(_) => (),
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    }
+
+    // The session was revoked or has expired
+    let session = match state.session_store.write().await.touch_session(&entry.family_id).await {
+        Ok(session) => session,
+        Err(SessionStoreError::SessionNotFound) => return (jar, Err(AuthAPIError::InvalidToken)),
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    };
+
+    // Rotate: the presented token can't be used again and a new one joins the same family
+    if let Err(e) = refresh_token_store.mark_token_used(&token).await {
+        return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+    }
+
+    let new_token = RefreshToken::default();
+
+    if let Err(e) = refresh_token_store
+        .add_token(new_token.clone(), entry.rotate())
+        .await
+    {
+        return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+    }
+
+    // Roles are read again, so changes apply from the next refresh on
+    let user = match state.user_store.read().await.get_user(&entry.email).await {
+        Ok(user) => user,
+        Err(UserStoreError::UserNotFound) => return (jar, Err(AuthAPIError::InvalidToken)),
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    };
+
+    let auth_token = match generate_auth_token(&session, &user.roles, entry.session_version) {
+        Ok(token) => token,
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e))),
+    };
+
+    let tokens = SessionTokens {
+        auth_token,
+        refresh_token: new_token,
+    };
+
+    match tokens.deliver(token_delivery, jar) {
+        (updated_jar, Some(body)) => (updated_jar, Ok((StatusCode::OK, Json(body)).into_response())),
+        (updated_jar, None) => (updated_jar, Ok(StatusCode::OK.into_response())),
+    }
+
+}
+
+#[derive(Deserialize)]
+pub struct RefreshRequest {
+    #[serde(rename = "refreshToken")]
+    pub refresh_token: Secret<String>,
+}
@@ -0,0 +1,76 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use secrecy::Secret;
+use serde::Deserialize;
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        data_stores::UserStoreError, AuthAPIError, Email, LoginAttemptId, TwoFACode,
+        TwoFACodeStoreError, TwoFAMethod,
+    },
+    routes::send_2fa_code,
+};
+
+#[derive(Deserialize)]
+pub struct Resend2FARequest {
+    pub email: Secret<String>,
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: Secret<String>,
+}
+
+// Sends a fresh code for a login attempt whose code got lost, without asking for the password again
+#[tracing::instrument(name = "Resend 2FA code", skip_all)]
+pub async fn resend_2fa(
+    State(state): State<AppState>,
+    Json(request): Json<Resend2FARequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+
+    let email = Email::parse(request.email)
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let login_attempt_id = LoginAttemptId::parse(request.login_attempt_id)
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let user = match state.user_store.read().await.get_user(&email).await {
+        Ok(user) => user,
+        Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::IncorrectCredentials),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    let mut two_fa_code_store = state.two_fa_code_store.write().await;
+
+    // Only the user who started the login can get its code resent
+    match two_fa_code_store.get_code(&login_attempt_id).await {
+        Ok((attempt_email, _)) if attempt_email == email => {}
+        Ok(_) | Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => {
+            return Err(AuthAPIError::IncorrectCredentials)
+        }
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    // Authenticator apps show a new code by themselves, there is nothing to send
+    if user.two_fa_method == TwoFAMethod::Totp {
+        return Err(AuthAPIError::TwoFAResendNotSupported);
+    }
+
+    let two_fa_code = TwoFACode::default();
+
+    two_fa_code_store
+        .resend_code(&login_attempt_id, two_fa_code.clone())
+        .await
+        .map_err(|e| match e {
+            TwoFACodeStoreError::LoginAttemptIdNotFound => AuthAPIError::IncorrectCredentials,
+            TwoFACodeStoreError::ResendTooSoon(seconds) => AuthAPIError::TwoFAResendTooSoon(seconds),
+            TwoFACodeStoreError::TooManyResends => AuthAPIError::TwoFAResendsExceeded,
+            e => AuthAPIError::UnexpectedError(e.into()),
+        })?;
+
+    drop(two_fa_code_store);
+
+    send_2fa_code(&user, &two_fa_code, &state)
+        .await
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    Ok(StatusCode::OK)
+
+}
@@ -0,0 +1,120 @@
+use auth_middleware::AuthenticatedUser;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use axum_extra::extract::CookieJar;
+use secrecy::Secret;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, Session, SessionStoreError},
+    utils::constants::{COOKIE_CONFIG, CSRF_COOKIE_NAME, JWT_COOKIE_NAME, REFRESH_COOKIE_NAME},
+};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionsResponse {
+    pub sessions: Vec<SessionResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionResponse {
+    pub id: String,
+    pub ip: String,
+    #[serde(rename = "userAgent")]
+    pub user_agent: Option<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: i64,
+    #[serde(rename = "lastSeen")]
+    pub last_seen: i64,
+    // Whether this is the session making the request
+    pub current: bool,
+}
+
+impl SessionResponse {
+    fn new(session: Session, current_session_id: &str) -> Self {
+        Self {
+            current: session.id == current_session_id,
+            id: session.id,
+            ip: session.ip,
+            user_agent: session.user_agent,
+            created_at: session.created_at,
+            last_seen: session.last_seen,
+        }
+    }
+}
+
+#[tracing::instrument(name = "Get sessions", skip_all)]
+pub async fn get_sessions(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+) -> Result<impl IntoResponse, AuthAPIError> {
+
+    let (email, current_session_id) = session_owner(user)?;
+
+    let mut sessions = state
+        .session_store
+        .read()
+        .await
+        .get_sessions(&email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    // Most recently started first
+    sessions.sort_by_key(|session| std::cmp::Reverse(session.created_at));
+
+    let response = Json(SessionsResponse {
+        sessions: sessions
+            .into_iter()
+            .map(|session| SessionResponse::new(session, &current_session_id))
+            .collect(),
+    });
+
+    Ok((StatusCode::OK, response))
+
+}
+
+#[tracing::instrument(name = "Delete session", skip_all)]
+pub async fn delete_session(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    jar: CookieJar,
+    Path(id): Path<String>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+
+    let (email, current_session_id) = match session_owner(user) {
+        Ok(owner) => owner,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    match state.session_store.write().await.remove_session(&email, &id).await {
+        Ok(()) => (),
+        Err(SessionStoreError::SessionNotFound) => return (jar, Err(AuthAPIError::SessionNotFound)),
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    }
+
+    // Deleting the current session is the same as logging out
+    if id == current_session_id {
+        let removed_jar = jar
+            .remove(COOKIE_CONFIG.removal(JWT_COOKIE_NAME))
+            .remove(COOKIE_CONFIG.removal(REFRESH_COOKIE_NAME))
+            .remove(COOKIE_CONFIG.removal(CSRF_COOKIE_NAME));
+        return (removed_jar, Ok(StatusCode::OK));
+    }
+
+    (jar, Ok(StatusCode::OK))
+
+}
+
+// The user and the session the request was made from
+fn session_owner(user: AuthenticatedUser) -> Result<(Email, String), AuthAPIError> {
+    let session_id = user.session_id.ok_or(AuthAPIError::InvalidToken)?;
+
+    let email = Email::parse(Secret::new(user.subject))
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    Ok((email, session_id))
+}
@@ -3,7 +3,8 @@ use secrecy::Secret;
 use serde::{Deserialize, Serialize};
 use crate::{
     app_state::AppState,
-    domain::{AuthAPIError, Email, User, Password}
+    domain::{AuthAPIError, Email, User, Password},
+    routes::issue_recovery_codes,
 };
 
 #[derive(Deserialize)]
@@ -17,6 +18,9 @@ pub struct SignupRequest{
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct SignupResponse {
     pub message: String,
+    // Only for users who turned on 2FA
+    #[serde(default, rename = "recoveryCodes", skip_serializing_if = "Option::is_none")]
+    pub recovery_codes: Option<Vec<String>>,
 }
 
 #[tracing::instrument(name = "Signup", skip_all)]
@@ -38,12 +42,21 @@ pub async fn signup(
         return Err(AuthAPIError::UserAlreadyExists);
     }
 
+    let email = user.email.clone();
     if let Err(e) = user_store.add_user(user).await {
         return Err(AuthAPIError::UnexpectedError(e.into()));
     }
 
+    drop(user_store);
+
+    let recovery_codes = match request.requires_2fa {
+        true => Some(issue_recovery_codes(&state, &email).await?),
+        false => None,
+    };
+
     let response = Json(SignupResponse {
-        message: "User created successfully!".to_string()
+        message: "User created successfully!".to_string(),
+        recovery_codes,
     });
     Ok((StatusCode::CREATED, response))
 
@@ -0,0 +1,112 @@
+use auth_middleware::AuthenticatedUser;
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use secrecy::{ExposeSecret, Secret};
+use serde::Deserialize;
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        data_stores::PhoneVerificationStoreError, AuthAPIError, Email, PhoneNumber, TwoFACode,
+        TwoFAMethod,
+    },
+    routes::{issue_recovery_codes, RecoveryCodesResponse},
+};
+
+#[derive(Deserialize)]
+pub struct EnrollSmsRequest {
+    #[serde(rename = "phoneNumber")]
+    pub phone_number: Secret<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ConfirmSmsRequest {
+    pub code: Secret<String>,
+}
+
+// Texts a code to the phone number the user wants to receive 2FA codes at. Starting over
+// replaces the number waiting for confirmation.
+#[tracing::instrument(name = "Enroll SMS", skip_all)]
+pub async fn enroll_sms(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Json(request): Json<EnrollSmsRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+
+    let email = sms_owner(user)?;
+    let phone_number = PhoneNumber::parse(request.phone_number).map_err(|_| AuthAPIError::InvalidPhoneNumber)?;
+    let code = TwoFACode::default();
+
+    state
+        .phone_verification_store
+        .write()
+        .await
+        .add_verification(email, phone_number.clone(), code.clone())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    state
+        .sms_client
+        .send_sms(&phone_number, &sms_content(&code))
+        .await
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    Ok(StatusCode::OK)
+}
+
+// Confirms the phone number with the texted code. From then on the user's second factor is
+// a code texted to that number, and they get a new set of recovery codes.
+#[tracing::instrument(name = "Confirm SMS", skip_all)]
+pub async fn confirm_sms(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Json(request): Json<ConfirmSmsRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+
+    let email = sms_owner(user)?;
+    let code = TwoFACode::parse(request.code).map_err(|_| AuthAPIError::InvalidVerificationCode)?;
+
+    let mut phone_verification_store = state.phone_verification_store.write().await;
+
+    let (phone_number, expected_code) = match phone_verification_store.get_verification(&email).await {
+        Ok(verification) => verification,
+        Err(PhoneVerificationStoreError::VerificationNotFound) => return Err(AuthAPIError::PhoneVerificationNotFound),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    if code != expected_code {
+        return Err(AuthAPIError::InvalidVerificationCode);
+    }
+
+    phone_verification_store
+        .remove_verification(&email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    drop(phone_verification_store);
+
+    let mut user_store = state.user_store.write().await;
+
+    user_store
+        .update_phone_number(&email, phone_number)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    user_store
+        .update_two_fa_method(&email, TwoFAMethod::Sms)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    drop(user_store);
+
+    let recovery_codes = issue_recovery_codes(&state, &email).await?;
+
+    Ok((StatusCode::OK, Json(RecoveryCodesResponse { recovery_codes })))
+}
+
+pub(crate) fn sms_content(code: &TwoFACode) -> String {
+    format!("Your verification code is {}", code.as_ref().expose_secret())
+}
+
+fn sms_owner(user: AuthenticatedUser) -> Result<Email, AuthAPIError> {
+    Email::parse(Secret::new(user.subject)).map_err(|_| AuthAPIError::InvalidToken)
+}
@@ -0,0 +1,258 @@
+use std::net::SocketAddr;
+use axum::{
+    extract::{ConnectInfo, State},
+    http::{header, HeaderMap},
+    response::IntoResponse,
+    Form, Json,
+};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        data_stores::{AuthorizationCodeStoreError, OAuthClientStoreError},
+        AuthorizationCode, ClientCredentials, ClientSecret, OAuthClient, OAuthError, Session,
+    },
+    utils::{
+        auth::{generate_client_access_token, generate_id_token, generate_service_token},
+        constants::TOKEN_TTL_SECONDS,
+    },
+};
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum GrantType {
+    AuthorizationCode,
+    ClientCredentials,
+    #[serde(other)]
+    Unsupported,
+}
+
+// Token request of the OAuth 2.0 token endpoint, sent as a form
+#[derive(Debug, Deserialize)]
+pub struct TokenRequest {
+    pub grant_type: Option<GrantType>,
+    pub code: Option<Secret<String>>,
+    pub redirect_uri: Option<String>,
+    pub code_verifier: Option<Secret<String>>,
+    // Scopes a service client asks for with the client credentials grant
+    pub scope: Option<String>,
+    // Clients may authenticate with these fields instead of HTTP Basic authentication
+    pub client_id: Option<String>,
+    pub client_secret: Option<Secret<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OAuthTokenResponse {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: i64,
+    // Not issued by either grant. Clients send the user through `/authorize` again, and
+    // service clients use their credentials.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id_token: Option<String>,
+    pub scope: String,
+}
+
+#[tracing::instrument(name = "Token", skip_all)]
+pub async fn token(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Form(request): Form<TokenRequest>,
+) -> Result<impl IntoResponse, OAuthError> {
+
+    let client = authenticate_client(
+        &state,
+        &headers,
+        request.client_id.clone(),
+        request.client_secret.clone(),
+    )
+    .await?;
+
+    let response = match request.grant_type {
+        Some(GrantType::AuthorizationCode) => {
+            authorization_code_grant(&state, addr, &headers, client, request).await?
+        }
+        Some(GrantType::ClientCredentials) => client_credentials_grant(client, request)?,
+        Some(GrantType::Unsupported) => return Err(OAuthError::UnsupportedGrantType),
+        None => return Err(OAuthError::InvalidRequest("grant_type is required".to_owned())),
+    };
+
+    // Responses carrying tokens must not be cached (RFC 6749 section 5.1)
+    Ok(([(header::CACHE_CONTROL, "no-store")], Json(response)))
+}
+
+// Identify the client from HTTP Basic authentication or the form fields. Confidential
+// clients must prove who they are with their secret.
+#[tracing::instrument(name = "Authenticate client", skip_all)]
+async fn authenticate_client(
+    state: &AppState,
+    headers: &HeaderMap,
+    client_id: Option<String>,
+    client_secret: Option<Secret<String>>,
+) -> Result<OAuthClient, OAuthError> {
+    let (client_id, client_secret) = match headers.get(header::AUTHORIZATION) {
+        Some(value) => {
+            let credentials = value
+                .to_str()
+                .ok()
+                .and_then(|value| ClientCredentials::parse_basic_auth(value).ok())
+                .ok_or(OAuthError::InvalidClient)?;
+            (credentials.client_id, Some(credentials.client_secret))
+        }
+        None => (client_id.ok_or(OAuthError::InvalidClient)?, client_secret),
+    };
+
+    let client_store = state.oauth_client_store.read().await;
+
+    let client = match client_store.get_client(&client_id).await {
+        Ok(client) => client,
+        Err(OAuthClientStoreError::ClientNotFound) => return Err(OAuthError::InvalidClient),
+        Err(e) => return Err(OAuthError::UnexpectedError(e.into())),
+    };
+
+    if client.confidential {
+        let client_secret = client_secret.ok_or(OAuthError::InvalidClient)?;
+
+        match client_store
+            .validate_client_secret(&client_id, &ClientSecret::new(client_secret))
+            .await
+        {
+            Ok(()) => {}
+            Err(OAuthClientStoreError::UnexpectedError(e)) => {
+                return Err(OAuthError::UnexpectedError(e))
+            }
+            Err(_) => return Err(OAuthError::InvalidClient),
+        }
+    }
+
+    Ok(client)
+}
+
+// Trade an authorization code for an access token limited to the scopes the user approved,
+// tied to a new session so the user can revoke it like their other sessions
+#[tracing::instrument(name = "Authorization code grant", skip_all)]
+async fn authorization_code_grant(
+    state: &AppState,
+    addr: SocketAddr,
+    headers: &HeaderMap,
+    client: OAuthClient,
+    request: TokenRequest,
+) -> Result<OAuthTokenResponse, OAuthError> {
+    let code = request
+        .code
+        .ok_or_else(|| OAuthError::InvalidRequest("code is required".to_owned()))?;
+    let code_verifier = request
+        .code_verifier
+        .ok_or_else(|| OAuthError::InvalidRequest("code_verifier is required".to_owned()))?;
+
+    let code = AuthorizationCode::parse(code).map_err(|_| OAuthError::InvalidGrant)?;
+
+    // The code is consumed even if the rest of the request turns out to be invalid, so a
+    // leaked code can only ever be tried once
+    let entry = match state.authorization_code_store.write().await.take_code(&code).await {
+        Ok(entry) => entry,
+        Err(AuthorizationCodeStoreError::CodeNotFound) => return Err(OAuthError::InvalidGrant),
+        Err(e) => return Err(OAuthError::UnexpectedError(e.into())),
+    };
+
+    let is_valid = entry.client_id == client.client_id
+        && request.redirect_uri.as_deref() == Some(entry.redirect_uri.as_str())
+        && entry.code_challenge.verify(&code_verifier);
+
+    if !is_valid {
+        return Err(OAuthError::InvalidGrant);
+    }
+
+    let user = state
+        .user_store
+        .read()
+        .await
+        .get_user(&entry.email)
+        .await
+        .map_err(|_| OAuthError::InvalidGrant)?;
+
+    let user_agent = headers
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned);
+
+    let session_version = state
+        .session_version_store
+        .read()
+        .await
+        .get_version(&user.email)
+        .await
+        .map_err(|e| OAuthError::UnexpectedError(e.into()))?;
+
+    // The user didn't authenticate to the client's session, so it proves nothing for
+    // routes that ask for a recent login
+    let mut session = Session::new(user.email, addr.ip().to_string(), user_agent, vec![]);
+    session.auth_time = 0;
+
+    let access_token = generate_client_access_token(&session, &client.client_id, &entry.scope, session_version)
+        .map_err(OAuthError::UnexpectedError)?;
+
+    state
+        .session_store
+        .write()
+        .await
+        .add_session(session)
+        .await
+        .map_err(|e| OAuthError::UnexpectedError(e.into()))?;
+
+    let id_token = if entry.scope.split_whitespace().any(|scope| scope == "openid") {
+        let id_token = generate_id_token(
+            &entry.email,
+            &client.client_id,
+            &entry.scope,
+            entry.nonce,
+            entry.auth_time,
+        )
+        .map_err(OAuthError::UnexpectedError)?;
+        Some(id_token.expose_secret().to_owned())
+    } else {
+        None
+    };
+
+    Ok(OAuthTokenResponse {
+        access_token: access_token.expose_secret().to_owned(),
+        token_type: "Bearer".to_owned(),
+        expires_in: *TOKEN_TTL_SECONDS,
+        refresh_token: None,
+        id_token,
+        scope: entry.scope,
+    })
+}
+
+// Issue a token a service client uses for itself, limited to the scopes it was registered with
+#[tracing::instrument(name = "Client credentials grant", skip_all)]
+fn client_credentials_grant(
+    client: OAuthClient,
+    request: TokenRequest,
+) -> Result<OAuthTokenResponse, OAuthError> {
+    // Only confidential clients have proven who they are at this point
+    if !client.confidential || client.scopes.is_empty() {
+        return Err(OAuthError::UnauthorizedClient);
+    }
+
+    let scope = client
+        .grant_scope(request.scope.as_deref())
+        .map_err(|e| OAuthError::InvalidScope(e.to_string()))?;
+
+    let access_token = generate_service_token(&client.client_id, &scope)
+        .map_err(OAuthError::UnexpectedError)?;
+
+    Ok(OAuthTokenResponse {
+        access_token: access_token.expose_secret().to_owned(),
+        token_type: "Bearer".to_owned(),
+        expires_in: *TOKEN_TTL_SECONDS,
+        refresh_token: None,
+        id_token: None,
+        scope,
+    })
+}
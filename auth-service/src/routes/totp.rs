@@ -0,0 +1,134 @@
+use auth_middleware::AuthenticatedUser;
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use chrono::Utc;
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        data_stores::TotpStoreError, AuthAPIError, Email, TotpEnrollment, TotpSecret, TwoFACode,
+        TwoFAMethod,
+    },
+    routes::{issue_recovery_codes, RecoveryCodesResponse},
+    utils::constants::TOTP_ISSUER,
+};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TotpEnrollmentResponse {
+    // Base32 encoded, for users who type it into their authenticator app
+    pub secret: String,
+    #[serde(rename = "otpauthUri")]
+    pub otpauth_uri: String,
+}
+
+#[derive(Deserialize)]
+pub struct ConfirmTotpRequest {
+    pub code: Secret<String>,
+}
+
+// Starts enrolling an authenticator app. Until the enrollment is confirmed, starting over
+// replaces the secret.
+#[tracing::instrument(name = "Enroll TOTP", skip_all)]
+pub async fn enroll_totp(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+) -> Result<impl IntoResponse, AuthAPIError> {
+
+    let email = totp_owner(user)?;
+    let secret = TotpSecret::default();
+
+    let response = TotpEnrollmentResponse {
+        secret: secret.to_base32().expose_secret().to_owned(),
+        otpauth_uri: secret.provisioning_uri(&email, TOTP_ISSUER).expose_secret().to_owned(),
+    };
+
+    state
+        .totp_store
+        .write()
+        .await
+        .add_enrollment(TotpEnrollment::new(email, secret))
+        .await
+        .map_err(|e| match e {
+            TotpStoreError::TotpAlreadyEnabled => AuthAPIError::TotpAlreadyEnabled,
+            e => AuthAPIError::UnexpectedError(e.into()),
+        })?;
+
+    Ok((StatusCode::OK, Json(response)))
+}
+
+// Confirms the enrollment with a first code, which proves the app was set up correctly.
+// From then on the user's second factor is a code from the app, and they get a new set of
+// recovery codes.
+#[tracing::instrument(name = "Confirm TOTP", skip_all)]
+pub async fn confirm_totp(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Json(request): Json<ConfirmTotpRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+
+    let email = totp_owner(user)?;
+    let code = TwoFACode::parse(request.code).map_err(|_| AuthAPIError::InvalidTotpCode)?;
+
+    let mut totp_store = state.totp_store.write().await;
+
+    let enrollment = match totp_store.get_enrollment(&email).await {
+        Ok(enrollment) if enrollment.confirmed => return Err(AuthAPIError::TotpAlreadyEnabled),
+        Ok(enrollment) => enrollment,
+        Err(TotpStoreError::EnrollmentNotFound) => return Err(AuthAPIError::TotpEnrollmentNotFound),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    let step = enrollment
+        .secret
+        .verify(&code, Utc::now().timestamp() as u64, None)
+        .ok_or(AuthAPIError::InvalidTotpCode)?;
+
+    totp_store
+        .confirm_enrollment(&email, step)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    state
+        .user_store
+        .write()
+        .await
+        .update_two_fa_method(&email, TwoFAMethod::Totp)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let recovery_codes = issue_recovery_codes(&state, &email).await?;
+
+    Ok((StatusCode::OK, Json(RecoveryCodesResponse { recovery_codes })))
+}
+
+// Checks a code from the user's authenticator app during login. Each code is only accepted
+// once, even though it stays valid for the whole time step.
+pub(crate) async fn verify_totp_code(
+    state: &AppState,
+    email: &Email,
+    code: &TwoFACode,
+) -> Result<(), AuthAPIError> {
+
+    let mut totp_store = state.totp_store.write().await;
+
+    let enrollment = match totp_store.get_enrollment(email).await {
+        Ok(enrollment) if enrollment.confirmed => enrollment,
+        Ok(_) | Err(TotpStoreError::EnrollmentNotFound) => return Err(AuthAPIError::IncorrectCredentials),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    let step = enrollment
+        .secret
+        .verify(code, Utc::now().timestamp() as u64, enrollment.last_used_step)
+        .ok_or(AuthAPIError::IncorrectCredentials)?;
+
+    totp_store.use_step(email, step).await.map_err(|e| match e {
+        TotpStoreError::CodeAlreadyUsed => AuthAPIError::IncorrectCredentials,
+        e => AuthAPIError::UnexpectedError(e.into()),
+    })
+}
+
+fn totp_owner(user: AuthenticatedUser) -> Result<Email, AuthAPIError> {
+    Email::parse(Secret::new(user.subject)).map_err(|_| AuthAPIError::InvalidToken)
+}
@@ -0,0 +1,147 @@
+use auth_middleware::AuthenticatedUser;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use axum_extra::extract::{cookie::Cookie, CookieJar};
+use color_eyre::eyre::Result;
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, TrustedDevice, TrustedDeviceStoreError},
+    utils::constants::{
+        COOKIE_CONFIG, TRUSTED_DEVICE_COOKIE_NAME, TRUSTED_DEVICE_KEY, TRUSTED_DEVICE_TTL_SECONDS,
+    },
+};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrustedDevicesResponse {
+    pub devices: Vec<TrustedDeviceResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrustedDeviceResponse {
+    pub id: String,
+    pub ip: String,
+    #[serde(rename = "userAgent")]
+    pub user_agent: Option<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: i64,
+    #[serde(rename = "expiresAt")]
+    pub expires_at: i64,
+    // Whether this is the browser making the request
+    pub current: bool,
+}
+
+impl TrustedDeviceResponse {
+    fn new(device: TrustedDevice, current_device_id: Option<&str>) -> Self {
+        Self {
+            current: current_device_id == Some(device.id.as_str()),
+            id: device.id,
+            ip: device.ip,
+            user_agent: device.user_agent,
+            created_at: device.created_at,
+            expires_at: device.expires_at,
+        }
+    }
+}
+
+#[tracing::instrument(name = "Get trusted devices", skip_all)]
+pub async fn get_trusted_devices(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AuthAPIError> {
+
+    let email = Email::parse(Secret::new(user.subject))
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let mut devices = state
+        .trusted_device_store
+        .read()
+        .await
+        .get_devices(&email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    // Most recently trusted first
+    devices.sort_by_key(|device| std::cmp::Reverse(device.created_at));
+
+    let current_device_id = device_id(&jar);
+    let response = Json(TrustedDevicesResponse {
+        devices: devices
+            .into_iter()
+            .map(|device| TrustedDeviceResponse::new(device, current_device_id.as_deref()))
+            .collect(),
+    });
+
+    Ok((StatusCode::OK, response))
+
+}
+
+#[tracing::instrument(name = "Delete trusted device", skip_all)]
+pub async fn delete_trusted_device(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    jar: CookieJar,
+    Path(id): Path<String>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+
+    let email = match Email::parse(Secret::new(user.subject)) {
+        Ok(email) => email,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
+    };
+
+    match state.trusted_device_store.write().await.remove_device(&email, &id).await {
+        Ok(()) => (),
+        Err(TrustedDeviceStoreError::DeviceNotFound) => return (jar, Err(AuthAPIError::TrustedDeviceNotFound)),
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    }
+
+    // The cookie of a revoked device is useless, so this browser can drop it
+    if device_id(&jar).as_deref() == Some(id.as_str()) {
+        return (jar.remove(COOKIE_CONFIG.removal(TRUSTED_DEVICE_COOKIE_NAME)), Ok(StatusCode::OK));
+    }
+
+    (jar, Ok(StatusCode::OK))
+
+}
+
+// Remembers the browser the user just verified their second factor on, returning its cookie
+pub(crate) async fn trust_device(
+    state: &AppState,
+    email: Email,
+    ip: String,
+    user_agent: Option<String>,
+) -> Result<Cookie<'static>> {
+
+    let device = TrustedDevice::new(email, ip, user_agent, *TRUSTED_DEVICE_TTL_SECONDS);
+    let value = device.cookie_value(TRUSTED_DEVICE_KEY.expose_secret());
+
+    state.trusted_device_store.write().await.add_device(device).await?;
+
+    Ok(COOKIE_CONFIG.build(TRUSTED_DEVICE_COOKIE_NAME, value, *TRUSTED_DEVICE_TTL_SECONDS))
+}
+
+// Whether the request comes from a browser the user trusted and hasn't revoked
+pub(crate) async fn is_trusted_device(state: &AppState, jar: &CookieJar, email: &Email) -> Result<bool> {
+    let Some(id) = device_id(jar) else {
+        return Ok(false);
+    };
+
+    match state.trusted_device_store.read().await.get_device(&id).await {
+        Ok(device) => Ok(device.email == *email),
+        Err(TrustedDeviceStoreError::DeviceNotFound) => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// The id of the device in the trusted device cookie, if it carries a valid signature
+fn device_id(jar: &CookieJar) -> Option<String> {
+    let cookie = jar.get(TRUSTED_DEVICE_COOKIE_NAME)?;
+    TrustedDevice::id_from_cookie(cookie.value(), TRUSTED_DEVICE_KEY.expose_secret()).ok()
+}
@@ -0,0 +1,19 @@
+use auth_middleware::AuthenticatedUser;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+
+// Claims about the user, as returned by the OpenID Connect UserInfo endpoint
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserInfoResponse {
+    pub sub: String,
+    pub email: String,
+}
+
+#[tracing::instrument(name = "User info", skip_all)]
+pub async fn userinfo(user: AuthenticatedUser) -> Json<UserInfoResponse> {
+    // Users are identified by their email address
+    Json(UserInfoResponse {
+        email: user.subject.clone(),
+        sub: user.subject,
+    })
+}
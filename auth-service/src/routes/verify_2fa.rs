@@ -1,11 +1,22 @@
-use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use std::net::SocketAddr;
+use axum::{
+    extract::{ConnectInfo, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
 use axum_extra::extract::CookieJar;
 use secrecy::Secret;
 use serde::Deserialize;
 
 use crate::{
-    app_state::AppState, domain::{AuthAPIError, Email, LoginAttemptId, TwoFACode},
-    utils::auth::generate_auth_cookie,
+    app_state::AppState,
+    domain::{
+        AuthAPIError, AuthMethod, Email, LoginAttemptId, RecoveryCode, Session, TwoFACode, TwoFACodeStoreError,
+        TwoFAMethod,
+    },
+    routes::{trust_device, use_recovery_code, verify_totp_code},
+    utils::auth::{start_session, TokenDelivery},
 };
 
 #[derive(Debug, Deserialize)]
@@ -15,11 +26,23 @@ pub struct Verify2FARequest {
     pub login_attempt_id: Secret<String>,
     #[serde(rename = "2FACode")]
     pub two_fa_code: Secret<String>,
+    #[serde(default, rename = "tokenDelivery")]
+    pub token_delivery: TokenDelivery,
+    // Skip 2FA on this browser next time
+    #[serde(default, rename = "rememberDevice")]
+    pub remember_device: bool,
+}
+
+enum SubmittedCode {
+    TwoFA(TwoFACode),
+    Recovery(RecoveryCode),
 }
 
 #[tracing::instrument(name = "Verify 2FA", skip_all)]
 pub async fn verify_2fa(
     State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     jar: CookieJar,
     Json(request): Json<Verify2FARequest>,
 ) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
@@ -34,33 +57,95 @@ pub async fn verify_2fa(
         Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
     };
 
-    let two_fa_code = match TwoFACode::parse(request.two_fa_code) {
-        Ok(two_fa_code) => two_fa_code,
-        Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
+    // Users who lost their second factor enter one of their recovery codes instead
+    let submitted_code = match TwoFACode::parse(request.two_fa_code.clone()) {
+        Ok(two_fa_code) => SubmittedCode::TwoFA(two_fa_code),
+        Err(_) => match RecoveryCode::parse(request.two_fa_code) {
+            Ok(recovery_code) => SubmittedCode::Recovery(recovery_code),
+            Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
+        },
     };
 
-    let mut two_fa_code_store = state.two_fa_code_store.write().await;
-
-    let code_tuple = match two_fa_code_store.get_code(&email).await {
+    let code_tuple = match state.two_fa_code_store.read().await.get_code(&login_attempt_id).await {
         Ok(code_tuple) => code_tuple,
         Err(_) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
     };
     
-    if !code_tuple.0.eq(&login_attempt_id) || !code_tuple.1.eq(&two_fa_code) {
+    if !code_tuple.0.eq(&email) {
         return (jar, Err(AuthAPIError::IncorrectCredentials));
     }
 
-    if let Err(e) = two_fa_code_store.remove_code(&email).await {
-        return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+    let user = match state.user_store.read().await.get_user(&email).await {
+        Ok(user) => user,
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    };
+
+    // Recovery codes prove the user as much as a one-time password does
+    let second_factor = match submitted_code {
+        SubmittedCode::Recovery(_) => AuthMethod::Otp,
+        SubmittedCode::TwoFA(_) => AuthMethod::from(user.two_fa_method),
+    };
+
+    // The code is checked against the user's chosen method
+    let verified = match (submitted_code, user.two_fa_method) {
+        (SubmittedCode::Recovery(code), _) => use_recovery_code(&state, &email, &code).await,
+        (SubmittedCode::TwoFA(code), TwoFAMethod::Email | TwoFAMethod::Sms) if code_tuple.1.eq(&code) => Ok(()),
+        (SubmittedCode::TwoFA(_), TwoFAMethod::Email | TwoFAMethod::Sms) => Err(AuthAPIError::IncorrectCredentials),
+        (SubmittedCode::TwoFA(code), TwoFAMethod::Totp) => verify_totp_code(&state, &email, &code).await,
+    };
+
+    // Wrong codes count against the login attempt, so the code can't be brute forced
+    if let Err(AuthAPIError::IncorrectCredentials) = verified {
+        return match state.two_fa_code_store.write().await.record_failed_attempt(&login_attempt_id).await {
+            Ok(()) => (jar, Err(AuthAPIError::IncorrectCredentials)),
+            Err(TwoFACodeStoreError::TooManyAttempts) => (jar, Err(AuthAPIError::TwoFAAttemptsExceeded)),
+            Err(e) => (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+        };
+    }
+
+    if let Err(e) = verified {
+        return (jar, Err(e));
+    }
+
+    // Removing the attempt makes it single use, even when the same code was sent twice at once
+    match state.two_fa_code_store.write().await.remove_code(&login_attempt_id).await {
+        Ok(()) => {}
+        Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => {
+            return (jar, Err(AuthAPIError::IncorrectCredentials))
+        }
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
     }
 
-    let cookie = match generate_auth_cookie(&email) {
-        Ok(cookie) => cookie,
+    let user_agent = headers
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned);
+
+    let session = Session::new(
+        email.clone(),
+        addr.ip().to_string(),
+        user_agent.clone(),
+        vec![AuthMethod::Password, second_factor],
+    );
+
+    let tokens = match start_session(session, &user.roles, &state).await {
+        Ok(tokens) => tokens,
         Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e))),
     };
 
-    let updated_jar = jar.add(cookie);
+    // Only trust the device once the login went through, so a failed login doesn't leave
+    // a trusted device behind that skips 2FA next time
+    let jar = match request.remember_device {
+        true => match trust_device(&state, email, addr.ip().to_string(), user_agent).await {
+            Ok(cookie) => jar.add(cookie),
+            Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e))),
+        },
+        false => jar,
+    };
 
-    (updated_jar, Ok(StatusCode::OK.into_response()))
+    match tokens.deliver(request.token_delivery, jar) {
+        (updated_jar, Some(body)) => (updated_jar, Ok((StatusCode::OK, Json(body)).into_response())),
+        (updated_jar, None) => (updated_jar, Ok(StatusCode::OK.into_response())),
+    }
 
 }
\ No newline at end of file
@@ -1,28 +1,228 @@
+use auth_middleware::bearer_token;
 use axum::{
     extract::State,
-    http::StatusCode,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
     Json
 };
-use secrecy::Secret;
-use serde::Deserialize;
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
 use crate::{
     app_state::AppState,
-    domain::AuthAPIError,
-    utils::auth::validate_token
+    domain::{
+        data_stores::OAuthClientStoreError, ApiKeyEntry, AuthAPIError, ClientCredentials,
+        ClientSecret, INTROSPECT_SCOPE,
+    },
+    utils::auth::{validate_token, Claims, ServiceClaims, ValidatedToken}
 };
 
 #[tracing::instrument(name = "Verify token", skip_all)]
 pub async fn verify_token(
     State(state): State<AppState>,
+    headers: HeaderMap,
     Json(request): Json<VerifyTokenRequest>,
-) -> Result<StatusCode, AuthAPIError> {
-    match validate_token(&request.token, state.banned_token_store.clone()).await {
-        Ok(_) => Ok(StatusCode::OK),
+) -> Result<Response, AuthAPIError> {
+
+    let credentials = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| ClientCredentials::parse_basic_auth(value).ok());
+
+    // Anyone may verify tokens unless an introspection client is configured. Service clients
+    // granted the introspect scope are always accepted.
+    let is_authenticated = match credentials {
+        Some(credentials) => is_introspection_client(&state, &credentials).await?,
+        None => state.introspection_client.is_none(),
+    };
+
+    if !is_authenticated {
+        return Err(AuthAPIError::InvalidClientCredentials);
+    }
+
+    // The token being verified is normally sent in the body. Clients checking their own
+    // token can send it as a bearer token instead, unless client credentials are required.
+    let token = match request.token.or_else(|| bearer_token(&headers)) {
+        Some(token) => token,
+        None => return Err(AuthAPIError::MissingToken),
+    };
+
+    let result = validate_token(
+        &token,
+        state.banned_token_store.clone(),
+        state.session_version_store.clone(),
+        state.session_store.clone(),
+        state.api_key_store.clone(),
+    )
+    .await;
+
+    // Introspection never fails on a bad token, it reports the token as inactive instead
+    if request.introspect {
+        let response = match result {
+            Ok(validated) => IntrospectionResponse::from(validated),
+            Err(_) => IntrospectionResponse::inactive(),
+        };
+        return Ok(Json(response).into_response());
+    }
+
+    // A bare status code can't tell whose token it is, so only the user's own tokens pass.
+    // Service tokens, API keys and tokens granted to OAuth clients need introspection.
+    match result.and_then(ValidatedToken::into_user) {
+        Ok(_) => Ok(StatusCode::OK.into_response()),
         Err(_) => Err(AuthAPIError::InvalidToken),
     }
 }
 
+#[tracing::instrument(name = "Authenticate introspection client", skip_all)]
+async fn is_introspection_client(
+    state: &AppState,
+    credentials: &ClientCredentials,
+) -> Result<bool, AuthAPIError> {
+    if state
+        .introspection_client
+        .as_ref()
+        .is_some_and(|client| credentials.matches(client))
+    {
+        return Ok(true);
+    }
+
+    let client_store = state.oauth_client_store.read().await;
+
+    let client = match client_store.get_client(&credentials.client_id).await {
+        Ok(client) => client,
+        Err(OAuthClientStoreError::ClientNotFound) => return Ok(false),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    if !client.allows_scope(INTROSPECT_SCOPE) {
+        return Ok(false);
+    }
+
+    let client_secret = ClientSecret::new(credentials.client_secret.clone());
+
+    match client_store
+        .validate_client_secret(&client.client_id, &client_secret)
+        .await
+    {
+        Ok(()) => Ok(true),
+        Err(OAuthClientStoreError::UnexpectedError(e)) => Err(AuthAPIError::UnexpectedError(e)),
+        Err(_) => Ok(false),
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct VerifyTokenRequest {
-    token: Secret<String>,
-}
\ No newline at end of file
+    #[serde(default)]
+    token: Option<Secret<String>>,
+    // Answer with an RFC 7662 introspection response instead of a bare status code
+    #[serde(default)]
+    introspect: bool,
+}
+
+// Inactive tokens only report `active`, so callers can't tell why a token was rejected
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct IntrospectionResponse {
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iat: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nbf: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aud: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub roles: Option<Vec<String>>,
+    // When and how the user last authenticated, so services can ask for a recent login
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auth_time: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amr: Option<Vec<String>>,
+    // Only set on tokens a service client got for itself
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+}
+
+impl IntrospectionResponse {
+    pub fn inactive() -> Self {
+        Self::default()
+    }
+}
+
+impl From<ValidatedToken> for IntrospectionResponse {
+    fn from(token: ValidatedToken) -> Self {
+        match token {
+            ValidatedToken::User(claims) => Self::from(claims),
+            ValidatedToken::Service(claims) => Self::from(claims),
+            ValidatedToken::ApiKey(entry) => Self::from(entry),
+        }
+    }
+}
+
+impl From<ServiceClaims> for IntrospectionResponse {
+    fn from(claims: ServiceClaims) -> Self {
+        Self {
+            active: true,
+            sub: Some(claims.sub),
+            exp: Some(claims.exp),
+            iat: Some(claims.iat),
+            nbf: Some(claims.nbf),
+            iss: Some(claims.iss),
+            aud: Some(claims.aud),
+            jti: Some(claims.jti),
+            scope: Some(claims.scope),
+            roles: None,
+            auth_time: None,
+            amr: None,
+            client_id: Some(claims.client_id),
+        }
+    }
+}
+
+// API keys only carry their scopes, not the roles of the user who created them
+impl From<ApiKeyEntry> for IntrospectionResponse {
+    fn from(entry: ApiKeyEntry) -> Self {
+        Self {
+            active: true,
+            sub: Some(entry.email.as_ref().expose_secret().to_owned()),
+            exp: entry.expires_at.and_then(|exp| exp.try_into().ok()),
+            iat: entry.created_at.try_into().ok(),
+            nbf: None,
+            iss: None,
+            aud: None,
+            jti: Some(entry.id),
+            scope: (!entry.scopes.is_empty()).then(|| entry.scopes.join(" ")),
+            roles: None,
+            auth_time: None,
+            amr: None,
+            client_id: None,
+        }
+    }
+}
+
+impl From<Claims> for IntrospectionResponse {
+    fn from(claims: Claims) -> Self {
+        Self {
+            active: true,
+            sub: Some(claims.sub),
+            exp: Some(claims.exp),
+            iat: Some(claims.iat),
+            nbf: Some(claims.nbf),
+            iss: Some(claims.iss),
+            aud: Some(claims.aud),
+            jti: Some(claims.jti),
+            scope: claims.scope,
+            roles: Some(claims.roles),
+            auth_time: Some(claims.auth_time),
+            amr: Some(claims.amr),
+            client_id: claims.azp,
+        }
+    }
+}
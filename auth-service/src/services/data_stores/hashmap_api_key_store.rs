@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+use chrono::Utc;
+
+use crate::domain::{
+    data_stores::{ApiKeyStore, ApiKeyStoreError},
+    ApiKey, ApiKeyEntry, Email,
+};
+
+// Keys are indexed by their hash, like in the database
+#[derive(Default)]
+pub struct HashmapApiKeyStore {
+    keys: HashMap<String, ApiKeyEntry>,
+}
+
+#[async_trait::async_trait]
+impl ApiKeyStore for HashmapApiKeyStore {
+    async fn add_key(&mut self, key: &ApiKey, entry: ApiKeyEntry) -> Result<(), ApiKeyStoreError> {
+        self.keys.insert(key.hash(), entry);
+        Ok(())
+    }
+
+    async fn get_key(&self, key: &ApiKey) -> Result<ApiKeyEntry, ApiKeyStoreError> {
+        self.keys
+            .get(&key.hash())
+            .cloned()
+            .ok_or(ApiKeyStoreError::KeyNotFound)
+    }
+
+    async fn get_keys(&self, email: &Email) -> Result<Vec<ApiKeyEntry>, ApiKeyStoreError> {
+        Ok(self
+            .keys
+            .values()
+            .filter(|entry| entry.email == *email)
+            .cloned()
+            .collect())
+    }
+
+    async fn touch_key(&mut self, id: &str) -> Result<(), ApiKeyStoreError> {
+        match self.keys.values_mut().find(|entry| entry.id == id) {
+            Some(entry) => {
+                entry.last_used_at = Some(Utc::now().timestamp());
+                Ok(())
+            }
+            None => Err(ApiKeyStoreError::KeyNotFound),
+        }
+    }
+
+    async fn remove_key(&mut self, email: &Email, id: &str) -> Result<(), ApiKeyStoreError> {
+        let hash = self
+            .keys
+            .iter()
+            .find(|(_, entry)| entry.id == id && entry.email == *email)
+            .map(|(hash, _)| hash.clone())
+            .ok_or(ApiKeyStoreError::KeyNotFound)?;
+
+        self.keys.remove(&hash);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+    use super::*;
+
+    fn email(email: &str) -> Email {
+        Email::parse(Secret::new(email.to_owned())).unwrap()
+    }
+
+    fn new_entry(email_address: &str) -> ApiKeyEntry {
+        ApiKeyEntry::new(email(email_address), "ci".to_owned(), vec!["read".to_owned()], None).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_add_and_get_key() {
+        let mut store = HashmapApiKeyStore::default();
+        let key = ApiKey::default();
+        let entry = new_entry("test@example.com");
+
+        store.add_key(&key, entry.clone()).await.unwrap();
+
+        assert_eq!(store.get_key(&key).await.unwrap(), entry);
+        assert_eq!(
+            store.get_key(&ApiKey::default()).await,
+            Err(ApiKeyStoreError::KeyNotFound)
+        );
+
+        let keys = store.get_keys(&email("test@example.com")).await.unwrap();
+        assert_eq!(keys, vec![entry]);
+        assert!(store.get_keys(&email("other@example.com")).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_touch_key() {
+        let mut store = HashmapApiKeyStore::default();
+        let key = ApiKey::default();
+        let entry = new_entry("test@example.com");
+
+        store.add_key(&key, entry.clone()).await.unwrap();
+        store.touch_key(&entry.id).await.unwrap();
+
+        assert!(store.get_key(&key).await.unwrap().last_used_at.is_some());
+        assert_eq!(store.touch_key("unknown").await, Err(ApiKeyStoreError::KeyNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_remove_key_only_for_owner() {
+        let mut store = HashmapApiKeyStore::default();
+        let key = ApiKey::default();
+        let entry = new_entry("test@example.com");
+
+        store.add_key(&key, entry.clone()).await.unwrap();
+
+        let result = store.remove_key(&email("other@example.com"), &entry.id).await;
+        assert_eq!(result, Err(ApiKeyStoreError::KeyNotFound));
+
+        store.remove_key(&email("test@example.com"), &entry.id).await.unwrap();
+        assert_eq!(store.get_key(&key).await, Err(ApiKeyStoreError::KeyNotFound));
+    }
+}
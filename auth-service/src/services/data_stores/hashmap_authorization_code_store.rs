@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+
+use secrecy::ExposeSecret;
+
+use crate::domain::{
+    data_stores::{AuthorizationCodeStore, AuthorizationCodeStoreError},
+    AuthorizationCode, AuthorizationCodeEntry,
+};
+
+#[derive(Default)]
+pub struct HashmapAuthorizationCodeStore {
+    codes: HashMap<String, AuthorizationCodeEntry>,
+}
+
+#[async_trait::async_trait]
+impl AuthorizationCodeStore for HashmapAuthorizationCodeStore {
+    async fn add_code(
+        &mut self,
+        code: AuthorizationCode,
+        entry: AuthorizationCodeEntry,
+    ) -> Result<(), AuthorizationCodeStoreError> {
+        self.codes.insert(code.as_ref().expose_secret().to_owned(), entry);
+        Ok(())
+    }
+
+    async fn take_code(
+        &mut self,
+        code: &AuthorizationCode,
+    ) -> Result<AuthorizationCodeEntry, AuthorizationCodeStoreError> {
+        self.codes
+            .remove(code.as_ref().expose_secret())
+            .ok_or(AuthorizationCodeStoreError::CodeNotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+    use crate::domain::{CodeChallenge, Email};
+    use super::*;
+
+    #[tokio::test]
+    async fn test_take_code_only_once() {
+        let mut store = HashmapAuthorizationCodeStore::default();
+        let code = AuthorizationCode::default();
+        let entry = AuthorizationCodeEntry {
+            client_id: "client".to_owned(),
+            redirect_uri: "https://app.example.com/callback".to_owned(),
+            email: Email::parse(Secret::new("test@example.com".to_owned())).unwrap(),
+            scope: "openid".to_owned(),
+            nonce: None,
+            code_challenge: CodeChallenge::parse(
+                "E9Melhoa2OwvFd-4gqnm8otrvpOSswkwtBTYkIhUh7k".to_owned(),
+                Some("S256"),
+            )
+            .unwrap(),
+            auth_time: 0,
+        };
+
+        store.add_code(code.clone(), entry.clone()).await.unwrap();
+
+        assert_eq!(store.take_code(&code).await, Ok(entry));
+        assert_eq!(store.take_code(&code).await, Err(AuthorizationCodeStoreError::CodeNotFound));
+    }
+}
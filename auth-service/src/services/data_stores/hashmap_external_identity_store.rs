@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+
+use crate::domain::{
+    data_stores::{ExternalIdentityStore, ExternalIdentityStoreError},
+    ExternalIdentity,
+};
+
+// Identities are indexed by provider and subject
+#[derive(Default)]
+pub struct HashmapExternalIdentityStore {
+    identities: HashMap<(String, String), ExternalIdentity>,
+}
+
+#[async_trait::async_trait]
+impl ExternalIdentityStore for HashmapExternalIdentityStore {
+    async fn add_identity(&mut self, identity: ExternalIdentity) -> Result<(), ExternalIdentityStoreError> {
+        let key = (identity.provider.clone(), identity.subject.clone());
+        if self.identities.contains_key(&key) {
+            return Err(ExternalIdentityStoreError::IdentityAlreadyExists);
+        }
+        self.identities.insert(key, identity);
+        Ok(())
+    }
+
+    async fn get_identity(
+        &self,
+        provider: &str,
+        subject: &str,
+    ) -> Result<ExternalIdentity, ExternalIdentityStoreError> {
+        self.identities
+            .get(&(provider.to_owned(), subject.to_owned()))
+            .cloned()
+            .ok_or(ExternalIdentityStoreError::IdentityNotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+    use crate::domain::Email;
+    use super::*;
+
+    fn identity(provider: &str, subject: &str) -> ExternalIdentity {
+        ExternalIdentity {
+            provider: provider.to_owned(),
+            subject: subject.to_owned(),
+            email: Email::parse(Secret::new("test@example.com".to_owned())).unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_and_get_identity() {
+        let mut store = HashmapExternalIdentityStore::default();
+
+        store.add_identity(identity("corp", "123")).await.unwrap();
+
+        assert_eq!(store.get_identity("corp", "123").await.unwrap(), identity("corp", "123"));
+        assert_eq!(
+            store.get_identity("google", "123").await,
+            Err(ExternalIdentityStoreError::IdentityNotFound)
+        );
+
+        let result = store.add_identity(identity("corp", "123")).await;
+        assert_eq!(result, Err(ExternalIdentityStoreError::IdentityAlreadyExists));
+    }
+}
@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+
+use secrecy::ExposeSecret;
+
+use crate::domain::{
+    data_stores::{MagicLinkStore, MagicLinkStoreError},
+    MagicLink, MagicLinkToken,
+};
+
+#[derive(Default)]
+pub struct HashmapMagicLinkStore {
+    links: HashMap<String, MagicLink>,
+}
+
+#[async_trait::async_trait]
+impl MagicLinkStore for HashmapMagicLinkStore {
+    async fn add_link(&mut self, token: MagicLinkToken, link: MagicLink) -> Result<(), MagicLinkStoreError> {
+        self.links.insert(token.as_ref().expose_secret().to_owned(), link);
+        Ok(())
+    }
+
+    // Expired links are treated as missing, like Redis does once their TTL runs out
+    async fn take_link(&mut self, token: &MagicLinkToken) -> Result<MagicLink, MagicLinkStoreError> {
+        self.links
+            .remove(token.as_ref().expose_secret())
+            .filter(|link| !link.is_expired())
+            .ok_or(MagicLinkStoreError::LinkNotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+    use super::*;
+    use crate::domain::{BrowserBinding, Email};
+
+    #[tokio::test]
+    async fn test_take_link_only_once_before_expiry() {
+        let mut store = HashmapMagicLinkStore::default();
+        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+        let binding = BrowserBinding::default();
+
+        let token = MagicLinkToken::default();
+        let link = MagicLink::new(email.clone(), &binding, 60);
+        store.add_link(token.clone(), link.clone()).await.unwrap();
+
+        assert_eq!(store.take_link(&token).await, Ok(link));
+        assert_eq!(store.take_link(&token).await, Err(MagicLinkStoreError::LinkNotFound));
+
+        let expired_token = MagicLinkToken::default();
+        store
+            .add_link(expired_token.clone(), MagicLink::new(email, &binding, 0))
+            .await
+            .unwrap();
+
+        assert_eq!(store.take_link(&expired_token).await, Err(MagicLinkStoreError::LinkNotFound));
+    }
+}
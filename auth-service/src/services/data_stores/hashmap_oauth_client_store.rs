@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+
+use secrecy::ExposeSecret;
+
+use crate::domain::{
+    data_stores::{OAuthClientStore, OAuthClientStoreError},
+    ClientSecret, OAuthClient,
+};
+
+#[derive(Default)]
+pub struct HashmapOAuthClientStore {
+    clients: HashMap<String, (OAuthClient, Option<ClientSecret>)>,
+}
+
+#[async_trait::async_trait]
+impl OAuthClientStore for HashmapOAuthClientStore {
+    async fn add_client(
+        &mut self,
+        mut client: OAuthClient,
+        client_secret: Option<ClientSecret>,
+    ) -> Result<(), OAuthClientStoreError> {
+        if self.clients.contains_key(&client.client_id) {
+            return Err(OAuthClientStoreError::ClientAlreadyExists);
+        }
+        client.confidential = client_secret.is_some();
+        self.clients.insert(client.client_id.clone(), (client, client_secret));
+        Ok(())
+    }
+
+    async fn get_client(&self, client_id: &str) -> Result<OAuthClient, OAuthClientStoreError> {
+        match self.clients.get(client_id) {
+            Some((client, _)) => Ok(client.clone()),
+            None => Err(OAuthClientStoreError::ClientNotFound),
+        }
+    }
+
+    async fn validate_client_secret(
+        &self,
+        client_id: &str,
+        client_secret: &ClientSecret,
+    ) -> Result<(), OAuthClientStoreError> {
+        match self.clients.get(client_id) {
+            Some((_, Some(secret)))
+                if secret.as_ref().expose_secret() == client_secret.as_ref().expose_secret() =>
+            {
+                Ok(())
+            }
+            Some(_) => Err(OAuthClientStoreError::InvalidCredentials),
+            None => Err(OAuthClientStoreError::ClientNotFound),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+    use super::*;
+
+    fn client() -> OAuthClient {
+        OAuthClient::new(
+            "app".to_owned(),
+            vec!["https://app.example.com/callback".to_owned()],
+            true,
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_add_and_get_client() {
+        let mut store = HashmapOAuthClientStore::default();
+        let client = client();
+
+        store.add_client(client.clone(), None).await.unwrap();
+
+        let stored = store.get_client(&client.client_id).await.unwrap();
+        assert_eq!(stored.redirect_uris, client.redirect_uris);
+        // Clients registered without a secret are public
+        assert!(!stored.confidential);
+
+        let result = store.add_client(client, None).await;
+        assert_eq!(result, Err(OAuthClientStoreError::ClientAlreadyExists));
+
+        let result = store.get_client("unknown").await;
+        assert_eq!(result, Err(OAuthClientStoreError::ClientNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_validate_client_secret() {
+        let mut store = HashmapOAuthClientStore::default();
+        let client = client();
+        let secret = ClientSecret::default();
+
+        store.add_client(client.clone(), Some(secret.clone())).await.unwrap();
+
+        assert!(store.validate_client_secret(&client.client_id, &secret).await.is_ok());
+
+        let wrong_secret = ClientSecret::new(Secret::new("wrong".to_owned()));
+        let result = store.validate_client_secret(&client.client_id, &wrong_secret).await;
+        assert_eq!(result, Err(OAuthClientStoreError::InvalidCredentials));
+    }
+}
@@ -0,0 +1,49 @@
+use std::collections::HashMap;
+
+use secrecy::ExposeSecret;
+
+use crate::domain::{
+    data_stores::{OidcLoginStore, OidcLoginStoreError},
+    OidcLogin, OidcState,
+};
+
+#[derive(Default)]
+pub struct HashmapOidcLoginStore {
+    logins: HashMap<String, OidcLogin>,
+}
+
+#[async_trait::async_trait]
+impl OidcLoginStore for HashmapOidcLoginStore {
+    async fn add_login(&mut self, state: OidcState, login: OidcLogin) -> Result<(), OidcLoginStoreError> {
+        self.logins.insert(state.as_ref().expose_secret().to_owned(), login);
+        Ok(())
+    }
+
+    async fn take_login(&mut self, state: &OidcState) -> Result<OidcLogin, OidcLoginStoreError> {
+        self.logins
+            .remove(state.as_ref().expose_secret())
+            .ok_or(OidcLoginStoreError::LoginNotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_take_login_only_once() {
+        let mut store = HashmapOidcLoginStore::default();
+        let state = OidcState::default();
+        let login = OidcLogin::new("corp".to_owned(), Some("/".to_owned()));
+
+        store.add_login(state.clone(), login.clone()).await.unwrap();
+
+        let taken = store.take_login(&state).await.unwrap();
+        assert_eq!(taken.provider, "corp");
+        assert_eq!(taken.nonce, login.nonce);
+        assert_eq!(taken.return_to.as_deref(), Some("/"));
+
+        let result = store.take_login(&state).await;
+        assert_eq!(result.unwrap_err(), OidcLoginStoreError::LoginNotFound);
+    }
+}
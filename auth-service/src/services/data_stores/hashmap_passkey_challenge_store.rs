@@ -0,0 +1,45 @@
+use std::collections::HashMap;
+
+use crate::domain::{
+    data_stores::{PasskeyChallengeStore, PasskeyChallengeStoreError},
+    PasskeyChallenge,
+};
+
+#[derive(Default)]
+pub struct HashmapPasskeyChallengeStore {
+    challenges: HashMap<String, PasskeyChallenge>,
+}
+
+#[async_trait::async_trait]
+impl PasskeyChallengeStore for HashmapPasskeyChallengeStore {
+    async fn add_challenge(&mut self, challenge: PasskeyChallenge) -> Result<(), PasskeyChallengeStoreError> {
+        self.challenges.insert(challenge.challenge.clone(), challenge);
+        Ok(())
+    }
+
+    async fn take_challenge(&mut self, challenge: &str) -> Result<PasskeyChallenge, PasskeyChallengeStoreError> {
+        self.challenges
+            .remove(challenge)
+            .ok_or(PasskeyChallengeStoreError::ChallengeNotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::PasskeyCeremony;
+    use super::*;
+
+    #[tokio::test]
+    async fn test_take_challenge_only_once() {
+        let mut store = HashmapPasskeyChallengeStore::default();
+        let challenge = PasskeyChallenge::new(PasskeyCeremony::Login);
+
+        store.add_challenge(challenge.clone()).await.unwrap();
+
+        assert_eq!(store.take_challenge(&challenge.challenge).await.unwrap(), challenge);
+        assert_eq!(
+            store.take_challenge(&challenge.challenge).await,
+            Err(PasskeyChallengeStoreError::ChallengeNotFound)
+        );
+    }
+}
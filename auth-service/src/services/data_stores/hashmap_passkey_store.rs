@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+use chrono::Utc;
+
+use crate::domain::{
+    data_stores::{PasskeyStore, PasskeyStoreError},
+    Email, Passkey,
+};
+
+// Passkeys are indexed by their credential ID
+#[derive(Default)]
+pub struct HashmapPasskeyStore {
+    passkeys: HashMap<String, Passkey>,
+}
+
+#[async_trait::async_trait]
+impl PasskeyStore for HashmapPasskeyStore {
+    async fn add_passkey(&mut self, passkey: Passkey) -> Result<(), PasskeyStoreError> {
+        if self.passkeys.contains_key(&passkey.id) {
+            return Err(PasskeyStoreError::PasskeyAlreadyExists);
+        }
+        self.passkeys.insert(passkey.id.clone(), passkey);
+        Ok(())
+    }
+
+    async fn get_passkey(&self, id: &str) -> Result<Passkey, PasskeyStoreError> {
+        self.passkeys
+            .get(id)
+            .cloned()
+            .ok_or(PasskeyStoreError::PasskeyNotFound)
+    }
+
+    async fn get_passkeys(&self, email: &Email) -> Result<Vec<Passkey>, PasskeyStoreError> {
+        Ok(self
+            .passkeys
+            .values()
+            .filter(|passkey| passkey.email == *email)
+            .cloned()
+            .collect())
+    }
+
+    async fn update_sign_count(&mut self, id: &str, sign_count: u32) -> Result<(), PasskeyStoreError> {
+        let passkey = self
+            .passkeys
+            .get_mut(id)
+            .ok_or(PasskeyStoreError::PasskeyNotFound)?;
+
+        passkey.sign_count = sign_count;
+        passkey.last_used_at = Some(Utc::now().timestamp());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+    use super::*;
+
+    fn passkey(id: &str, email: &str) -> Passkey {
+        Passkey {
+            id: id.to_owned(),
+            email: Email::parse(Secret::new(email.to_owned())).unwrap(),
+            name: "Laptop".to_owned(),
+            public_key: vec![4; 65],
+            sign_count: 0,
+            created_at: Utc::now().timestamp(),
+            last_used_at: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_and_update_passkeys() {
+        let mut store = HashmapPasskeyStore::default();
+
+        store.add_passkey(passkey("a", "test@example.com")).await.unwrap();
+        store.add_passkey(passkey("b", "other@example.com")).await.unwrap();
+
+        let result = store.add_passkey(passkey("a", "test@example.com")).await;
+        assert_eq!(result, Err(PasskeyStoreError::PasskeyAlreadyExists));
+
+        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+        let passkeys = store.get_passkeys(&email).await.unwrap();
+        assert_eq!(passkeys.len(), 1);
+        assert_eq!(passkeys[0].id, "a");
+
+        store.update_sign_count("a", 7).await.unwrap();
+        let updated = store.get_passkey("a").await.unwrap();
+        assert_eq!(updated.sign_count, 7);
+        assert!(updated.last_used_at.is_some());
+
+        let result = store.update_sign_count("c", 1).await;
+        assert_eq!(result, Err(PasskeyStoreError::PasskeyNotFound));
+    }
+}
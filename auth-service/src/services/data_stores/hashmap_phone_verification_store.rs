@@ -0,0 +1,63 @@
+use std::collections::HashMap;
+
+use crate::domain::{
+    data_stores::{PhoneVerificationStore, PhoneVerificationStoreError, TwoFACode},
+    Email, PhoneNumber,
+};
+
+#[derive(Default)]
+pub struct HashmapPhoneVerificationStore {
+    verifications: HashMap<Email, (PhoneNumber, TwoFACode)>,
+}
+
+#[async_trait::async_trait]
+impl PhoneVerificationStore for HashmapPhoneVerificationStore {
+    async fn add_verification(
+        &mut self,
+        email: Email,
+        phone_number: PhoneNumber,
+        code: TwoFACode,
+    ) -> Result<(), PhoneVerificationStoreError> {
+        self.verifications.insert(email, (phone_number, code));
+        Ok(())
+    }
+
+    async fn get_verification(&self, email: &Email) -> Result<(PhoneNumber, TwoFACode), PhoneVerificationStoreError> {
+        self.verifications
+            .get(email)
+            .cloned()
+            .ok_or(PhoneVerificationStoreError::VerificationNotFound)
+    }
+
+    async fn remove_verification(&mut self, email: &Email) -> Result<(), PhoneVerificationStoreError> {
+        self.verifications
+            .remove(email)
+            .map(|_| ())
+            .ok_or(PhoneVerificationStoreError::VerificationNotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+    use super::*;
+
+    #[tokio::test]
+    async fn test_verification_is_replaced_and_removed() {
+        let mut store = HashmapPhoneVerificationStore::default();
+        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+        let first = PhoneNumber::parse(Secret::new("+14155552671".to_owned())).unwrap();
+        let second = PhoneNumber::parse(Secret::new("+442071838750".to_owned())).unwrap();
+        let code = TwoFACode::default();
+
+        store.add_verification(email.clone(), first, TwoFACode::default()).await.unwrap();
+        store.add_verification(email.clone(), second.clone(), code.clone()).await.unwrap();
+        assert_eq!(store.get_verification(&email).await, Ok((second, code)));
+
+        assert_eq!(store.remove_verification(&email).await, Ok(()));
+        assert_eq!(
+            store.get_verification(&email).await,
+            Err(PhoneVerificationStoreError::VerificationNotFound)
+        );
+    }
+}
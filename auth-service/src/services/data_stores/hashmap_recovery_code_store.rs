@@ -0,0 +1,65 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::domain::{
+    data_stores::{RecoveryCodeStore, RecoveryCodeStoreError},
+    Email, RecoveryCode,
+};
+
+// Code hashes are grouped by user
+#[derive(Default)]
+pub struct HashmapRecoveryCodeStore {
+    codes: HashMap<Email, HashSet<String>>,
+}
+
+#[async_trait::async_trait]
+impl RecoveryCodeStore for HashmapRecoveryCodeStore {
+    async fn replace_codes(&mut self, email: &Email, codes: &[RecoveryCode]) -> Result<(), RecoveryCodeStoreError> {
+        self.codes
+            .insert(email.clone(), codes.iter().map(RecoveryCode::hash).collect());
+        Ok(())
+    }
+
+    async fn use_code(&mut self, email: &Email, code: &RecoveryCode) -> Result<(), RecoveryCodeStoreError> {
+        let removed = self
+            .codes
+            .get_mut(email)
+            .is_some_and(|hashes| hashes.remove(&code.hash()));
+
+        match removed {
+            true => Ok(()),
+            false => Err(RecoveryCodeStoreError::CodeNotFound),
+        }
+    }
+
+    async fn count_codes(&self, email: &Email) -> Result<usize, RecoveryCodeStoreError> {
+        Ok(self.codes.get(email).map_or(0, HashSet::len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+    use super::*;
+
+    #[tokio::test]
+    async fn test_codes_are_single_use_and_replaced() {
+        let mut store = HashmapRecoveryCodeStore::default();
+        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+        let codes = RecoveryCode::generate_set();
+
+        store.replace_codes(&email, &codes).await.unwrap();
+        assert_eq!(store.count_codes(&email).await.unwrap(), codes.len());
+
+        assert_eq!(store.use_code(&email, &codes[0]).await, Ok(()));
+        assert_eq!(store.use_code(&email, &codes[0]).await, Err(RecoveryCodeStoreError::CodeNotFound));
+        assert_eq!(store.count_codes(&email).await.unwrap(), codes.len() - 1);
+
+        let new_codes = RecoveryCode::generate_set();
+        store.replace_codes(&email, &new_codes).await.unwrap();
+        assert_eq!(store.use_code(&email, &codes[1]).await, Err(RecoveryCodeStoreError::CodeNotFound));
+        assert_eq!(store.use_code(&email, &new_codes[1]).await, Ok(()));
+
+        let other_email = Email::parse(Secret::new("other@example.com".to_owned())).unwrap();
+        assert_eq!(store.use_code(&other_email, &new_codes[2]).await, Err(RecoveryCodeStoreError::CodeNotFound));
+    }
+}
@@ -0,0 +1,117 @@
+use std::collections::{HashMap, HashSet};
+use secrecy::ExposeSecret;
+
+use crate::domain::data_stores::{
+    RefreshToken, RefreshTokenEntry, RefreshTokenStore, RefreshTokenStoreError,
+};
+
+#[derive(Default)]
+pub struct HashmapRefreshTokenStore {
+    tokens: HashMap<String, RefreshTokenEntry>,
+    revoked_families: HashSet<String>,
+}
+
+#[async_trait::async_trait]
+impl RefreshTokenStore for HashmapRefreshTokenStore {
+    async fn add_token(
+        &mut self,
+        token: RefreshToken,
+        entry: RefreshTokenEntry,
+    ) -> Result<(), RefreshTokenStoreError> {
+        self.tokens.insert(token.as_ref().expose_secret().to_owned(), entry);
+        Ok(())
+    }
+
+    async fn get_token(
+        &self,
+        token: &RefreshToken,
+    ) -> Result<RefreshTokenEntry, RefreshTokenStoreError> {
+        match self.tokens.get(token.as_ref().expose_secret()) {
+            Some(entry) => Ok(entry.clone()),
+            None => Err(RefreshTokenStoreError::TokenNotFound),
+        }
+    }
+
+    async fn mark_token_used(&mut self, token: &RefreshToken) -> Result<(), RefreshTokenStoreError> {
+        match self.tokens.get_mut(token.as_ref().expose_secret()) {
+            Some(entry) => {
+                entry.used = true;
+                Ok(())
+            }
+            None => Err(RefreshTokenStoreError::TokenNotFound),
+        }
+    }
+
+    async fn revoke_family(&mut self, family_id: &str) -> Result<(), RefreshTokenStoreError> {
+        self.revoked_families.insert(family_id.to_owned());
+        Ok(())
+    }
+
+    async fn is_family_revoked(&self, family_id: &str) -> Result<bool, RefreshTokenStoreError> {
+        Ok(self.revoked_families.contains(family_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+    use crate::domain::Email;
+    use super::*;
+
+    fn entry() -> RefreshTokenEntry {
+        RefreshTokenEntry::new(
+            Email::parse(Secret::new("test@example.com".to_owned())).unwrap(),
+            uuid::Uuid::new_v4().to_string(),
+            0,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_add_and_get_token() {
+        let mut store = HashmapRefreshTokenStore::default();
+        let token = RefreshToken::default();
+        let entry = entry();
+
+        let result = store.add_token(token.clone(), entry.clone()).await;
+
+        assert!(result.is_ok());
+        assert_eq!(store.get_token(&token).await, Ok(entry));
+    }
+
+    #[tokio::test]
+    async fn test_get_token_not_found() {
+        let store = HashmapRefreshTokenStore::default();
+
+        let result = store.get_token(&RefreshToken::default()).await;
+
+        assert_eq!(result, Err(RefreshTokenStoreError::TokenNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_mark_token_used() {
+        let mut store = HashmapRefreshTokenStore::default();
+        let token = RefreshToken::default();
+        store
+            .tokens
+            .insert(token.as_ref().expose_secret().to_owned(), entry());
+
+        let result = store.mark_token_used(&token).await;
+
+        assert!(result.is_ok());
+        assert!(store.get_token(&token).await.unwrap().used);
+    }
+
+    #[tokio::test]
+    async fn test_revoke_family() {
+        let mut store = HashmapRefreshTokenStore::default();
+        let entry = entry();
+        let rotated = entry.rotate();
+
+        assert_eq!(store.is_family_revoked(&entry.family_id).await, Ok(false));
+
+        let result = store.revoke_family(&entry.family_id).await;
+
+        assert!(result.is_ok());
+        assert_eq!(store.is_family_revoked(&rotated.family_id).await, Ok(true));
+    }
+}
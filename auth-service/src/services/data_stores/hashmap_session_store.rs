@@ -0,0 +1,207 @@
+use std::collections::HashMap;
+use chrono::Utc;
+
+use crate::domain::{
+    data_stores::{SessionStore, SessionStoreError},
+    AuthMethod, Email, Session,
+};
+
+#[derive(Default)]
+pub struct HashmapSessionStore {
+    sessions: HashMap<String, Session>,
+}
+
+#[async_trait::async_trait]
+impl SessionStore for HashmapSessionStore {
+    async fn add_session(&mut self, session: Session) -> Result<(), SessionStoreError> {
+        self.sessions.insert(session.id.clone(), session);
+        Ok(())
+    }
+
+    async fn get_session(&self, id: &str) -> Result<Session, SessionStoreError> {
+        self.sessions
+            .get(id)
+            .cloned()
+            .ok_or(SessionStoreError::SessionNotFound)
+    }
+
+    async fn get_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError> {
+        Ok(self
+            .sessions
+            .values()
+            .filter(|session| session.email == *email)
+            .cloned()
+            .collect())
+    }
+
+    async fn touch_session(&mut self, id: &str) -> Result<Session, SessionStoreError> {
+        match self.sessions.get_mut(id) {
+            Some(session) => {
+                session.last_seen = Utc::now().timestamp();
+                Ok(session.clone())
+            }
+            None => Err(SessionStoreError::SessionNotFound),
+        }
+    }
+
+    async fn record_authentication(&mut self, id: &str, amr: Vec<AuthMethod>) -> Result<Session, SessionStoreError> {
+        match self.sessions.get_mut(id) {
+            Some(session) => {
+                session.auth_time = Utc::now().timestamp();
+                session.amr = amr;
+                session.failed_reauthentications = 0;
+                Ok(session.clone())
+            }
+            None => Err(SessionStoreError::SessionNotFound),
+        }
+    }
+
+    async fn record_failed_reauthentication(&mut self, id: &str) -> Result<u32, SessionStoreError> {
+        match self.sessions.get_mut(id) {
+            Some(session) => {
+                session.failed_reauthentications += 1;
+                Ok(session.failed_reauthentications)
+            }
+            None => Err(SessionStoreError::SessionNotFound),
+        }
+    }
+
+    async fn remove_session(&mut self, email: &Email, id: &str) -> Result<(), SessionStoreError> {
+        match self.sessions.get(id) {
+            Some(session) if session.email == *email => {
+                self.sessions.remove(id);
+                Ok(())
+            }
+            _ => Err(SessionStoreError::SessionNotFound),
+        }
+    }
+
+    async fn remove_sessions(&mut self, email: &Email) -> Result<(), SessionStoreError> {
+        self.sessions.retain(|_, session| session.email != *email);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+    use super::*;
+
+    fn new_session(email: &str) -> Session {
+        Session::new(
+            Email::parse(Secret::new(email.to_owned())).unwrap(),
+            "127.0.0.1".to_owned(),
+            Some("test-agent".to_owned()),
+            vec![AuthMethod::Password],
+        )
+    }
+
+    #[tokio::test]
+    async fn test_add_and_get_sessions() {
+        let mut store = HashmapSessionStore::default();
+        let session = new_session("test@example.com");
+        let other_session = new_session("other@example.com");
+
+        store.add_session(session.clone()).await.unwrap();
+        store.add_session(other_session).await.unwrap();
+
+        assert_eq!(store.get_sessions(&session.email).await, Ok(vec![session]));
+    }
+
+    #[tokio::test]
+    async fn test_get_session() {
+        let mut store = HashmapSessionStore::default();
+        let session = new_session("test@example.com");
+        store.add_session(session.clone()).await.unwrap();
+
+        assert_eq!(store.get_session(&session.id).await, Ok(session));
+        assert_eq!(
+            store.get_session("unknown").await,
+            Err(SessionStoreError::SessionNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_touch_session() {
+        let mut store = HashmapSessionStore::default();
+        let mut session = new_session("test@example.com");
+        session.last_seen -= 60;
+        store.add_session(session.clone()).await.unwrap();
+
+        let touched = store.touch_session(&session.id).await.unwrap();
+        assert!(touched.last_seen > session.last_seen);
+        assert_eq!(store.sessions[&session.id], touched);
+        assert_eq!(
+            store.touch_session("unknown").await,
+            Err(SessionStoreError::SessionNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_record_authentication() {
+        let mut store = HashmapSessionStore::default();
+        let mut session = new_session("test@example.com");
+        session.auth_time -= 3600;
+        store.add_session(session.clone()).await.unwrap();
+
+        let amr = vec![AuthMethod::Password, AuthMethod::Otp];
+        let updated = store.record_authentication(&session.id, amr.clone()).await.unwrap();
+
+        assert!(updated.auth_time > session.auth_time);
+        assert_eq!(updated.amr, amr);
+        assert_eq!(store.sessions[&session.id], updated);
+        assert_eq!(
+            store.record_authentication("unknown", amr).await,
+            Err(SessionStoreError::SessionNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_record_failed_reauthentication() {
+        let mut store = HashmapSessionStore::default();
+        let session = new_session("test@example.com");
+        store.add_session(session.clone()).await.unwrap();
+
+        assert_eq!(store.record_failed_reauthentication(&session.id).await, Ok(1));
+        assert_eq!(store.record_failed_reauthentication(&session.id).await, Ok(2));
+
+        // Reauthenticating starts counting again
+        store.record_authentication(&session.id, vec![AuthMethod::Password]).await.unwrap();
+
+        assert_eq!(store.record_failed_reauthentication(&session.id).await, Ok(1));
+        assert_eq!(
+            store.record_failed_reauthentication("unknown").await,
+            Err(SessionStoreError::SessionNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_remove_session() {
+        let mut store = HashmapSessionStore::default();
+        let session = new_session("test@example.com");
+        let other_email = Email::parse(Secret::new("other@example.com".to_owned())).unwrap();
+        store.add_session(session.clone()).await.unwrap();
+
+        // Sessions can only be removed by the user they belong to
+        assert_eq!(
+            store.remove_session(&other_email, &session.id).await,
+            Err(SessionStoreError::SessionNotFound)
+        );
+        assert_eq!(store.remove_session(&session.email, &session.id).await, Ok(()));
+        assert_eq!(store.get_sessions(&session.email).await, Ok(vec![]));
+    }
+
+    #[tokio::test]
+    async fn test_remove_sessions() {
+        let mut store = HashmapSessionStore::default();
+        let session = new_session("test@example.com");
+        let other_session = new_session("other@example.com");
+        store.add_session(session.clone()).await.unwrap();
+        store.add_session(session.clone()).await.unwrap();
+        store.add_session(other_session.clone()).await.unwrap();
+
+        assert_eq!(store.remove_sessions(&session.email).await, Ok(()));
+        assert_eq!(store.get_sessions(&session.email).await, Ok(vec![]));
+        assert_eq!(store.get_sessions(&other_session.email).await, Ok(vec![other_session]));
+    }
+}
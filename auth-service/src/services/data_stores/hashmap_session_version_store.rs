@@ -0,0 +1,50 @@
+use std::collections::HashMap;
+
+use crate::domain::{
+    data_stores::{SessionVersionStore, SessionVersionStoreError},
+    Email,
+};
+
+#[derive(Default)]
+pub struct HashmapSessionVersionStore {
+    versions: HashMap<Email, u64>,
+}
+
+#[async_trait::async_trait]
+impl SessionVersionStore for HashmapSessionVersionStore {
+    async fn get_version(&self, email: &Email) -> Result<u64, SessionVersionStoreError> {
+        Ok(self.versions.get(email).copied().unwrap_or_default())
+    }
+
+    async fn increment_version(&mut self, email: &Email) -> Result<u64, SessionVersionStoreError> {
+        let version = self.versions.entry(email.clone()).or_default();
+        *version += 1;
+        Ok(*version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+    use super::*;
+
+    #[tokio::test]
+    async fn test_get_version_defaults_to_zero() {
+        let store = HashmapSessionVersionStore::default();
+        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+
+        assert_eq!(store.get_version(&email).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_increment_version() {
+        let mut store = HashmapSessionVersionStore::default();
+        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+        let other_email = Email::parse(Secret::new("other@example.com".to_owned())).unwrap();
+
+        assert_eq!(store.increment_version(&email).await.unwrap(), 1);
+        assert_eq!(store.increment_version(&email).await.unwrap(), 2);
+        assert_eq!(store.get_version(&email).await.unwrap(), 2);
+        assert_eq!(store.get_version(&other_email).await.unwrap(), 0);
+    }
+}
@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+
+use crate::domain::{
+    data_stores::{TotpStore, TotpStoreError},
+    Email, TotpEnrollment,
+};
+
+#[derive(Default)]
+pub struct HashmapTotpStore {
+    enrollments: HashMap<Email, TotpEnrollment>,
+}
+
+#[async_trait::async_trait]
+impl TotpStore for HashmapTotpStore {
+    async fn add_enrollment(&mut self, enrollment: TotpEnrollment) -> Result<(), TotpStoreError> {
+        if self.enrollments.get(&enrollment.email).is_some_and(|existing| existing.confirmed) {
+            return Err(TotpStoreError::TotpAlreadyEnabled);
+        }
+        self.enrollments.insert(enrollment.email.clone(), enrollment);
+        Ok(())
+    }
+
+    async fn get_enrollment(&self, email: &Email) -> Result<TotpEnrollment, TotpStoreError> {
+        self.enrollments
+            .get(email)
+            .cloned()
+            .ok_or(TotpStoreError::EnrollmentNotFound)
+    }
+
+    async fn confirm_enrollment(&mut self, email: &Email, step: u64) -> Result<(), TotpStoreError> {
+        match self.enrollments.get_mut(email) {
+            Some(enrollment) if !enrollment.confirmed => {
+                enrollment.confirmed = true;
+                enrollment.last_used_step = Some(step);
+                Ok(())
+            }
+            _ => Err(TotpStoreError::EnrollmentNotFound),
+        }
+    }
+
+    async fn use_step(&mut self, email: &Email, step: u64) -> Result<(), TotpStoreError> {
+        match self.enrollments.get_mut(email) {
+            Some(enrollment) if enrollment.last_used_step < Some(step) => {
+                enrollment.last_used_step = Some(step);
+                Ok(())
+            }
+            _ => Err(TotpStoreError::CodeAlreadyUsed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+    use super::*;
+    use crate::domain::TotpSecret;
+
+    #[tokio::test]
+    async fn test_enroll_confirm_and_use_steps() {
+        let mut store = HashmapTotpStore::default();
+        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+
+        store.add_enrollment(TotpEnrollment::new(email.clone(), TotpSecret::default())).await.unwrap();
+
+        // Pending enrollments can be replaced
+        let secret = TotpSecret::default();
+        store.add_enrollment(TotpEnrollment::new(email.clone(), secret.clone())).await.unwrap();
+        assert_eq!(store.get_enrollment(&email).await.unwrap().secret, secret);
+
+        store.confirm_enrollment(&email, 10).await.unwrap();
+        let enrollment = store.get_enrollment(&email).await.unwrap();
+        assert!(enrollment.confirmed);
+        assert_eq!(enrollment.last_used_step, Some(10));
+
+        let result = store.add_enrollment(TotpEnrollment::new(email.clone(), TotpSecret::default())).await;
+        assert_eq!(result, Err(TotpStoreError::TotpAlreadyEnabled));
+
+        assert_eq!(store.use_step(&email, 10).await, Err(TotpStoreError::CodeAlreadyUsed));
+        assert_eq!(store.use_step(&email, 11).await, Ok(()));
+        assert_eq!(store.use_step(&email, 11).await, Err(TotpStoreError::CodeAlreadyUsed));
+    }
+}
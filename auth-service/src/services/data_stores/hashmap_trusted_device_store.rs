@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+
+use crate::domain::{
+    data_stores::{TrustedDeviceStore, TrustedDeviceStoreError},
+    Email, TrustedDevice,
+};
+
+#[derive(Default)]
+pub struct HashmapTrustedDeviceStore {
+    devices: HashMap<String, TrustedDevice>,
+}
+
+#[async_trait::async_trait]
+impl TrustedDeviceStore for HashmapTrustedDeviceStore {
+    async fn add_device(&mut self, device: TrustedDevice) -> Result<(), TrustedDeviceStoreError> {
+        self.devices.insert(device.id.clone(), device);
+        Ok(())
+    }
+
+    async fn get_device(&self, id: &str) -> Result<TrustedDevice, TrustedDeviceStoreError> {
+        match self.devices.get(id) {
+            Some(device) if !device.is_expired() => Ok(device.clone()),
+            _ => Err(TrustedDeviceStoreError::DeviceNotFound),
+        }
+    }
+
+    async fn get_devices(&self, email: &Email) -> Result<Vec<TrustedDevice>, TrustedDeviceStoreError> {
+        Ok(self
+            .devices
+            .values()
+            .filter(|device| device.email == *email && !device.is_expired())
+            .cloned()
+            .collect())
+    }
+
+    async fn remove_device(&mut self, email: &Email, id: &str) -> Result<(), TrustedDeviceStoreError> {
+        match self.devices.get(id) {
+            Some(device) if device.email == *email => {
+                self.devices.remove(id);
+                Ok(())
+            }
+            _ => Err(TrustedDeviceStoreError::DeviceNotFound),
+        }
+    }
+
+    async fn remove_devices(&mut self, email: &Email) -> Result<(), TrustedDeviceStoreError> {
+        self.devices.retain(|_, device| device.email != *email);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+    use super::*;
+
+    fn new_device(email: &str) -> TrustedDevice {
+        TrustedDevice::new(
+            Email::parse(Secret::new(email.to_owned())).unwrap(),
+            "127.0.0.1".to_owned(),
+            Some("test-agent".to_owned()),
+            60,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_add_and_get_devices() {
+        let mut store = HashmapTrustedDeviceStore::default();
+        let device = new_device("test@example.com");
+        let mut expired_device = new_device("test@example.com");
+        expired_device.expires_at -= 120;
+        store.add_device(device.clone()).await.unwrap();
+        store.add_device(expired_device.clone()).await.unwrap();
+        store.add_device(new_device("other@example.com")).await.unwrap();
+
+        assert_eq!(store.get_device(&device.id).await, Ok(device.clone()));
+        assert_eq!(
+            store.get_device(&expired_device.id).await,
+            Err(TrustedDeviceStoreError::DeviceNotFound)
+        );
+        assert_eq!(store.get_devices(&device.email).await, Ok(vec![device]));
+    }
+
+    #[tokio::test]
+    async fn test_remove_devices() {
+        let mut store = HashmapTrustedDeviceStore::default();
+        let device = new_device("test@example.com");
+        let other_device = new_device("other@example.com");
+        store.add_device(device.clone()).await.unwrap();
+        store.add_device(new_device("test@example.com")).await.unwrap();
+        store.add_device(other_device.clone()).await.unwrap();
+
+        // Devices can only be removed by the user who trusted them
+        assert_eq!(
+            store.remove_device(&other_device.email, &device.id).await,
+            Err(TrustedDeviceStoreError::DeviceNotFound)
+        );
+        assert_eq!(store.remove_device(&device.email, &device.id).await, Ok(()));
+        assert_eq!(store.get_devices(&device.email).await.unwrap().len(), 1);
+
+        assert_eq!(store.remove_devices(&device.email).await, Ok(()));
+        assert_eq!(store.get_devices(&device.email).await, Ok(vec![]));
+        assert_eq!(store.get_devices(&other_device.email).await, Ok(vec![other_device]));
+    }
+}
@@ -1,13 +1,62 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use chrono::Utc;
 
-use crate::domain::{
-    data_stores::{LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError},
-    email::Email,
+use crate::{
+    domain::{
+        data_stores::{LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError},
+        email::Email,
+    },
+    utils::constants::{
+        TWO_FA_MAX_ATTEMPTS, TWO_FA_MAX_PENDING_ATTEMPTS, TWO_FA_MAX_RESENDS,
+        TWO_FA_RESEND_COOLDOWN_SECONDS,
+    },
 };
 
-#[derive(Default)]
 pub struct HashmapTwoFACodeStore {
-    codes: HashMap<Email, (LoginAttemptId, TwoFACode)>,
+    codes: HashMap<LoginAttemptId, Attempt>,
+    // Pending attempts of each user, oldest first
+    user_attempts: HashMap<Email, VecDeque<LoginAttemptId>>,
+    resend_cooldown_seconds: i64,
+}
+
+impl HashmapTwoFACodeStore {
+    pub fn with_resend_cooldown(mut self, seconds: i64) -> Self {
+        self.resend_cooldown_seconds = seconds;
+        self
+    }
+
+    fn remove(&mut self, login_attempt_id: &LoginAttemptId) -> Option<Attempt> {
+        let attempt = self.codes.remove(login_attempt_id)?;
+
+        if let Some(ids) = self.user_attempts.get_mut(&attempt.email) {
+            ids.retain(|id| id != login_attempt_id);
+            if ids.is_empty() {
+                self.user_attempts.remove(&attempt.email);
+            }
+        }
+
+        Some(attempt)
+    }
+}
+
+impl Default for HashmapTwoFACodeStore {
+    fn default() -> Self {
+        Self {
+            codes: HashMap::new(),
+            user_attempts: HashMap::new(),
+            resend_cooldown_seconds: *TWO_FA_RESEND_COOLDOWN_SECONDS,
+        }
+    }
+}
+
+// A login waiting for its code, and how it used the code so far
+struct Attempt {
+    email: Email,
+    code: TwoFACode,
+    failed_attempts: u32,
+    resends: u32,
+    // Unix timestamp of when the last code was sent
+    sent_at: i64,
 }
 
 #[async_trait::async_trait]
@@ -19,15 +68,33 @@ impl TwoFACodeStore for HashmapTwoFACodeStore {
         login_attempt_id: LoginAttemptId,
         code: TwoFACode,
     ) -> Result<(), TwoFACodeStoreError> {
-        self.codes.insert(email, (login_attempt_id, code));
+        let ids = self.user_attempts.entry(email.clone()).or_default();
+        ids.push_back(login_attempt_id.clone());
+
+        let mut dropped = Vec::new();
+        while ids.len() > *TWO_FA_MAX_PENDING_ATTEMPTS as usize {
+            dropped.extend(ids.pop_front());
+        }
+        for id in dropped {
+            self.codes.remove(&id);
+        }
+
+        let attempt = Attempt {
+            email,
+            code,
+            failed_attempts: 0,
+            resends: 0,
+            sent_at: Utc::now().timestamp(),
+        };
+        self.codes.insert(login_attempt_id, attempt);
         Ok(())
     }
 
     async fn remove_code(
         &mut self,
-        email: &Email
+        login_attempt_id: &LoginAttemptId,
     ) -> Result<(), TwoFACodeStoreError> {
-        match self.codes.remove(email) {
+        match self.remove(login_attempt_id) {
             Some(_) => Ok(()),
             None    => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
         }
@@ -35,24 +102,76 @@ impl TwoFACodeStore for HashmapTwoFACodeStore {
 
     async fn get_code(
         &self,
-        email: &Email,
-    ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
-        match self.codes.get(email) {
-            Some(value) => Ok(value.clone()),
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(Email, TwoFACode), TwoFACodeStoreError> {
+        match self.codes.get(login_attempt_id) {
+            Some(attempt) => Ok((attempt.email.clone(), attempt.code.clone())),
             None => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
         }
     }
 
+    async fn record_failed_attempt(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let attempt = self
+            .codes
+            .get_mut(login_attempt_id)
+            .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+
+        attempt.failed_attempts += 1;
+
+        if attempt.failed_attempts >= *TWO_FA_MAX_ATTEMPTS {
+            self.remove(login_attempt_id);
+            return Err(TwoFACodeStoreError::TooManyAttempts);
+        }
+
+        Ok(())
+    }
+
+    async fn resend_code(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+        code: TwoFACode,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let attempt = self
+            .codes
+            .get_mut(login_attempt_id)
+            .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+
+        let now = Utc::now().timestamp();
+
+        if attempt.resends >= *TWO_FA_MAX_RESENDS {
+            return Err(TwoFACodeStoreError::TooManyResends);
+        }
+
+        let retry_after = attempt.sent_at + self.resend_cooldown_seconds - now;
+        if retry_after > 0 {
+            return Err(TwoFACodeStoreError::ResendTooSoon(retry_after as u64));
+        }
+
+        attempt.code = code;
+        attempt.resends += 1;
+        attempt.sent_at = now;
+
+        Ok(())
+    }
+
 }
 
 #[cfg(test)]
 mod tests {
     use secrecy::Secret;
     use super::*;
+
+    fn test_email() -> Email {
+        Email::parse(Secret::new("test@example.com".to_owned())).unwrap()
+    }
+
     #[tokio::test]
     async fn test_add_code() {
         let mut store = HashmapTwoFACodeStore::default();
-        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+        let email = test_email();
         let login_attempt_id = LoginAttemptId::default();
         let code = TwoFACode::default();
 
@@ -61,53 +180,160 @@ mod tests {
             .await;
 
         assert!(result.is_ok());
-        assert_eq!(store.codes.get(&email), Some(&(login_attempt_id, code)));
+        assert_eq!(store.get_code(&login_attempt_id).await, Ok((email, code)));
     }
 
     #[tokio::test]
     async fn test_remove_code() {
         let mut store = HashmapTwoFACodeStore::default();
-        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+        let email = test_email();
         let login_attempt_id = LoginAttemptId::default();
-        let code = TwoFACode::default();
 
         store
-            .codes
-            .insert(email.clone(), (login_attempt_id.clone(), code.clone()));
+            .add_code(email.clone(), login_attempt_id.clone(), TwoFACode::default())
+            .await
+            .unwrap();
 
-        let result = store.remove_code(&email).await;
+        let result = store.remove_code(&login_attempt_id).await;
 
         assert!(result.is_ok());
-        assert_eq!(store.codes.get(&email), None);
+        assert!(!store.codes.contains_key(&login_attempt_id));
+        assert!(!store.user_attempts.contains_key(&email));
     }
 
     #[tokio::test]
-    async fn test_get_code() {
+    async fn test_get_code_not_found() {
+        let store = HashmapTwoFACodeStore::default();
+
+        let result = store.get_code(&LoginAttemptId::default()).await;
+
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err(),
+            TwoFACodeStoreError::LoginAttemptIdNotFound
+        );
+    }
+
+    #[tokio::test]
+    async fn test_attempts_are_independent() {
+        let mut store = HashmapTwoFACodeStore::default();
+        let email = test_email();
+        let (laptop, phone) = (LoginAttemptId::default(), LoginAttemptId::default());
+        let (laptop_code, phone_code) = (TwoFACode::default(), TwoFACode::default());
+
+        store.add_code(email.clone(), laptop.clone(), laptop_code.clone()).await.unwrap();
+        store.add_code(email.clone(), phone.clone(), phone_code.clone()).await.unwrap();
+
+        assert_eq!(store.get_code(&laptop).await, Ok((email.clone(), laptop_code)));
+
+        store.remove_code(&phone).await.unwrap();
+
+        assert!(store.get_code(&laptop).await.is_ok());
+        assert_eq!(
+            store.get_code(&phone).await,
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_oldest_attempt_is_dropped_over_limit() {
+        let mut store = HashmapTwoFACodeStore::default();
+        let email = test_email();
+        let ids: Vec<_> = (0..=*TWO_FA_MAX_PENDING_ATTEMPTS).map(|_| LoginAttemptId::default()).collect();
+
+        for id in &ids {
+            store.add_code(email.clone(), id.clone(), TwoFACode::default()).await.unwrap();
+        }
+
+        assert_eq!(
+            store.get_code(&ids[0]).await,
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+        for id in &ids[1..] {
+            assert!(store.get_code(id).await.is_ok());
+        }
+        assert_eq!(store.codes.len(), *TWO_FA_MAX_PENDING_ATTEMPTS as usize);
+    }
+
+    #[tokio::test]
+    async fn test_record_failed_attempt() {
         let mut store = HashmapTwoFACodeStore::default();
-        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
         let login_attempt_id = LoginAttemptId::default();
-        let code = TwoFACode::default();
+
+        assert_eq!(
+            store.record_failed_attempt(&login_attempt_id).await,
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+
         store
-            .codes
-            .insert(email.clone(), (login_attempt_id.clone(), code.clone()));
+            .add_code(test_email(), login_attempt_id.clone(), TwoFACode::default())
+            .await
+            .unwrap();
 
-        let result = store.get_code(&email).await;
+        for _ in 1..*TWO_FA_MAX_ATTEMPTS {
+            assert_eq!(store.record_failed_attempt(&login_attempt_id).await, Ok(()));
+        }
 
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap(), (login_attempt_id, code));
+        assert_eq!(
+            store.record_failed_attempt(&login_attempt_id).await,
+            Err(TwoFACodeStoreError::TooManyAttempts)
+        );
+        assert_eq!(
+            store.get_code(&login_attempt_id).await,
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
     }
 
     #[tokio::test]
-    async fn test_get_code_not_found() {
-        let store = HashmapTwoFACodeStore::default();
-        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+    async fn test_new_attempt_has_its_own_failed_attempts() {
+        let mut store = HashmapTwoFACodeStore::default();
+        let email = test_email();
+        let (first, second) = (LoginAttemptId::default(), LoginAttemptId::default());
 
-        let result = store.get_code(&email).await;
+        store.add_code(email.clone(), first.clone(), TwoFACode::default()).await.unwrap();
+
+        for _ in 1..*TWO_FA_MAX_ATTEMPTS {
+            store.record_failed_attempt(&first).await.unwrap();
+        }
+
+        store.add_code(email, second.clone(), TwoFACode::default()).await.unwrap();
+
+        assert_eq!(store.record_failed_attempt(&second).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_resend_code() {
+        let mut store = HashmapTwoFACodeStore::default();
+        let email = test_email();
+        let login_attempt_id = LoginAttemptId::default();
+
+        store
+            .add_code(email.clone(), login_attempt_id.clone(), TwoFACode::default())
+            .await
+            .unwrap();
 
-        assert!(result.is_err());
         assert_eq!(
-            result.unwrap_err(),
-            TwoFACodeStoreError::LoginAttemptIdNotFound
+            store.resend_code(&LoginAttemptId::default(), TwoFACode::default()).await,
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+        assert!(matches!(
+            store.resend_code(&login_attempt_id, TwoFACode::default()).await,
+            Err(TwoFACodeStoreError::ResendTooSoon(seconds)) if seconds as i64 <= *TWO_FA_RESEND_COOLDOWN_SECONDS
+        ));
+
+        for _ in 0..*TWO_FA_MAX_RESENDS {
+            // As if the cooldown passed
+            store.codes.get_mut(&login_attempt_id).unwrap().sent_at -= store.resend_cooldown_seconds;
+
+            let code = TwoFACode::default();
+            assert_eq!(store.resend_code(&login_attempt_id, code.clone()).await, Ok(()));
+            assert_eq!(store.get_code(&login_attempt_id).await, Ok((email.clone(), code)));
+        }
+
+        store.codes.get_mut(&login_attempt_id).unwrap().sent_at -= store.resend_cooldown_seconds;
+        assert_eq!(
+            store.resend_code(&login_attempt_id, TwoFACode::default()).await,
+            Err(TwoFACodeStoreError::TooManyResends)
         );
     }
-}
\ No newline at end of file
+}
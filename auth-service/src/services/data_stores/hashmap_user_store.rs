@@ -1,6 +1,8 @@
 use std::collections::HashMap;
 
-use crate::domain::{user::User, data_stores::UserStoreError, UserStore, Email, Password};
+use crate::domain::{
+    user::User, data_stores::UserStoreError, UserStore, Email, Password, PhoneNumber, TwoFAMethod,
+};
 
 // Create a new struct called `HashmapUserStore` containing a `users` field
 // which stores a `HashMap`` of email `String`s mapped to `User` objects.
@@ -59,6 +61,49 @@ impl UserStore for HashmapUserStore {
         }
    }
 
+   async fn update_password(
+       &mut self,
+       email: &Email,
+       password: Password
+   ) -> Result<(), UserStoreError> {
+        match self.users.get_mut(email) {
+            Some(user) => {
+                user.password = password;
+                Ok(())
+            },
+            None => Err(UserStoreError::UserNotFound)
+        }
+   }
+
+   async fn update_two_fa_method(
+       &mut self,
+       email: &Email,
+       method: TwoFAMethod
+   ) -> Result<(), UserStoreError> {
+        match self.users.get_mut(email) {
+            Some(user) => {
+                user.requires_2fa = true;
+                user.two_fa_method = method;
+                Ok(())
+            },
+            None => Err(UserStoreError::UserNotFound)
+        }
+   }
+
+   async fn update_phone_number(
+       &mut self,
+       email: &Email,
+       phone_number: PhoneNumber
+   ) -> Result<(), UserStoreError> {
+        match self.users.get_mut(email) {
+            Some(user) => {
+                user.phone_number = Some(phone_number);
+                Ok(())
+            },
+            None => Err(UserStoreError::UserNotFound)
+        }
+   }
+
 }
 
 #[cfg(test)]
@@ -74,7 +119,10 @@ mod tests {
         let user = User {
             email: Email::parse(Secret::new("johnwick@gmail.com".to_owned())).unwrap(),
             password: Password::parse(Secret::new("********".to_owned())).unwrap(),
-            requires_2fa: false
+            requires_2fa: false,
+            two_fa_method: TwoFAMethod::Email,
+            phone_number: None,
+            roles: vec![],
         };
 
         // When-Then
@@ -96,7 +144,10 @@ mod tests {
         let user = User {
             email: email.clone(),
             password: Password::parse(Secret::new("********".to_owned())).unwrap(),
-            requires_2fa: false
+            requires_2fa: false,
+            two_fa_method: TwoFAMethod::Email,
+            phone_number: None,
+            roles: vec![],
         };
 
         user_store.users.insert(email.clone(), user.clone());
@@ -122,7 +173,10 @@ mod tests {
         let user = User {
             email: email.clone(),
             password: password.clone(),
-            requires_2fa: true
+            requires_2fa: true,
+            two_fa_method: TwoFAMethod::Email,
+            phone_number: None,
+            roles: vec![],
         };
 
         // When-Then
@@ -147,4 +201,72 @@ mod tests {
 
     }
 
+    #[tokio::test]
+    async fn test_update_password() {
+
+        // Given
+        let mut user_store: HashmapUserStore = HashmapUserStore::default();
+        let email = Email::parse(Secret::new("johnwick@gmail.com".to_owned())).unwrap();
+        let password = Password::parse(Secret::new("********".to_owned())).unwrap();
+        let new_password = Password::parse(Secret::new("newpassword".to_owned())).unwrap();
+
+        user_store.users.insert(email.clone(), User::new(email.clone(), password.clone(), false));
+
+        // When-Then
+        let result = user_store.update_password(&email, new_password.clone()).await;
+        assert_eq!(result, Ok(()));
+        assert_eq!(user_store.validate_user(&email, &password).await, Err(UserStoreError::InvalidCredentials));
+        assert_eq!(user_store.validate_user(&email, &new_password).await, Ok(()));
+
+        // User that doesn't exist
+        let random_email = Email::parse(Secret::new("test@gmail.com".to_owned())).unwrap();
+        let result = user_store.update_password(&random_email, new_password).await;
+        assert_eq!(result, Err(UserStoreError::UserNotFound));
+
+    }
+
+    #[tokio::test]
+    async fn test_update_two_fa_method() {
+
+        // Given
+        let mut user_store: HashmapUserStore = HashmapUserStore::default();
+        let email = Email::parse(Secret::new("johnwick@gmail.com".to_owned())).unwrap();
+        let password = Password::parse(Secret::new("********".to_owned())).unwrap();
+
+        user_store.users.insert(email.clone(), User::new(email.clone(), password, false));
+
+        // When-Then
+        let result = user_store.update_two_fa_method(&email, TwoFAMethod::Totp).await;
+        assert_eq!(result, Ok(()));
+
+        let user = user_store.get_user(&email).await.unwrap();
+        assert!(user.requires_2fa);
+        assert_eq!(user.two_fa_method, TwoFAMethod::Totp);
+
+    }
+
+    #[tokio::test]
+    async fn test_update_phone_number() {
+
+        // Given
+        let mut user_store: HashmapUserStore = HashmapUserStore::default();
+        let email = Email::parse(Secret::new("johnwick@gmail.com".to_owned())).unwrap();
+        let password = Password::parse(Secret::new("********".to_owned())).unwrap();
+        let phone_number = PhoneNumber::parse(Secret::new("+14155552671".to_owned())).unwrap();
+
+        user_store.users.insert(email.clone(), User::new(email.clone(), password, false));
+
+        // When-Then
+        let result = user_store.update_phone_number(&email, phone_number.clone()).await;
+        assert_eq!(result, Ok(()));
+
+        let user = user_store.get_user(&email).await.unwrap();
+        assert_eq!(user.phone_number, Some(phone_number));
+
+        let random_email = Email::parse(Secret::new("test@gmail.com".to_owned())).unwrap();
+        let result = user_store.update_phone_number(&random_email, PhoneNumber::parse(Secret::new("+14155552671".to_owned())).unwrap()).await;
+        assert_eq!(result, Err(UserStoreError::UserNotFound));
+
+    }
+
 }
\ No newline at end of file
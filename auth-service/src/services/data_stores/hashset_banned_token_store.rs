@@ -1,47 +1,79 @@
-use std::collections::HashSet;
-use secrecy::{ExposeSecret, Secret};
+use std::collections::HashMap;
+use chrono::Utc;
 
 use crate::domain::data_stores::{BannedTokenStore, BannedTokenStoreError};
 
 #[derive(Default)]
 pub struct HashsetBannedTokenStore {
-    tokens: HashSet<String>,
+    // Maps the jti of each banned token to its expiry
+    tokens: HashMap<String, usize>,
 }
 
 #[async_trait::async_trait]
 impl BannedTokenStore for HashsetBannedTokenStore {
-    async fn add_token(&mut self, token: Secret<String>) -> Result<(), BannedTokenStoreError> {
-        self.tokens.insert(token.expose_secret().to_owned());
+    async fn add_token(&mut self, jti: String, exp: usize) -> Result<(), BannedTokenStoreError> {
+        let now = now();
+        // Entries for tokens that have expired since they were banned are no longer needed
+        self.tokens.retain(|_, token_exp| *token_exp > now);
+        if exp > now {
+            self.tokens.insert(jti, exp);
+        }
         Ok(())
     }
 
-    async fn contains_token(&self, token: &Secret<String>) -> Result<bool, BannedTokenStoreError> {
-        Ok(self.tokens.contains(token.expose_secret()))
+    async fn contains_token(&self, jti: &str) -> Result<bool, BannedTokenStoreError> {
+        Ok(self.tokens.get(jti).is_some_and(|exp| *exp > now()))
     }
 }
 
+fn now() -> usize {
+    Utc::now().timestamp().max(0) as usize
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     #[tokio::test]
     async fn test_add_token() {
         let mut store = HashsetBannedTokenStore::default();
-        let token = Secret::new("test_token".to_owned());
+        let jti = "test_jti".to_owned();
+        let exp = now() + 600;
 
-        let result = store.add_token(token.clone()).await;
+        let result = store.add_token(jti.clone(), exp).await;
 
         assert!(result.is_ok());
-        assert!(store.tokens.contains(token.expose_secret()));
+        assert_eq!(store.tokens.get(&jti), Some(&exp));
     }
 
     #[tokio::test]
-    async fn test_contains_token() {
+    async fn test_add_token_already_expired() {
+        let mut store = HashsetBannedTokenStore::default();
+
+        let result = store.add_token("test_jti".to_owned(), now() - 1).await;
+
+        assert!(result.is_ok());
+        assert!(store.tokens.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_add_token_prunes_expired_entries() {
         let mut store = HashsetBannedTokenStore::default();
-        let token = Secret::new("test_token".to_owned());
-        store.tokens.insert(token.expose_secret().to_owned());
+        store.tokens.insert("expired_jti".to_owned(), now() - 1);
 
-        let result = store.contains_token(&token).await;
+        let result = store.add_token("test_jti".to_owned(), now() + 600).await;
 
-        assert!(result.unwrap());
+        assert!(result.is_ok());
+        assert!(!store.tokens.contains_key("expired_jti"));
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_contains_token() {
+        let mut store = HashsetBannedTokenStore::default();
+        store.tokens.insert("test_jti".to_owned(), now() + 600);
+        store.tokens.insert("expired_jti".to_owned(), now() - 1);
+
+        assert!(store.contains_token("test_jti").await.unwrap());
+        assert!(!store.contains_token("expired_jti").await.unwrap());
+        assert!(!store.contains_token("unknown_jti").await.unwrap());
+    }
+}
@@ -0,0 +1,23 @@
+use color_eyre::eyre::Result;
+use crate::domain::{PhoneNumber, SmsClient};
+use secrecy::ExposeSecret;
+
+pub struct MockSmsClient;
+
+#[async_trait::async_trait]
+impl SmsClient for MockSmsClient {
+    async fn send_sms(
+        &self,
+        recipient: &PhoneNumber,
+        content: &str,
+    ) -> Result<()> {
+        // Like the mock email client, texts are only written to standard output
+        println!(
+            "Sending SMS to {} with content: {}",
+            recipient.as_ref().expose_secret(),
+            content
+        );
+
+        Ok(())
+    }
+}
@@ -1,15 +1,77 @@
 pub mod hashmap_user_store;
 pub mod hashset_banned_token_store;
 pub mod hashmap_two_fa_code_store;
+pub mod hashmap_refresh_token_store;
+pub mod hashmap_session_store;
+pub mod hashmap_session_version_store;
+pub mod hashmap_authorization_code_store;
+pub mod hashmap_oauth_client_store;
+pub mod hashmap_api_key_store;
+pub mod hashmap_oidc_login_store;
+pub mod hashmap_external_identity_store;
+pub mod hashmap_passkey_store;
+pub mod hashmap_passkey_challenge_store;
+pub mod hashmap_totp_store;
+pub mod hashmap_recovery_code_store;
+pub mod hashmap_phone_verification_store;
+pub mod hashmap_magic_link_store;
+pub mod hashmap_trusted_device_store;
 pub mod mock_email_client;
+pub mod mock_sms_client;
 pub mod postgres_user_store;
+pub mod postgres_oauth_client_store;
+pub mod postgres_api_key_store;
+pub mod postgres_external_identity_store;
+pub mod postgres_passkey_store;
+pub mod postgres_totp_store;
+pub mod postgres_recovery_code_store;
 pub mod redis_banned_token_store;
+pub mod redis_refresh_token_store;
+pub mod redis_session_store;
+pub mod redis_session_version_store;
 pub mod redis_two_fa_code_store;
+pub mod redis_authorization_code_store;
+pub mod redis_oidc_login_store;
+pub mod redis_passkey_challenge_store;
+pub mod redis_phone_verification_store;
+pub mod redis_magic_link_store;
+pub mod redis_trusted_device_store;
 
 pub use hashmap_user_store::*;
 pub use hashset_banned_token_store::*;
 pub use hashmap_two_fa_code_store::*;
+pub use hashmap_refresh_token_store::*;
+pub use hashmap_session_store::*;
+pub use hashmap_session_version_store::*;
+pub use hashmap_authorization_code_store::*;
+pub use hashmap_oauth_client_store::*;
+pub use hashmap_api_key_store::*;
+pub use hashmap_oidc_login_store::*;
+pub use hashmap_external_identity_store::*;
+pub use hashmap_passkey_store::*;
+pub use hashmap_passkey_challenge_store::*;
+pub use hashmap_totp_store::*;
+pub use hashmap_recovery_code_store::*;
+pub use hashmap_phone_verification_store::*;
+pub use hashmap_magic_link_store::*;
+pub use hashmap_trusted_device_store::*;
 pub use mock_email_client::*;
+pub use mock_sms_client::*;
 pub use postgres_user_store::*;
+pub use postgres_oauth_client_store::*;
+pub use postgres_api_key_store::*;
+pub use postgres_external_identity_store::*;
+pub use postgres_passkey_store::*;
+pub use postgres_totp_store::*;
+pub use postgres_recovery_code_store::*;
 pub use redis_banned_token_store::*;
-pub use redis_two_fa_code_store::*;
\ No newline at end of file
+pub use redis_refresh_token_store::*;
+pub use redis_session_store::*;
+pub use redis_session_version_store::*;
+pub use redis_two_fa_code_store::*;
+pub use redis_authorization_code_store::*;
+pub use redis_oidc_login_store::*;
+pub use redis_passkey_challenge_store::*;
+pub use redis_phone_verification_store::*;
+pub use redis_magic_link_store::*;
+pub use redis_trusted_device_store::*;
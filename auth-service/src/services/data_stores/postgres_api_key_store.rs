@@ -0,0 +1,147 @@
+use chrono::Utc;
+use color_eyre::eyre::{eyre, Result};
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+
+use crate::domain::{
+    data_stores::{ApiKeyStore, ApiKeyStoreError},
+    ApiKey, ApiKeyEntry, Email,
+};
+
+pub struct PostgresApiKeyStore {
+    pool: PgPool,
+}
+
+impl PostgresApiKeyStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl ApiKeyStore for PostgresApiKeyStore {
+
+    #[tracing::instrument(name = "Adding API key to PostgreSQL", skip_all)]
+    async fn add_key(&mut self, key: &ApiKey, entry: ApiKeyEntry) -> Result<(), ApiKeyStoreError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO api_keys (id, email, name, key_hash, scopes, created_at, expires_at, last_used_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+            entry.id,
+            entry.email.as_ref().expose_secret(),
+            entry.name,
+            key.hash(),
+            &entry.scopes,
+            entry.created_at,
+            entry.expires_at,
+            entry.last_used_at
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| ApiKeyStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving API key from PostgreSQL", skip_all)]
+    async fn get_key(&self, key: &ApiKey) -> Result<ApiKeyEntry, ApiKeyStoreError> {
+        sqlx::query!(
+            r#"
+            SELECT id, email, name, scopes, created_at, expires_at, last_used_at
+            FROM api_keys
+            WHERE key_hash = $1
+            "#,
+            key.hash()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| ApiKeyStoreError::UnexpectedError(e.into()))?
+        .map(|row| {
+            Ok(ApiKeyEntry {
+                id: row.id,
+                email: Email::parse(Secret::new(row.email))
+                    .map_err(|e| ApiKeyStoreError::UnexpectedError(eyre!(e)))?,
+                name: row.name,
+                scopes: row.scopes,
+                created_at: row.created_at,
+                expires_at: row.expires_at,
+                last_used_at: row.last_used_at,
+            })
+        })
+        .ok_or(ApiKeyStoreError::KeyNotFound)?
+    }
+
+    #[tracing::instrument(name = "Retrieving API keys of user from PostgreSQL", skip_all)]
+    async fn get_keys(&self, email: &Email) -> Result<Vec<ApiKeyEntry>, ApiKeyStoreError> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT id, name, scopes, created_at, expires_at, last_used_at
+            FROM api_keys
+            WHERE email = $1
+            ORDER BY created_at DESC
+            "#,
+            email.as_ref().expose_secret()
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ApiKeyStoreError::UnexpectedError(e.into()))?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| ApiKeyEntry {
+                id: row.id,
+                email: email.clone(),
+                name: row.name,
+                scopes: row.scopes,
+                created_at: row.created_at,
+                expires_at: row.expires_at,
+                last_used_at: row.last_used_at,
+            })
+            .collect())
+    }
+
+    #[tracing::instrument(name = "Touching API key in PostgreSQL", skip_all)]
+    async fn touch_key(&mut self, id: &str) -> Result<(), ApiKeyStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE api_keys
+            SET last_used_at = $1
+            WHERE id = $2
+            "#,
+            Utc::now().timestamp(),
+            id
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| ApiKeyStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(ApiKeyStoreError::KeyNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Removing API key from PostgreSQL", skip_all)]
+    async fn remove_key(&mut self, email: &Email, id: &str) -> Result<(), ApiKeyStoreError> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM api_keys
+            WHERE id = $1 AND email = $2
+            "#,
+            id,
+            email.as_ref().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| ApiKeyStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(ApiKeyStoreError::KeyNotFound);
+        }
+
+        Ok(())
+    }
+
+}
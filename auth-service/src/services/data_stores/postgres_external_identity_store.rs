@@ -0,0 +1,77 @@
+use color_eyre::eyre::{eyre, Result};
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+
+use crate::domain::{
+    data_stores::{ExternalIdentityStore, ExternalIdentityStoreError},
+    Email, ExternalIdentity,
+};
+
+pub struct PostgresExternalIdentityStore {
+    pool: PgPool,
+}
+
+impl PostgresExternalIdentityStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl ExternalIdentityStore for PostgresExternalIdentityStore {
+
+    #[tracing::instrument(name = "Adding external identity to PostgreSQL", skip_all)]
+    async fn add_identity(&mut self, identity: ExternalIdentity) -> Result<(), ExternalIdentityStoreError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO external_identities (provider, subject, email)
+            VALUES ($1, $2, $3)
+            "#,
+            identity.provider,
+            identity.subject,
+            identity.email.as_ref().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(e) if e.code().as_deref() == Some(UNIQUE_VIOLATION) => {
+                ExternalIdentityStoreError::IdentityAlreadyExists
+            }
+            e => ExternalIdentityStoreError::UnexpectedError(e.into()),
+        })?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving external identity from PostgreSQL", skip_all)]
+    async fn get_identity(
+        &self,
+        provider: &str,
+        subject: &str,
+    ) -> Result<ExternalIdentity, ExternalIdentityStoreError> {
+        sqlx::query!(
+            r#"
+            SELECT provider, subject, email
+            FROM external_identities
+            WHERE provider = $1 AND subject = $2
+            "#,
+            provider,
+            subject
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| ExternalIdentityStoreError::UnexpectedError(e.into()))?
+        .map(|row| {
+            Ok(ExternalIdentity {
+                provider: row.provider,
+                subject: row.subject,
+                email: Email::parse(Secret::new(row.email))
+                    .map_err(|e| ExternalIdentityStoreError::UnexpectedError(eyre!(e)))?,
+            })
+        })
+        .ok_or(ExternalIdentityStoreError::IdentityNotFound)?
+    }
+
+}
+
+const UNIQUE_VIOLATION: &str = "23505";
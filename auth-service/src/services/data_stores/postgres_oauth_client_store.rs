@@ -0,0 +1,118 @@
+use color_eyre::eyre::Result;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+
+use crate::domain::{
+    data_stores::{OAuthClientStore, OAuthClientStoreError},
+    ClientSecret, OAuthClient,
+};
+use super::postgres_user_store::{compute_password_hash, verify_password_hash};
+
+pub struct PostgresOAuthClientStore {
+    pool: PgPool,
+}
+
+impl PostgresOAuthClientStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl OAuthClientStore for PostgresOAuthClientStore {
+
+    // Client secrets are hashed like passwords, so a database leak doesn't expose them
+    #[tracing::instrument(name = "Adding OAuth client to PostgreSQL", skip_all)]
+    async fn add_client(
+        &mut self,
+        client: OAuthClient,
+        client_secret: Option<ClientSecret>,
+    ) -> Result<(), OAuthClientStoreError> {
+
+        let client_secret_hash = match client_secret {
+            Some(secret) => Some(
+                compute_password_hash(secret.as_ref().to_owned())
+                    .await
+                    .map_err(OAuthClientStoreError::UnexpectedError)?,
+            ),
+            None => None,
+        };
+
+        sqlx::query!(
+            r#"
+            INSERT INTO oauth_clients (client_id, name, redirect_uris, client_secret_hash, scopes)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            client.client_id,
+            client.name,
+            &client.redirect_uris,
+            client_secret_hash.as_ref().map(|hash| hash.expose_secret()),
+            &client.scopes
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(e) if e.code().as_deref() == Some(UNIQUE_VIOLATION) => {
+                OAuthClientStoreError::ClientAlreadyExists
+            }
+            e => OAuthClientStoreError::UnexpectedError(e.into()),
+        })?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving OAuth client from PostgreSQL", skip_all)]
+    async fn get_client(&self, client_id: &str) -> Result<OAuthClient, OAuthClientStoreError> {
+        let (client, _) = self.get_client_with_secret_hash(client_id).await?;
+        Ok(client)
+    }
+
+    #[tracing::instrument(name = "Validating OAuth client secret in PostgreSQL", skip_all)]
+    async fn validate_client_secret(
+        &self,
+        client_id: &str,
+        client_secret: &ClientSecret,
+    ) -> Result<(), OAuthClientStoreError> {
+        let (_, client_secret_hash) = self.get_client_with_secret_hash(client_id).await?;
+
+        let client_secret_hash =
+            client_secret_hash.ok_or(OAuthClientStoreError::InvalidCredentials)?;
+
+        verify_password_hash(client_secret_hash, client_secret.as_ref().to_owned())
+            .await
+            .map_err(|_| OAuthClientStoreError::InvalidCredentials)
+    }
+
+}
+
+impl PostgresOAuthClientStore {
+    async fn get_client_with_secret_hash(
+        &self,
+        client_id: &str,
+    ) -> Result<(OAuthClient, Option<Secret<String>>), OAuthClientStoreError> {
+        sqlx::query!(
+            r#"
+            SELECT client_id, name, redirect_uris, client_secret_hash, scopes
+            FROM oauth_clients
+            WHERE client_id = $1
+            "#,
+            client_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| OAuthClientStoreError::UnexpectedError(e.into()))?
+        .map(|row| {
+            let client = OAuthClient {
+                client_id: row.client_id,
+                name: row.name,
+                redirect_uris: row.redirect_uris,
+                confidential: row.client_secret_hash.is_some(),
+                scopes: row.scopes,
+            };
+            (client, row.client_secret_hash.map(Secret::new))
+        })
+        .ok_or(OAuthClientStoreError::ClientNotFound)
+    }
+}
+
+const UNIQUE_VIOLATION: &str = "23505";
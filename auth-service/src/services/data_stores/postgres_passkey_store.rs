@@ -0,0 +1,133 @@
+use chrono::Utc;
+use color_eyre::eyre::{eyre, Result};
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+
+use crate::domain::{
+    data_stores::{PasskeyStore, PasskeyStoreError},
+    Email, Passkey,
+};
+
+pub struct PostgresPasskeyStore {
+    pool: PgPool,
+}
+
+impl PostgresPasskeyStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl PasskeyStore for PostgresPasskeyStore {
+
+    #[tracing::instrument(name = "Adding passkey to PostgreSQL", skip_all)]
+    async fn add_passkey(&mut self, passkey: Passkey) -> Result<(), PasskeyStoreError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO passkeys (id, email, name, public_key, sign_count, created_at, last_used_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+            passkey.id,
+            passkey.email.as_ref().expose_secret(),
+            passkey.name,
+            passkey.public_key,
+            passkey.sign_count as i64,
+            passkey.created_at,
+            passkey.last_used_at
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(e) if e.code().as_deref() == Some(UNIQUE_VIOLATION) => {
+                PasskeyStoreError::PasskeyAlreadyExists
+            }
+            e => PasskeyStoreError::UnexpectedError(e.into()),
+        })?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving passkey from PostgreSQL", skip_all)]
+    async fn get_passkey(&self, id: &str) -> Result<Passkey, PasskeyStoreError> {
+        sqlx::query!(
+            r#"
+            SELECT id, email, name, public_key, sign_count, created_at, last_used_at
+            FROM passkeys
+            WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| PasskeyStoreError::UnexpectedError(e.into()))?
+        .map(|row| {
+            Ok(Passkey {
+                id: row.id,
+                email: Email::parse(Secret::new(row.email))
+                    .map_err(|e| PasskeyStoreError::UnexpectedError(eyre!(e)))?,
+                name: row.name,
+                public_key: row.public_key,
+                sign_count: row.sign_count as u32,
+                created_at: row.created_at,
+                last_used_at: row.last_used_at,
+            })
+        })
+        .ok_or(PasskeyStoreError::PasskeyNotFound)?
+    }
+
+    #[tracing::instrument(name = "Retrieving passkeys of user from PostgreSQL", skip_all)]
+    async fn get_passkeys(&self, email: &Email) -> Result<Vec<Passkey>, PasskeyStoreError> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT id, name, public_key, sign_count, created_at, last_used_at
+            FROM passkeys
+            WHERE email = $1
+            ORDER BY created_at
+            "#,
+            email.as_ref().expose_secret()
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| PasskeyStoreError::UnexpectedError(e.into()))?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| Passkey {
+                id: row.id,
+                email: email.clone(),
+                name: row.name,
+                public_key: row.public_key,
+                sign_count: row.sign_count as u32,
+                created_at: row.created_at,
+                last_used_at: row.last_used_at,
+            })
+            .collect())
+    }
+
+    #[tracing::instrument(name = "Updating passkey sign count in PostgreSQL", skip_all)]
+    async fn update_sign_count(&mut self, id: &str, sign_count: u32) -> Result<(), PasskeyStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE passkeys
+            SET sign_count = $1, last_used_at = $2
+            WHERE id = $3
+            "#,
+            sign_count as i64,
+            Utc::now().timestamp(),
+            id
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| PasskeyStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(PasskeyStoreError::PasskeyNotFound);
+        }
+
+        Ok(())
+    }
+
+}
+
+const UNIQUE_VIOLATION: &str = "23505";
@@ -0,0 +1,100 @@
+use color_eyre::eyre::Result;
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+
+use crate::domain::{
+    data_stores::{RecoveryCodeStore, RecoveryCodeStoreError},
+    Email, RecoveryCode,
+};
+
+pub struct PostgresRecoveryCodeStore {
+    pool: PgPool,
+}
+
+impl PostgresRecoveryCodeStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl RecoveryCodeStore for PostgresRecoveryCodeStore {
+
+    // The old codes are deleted in the same transaction, so users never end up with both sets
+    #[tracing::instrument(name = "Replacing recovery codes in PostgreSQL", skip_all)]
+    async fn replace_codes(&mut self, email: &Email, codes: &[RecoveryCode]) -> Result<(), RecoveryCodeStoreError> {
+        let hashes: Vec<String> = codes.iter().map(RecoveryCode::hash).collect();
+
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| RecoveryCodeStoreError::UnexpectedError(e.into()))?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM recovery_codes
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret()
+        )
+        .execute(&mut transaction)
+        .await
+        .map_err(|e| RecoveryCodeStoreError::UnexpectedError(e.into()))?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO recovery_codes (email, code_hash)
+            SELECT $1, UNNEST($2::TEXT[])
+            "#,
+            email.as_ref().expose_secret(),
+            &hashes
+        )
+        .execute(&mut transaction)
+        .await
+        .map_err(|e| RecoveryCodeStoreError::UnexpectedError(e.into()))?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| RecoveryCodeStoreError::UnexpectedError(e.into()))
+    }
+
+    #[tracing::instrument(name = "Using recovery code in PostgreSQL", skip_all)]
+    async fn use_code(&mut self, email: &Email, code: &RecoveryCode) -> Result<(), RecoveryCodeStoreError> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM recovery_codes
+            WHERE email = $1 AND code_hash = $2
+            "#,
+            email.as_ref().expose_secret(),
+            code.hash()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| RecoveryCodeStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(RecoveryCodeStoreError::CodeNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Counting recovery codes in PostgreSQL", skip_all)]
+    async fn count_codes(&self, email: &Email) -> Result<usize, RecoveryCodeStoreError> {
+        let count = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) AS "count!"
+            FROM recovery_codes
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret()
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| RecoveryCodeStoreError::UnexpectedError(e.into()))?;
+
+        Ok(count as usize)
+    }
+}
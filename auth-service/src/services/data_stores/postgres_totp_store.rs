@@ -0,0 +1,130 @@
+use color_eyre::eyre::Result;
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+
+use crate::domain::{
+    data_stores::{TotpStore, TotpStoreError},
+    Email, TotpCipher, TotpEnrollment,
+};
+
+// Secrets are encrypted with the cipher before they are written
+pub struct PostgresTotpStore {
+    pool: PgPool,
+    cipher: TotpCipher,
+}
+
+impl PostgresTotpStore {
+    pub fn new(pool: PgPool, cipher: TotpCipher) -> Self {
+        Self { pool, cipher }
+    }
+}
+
+#[async_trait::async_trait]
+impl TotpStore for PostgresTotpStore {
+
+    #[tracing::instrument(name = "Adding TOTP enrollment to PostgreSQL", skip_all)]
+    async fn add_enrollment(&mut self, enrollment: TotpEnrollment) -> Result<(), TotpStoreError> {
+        let encrypted_secret = self
+            .cipher
+            .encrypt(&enrollment.email, &enrollment.secret)
+            .map_err(TotpStoreError::UnexpectedError)?;
+
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO totp_enrollments (email, encrypted_secret, confirmed, last_used_step)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (email) DO UPDATE
+            SET encrypted_secret = EXCLUDED.encrypted_secret,
+                confirmed = EXCLUDED.confirmed,
+                last_used_step = EXCLUDED.last_used_step
+            WHERE totp_enrollments.confirmed = FALSE
+            "#,
+            enrollment.email.as_ref().expose_secret(),
+            encrypted_secret,
+            enrollment.confirmed,
+            enrollment.last_used_step.map(|step| step as i64)
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| TotpStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(TotpStoreError::TotpAlreadyEnabled);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving TOTP enrollment from PostgreSQL", skip_all)]
+    async fn get_enrollment(&self, email: &Email) -> Result<TotpEnrollment, TotpStoreError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT encrypted_secret, confirmed, last_used_step
+            FROM totp_enrollments
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| TotpStoreError::UnexpectedError(e.into()))?
+        .ok_or(TotpStoreError::EnrollmentNotFound)?;
+
+        let secret = self
+            .cipher
+            .decrypt(email, &row.encrypted_secret)
+            .map_err(TotpStoreError::UnexpectedError)?;
+
+        Ok(TotpEnrollment {
+            email: email.clone(),
+            secret,
+            confirmed: row.confirmed,
+            last_used_step: row.last_used_step.map(|step| step as u64),
+        })
+    }
+
+    #[tracing::instrument(name = "Confirming TOTP enrollment in PostgreSQL", skip_all)]
+    async fn confirm_enrollment(&mut self, email: &Email, step: u64) -> Result<(), TotpStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE totp_enrollments
+            SET confirmed = TRUE, last_used_step = $1
+            WHERE email = $2 AND confirmed = FALSE
+            "#,
+            step as i64,
+            email.as_ref().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| TotpStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(TotpStoreError::EnrollmentNotFound);
+        }
+
+        Ok(())
+    }
+
+    // A single conditional update, so concurrent requests can't use the same code twice
+    #[tracing::instrument(name = "Recording TOTP code use in PostgreSQL", skip_all)]
+    async fn use_step(&mut self, email: &Email, step: u64) -> Result<(), TotpStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE totp_enrollments
+            SET last_used_step = $1
+            WHERE email = $2 AND (last_used_step IS NULL OR last_used_step < $1)
+            "#,
+            step as i64,
+            email.as_ref().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| TotpStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(TotpStoreError::CodeAlreadyUsed);
+        }
+
+        Ok(())
+    }
+}
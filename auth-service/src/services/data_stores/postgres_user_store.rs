@@ -8,7 +8,7 @@ use sqlx::PgPool;
 
 use crate::domain::{
     data_stores::{UserStore, UserStoreError},
-    Email, Password, User,
+    Email, Password, PhoneNumber, TwoFAMethod, User,
 };
 
 pub struct PostgresUserStore {
@@ -33,12 +33,15 @@ impl UserStore for PostgresUserStore {
 
         sqlx::query!(
             r#"
-            INSERT INTO users (email, password_hash, requires_2fa)
-            VALUES ($1, $2, $3)
+            INSERT INTO users (email, password_hash, requires_2fa, two_fa_method, phone_number, roles)
+            VALUES ($1, $2, $3, $4, $5, $6)
             "#,
             user.email.as_ref().expose_secret(),
             &password_hash.expose_secret(),
-            user.requires_2fa
+            user.requires_2fa,
+            user.two_fa_method.as_str(),
+            user.phone_number.as_ref().map(|phone_number| phone_number.as_ref().expose_secret()),
+            &user.roles
         )
         .execute(&self.pool)
         .await
@@ -51,7 +54,7 @@ impl UserStore for PostgresUserStore {
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
         sqlx::query!(
             r#"
-            SELECT email, password_hash, requires_2fa
+            SELECT email, password_hash, requires_2fa, two_fa_method, phone_number, roles
             FROM users
             WHERE email = $1
             "#,
@@ -67,6 +70,15 @@ impl UserStore for PostgresUserStore {
                 password: Password::parse(Secret::new(row.password_hash))
                     .map_err(UserStoreError::UnexpectedError)?,
                 requires_2fa: row.requires_2fa,
+                two_fa_method: TwoFAMethod::parse(&row.two_fa_method).ok_or_else(|| {
+                    UserStoreError::UnexpectedError(eyre!("Unknown 2FA method {}", row.two_fa_method))
+                })?,
+                phone_number: row
+                    .phone_number
+                    .map(|phone_number| PhoneNumber::parse(Secret::new(phone_number)))
+                    .transpose()
+                    .map_err(UserStoreError::UnexpectedError)?,
+                roles: row.roles,
             })
         })
         .ok_or(UserStoreError::UserNotFound)?
@@ -88,6 +100,91 @@ impl UserStore for PostgresUserStore {
         .map_err(|_| UserStoreError::InvalidCredentials)
     }
 
+    #[tracing::instrument(name = "Updating user password in PostgreSQL", skip_all)]
+    async fn update_password(
+        &mut self,
+        email: &Email,
+        password: Password,
+    ) -> Result<(), UserStoreError> {
+
+        let password_hash = compute_password_hash(password.as_ref().to_owned())
+            .await
+            .map_err(UserStoreError::UnexpectedError)?;
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET password_hash = $1
+            WHERE email = $2
+            "#,
+            password_hash.expose_secret(),
+            email.as_ref().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Updating user 2FA method in PostgreSQL", skip_all)]
+    async fn update_two_fa_method(
+        &mut self,
+        email: &Email,
+        method: TwoFAMethod,
+    ) -> Result<(), UserStoreError> {
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET requires_2fa = TRUE, two_fa_method = $1
+            WHERE email = $2
+            "#,
+            method.as_str(),
+            email.as_ref().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Updating user phone number in PostgreSQL", skip_all)]
+    async fn update_phone_number(
+        &mut self,
+        email: &Email,
+        phone_number: PhoneNumber,
+    ) -> Result<(), UserStoreError> {
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET phone_number = $1
+            WHERE email = $2
+            "#,
+            phone_number.as_ref().expose_secret(),
+            email.as_ref().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
 }
 
 // Helper function to verify if a given password matches an expected hash
@@ -96,7 +193,7 @@ impl UserStore for PostgresUserStore {
 // separate thread pool using tokio::task::spawn_blocking
 
 #[tracing::instrument(name = "Verify password hash", skip_all)]
-async fn verify_password_hash(
+pub(crate) async fn verify_password_hash(
     expected_password_hash: Secret<String>,
     password_candidate: Secret<String>,
 ) -> Result<()> {
@@ -109,7 +206,7 @@ async fn verify_password_hash(
         // This code block ensures that the operations within the closure are executed within the context of the current span. 
         // This is especially useful for tracing operations that are performed in a different thread or task, such as within tokio::task::spawn_blocking.
         current_span.in_scope(||{
-            let expected_password_hash: PasswordHash<'_> = PasswordHash::new(&expected_password_hash.expose_secret())?;
+            let expected_password_hash: PasswordHash<'_> = PasswordHash::new(expected_password_hash.expose_secret())?;
             Argon2::default()
                 .verify_password(password_candidate.expose_secret().as_bytes(), &expected_password_hash)
                 .wrap_err("Failed to verify password hash")
@@ -126,7 +223,7 @@ async fn verify_password_hash(
 // separate thread pool using tokio::task::spawn_blocking
 
 #[tracing::instrument(name = "Computing password hash", skip_all)]
-async fn compute_password_hash(password: Secret<String>) -> Result<Secret<String>> {
+pub(crate) async fn compute_password_hash(password: Secret<String>) -> Result<Secret<String>> {
 
     // This line retrieves the current span from the tracing context. 
     // The span represents the execution context for the compute_password_hash function.
@@ -0,0 +1,110 @@
+use color_eyre::eyre::Context;
+use std::sync::Arc;
+use redis::{Commands, Connection};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::domain::{
+    data_stores::{AuthorizationCodeStore, AuthorizationCodeStoreError},
+    AuthorizationCode, AuthorizationCodeEntry, CodeChallenge, Email,
+};
+
+pub struct RedisAuthorizationCodeStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisAuthorizationCodeStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl AuthorizationCodeStore for RedisAuthorizationCodeStore {
+    #[tracing::instrument(name = "Adding authorization code to Redis", skip_all)]
+    async fn add_code(
+        &mut self,
+        code: AuthorizationCode,
+        entry: AuthorizationCodeEntry,
+    ) -> Result<(), AuthorizationCodeStoreError> {
+        let serialized_data = serde_json::to_string(&AuthorizationCodeData::from(&entry))
+            .wrap_err("Failed to serialize authorization code entry")
+            .map_err(AuthorizationCodeStoreError::UnexpectedError)?;
+
+        let _: () = self
+            .conn
+            .write()
+            .await
+            .set_ex(get_key(&code), serialized_data, AUTHORIZATION_CODE_TTL_SECONDS)
+            .wrap_err("Failed to set authorization code in Redis")
+            .map_err(AuthorizationCodeStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Taking authorization code from Redis", skip_all)]
+    async fn take_code(
+        &mut self,
+        code: &AuthorizationCode,
+    ) -> Result<AuthorizationCodeEntry, AuthorizationCodeStoreError> {
+        // GETDEL makes sure concurrent requests can't both redeem the code
+        let value: Option<String> = redis::cmd("GETDEL")
+            .arg(get_key(code))
+            .query(&mut *self.conn.write().await)
+            .wrap_err("Failed to take authorization code from Redis")
+            .map_err(AuthorizationCodeStoreError::UnexpectedError)?;
+
+        let value = value.ok_or(AuthorizationCodeStoreError::CodeNotFound)?;
+
+        let data: AuthorizationCodeData = serde_json::from_str(&value)
+            .wrap_err("Failed to deserialize authorization code entry")
+            .map_err(AuthorizationCodeStoreError::UnexpectedError)?;
+
+        let email = Email::parse(Secret::new(data.email))
+            .map_err(AuthorizationCodeStoreError::UnexpectedError)?;
+
+        Ok(AuthorizationCodeEntry {
+            client_id: data.client_id,
+            redirect_uri: data.redirect_uri,
+            email,
+            scope: data.scope,
+            nonce: data.nonce,
+            code_challenge: data.code_challenge,
+            auth_time: data.auth_time,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct AuthorizationCodeData {
+    client_id: String,
+    redirect_uri: String,
+    email: String,
+    scope: String,
+    nonce: Option<String>,
+    code_challenge: CodeChallenge,
+    auth_time: i64,
+}
+
+impl From<&AuthorizationCodeEntry> for AuthorizationCodeData {
+    fn from(entry: &AuthorizationCodeEntry) -> Self {
+        Self {
+            client_id: entry.client_id.clone(),
+            redirect_uri: entry.redirect_uri.clone(),
+            email: entry.email.as_ref().expose_secret().to_owned(),
+            scope: entry.scope.clone(),
+            nonce: entry.nonce.clone(),
+            code_challenge: entry.code_challenge.clone(),
+            auth_time: entry.auth_time,
+        }
+    }
+}
+
+// Clients redeem codes right after the redirect, so they don't need to live long
+const AUTHORIZATION_CODE_TTL_SECONDS: u64 = 60;
+const AUTHORIZATION_CODE_KEY_PREFIX: &str = "authorization_code:";
+
+fn get_key(code: &AuthorizationCode) -> String {
+    format!("{}{}", AUTHORIZATION_CODE_KEY_PREFIX, code.as_ref().expose_secret())
+}
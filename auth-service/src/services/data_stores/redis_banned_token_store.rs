@@ -1,15 +1,12 @@
+use chrono::Utc;
 use color_eyre::eyre::{
     Context,
 };
 use redis::{Commands, Connection};
 use std::sync::Arc;
-use secrecy::{ExposeSecret, Secret};
 use tokio::sync::RwLock;
 
-use crate::{
-    domain::data_stores::{BannedTokenStore, BannedTokenStoreError},
-    utils::auth::TOKEN_TTL_SECONDS,
-};
+use crate::domain::data_stores::{BannedTokenStore, BannedTokenStoreError};
 
 pub struct RedisBannedTokenStore {
     conn: Arc<RwLock<Connection>>,
@@ -23,25 +20,24 @@ impl RedisBannedTokenStore {
 
 #[async_trait::async_trait]
 impl BannedTokenStore for RedisBannedTokenStore {
-    async fn add_token(&mut self, token: Secret<String>) -> Result<(), BannedTokenStoreError> {
-
-        // TODO:
-        // 1. Create a new key using the get_key helper function.
-        // 2. Call the set_ex command on the Redis connection to set a new key/value pair with an expiration time (TTL). 
-        // The value should simply be a `true` (boolean value).
-        // The expiration time should be set to TOKEN_TTL_SECONDS.
-        // NOTE: The TTL is expected to be a u64 so you will have to cast TOKEN_TTL_SECONDS to a u64. 
-        // Return BannedTokenStoreError::UnexpectedError if casting fails or the call to set_ex fails.
+    async fn add_token(&mut self, jti: String, exp: usize) -> Result<(), BannedTokenStoreError> {
 
-        let token_key = get_key(token.expose_secret());
+        let token_key = get_key(&jti);
 
         let value = true;
 
-        let ttl: u64 = TOKEN_TTL_SECONDS
+        // The entry lives exactly as long as the token would have
+        let exp: i64 = exp
             .try_into()
-            .wrap_err("Failed to cast TOKEN_TTL_SECONDS to u64")
+            .wrap_err("Failed to cast token expiry to i64")
             .map_err(BannedTokenStoreError::UnexpectedError)?;
 
+        let ttl: u64 = match u64::try_from(exp - Utc::now().timestamp()) {
+            Ok(ttl) if ttl > 0 => ttl,
+            // The token has already expired, so there is nothing to ban
+            _ => return Ok(()),
+        };
+
         let _: () = self
             .conn
             .write()
@@ -53,9 +49,9 @@ impl BannedTokenStore for RedisBannedTokenStore {
         Ok(())
     }
 
-    async fn contains_token(&self, token: &Secret<String>) -> Result<bool, BannedTokenStoreError> {
+    async fn contains_token(&self, jti: &str) -> Result<bool, BannedTokenStoreError> {
         // Check if the token exists by calling the exists method on the Redis connection
-        let token_key = get_key(token.expose_secret());
+        let token_key = get_key(jti);
 
         let is_banned: bool = self
             .conn
@@ -72,6 +68,6 @@ impl BannedTokenStore for RedisBannedTokenStore {
 // We are using a key prefix to prevent collisions and organize data!
 const BANNED_TOKEN_KEY_PREFIX: &str = "banned_token:";
 
-fn get_key(token: &str) -> String {
-    format!("{}{}", BANNED_TOKEN_KEY_PREFIX, token)
-}
\ No newline at end of file
+fn get_key(jti: &str) -> String {
+    format!("{}{}", BANNED_TOKEN_KEY_PREFIX, jti)
+}
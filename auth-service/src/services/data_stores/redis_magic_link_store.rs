@@ -0,0 +1,86 @@
+use chrono::Utc;
+use color_eyre::eyre::Context;
+use std::sync::Arc;
+use redis::{Commands, Connection};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::domain::{
+    data_stores::{MagicLinkStore, MagicLinkStoreError},
+    Email, MagicLink, MagicLinkToken,
+};
+
+pub struct RedisMagicLinkStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisMagicLinkStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl MagicLinkStore for RedisMagicLinkStore {
+    #[tracing::instrument(name = "Adding magic link to Redis", skip_all)]
+    async fn add_link(&mut self, token: MagicLinkToken, link: MagicLink) -> Result<(), MagicLinkStoreError> {
+        // Redis drops the link once it expires
+        let ttl_seconds = (link.expires_at - Utc::now().timestamp()).max(1) as u64;
+
+        let data = MagicLinkData {
+            email: link.email.as_ref().expose_secret().to_owned(),
+            browser_binding: link.browser_binding,
+            expires_at: link.expires_at,
+        };
+
+        let serialized_data = serde_json::to_string(&data)
+            .wrap_err("Failed to serialize magic link")
+            .map_err(MagicLinkStoreError::UnexpectedError)?;
+
+        let _: () = self
+            .conn
+            .write()
+            .await
+            .set_ex(get_key(&token), serialized_data, ttl_seconds)
+            .wrap_err("Failed to set magic link in Redis")
+            .map_err(MagicLinkStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Taking magic link from Redis", skip_all)]
+    async fn take_link(&mut self, token: &MagicLinkToken) -> Result<MagicLink, MagicLinkStoreError> {
+        // GETDEL makes sure a link can't be used twice
+        let value: Option<String> = redis::cmd("GETDEL")
+            .arg(get_key(token))
+            .query(&mut *self.conn.write().await)
+            .wrap_err("Failed to take magic link from Redis")
+            .map_err(MagicLinkStoreError::UnexpectedError)?;
+
+        let value = value.ok_or(MagicLinkStoreError::LinkNotFound)?;
+
+        let data: MagicLinkData = serde_json::from_str(&value)
+            .wrap_err("Failed to deserialize magic link")
+            .map_err(MagicLinkStoreError::UnexpectedError)?;
+
+        Ok(MagicLink {
+            email: Email::parse(Secret::new(data.email)).map_err(MagicLinkStoreError::UnexpectedError)?,
+            browser_binding: data.browser_binding,
+            expires_at: data.expires_at,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct MagicLinkData {
+    email: String,
+    browser_binding: String,
+    expires_at: i64,
+}
+
+const MAGIC_LINK_PREFIX: &str = "magic_link:";
+
+fn get_key(token: &MagicLinkToken) -> String {
+    format!("{}{}", MAGIC_LINK_PREFIX, token.as_ref().expose_secret())
+}
@@ -0,0 +1,97 @@
+use color_eyre::eyre::Context;
+use std::sync::Arc;
+use redis::{Commands, Connection};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::domain::{
+    data_stores::{OidcLoginStore, OidcLoginStoreError},
+    Email, OidcLogin, OidcState, OIDC_LOGIN_TTL_SECONDS,
+};
+
+pub struct RedisOidcLoginStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisOidcLoginStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl OidcLoginStore for RedisOidcLoginStore {
+    #[tracing::instrument(name = "Adding OIDC login to Redis", skip_all)]
+    async fn add_login(&mut self, state: OidcState, login: OidcLogin) -> Result<(), OidcLoginStoreError> {
+        let serialized_data = serde_json::to_string(&OidcLoginData::from(&login))
+            .wrap_err("Failed to serialize OIDC login")
+            .map_err(OidcLoginStoreError::UnexpectedError)?;
+
+        let _: () = self
+            .conn
+            .write()
+            .await
+            .set_ex(get_key(&state), serialized_data, OIDC_LOGIN_TTL_SECONDS)
+            .wrap_err("Failed to set OIDC login in Redis")
+            .map_err(OidcLoginStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Taking OIDC login from Redis", skip_all)]
+    async fn take_login(&mut self, state: &OidcState) -> Result<OidcLogin, OidcLoginStoreError> {
+        // GETDEL makes sure a callback can't be replayed
+        let value: Option<String> = redis::cmd("GETDEL")
+            .arg(get_key(state))
+            .query(&mut *self.conn.write().await)
+            .wrap_err("Failed to take OIDC login from Redis")
+            .map_err(OidcLoginStoreError::UnexpectedError)?;
+
+        let value = value.ok_or(OidcLoginStoreError::LoginNotFound)?;
+
+        let data: OidcLoginData = serde_json::from_str(&value)
+            .wrap_err("Failed to deserialize OIDC login")
+            .map_err(OidcLoginStoreError::UnexpectedError)?;
+
+        Ok(OidcLogin {
+            provider: data.provider,
+            nonce: data.nonce,
+            code_verifier: Secret::new(data.code_verifier),
+            return_to: data.return_to,
+            link_to: data
+                .link_to
+                .map(|email| Email::parse(Secret::new(email)))
+                .transpose()
+                .map_err(OidcLoginStoreError::UnexpectedError)?,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct OidcLoginData {
+    provider: String,
+    nonce: String,
+    code_verifier: String,
+    return_to: Option<String>,
+    #[serde(default)]
+    link_to: Option<String>,
+}
+
+impl From<&OidcLogin> for OidcLoginData {
+    fn from(login: &OidcLogin) -> Self {
+        Self {
+            provider: login.provider.clone(),
+            nonce: login.nonce.clone(),
+            code_verifier: login.code_verifier.expose_secret().to_owned(),
+            return_to: login.return_to.clone(),
+            link_to: login.link_to.as_ref().map(|email| email.as_ref().expose_secret().to_owned()),
+        }
+    }
+}
+
+const OIDC_LOGIN_KEY_PREFIX: &str = "oidc_login:";
+
+fn get_key(state: &OidcState) -> String {
+    format!("{}{}", OIDC_LOGIN_KEY_PREFIX, state.as_ref().expose_secret())
+}
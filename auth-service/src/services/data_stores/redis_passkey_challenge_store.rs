@@ -0,0 +1,109 @@
+use color_eyre::eyre::{eyre, Context};
+use std::sync::Arc;
+use redis::{Commands, Connection};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::domain::{
+    data_stores::{PasskeyChallengeStore, PasskeyChallengeStoreError},
+    Email, PasskeyCeremony, PasskeyChallenge, PASSKEY_CHALLENGE_TTL_SECONDS,
+};
+
+pub struct RedisPasskeyChallengeStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisPasskeyChallengeStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl PasskeyChallengeStore for RedisPasskeyChallengeStore {
+    #[tracing::instrument(name = "Adding passkey challenge to Redis", skip_all)]
+    async fn add_challenge(&mut self, challenge: PasskeyChallenge) -> Result<(), PasskeyChallengeStoreError> {
+        let serialized_data = serde_json::to_string(&PasskeyChallengeData::from(&challenge.ceremony))
+            .wrap_err("Failed to serialize passkey challenge")
+            .map_err(PasskeyChallengeStoreError::UnexpectedError)?;
+
+        let _: () = self
+            .conn
+            .write()
+            .await
+            .set_ex(get_key(&challenge.challenge), serialized_data, PASSKEY_CHALLENGE_TTL_SECONDS)
+            .wrap_err("Failed to set passkey challenge in Redis")
+            .map_err(PasskeyChallengeStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Taking passkey challenge from Redis", skip_all)]
+    async fn take_challenge(&mut self, challenge: &str) -> Result<PasskeyChallenge, PasskeyChallengeStoreError> {
+        // GETDEL makes sure a challenge can't be answered twice
+        let value: Option<String> = redis::cmd("GETDEL")
+            .arg(get_key(challenge))
+            .query(&mut *self.conn.write().await)
+            .wrap_err("Failed to take passkey challenge from Redis")
+            .map_err(PasskeyChallengeStoreError::UnexpectedError)?;
+
+        let value = value.ok_or(PasskeyChallengeStoreError::ChallengeNotFound)?;
+
+        let data: PasskeyChallengeData = serde_json::from_str(&value)
+            .wrap_err("Failed to deserialize passkey challenge")
+            .map_err(PasskeyChallengeStoreError::UnexpectedError)?;
+
+        Ok(PasskeyChallenge {
+            challenge: challenge.to_owned(),
+            ceremony: data.try_into().map_err(PasskeyChallengeStoreError::UnexpectedError)?,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct PasskeyChallengeData {
+    ceremony: String,
+    email: Option<String>,
+}
+
+impl From<&PasskeyCeremony> for PasskeyChallengeData {
+    fn from(ceremony: &PasskeyCeremony) -> Self {
+        let (ceremony, email) = match ceremony {
+            PasskeyCeremony::Registration(email) => ("registration", Some(email)),
+            PasskeyCeremony::SecondFactor(email) => ("second_factor", Some(email)),
+            PasskeyCeremony::Login => ("login", None),
+        };
+
+        Self {
+            ceremony: ceremony.to_owned(),
+            email: email.map(|email| email.as_ref().expose_secret().to_owned()),
+        }
+    }
+}
+
+impl TryFrom<PasskeyChallengeData> for PasskeyCeremony {
+    type Error = color_eyre::eyre::Report;
+
+    fn try_from(data: PasskeyChallengeData) -> Result<Self, Self::Error> {
+        let email = || {
+            data.email
+                .clone()
+                .ok_or(eyre!("Passkey challenge has no email"))
+                .and_then(|email| Email::parse(Secret::new(email)))
+        };
+
+        match data.ceremony.as_str() {
+            "registration" => Ok(Self::Registration(email()?)),
+            "second_factor" => Ok(Self::SecondFactor(email()?)),
+            "login" => Ok(Self::Login),
+            ceremony => Err(eyre!("Unknown passkey ceremony: {}", ceremony)),
+        }
+    }
+}
+
+const PASSKEY_CHALLENGE_KEY_PREFIX: &str = "passkey_challenge:";
+
+fn get_key(challenge: &str) -> String {
+    format!("{}{}", PASSKEY_CHALLENGE_KEY_PREFIX, challenge)
+}
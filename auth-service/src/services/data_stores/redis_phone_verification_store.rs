@@ -0,0 +1,104 @@
+use color_eyre::eyre::Context;
+use std::sync::Arc;
+use redis::{Commands, Connection};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::domain::{
+    data_stores::{PhoneVerificationStore, PhoneVerificationStoreError, TwoFACode},
+    Email, PhoneNumber,
+};
+
+pub struct RedisPhoneVerificationStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisPhoneVerificationStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl PhoneVerificationStore for RedisPhoneVerificationStore {
+    #[tracing::instrument(name = "Adding phone verification to Redis", skip_all)]
+    async fn add_verification(
+        &mut self,
+        email: Email,
+        phone_number: PhoneNumber,
+        code: TwoFACode,
+    ) -> Result<(), PhoneVerificationStoreError> {
+        let data = PhoneVerificationData {
+            phone_number: phone_number.as_ref().expose_secret().to_owned(),
+            code: code.as_ref().expose_secret().to_owned(),
+        };
+
+        let serialized_data = serde_json::to_string(&data)
+            .wrap_err("Failed to serialize phone verification")
+            .map_err(PhoneVerificationStoreError::UnexpectedError)?;
+
+        let _: () = self
+            .conn
+            .write()
+            .await
+            .set_ex(get_key(&email), serialized_data, TEN_MINUTES_IN_SECONDS)
+            .wrap_err("Failed to set phone verification in Redis")
+            .map_err(PhoneVerificationStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving phone verification from Redis", skip_all)]
+    async fn get_verification(&self, email: &Email) -> Result<(PhoneNumber, TwoFACode), PhoneVerificationStoreError> {
+        let value: Option<String> = self
+            .conn
+            .write()
+            .await
+            .get(get_key(email))
+            .wrap_err("Failed to get phone verification from Redis")
+            .map_err(PhoneVerificationStoreError::UnexpectedError)?;
+
+        let value = value.ok_or(PhoneVerificationStoreError::VerificationNotFound)?;
+
+        let data: PhoneVerificationData = serde_json::from_str(&value)
+            .wrap_err("Failed to deserialize phone verification")
+            .map_err(PhoneVerificationStoreError::UnexpectedError)?;
+
+        let phone_number = PhoneNumber::parse(Secret::new(data.phone_number))
+            .map_err(PhoneVerificationStoreError::UnexpectedError)?;
+        let code = TwoFACode::parse(Secret::new(data.code))
+            .map_err(PhoneVerificationStoreError::UnexpectedError)?;
+
+        Ok((phone_number, code))
+    }
+
+    #[tracing::instrument(name = "Removing phone verification from Redis", skip_all)]
+    async fn remove_verification(&mut self, email: &Email) -> Result<(), PhoneVerificationStoreError> {
+        let removed: u64 = self
+            .conn
+            .write()
+            .await
+            .del(get_key(email))
+            .wrap_err("Failed to delete phone verification from Redis")
+            .map_err(PhoneVerificationStoreError::UnexpectedError)?;
+
+        match removed {
+            0 => Err(PhoneVerificationStoreError::VerificationNotFound),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct PhoneVerificationData {
+    phone_number: String,
+    code: String,
+}
+
+const TEN_MINUTES_IN_SECONDS: u64 = 600;
+const PHONE_VERIFICATION_PREFIX: &str = "phone_verification:";
+
+fn get_key(email: &Email) -> String {
+    format!("{}{}", PHONE_VERIFICATION_PREFIX, email.as_ref().expose_secret())
+}
@@ -0,0 +1,166 @@
+use color_eyre::eyre::Context;
+use std::sync::Arc;
+use redis::{Commands, Connection};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::{
+    domain::{
+        data_stores::{RefreshToken, RefreshTokenEntry, RefreshTokenStore, RefreshTokenStoreError},
+        Email,
+    },
+    utils::constants::REFRESH_TOKEN_TTL_SECONDS,
+};
+
+pub struct RedisRefreshTokenStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisRefreshTokenStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl RefreshTokenStore for RedisRefreshTokenStore {
+    #[tracing::instrument(name = "Adding refresh token to Redis", skip_all)]
+    async fn add_token(
+        &mut self,
+        token: RefreshToken,
+        entry: RefreshTokenEntry,
+    ) -> Result<(), RefreshTokenStoreError> {
+        let key = get_token_key(&token);
+
+        let serialized_data = serde_json::to_string(&RefreshTokenData::from(&entry))
+            .wrap_err("Failed to serialize refresh token entry")
+            .map_err(RefreshTokenStoreError::UnexpectedError)?;
+
+        let _: () = self
+            .conn
+            .write()
+            .await
+            .set_ex(&key, serialized_data, ttl()?)
+            .wrap_err("Failed to set refresh token in Redis")
+            .map_err(RefreshTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving refresh token from Redis", skip_all)]
+    async fn get_token(
+        &self,
+        token: &RefreshToken,
+    ) -> Result<RefreshTokenEntry, RefreshTokenStoreError> {
+        let key = get_token_key(token);
+
+        let value: Option<String> = self
+            .conn
+            .write()
+            .await
+            .get(&key)
+            .wrap_err("Failed to get refresh token from Redis")
+            .map_err(RefreshTokenStoreError::UnexpectedError)?;
+
+        let value = value.ok_or(RefreshTokenStoreError::TokenNotFound)?;
+
+        let data: RefreshTokenData = serde_json::from_str(&value)
+            .wrap_err("Failed to deserialize refresh token entry")
+            .map_err(RefreshTokenStoreError::UnexpectedError)?;
+
+        let email = Email::parse(Secret::new(data.email))
+            .map_err(RefreshTokenStoreError::UnexpectedError)?;
+
+        Ok(RefreshTokenEntry {
+            email,
+            family_id: data.family_id,
+            used: data.used,
+            session_version: data.session_version,
+        })
+    }
+
+    #[tracing::instrument(name = "Marking refresh token as used in Redis", skip_all)]
+    async fn mark_token_used(&mut self, token: &RefreshToken) -> Result<(), RefreshTokenStoreError> {
+        let mut entry = self.get_token(token).await?;
+        entry.used = true;
+
+        let serialized_data = serde_json::to_string(&RefreshTokenData::from(&entry))
+            .wrap_err("Failed to serialize refresh token entry")
+            .map_err(RefreshTokenStoreError::UnexpectedError)?;
+
+        // KEEPTTL preserves the expiry set when the token was issued
+        let _: () = redis::cmd("SET")
+            .arg(get_token_key(token))
+            .arg(serialized_data)
+            .arg("KEEPTTL")
+            .query(&mut *self.conn.write().await)
+            .wrap_err("Failed to update refresh token in Redis")
+            .map_err(RefreshTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Revoking refresh token family in Redis", skip_all)]
+    async fn revoke_family(&mut self, family_id: &str) -> Result<(), RefreshTokenStoreError> {
+        let _: () = self
+            .conn
+            .write()
+            .await
+            .set_ex(get_family_key(family_id), true, ttl()?)
+            .wrap_err("Failed to revoke refresh token family in Redis")
+            .map_err(RefreshTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Checking refresh token family in Redis", skip_all)]
+    async fn is_family_revoked(&self, family_id: &str) -> Result<bool, RefreshTokenStoreError> {
+        let is_revoked: bool = self
+            .conn
+            .write()
+            .await
+            .exists(get_family_key(family_id))
+            .wrap_err("Failed to check refresh token family in Redis")
+            .map_err(RefreshTokenStoreError::UnexpectedError)?;
+
+        Ok(is_revoked)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct RefreshTokenData {
+    email: String,
+    family_id: String,
+    used: bool,
+    session_version: u64,
+}
+
+impl From<&RefreshTokenEntry> for RefreshTokenData {
+    fn from(entry: &RefreshTokenEntry) -> Self {
+        Self {
+            email: entry.email.as_ref().expose_secret().to_owned(),
+            family_id: entry.family_id.clone(),
+            used: entry.used,
+            session_version: entry.session_version,
+        }
+    }
+}
+
+const REFRESH_TOKEN_KEY_PREFIX: &str = "refresh_token:";
+const REFRESH_TOKEN_FAMILY_KEY_PREFIX: &str = "refresh_token_family:";
+
+fn ttl() -> Result<u64, RefreshTokenStoreError> {
+    (*REFRESH_TOKEN_TTL_SECONDS)
+        .try_into()
+        .wrap_err("Failed to cast REFRESH_TOKEN_TTL_SECONDS to u64")
+        .map_err(RefreshTokenStoreError::UnexpectedError)
+}
+
+fn get_token_key(token: &RefreshToken) -> String {
+    format!("{}{}", REFRESH_TOKEN_KEY_PREFIX, token.hash())
+}
+
+fn get_family_key(family_id: &str) -> String {
+    format!("{}{}", REFRESH_TOKEN_FAMILY_KEY_PREFIX, family_id)
+}
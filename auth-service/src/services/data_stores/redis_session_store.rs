@@ -0,0 +1,257 @@
+use chrono::Utc;
+use color_eyre::eyre::Context;
+use redis::{Commands, Connection};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+use crate::{
+    domain::{
+        data_stores::{SessionStore, SessionStoreError},
+        AuthMethod, Email, Session,
+    },
+    utils::constants::REFRESH_TOKEN_TTL_SECONDS,
+};
+
+pub struct RedisSessionStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisSessionStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl SessionStore for RedisSessionStore {
+    #[tracing::instrument(name = "Adding session to Redis", skip_all)]
+    async fn add_session(&mut self, session: Session) -> Result<(), SessionStoreError> {
+        let mut conn = self.conn.write().await;
+
+        let _: () = conn
+            .sadd(get_user_key(&session.email), &session.id)
+            .wrap_err("Failed to add session to user in Redis")
+            .map_err(SessionStoreError::UnexpectedError)?;
+
+        set_session(&mut conn, &session)
+    }
+
+    #[tracing::instrument(name = "Retrieving session from Redis", skip_all)]
+    async fn get_session(&self, id: &str) -> Result<Session, SessionStoreError> {
+        get_session(&mut *self.conn.write().await, id)
+    }
+
+    #[tracing::instrument(name = "Retrieving sessions from Redis", skip_all)]
+    async fn get_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError> {
+        let mut conn = self.conn.write().await;
+        let user_key = get_user_key(email);
+
+        let ids: Vec<String> = conn
+            .smembers(&user_key)
+            .wrap_err("Failed to get user sessions from Redis")
+            .map_err(SessionStoreError::UnexpectedError)?;
+
+        let mut sessions = Vec::with_capacity(ids.len());
+        for id in ids {
+            match get_session(&mut conn, &id) {
+                Ok(session) => sessions.push(session),
+                // The session expired, so its id can be dropped from the user's set
+                Err(SessionStoreError::SessionNotFound) => {
+                    let _: () = conn
+                        .srem(&user_key, &id)
+                        .wrap_err("Failed to remove expired session from user in Redis")
+                        .map_err(SessionStoreError::UnexpectedError)?;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(sessions)
+    }
+
+    #[tracing::instrument(name = "Touching session in Redis", skip_all)]
+    async fn touch_session(&mut self, id: &str) -> Result<Session, SessionStoreError> {
+        let mut conn = self.conn.write().await;
+
+        let mut session = get_session(&mut conn, id)?;
+        session.last_seen = Utc::now().timestamp();
+
+        set_session(&mut conn, &session)?;
+        Ok(session)
+    }
+
+    #[tracing::instrument(name = "Recording session authentication in Redis", skip_all)]
+    async fn record_authentication(&mut self, id: &str, amr: Vec<AuthMethod>) -> Result<Session, SessionStoreError> {
+        let mut conn = self.conn.write().await;
+
+        let mut session = get_session(&mut conn, id)?;
+        session.auth_time = Utc::now().timestamp();
+        session.amr = amr;
+        session.failed_reauthentications = 0;
+
+        set_session(&mut conn, &session)?;
+        Ok(session)
+    }
+
+    #[tracing::instrument(name = "Recording failed reauthentication in Redis", skip_all)]
+    async fn record_failed_reauthentication(&mut self, id: &str) -> Result<u32, SessionStoreError> {
+        let mut conn = self.conn.write().await;
+
+        let mut session = get_session(&mut conn, id)?;
+        session.failed_reauthentications += 1;
+
+        set_session(&mut conn, &session)?;
+        Ok(session.failed_reauthentications)
+    }
+
+    #[tracing::instrument(name = "Removing session from Redis", skip_all)]
+    async fn remove_session(&mut self, email: &Email, id: &str) -> Result<(), SessionStoreError> {
+        let mut conn = self.conn.write().await;
+
+        // Only sessions that belong to the user can be removed
+        let removed: usize = conn
+            .srem(get_user_key(email), id)
+            .wrap_err("Failed to remove session from user in Redis")
+            .map_err(SessionStoreError::UnexpectedError)?;
+
+        if removed == 0 {
+            return Err(SessionStoreError::SessionNotFound);
+        }
+
+        let _: () = conn
+            .del(get_session_key(id))
+            .wrap_err("Failed to delete session from Redis")
+            .map_err(SessionStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Removing all sessions from Redis", skip_all)]
+    async fn remove_sessions(&mut self, email: &Email) -> Result<(), SessionStoreError> {
+        let mut conn = self.conn.write().await;
+        let user_key = get_user_key(email);
+
+        let ids: Vec<String> = conn
+            .smembers(&user_key)
+            .wrap_err("Failed to get user sessions from Redis")
+            .map_err(SessionStoreError::UnexpectedError)?;
+
+        let mut keys: Vec<String> = ids.iter().map(|id| get_session_key(id)).collect();
+        keys.push(user_key);
+
+        let _: () = conn
+            .del(keys)
+            .wrap_err("Failed to delete sessions from Redis")
+            .map_err(SessionStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SessionData {
+    id: String,
+    email: String,
+    ip: String,
+    user_agent: Option<String>,
+    created_at: i64,
+    last_seen: i64,
+    // Missing on sessions stored before reauthentication was tracked
+    #[serde(default)]
+    auth_time: Option<i64>,
+    #[serde(default)]
+    amr: Vec<AuthMethod>,
+    #[serde(default)]
+    failed_reauthentications: u32,
+}
+
+impl From<&Session> for SessionData {
+    fn from(session: &Session) -> Self {
+        Self {
+            id: session.id.clone(),
+            email: session.email.as_ref().expose_secret().to_owned(),
+            ip: session.ip.clone(),
+            user_agent: session.user_agent.clone(),
+            created_at: session.created_at,
+            last_seen: session.last_seen,
+            auth_time: Some(session.auth_time),
+            amr: session.amr.clone(),
+            failed_reauthentications: session.failed_reauthentications,
+        }
+    }
+}
+
+impl TryFrom<SessionData> for Session {
+    type Error = SessionStoreError;
+
+    fn try_from(data: SessionData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: data.id,
+            email: Email::parse(Secret::new(data.email))
+                .map_err(SessionStoreError::UnexpectedError)?,
+            ip: data.ip,
+            user_agent: data.user_agent,
+            created_at: data.created_at,
+            last_seen: data.last_seen,
+            auth_time: data.auth_time.unwrap_or(data.created_at),
+            amr: data.amr,
+            failed_reauthentications: data.failed_reauthentications,
+        })
+    }
+}
+
+// Sessions expire together with the refresh token that keeps them alive. The user's set
+// of sessions is kept as long as their latest session, so it doesn't expire while a
+// session that is still used is listed in it.
+fn set_session(conn: &mut Connection, session: &Session) -> Result<(), SessionStoreError> {
+    let serialized_data = serde_json::to_string(&SessionData::from(session))
+        .wrap_err("Failed to serialize session")
+        .map_err(SessionStoreError::UnexpectedError)?;
+
+    let _: () = conn
+        .set_ex(get_session_key(&session.id), serialized_data, ttl()?)
+        .wrap_err("Failed to set session in Redis")
+        .map_err(SessionStoreError::UnexpectedError)?;
+
+    let _: () = conn
+        .expire(get_user_key(&session.email), *REFRESH_TOKEN_TTL_SECONDS)
+        .wrap_err("Failed to set user sessions expiry in Redis")
+        .map_err(SessionStoreError::UnexpectedError)?;
+
+    Ok(())
+}
+
+fn get_session(conn: &mut Connection, id: &str) -> Result<Session, SessionStoreError> {
+    let value: Option<String> = conn
+        .get(get_session_key(id))
+        .wrap_err("Failed to get session from Redis")
+        .map_err(SessionStoreError::UnexpectedError)?;
+
+    let value = value.ok_or(SessionStoreError::SessionNotFound)?;
+
+    let data: SessionData = serde_json::from_str(&value)
+        .wrap_err("Failed to deserialize session")
+        .map_err(SessionStoreError::UnexpectedError)?;
+
+    data.try_into()
+}
+
+const SESSION_KEY_PREFIX: &str = "session:";
+const USER_SESSIONS_KEY_PREFIX: &str = "user_sessions:";
+
+fn ttl() -> Result<u64, SessionStoreError> {
+    (*REFRESH_TOKEN_TTL_SECONDS)
+        .try_into()
+        .wrap_err("Failed to cast REFRESH_TOKEN_TTL_SECONDS to u64")
+        .map_err(SessionStoreError::UnexpectedError)
+}
+
+fn get_session_key(id: &str) -> String {
+    format!("{}{}", SESSION_KEY_PREFIX, id)
+}
+
+fn get_user_key(email: &Email) -> String {
+    format!("{}{}", USER_SESSIONS_KEY_PREFIX, email.as_ref().expose_secret())
+}
@@ -0,0 +1,56 @@
+use color_eyre::eyre::Context;
+use redis::{Commands, Connection};
+use secrecy::ExposeSecret;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+use crate::domain::{
+    data_stores::{SessionVersionStore, SessionVersionStoreError},
+    Email,
+};
+
+pub struct RedisSessionVersionStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisSessionVersionStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl SessionVersionStore for RedisSessionVersionStore {
+    #[tracing::instrument(name = "Retrieving session version from Redis", skip_all)]
+    async fn get_version(&self, email: &Email) -> Result<u64, SessionVersionStoreError> {
+        let version: Option<u64> = self
+            .conn
+            .write()
+            .await
+            .get(get_key(email))
+            .wrap_err("Failed to get session version from Redis")
+            .map_err(SessionVersionStoreError::UnexpectedError)?;
+
+        Ok(version.unwrap_or_default())
+    }
+
+    // Versions never expire, since a reset would revive tokens issued before the last increment
+    #[tracing::instrument(name = "Incrementing session version in Redis", skip_all)]
+    async fn increment_version(&mut self, email: &Email) -> Result<u64, SessionVersionStoreError> {
+        let version: u64 = self
+            .conn
+            .write()
+            .await
+            .incr(get_key(email), 1)
+            .wrap_err("Failed to increment session version in Redis")
+            .map_err(SessionVersionStoreError::UnexpectedError)?;
+
+        Ok(version)
+    }
+}
+
+const SESSION_VERSION_KEY_PREFIX: &str = "session_version:";
+
+fn get_key(email: &Email) -> String {
+    format!("{}{}", SESSION_VERSION_KEY_PREFIX, email.as_ref().expose_secret())
+}
@@ -0,0 +1,200 @@
+use chrono::Utc;
+use color_eyre::eyre::Context;
+use redis::{Commands, Connection};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+use crate::{
+    domain::{
+        data_stores::{TrustedDeviceStore, TrustedDeviceStoreError},
+        Email, TrustedDevice,
+    },
+    utils::constants::TRUSTED_DEVICE_TTL_SECONDS,
+};
+
+pub struct RedisTrustedDeviceStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisTrustedDeviceStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl TrustedDeviceStore for RedisTrustedDeviceStore {
+    #[tracing::instrument(name = "Adding trusted device to Redis", skip_all)]
+    async fn add_device(&mut self, device: TrustedDevice) -> Result<(), TrustedDeviceStoreError> {
+        let mut conn = self.conn.write().await;
+
+        let serialized_data = serde_json::to_string(&TrustedDeviceData::from(&device))
+            .wrap_err("Failed to serialize trusted device")
+            .map_err(TrustedDeviceStoreError::UnexpectedError)?;
+
+        // Redis drops the device once it expires
+        let ttl_seconds = (device.expires_at - Utc::now().timestamp()).max(1) as u64;
+
+        let _: () = conn
+            .set_ex(get_device_key(&device.id), serialized_data, ttl_seconds)
+            .wrap_err("Failed to set trusted device in Redis")
+            .map_err(TrustedDeviceStoreError::UnexpectedError)?;
+
+        let user_key = get_user_key(&device.email);
+
+        let _: () = conn
+            .sadd(&user_key, &device.id)
+            .wrap_err("Failed to add trusted device to user in Redis")
+            .map_err(TrustedDeviceStoreError::UnexpectedError)?;
+
+        let _: () = conn
+            .expire(&user_key, *TRUSTED_DEVICE_TTL_SECONDS)
+            .wrap_err("Failed to set user trusted devices expiry in Redis")
+            .map_err(TrustedDeviceStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving trusted device from Redis", skip_all)]
+    async fn get_device(&self, id: &str) -> Result<TrustedDevice, TrustedDeviceStoreError> {
+        get_device(&mut *self.conn.write().await, id)
+    }
+
+    #[tracing::instrument(name = "Retrieving trusted devices from Redis", skip_all)]
+    async fn get_devices(&self, email: &Email) -> Result<Vec<TrustedDevice>, TrustedDeviceStoreError> {
+        let mut conn = self.conn.write().await;
+        let user_key = get_user_key(email);
+
+        let ids: Vec<String> = conn
+            .smembers(&user_key)
+            .wrap_err("Failed to get user trusted devices from Redis")
+            .map_err(TrustedDeviceStoreError::UnexpectedError)?;
+
+        let mut devices = Vec::with_capacity(ids.len());
+        for id in ids {
+            match get_device(&mut conn, &id) {
+                Ok(device) => devices.push(device),
+                // The device expired, so its id can be dropped from the user's set
+                Err(TrustedDeviceStoreError::DeviceNotFound) => {
+                    let _: () = conn
+                        .srem(&user_key, &id)
+                        .wrap_err("Failed to remove expired trusted device from user in Redis")
+                        .map_err(TrustedDeviceStoreError::UnexpectedError)?;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(devices)
+    }
+
+    #[tracing::instrument(name = "Removing trusted device from Redis", skip_all)]
+    async fn remove_device(&mut self, email: &Email, id: &str) -> Result<(), TrustedDeviceStoreError> {
+        let mut conn = self.conn.write().await;
+
+        // Only devices that belong to the user can be removed
+        let removed: usize = conn
+            .srem(get_user_key(email), id)
+            .wrap_err("Failed to remove trusted device from user in Redis")
+            .map_err(TrustedDeviceStoreError::UnexpectedError)?;
+
+        if removed == 0 {
+            return Err(TrustedDeviceStoreError::DeviceNotFound);
+        }
+
+        let _: () = conn
+            .del(get_device_key(id))
+            .wrap_err("Failed to delete trusted device from Redis")
+            .map_err(TrustedDeviceStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Removing all trusted devices from Redis", skip_all)]
+    async fn remove_devices(&mut self, email: &Email) -> Result<(), TrustedDeviceStoreError> {
+        let mut conn = self.conn.write().await;
+        let user_key = get_user_key(email);
+
+        let ids: Vec<String> = conn
+            .smembers(&user_key)
+            .wrap_err("Failed to get user trusted devices from Redis")
+            .map_err(TrustedDeviceStoreError::UnexpectedError)?;
+
+        let mut keys: Vec<String> = ids.iter().map(|id| get_device_key(id)).collect();
+        keys.push(user_key);
+
+        let _: () = conn
+            .del(keys)
+            .wrap_err("Failed to delete trusted devices from Redis")
+            .map_err(TrustedDeviceStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct TrustedDeviceData {
+    id: String,
+    email: String,
+    ip: String,
+    user_agent: Option<String>,
+    created_at: i64,
+    expires_at: i64,
+}
+
+impl From<&TrustedDevice> for TrustedDeviceData {
+    fn from(device: &TrustedDevice) -> Self {
+        Self {
+            id: device.id.clone(),
+            email: device.email.as_ref().expose_secret().to_owned(),
+            ip: device.ip.clone(),
+            user_agent: device.user_agent.clone(),
+            created_at: device.created_at,
+            expires_at: device.expires_at,
+        }
+    }
+}
+
+impl TryFrom<TrustedDeviceData> for TrustedDevice {
+    type Error = TrustedDeviceStoreError;
+
+    fn try_from(data: TrustedDeviceData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: data.id,
+            email: Email::parse(Secret::new(data.email))
+                .map_err(TrustedDeviceStoreError::UnexpectedError)?,
+            ip: data.ip,
+            user_agent: data.user_agent,
+            created_at: data.created_at,
+            expires_at: data.expires_at,
+        })
+    }
+}
+
+fn get_device(conn: &mut Connection, id: &str) -> Result<TrustedDevice, TrustedDeviceStoreError> {
+    let value: Option<String> = conn
+        .get(get_device_key(id))
+        .wrap_err("Failed to get trusted device from Redis")
+        .map_err(TrustedDeviceStoreError::UnexpectedError)?;
+
+    let value = value.ok_or(TrustedDeviceStoreError::DeviceNotFound)?;
+
+    let data: TrustedDeviceData = serde_json::from_str(&value)
+        .wrap_err("Failed to deserialize trusted device")
+        .map_err(TrustedDeviceStoreError::UnexpectedError)?;
+
+    data.try_into()
+}
+
+const TRUSTED_DEVICE_KEY_PREFIX: &str = "trusted_device:";
+const USER_TRUSTED_DEVICES_KEY_PREFIX: &str = "user_trusted_devices:";
+
+fn get_device_key(id: &str) -> String {
+    format!("{}{}", TRUSTED_DEVICE_KEY_PREFIX, id)
+}
+
+fn get_user_key(email: &Email) -> String {
+    format!("{}{}", USER_TRUSTED_DEVICES_KEY_PREFIX, email.as_ref().expose_secret())
+}
@@ -1,3 +1,4 @@
+use chrono::Utc;
 use color_eyre::eyre::Context;
 use std::sync::Arc;
 use redis::{Commands, Connection};
@@ -5,124 +6,254 @@ use secrecy::{ExposeSecret, Secret};
 use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock;
 
-use crate::domain::{
-    data_stores::{LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError},
-    Email,
+use crate::{
+    domain::{
+        data_stores::{LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError},
+        Email,
+    },
+    utils::constants::{
+        TWO_FA_MAX_ATTEMPTS, TWO_FA_MAX_PENDING_ATTEMPTS, TWO_FA_MAX_RESENDS,
+        TWO_FA_RESEND_COOLDOWN_SECONDS,
+    },
 };
 
 pub struct RedisTwoFACodeStore {
     conn: Arc<RwLock<Connection>>,
+    resend_cooldown_seconds: i64,
 }
 
 impl RedisTwoFACodeStore {
     pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
-        Self { conn }
+        Self { conn, resend_cooldown_seconds: *TWO_FA_RESEND_COOLDOWN_SECONDS }
+    }
+
+    pub fn with_resend_cooldown(mut self, seconds: i64) -> Self {
+        self.resend_cooldown_seconds = seconds;
+        self
     }
 }
 
 #[async_trait::async_trait]
 impl TwoFACodeStore for RedisTwoFACodeStore {
+    #[tracing::instrument(name = "Adding 2FA code to Redis", skip_all)]
     async fn add_code(
         &mut self,
         email: Email,
         login_attempt_id: LoginAttemptId,
         code: TwoFACode,
     ) -> Result<(), TwoFACodeStoreError> {
-        // TODO:
-        // 1. Create a new key using the get_key helper function.
-        // 2. Create a TwoFATuple instance.
-        // 3. Use serde_json::to_string to serialize the TwoFATuple instance into a JSON string. 
-        // Return TwoFACodeStoreError::UnexpectedError if serialization fails.
-        // 4. Call the set_ex command on the Redis connection to set a new key/value pair with an expiration time (TTL). 
-        // The value should be the serialized 2FA tuple.
-        // The expiration time should be set to TEN_MINUTES_IN_SECONDS.
-        // Return TwoFACodeStoreError::UnexpectedError if casting fails or the call to set_ex fails.
-        let key = get_key(&email);
-
-        let data = TwoFATuple(
-            login_attempt_id.as_ref().expose_secret().to_owned(),
-            code.as_ref().expose_secret().to_owned(),
-        );
+        let mut conn = self.conn.write().await;
+        let id = login_attempt_id.as_ref().expose_secret();
+
+        let data = TwoFAData {
+            email: email.as_ref().expose_secret().to_owned(),
+            code: code.as_ref().expose_secret().to_owned(),
+            failed_attempts: 0,
+            resends: 0,
+            sent_at: Utc::now().timestamp(),
+        };
 
         let serialized_data = serde_json::to_string(&data)
-            .wrap_err("Failed to serialize 2FA tuple")
+            .wrap_err("Failed to serialize 2FA data")
             .map_err(TwoFACodeStoreError::UnexpectedError)?;
 
-        let _: () = self
-            .conn
-            .write()
-            .await
-            .set_ex(&key, serialized_data, TEN_MINUTES_IN_SECONDS)
+        let _: () = conn
+            .set_ex(get_key(id), serialized_data, TEN_MINUTES_IN_SECONDS)
             .wrap_err("Failed to set 2FA code in Redis")
             .map_err(TwoFACodeStoreError::UnexpectedError)?;
 
-        Ok(())
+        // The user's pending attempts, oldest first
+        let user_key = get_user_key(email.as_ref().expose_secret());
 
-    }
+        let _: () = conn
+            .rpush(&user_key, id)
+            .wrap_err("Failed to add 2FA code to user in Redis")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
 
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
+        let _: () = conn
+            .expire(&user_key, TEN_MINUTES_IN_SECONDS as i64)
+            .wrap_err("Failed to set user 2FA codes expiry in Redis")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
 
-        // TODO:
-        // 1. Create a new key using the get_key helper function.
-        // 2. Call the del command on the Redis connection to delete the 2FA code entry. 
-        // Return TwoFACodeStoreError::UnexpectedError if the operation fails.
+        // Attempts that were verified or expired in the meantime don't count
+        let ids: Vec<String> = conn
+            .lrange(&user_key, 0, -1)
+            .wrap_err("Failed to get user 2FA codes from Redis")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
 
-        let key = get_key(email);
+        let mut pending = Vec::with_capacity(ids.len());
+        for id in ids {
+            let exists: bool = conn
+                .exists(get_key(&id))
+                .wrap_err("Failed to check 2FA code in Redis")
+                .map_err(TwoFACodeStoreError::UnexpectedError)?;
 
-        let _: () = self
-            .conn
-            .write()
-            .await
-            .del(&key)
-            .wrap_err("Failed to delete 2FA code from Redis")
-            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+            match exists {
+                true => pending.push(id),
+                false => {
+                    let _: () = conn
+                        .lrem(&user_key, 0, &id)
+                        .wrap_err("Failed to remove expired 2FA code from user in Redis")
+                        .map_err(TwoFACodeStoreError::UnexpectedError)?;
+                }
+            }
+        }
+
+        let excess = pending.len().saturating_sub(*TWO_FA_MAX_PENDING_ATTEMPTS as usize);
+        for id in &pending[..excess] {
+            delete_code(&mut conn, id, &user_key)?;
+        }
 
         Ok(())
+    }
+
+    #[tracing::instrument(name = "Removing 2FA code from Redis", skip_all)]
+    async fn remove_code(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let mut conn = self.conn.write().await;
+        let id = login_attempt_id.as_ref().expose_secret();
 
+        let data = get_data(&mut conn, id)?;
+        delete_code(&mut conn, id, &get_user_key(&data.email))
     }
 
+    #[tracing::instrument(name = "Retrieving 2FA code from Redis", skip_all)]
     async fn get_code(
         &self,
-        email: &Email,
-    ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
-
-        // TODO:
-        // 1. Create a new key using the get_key helper function.
-        // 2. Call the get command on the Redis connection to get the value stored for the key. 
-        // Return TwoFACodeStoreError::LoginAttemptIdNotFound if the operation fails.
-        // If the operation succeeds, call serde_json::from_str to parse the JSON string into a TwoFATuple. 
-        // Then, parse the login attempt ID string and 2FA code string into a LoginAttemptId and TwoFACode type respectively.
-        // Return TwoFACodeStoreError::UnexpectedError if parsing fails.
-
-        let key = get_key(email);
-
-        match self.conn.write().await.get::<_, String>(&key) {
-            Ok(value) => {
-                let data: TwoFATuple = serde_json::from_str(&value)
-                    .wrap_err("Failed to deserialize 2FA tuple")
-                    .map_err(TwoFACodeStoreError::UnexpectedError)?;
-
-                let login_attempt_id = LoginAttemptId::parse(Secret::new(data.0))
-                    .map_err(TwoFACodeStoreError::UnexpectedError)?;
-
-                let email_code =
-                    TwoFACode::parse(Secret::new(data.1))
-                        .map_err(TwoFACodeStoreError::UnexpectedError)?;
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(Email, TwoFACode), TwoFACodeStoreError> {
+        let data = get_data(&mut *self.conn.write().await, login_attempt_id.as_ref().expose_secret())?;
 
-                Ok((login_attempt_id, email_code))
-            }
-            Err(_) => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
+        let email = Email::parse(Secret::new(data.email))
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        let email_code = TwoFACode::parse(Secret::new(data.code))
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        Ok((email, email_code))
+    }
+
+    #[tracing::instrument(name = "Recording failed 2FA attempt in Redis", skip_all)]
+    async fn record_failed_attempt(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let mut conn = self.conn.write().await;
+        let id = login_attempt_id.as_ref().expose_secret();
+
+        let mut data = get_data(&mut conn, id)?;
+        data.failed_attempts += 1;
+
+        if data.failed_attempts >= *TWO_FA_MAX_ATTEMPTS {
+            delete_code(&mut conn, id, &get_user_key(&data.email))?;
+            return Err(TwoFACodeStoreError::TooManyAttempts);
+        }
+
+        let serialized_data = serde_json::to_string(&data)
+            .wrap_err("Failed to serialize 2FA data")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        // KEEPTTL so wrong codes don't extend the life of the code
+        let _: () = redis::cmd("SET")
+            .arg(get_key(id))
+            .arg(serialized_data)
+            .arg("KEEPTTL")
+            .query(&mut *conn)
+            .wrap_err("Failed to update 2FA code in Redis")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Resending 2FA code in Redis", skip_all)]
+    async fn resend_code(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+        code: TwoFACode,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let mut conn = self.conn.write().await;
+        let id = login_attempt_id.as_ref().expose_secret();
+
+        let mut data = get_data(&mut conn, id)?;
+
+        if data.resends >= *TWO_FA_MAX_RESENDS {
+            return Err(TwoFACodeStoreError::TooManyResends);
+        }
+
+        let now = Utc::now().timestamp();
+        let retry_after = data.sent_at + self.resend_cooldown_seconds - now;
+        if retry_after > 0 {
+            return Err(TwoFACodeStoreError::ResendTooSoon(retry_after as u64));
         }
 
+        data.code = code.as_ref().expose_secret().to_owned();
+        data.resends += 1;
+        data.sent_at = now;
+
+        let serialized_data = serde_json::to_string(&data)
+            .wrap_err("Failed to serialize 2FA data")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        // The fresh code gets the full lifetime
+        let _: () = conn
+            .set_ex(get_key(id), serialized_data, TEN_MINUTES_IN_SECONDS)
+            .wrap_err("Failed to set 2FA code in Redis")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        let _: () = conn
+            .expire(get_user_key(&data.email), TEN_MINUTES_IN_SECONDS as i64)
+            .wrap_err("Failed to set user 2FA codes expiry in Redis")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        Ok(())
     }
 }
 
 #[derive(Serialize, Deserialize)]
-struct TwoFATuple(pub String, pub String);
+struct TwoFAData {
+    email: String,
+    code: String,
+    failed_attempts: u32,
+    resends: u32,
+    // Unix timestamp of when the last code was sent
+    sent_at: i64,
+}
+
+fn get_data(conn: &mut Connection, id: &str) -> Result<TwoFAData, TwoFACodeStoreError> {
+    let value: Option<String> = conn
+        .get(get_key(id))
+        .wrap_err("Failed to get 2FA code from Redis")
+        .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+    serde_json::from_str(&value.ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?)
+        .wrap_err("Failed to deserialize 2FA data")
+        .map_err(TwoFACodeStoreError::UnexpectedError)
+}
+
+fn delete_code(conn: &mut Connection, id: &str, user_key: &str) -> Result<(), TwoFACodeStoreError> {
+    let _: () = conn
+        .del(get_key(id))
+        .wrap_err("Failed to delete 2FA code from Redis")
+        .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+    let _: () = conn
+        .lrem(user_key, 0, id)
+        .wrap_err("Failed to remove 2FA code from user in Redis")
+        .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+    Ok(())
+}
 
 const TEN_MINUTES_IN_SECONDS: u64 = 600;
 const TWO_FA_CODE_PREFIX: &str = "two_fa_code:";
+const USER_TWO_FA_CODES_PREFIX: &str = "user_two_fa_codes:";
+
+fn get_key(login_attempt_id: &str) -> String {
+    format!("{}{}", TWO_FA_CODE_PREFIX, login_attempt_id)
+}
 
-fn get_key(email: &Email) -> String {
-    format!("{}{}", TWO_FA_CODE_PREFIX, email.as_ref().expose_secret())
-}
\ No newline at end of file
+fn get_user_key(email: &str) -> String {
+    format!("{}{}", USER_TWO_FA_CODES_PREFIX, email)
+}
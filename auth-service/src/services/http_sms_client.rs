@@ -0,0 +1,133 @@
+use color_eyre::eyre::Result;
+use reqwest::{Client, Url};
+use secrecy::{ExposeSecret, Secret};
+
+use crate::domain::{PhoneNumber, SmsClient};
+
+// Where the SMS provider is reached, read from the environment
+#[derive(Debug, Clone)]
+pub struct SmsProviderConfig {
+    pub base_url: String,
+    pub sender: String,
+    pub authorization_token: Secret<String>,
+}
+
+// Sends texts through an SMS provider's HTTP API
+pub struct HttpSmsClient {
+    http_client: Client,
+    base_url: String,
+    // Number (or alphanumeric sender ID) the texts come from
+    sender: String,
+    authorization_token: Secret<String>,
+}
+
+impl HttpSmsClient {
+    pub fn new(
+        base_url: String,
+        sender: String,
+        authorization_token: Secret<String>,
+        http_client: Client,
+    ) -> Self {
+        Self {
+            http_client,
+            base_url,
+            sender,
+            authorization_token,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl SmsClient for HttpSmsClient {
+    #[tracing::instrument(name = "Sending SMS", skip_all)]
+    async fn send_sms(&self, recipient: &PhoneNumber, content: &str) -> Result<()> {
+        let base = Url::parse(&self.base_url)?;
+        let url = base.join("/sms")?;
+
+        let request_body = SendSmsRequest {
+            from: &self.sender,
+            to: recipient.as_ref().expose_secret(),
+            body: content,
+        };
+
+        self.http_client
+            .post(url)
+            .bearer_auth(self.authorization_token.expose_secret())
+            .json(&request_body)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+#[derive(serde::Serialize, Debug)]
+struct SendSmsRequest<'a> {
+    from: &'a str,
+    to: &'a str,
+    body: &'a str,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::constants::test;
+
+    use super::*;
+    use fake::faker::lorem::en::Sentence;
+    use fake::{Fake, Faker};
+    use wiremock::matchers::{any, body_partial_json, header_exists, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn phone_number() -> PhoneNumber {
+        PhoneNumber::parse(Secret::new("+14155552671".to_owned())).unwrap()
+    }
+
+    fn sms_client(base_url: String) -> HttpSmsClient {
+        let http_client = Client::builder()
+            .timeout(test::sms_client::TIMEOUT)
+            .build()
+            .unwrap();
+        HttpSmsClient::new(base_url, test::sms_client::SENDER.to_owned(), Secret::new(Faker.fake()), http_client)
+    }
+
+    #[tokio::test]
+    async fn send_sms_sends_the_expected_request() {
+        let mock_server = MockServer::start().await;
+        let sms_client = sms_client(mock_server.uri());
+        let content: String = Sentence(1..2).fake();
+
+        Mock::given(header_exists("Authorization"))
+            .and(path("/sms"))
+            .and(method("POST"))
+            .and(body_partial_json(serde_json::json!({
+                "from": test::sms_client::SENDER,
+                "to": "+14155552671",
+                "body": content,
+            })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = sms_client.send_sms(&phone_number(), &content).await;
+
+        assert!(outcome.is_ok());
+    }
+
+    #[tokio::test]
+    async fn send_sms_fails_if_the_server_returns_500() {
+        let mock_server = MockServer::start().await;
+        let sms_client = sms_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(500))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = sms_client.send_sms(&phone_number(), "123456").await;
+
+        assert!(outcome.is_err());
+    }
+}
@@ -1,2 +1,4 @@
 pub mod data_stores;
+pub mod http_sms_client;
+pub mod oidc_client;
 pub mod postmark_email_client;
\ No newline at end of file
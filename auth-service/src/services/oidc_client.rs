@@ -0,0 +1,214 @@
+use color_eyre::eyre::{eyre, Context, Result};
+use jsonwebtoken::{
+    decode, decode_header,
+    jwk::{AlgorithmParameters, Jwk, JwkSet},
+    DecodingKey, Validation,
+};
+use reqwest::{Client, Url};
+use secrecy::{ExposeSecret, Secret};
+use serde::Deserialize;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+use crate::domain::{OidcLogin, OidcProviderConfig, OidcState};
+
+// Logs users in with an external OpenID Connect provider using the authorization code flow
+#[derive(Clone)]
+pub struct OidcClient {
+    http_client: Client,
+    config: OidcProviderConfig,
+    cache: Arc<RwLock<ProviderCache>>,
+}
+
+// The provider's metadata and keys are fetched once and kept, the keys are fetched again when
+// an ID token is signed with a key they don't have
+#[derive(Default)]
+struct ProviderCache {
+    metadata: Option<ProviderMetadata>,
+    jwks: Option<JwkSet>,
+}
+
+impl OidcClient {
+    pub fn new(config: OidcProviderConfig, http_client: Client) -> Self {
+        Self {
+            http_client,
+            config,
+            cache: Arc::new(RwLock::new(ProviderCache::default())),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    // Where to send the browser to log in at the provider
+    #[tracing::instrument(name = "Building OIDC authorization URL", skip_all)]
+    pub async fn authorization_url(
+        &self,
+        state: &OidcState,
+        login: &OidcLogin,
+        redirect_uri: &str,
+    ) -> Result<String> {
+        let metadata = self.discover().await?;
+
+        let mut url = Url::parse(&metadata.authorization_endpoint)
+            .wrap_err("Invalid authorization endpoint")?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.config.client_id)
+            .append_pair("redirect_uri", redirect_uri)
+            .append_pair("scope", &self.config.scopes.join(" "))
+            .append_pair("state", state.as_ref().expose_secret())
+            .append_pair("nonce", &login.nonce)
+            .append_pair("code_challenge", &login.code_challenge())
+            .append_pair("code_challenge_method", "S256");
+
+        Ok(url.into())
+    }
+
+    // Trade the authorization code from the callback for the user's verified ID token claims
+    #[tracing::instrument(name = "Exchanging OIDC authorization code", skip_all)]
+    pub async fn exchange_code(
+        &self,
+        code: &Secret<String>,
+        login: &OidcLogin,
+        redirect_uri: &str,
+    ) -> Result<ExternalIdTokenClaims> {
+        let metadata = self.discover().await?;
+
+        let response: TokenResponse = self
+            .http_client
+            .post(&metadata.token_endpoint)
+            .basic_auth(&self.config.client_id, Some(self.config.client_secret.expose_secret()))
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code.expose_secret()),
+                ("redirect_uri", redirect_uri),
+                ("code_verifier", login.code_verifier.expose_secret()),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+            .wrap_err("Invalid token response")?;
+
+        self.verify_id_token(&response.id_token, &metadata, login).await
+    }
+
+    async fn verify_id_token(
+        &self,
+        id_token: &str,
+        metadata: &ProviderMetadata,
+        login: &OidcLogin,
+    ) -> Result<ExternalIdTokenClaims> {
+        let header = decode_header(id_token)?;
+
+        // The provider rotated its keys if the cached ones don't include the token's key
+        let cached = self.cache.read().await.jwks.as_ref().and_then(|jwks| find_key(jwks, &header.kid));
+        let jwk = match cached {
+            Some(jwk) => jwk,
+            None => {
+                let jwks = self.fetch_jwks(metadata).await?;
+                find_key(&jwks, &header.kid).ok_or(eyre!("No matching key in the provider's JWKS"))?
+            }
+        };
+
+        // A symmetric key would have to be published to be in the JWKS, so anyone could sign with it
+        if matches!(jwk.algorithm, AlgorithmParameters::OctetKey(_)) {
+            return Err(eyre!("Symmetric keys are not accepted for ID tokens"));
+        }
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&self.config.issuer]);
+        validation.set_audience(&[&self.config.client_id]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+        let claims =
+            decode::<ExternalIdTokenClaims>(id_token, &DecodingKey::from_jwk(&jwk)?, &validation)?
+                .claims;
+
+        if claims.nonce.as_deref() != Some(login.nonce.as_str()) {
+            return Err(eyre!("ID token nonce does not match the login"));
+        }
+
+        Ok(claims)
+    }
+
+    async fn fetch_jwks(&self, metadata: &ProviderMetadata) -> Result<JwkSet> {
+        let jwks: JwkSet = self
+            .http_client
+            .get(&metadata.jwks_uri)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+            .wrap_err("Invalid JWKS")?;
+
+        self.cache.write().await.jwks = Some(jwks.clone());
+        Ok(jwks)
+    }
+
+    async fn discover(&self) -> Result<ProviderMetadata> {
+        if let Some(metadata) = &self.cache.read().await.metadata {
+            return Ok(metadata.clone());
+        }
+
+        let url = format!(
+            "{}/.well-known/openid-configuration",
+            self.config.issuer.trim_end_matches('/')
+        );
+
+        let metadata: ProviderMetadata = self
+            .http_client
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+            .wrap_err("Invalid provider metadata")?;
+
+        // The metadata must belong to the configured issuer (OpenID Connect Discovery section 4.3)
+        if metadata.issuer != self.config.issuer {
+            return Err(eyre!("Provider metadata issuer does not match"));
+        }
+
+        self.cache.write().await.metadata = Some(metadata.clone());
+        Ok(metadata)
+    }
+}
+
+// Tokens without a key ID can only be matched if the provider has a single key
+fn find_key(jwks: &JwkSet, kid: &Option<String>) -> Option<Jwk> {
+    match kid {
+        Some(kid) => jwks.find(kid),
+        None if jwks.keys.len() == 1 => jwks.keys.first(),
+        None => None,
+    }
+    .cloned()
+}
+
+// The claims of a provider's ID token that are used to link the user
+#[derive(Debug, Deserialize)]
+pub struct ExternalIdTokenClaims {
+    pub sub: String,
+    pub email: Option<String>,
+    #[serde(default)]
+    pub email_verified: bool,
+    pub nonce: Option<String>,
+}
+
+#[derive(Clone, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
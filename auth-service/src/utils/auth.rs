@@ -1,31 +1,231 @@
 use color_eyre::eyre::{eyre, Context, ContextCompat, Result};
-use axum_extra::extract::cookie::{Cookie, SameSite};
+use auth_middleware::{AuthError, AuthenticatedUser, TokenValidator};
+use axum_extra::extract::{
+    cookie::{Cookie, SameSite},
+    CookieJar,
+};
 use chrono::Utc;
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Validation};
+use jsonwebtoken::{decode, decode_header, encode, Header, Validation};
 use secrecy::{ExposeSecret, Secret};
 use serde::{Deserialize, Serialize};
 
-use crate::app_state::BannedTokenStoreType;
-use crate::domain::email::Email;
-use super::constants::{JWT_COOKIE_NAME, JWT_SECRET};
+use crate::app_state::{
+    ApiKeyStoreType, AppState, BannedTokenStoreType, RefreshTokenStoreType, SessionStoreType,
+    SessionVersionStoreType,
+};
+use crate::domain::{
+    data_stores::{RefreshToken, RefreshTokenEntry},
+    email::Email,
+    ApiKey, ApiKeyEntry, Session,
+};
+use super::csrf::create_csrf_cookie;
+use super::constants::{
+    COOKIE_CONFIG, JWT_AUDIENCE, JWT_COOKIE_NAME, JWT_ISSUER, JWT_KEY_SET, REFRESH_COOKIE_NAME,
+    REFRESH_TOKEN_TTL_SECONDS, TOKEN_TTL_SECONDS,
+};
+
+// Register a new session for the user and issue the auth and refresh tokens that carry it
+#[tracing::instrument(name = "Start session", skip_all)]
+pub async fn start_session(
+    session: Session,
+    roles: &[String],
+    state: &AppState,
+) -> Result<SessionTokens> {
+    let session_version = state
+        .session_version_store
+        .read()
+        .await
+        .get_version(&session.email)
+        .await?;
+
+    let auth_token = generate_auth_token(&session, roles, session_version)?;
+    let refresh_token = generate_refresh_token(
+        &session.email,
+        &session.id,
+        session_version,
+        state.refresh_token_store.clone(),
+    )
+    .await?;
+
+    state
+        .session_store
+        .write()
+        .await
+        .add_session(session)
+        .await
+        .wrap_err("Failed to store session")?;
+
+    Ok(SessionTokens { auth_token, refresh_token })
+}
+
+// How a client wants to receive its tokens. Browsers use cookies, while clients
+// that can't store cookies get the tokens in the response body and send them back
+// in an `Authorization: Bearer` header.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenDelivery {
+    #[default]
+    Cookie,
+    Body,
+}
+
+// The auth and refresh tokens of a session that was just started or refreshed
+pub struct SessionTokens {
+    pub auth_token: Secret<String>,
+    pub refresh_token: RefreshToken,
+}
+
+impl SessionTokens {
+    // Add the tokens to the cookie jar, or return them for the response body
+    pub fn deliver(self, delivery: TokenDelivery, jar: CookieJar) -> (CookieJar, Option<TokenResponse>) {
+        match delivery {
+            TokenDelivery::Cookie => {
+                let updated_jar = jar
+                    .add(create_auth_cookie(self.auth_token))
+                    .add(create_refresh_cookie(self.refresh_token))
+                    .add(create_csrf_cookie());
+                (updated_jar, None)
+            }
+            TokenDelivery::Body => (jar, Some(TokenResponse::from(self))),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TokenResponse {
+    #[serde(rename = "accessToken")]
+    pub access_token: String,
+    #[serde(rename = "tokenType")]
+    pub token_type: String,
+    #[serde(rename = "expiresIn")]
+    pub expires_in: i64,
+    #[serde(rename = "refreshToken")]
+    pub refresh_token: String,
+}
+
+impl From<SessionTokens> for TokenResponse {
+    fn from(tokens: SessionTokens) -> Self {
+        Self {
+            access_token: tokens.auth_token.expose_secret().to_owned(),
+            token_type: "Bearer".to_owned(),
+            expires_in: *TOKEN_TTL_SECONDS,
+            refresh_token: tokens.refresh_token.as_ref().expose_secret().to_owned(),
+        }
+    }
+}
 
 // Create cookie with a new JWT auth token
 #[tracing::instrument(name = "Generate auth cookie", skip_all)]
-pub fn generate_auth_cookie(email: &Email) -> Result<Cookie<'static>> {
-    let token = generate_auth_token(email)?;
+pub fn generate_auth_cookie(
+    session: &Session,
+    roles: &[String],
+    session_version: u64,
+) -> Result<Cookie<'static>> {
+    let token = generate_auth_token(session, roles, session_version)?;
     Ok(create_auth_cookie(token))
 }
 
 // Create cookie and set the value to the passed-in token string
 #[tracing::instrument(name = "Create auth cookie", skip_all)]
 fn create_auth_cookie(token: Secret<String>) -> Cookie<'static> {
-    let cookie = Cookie::build((JWT_COOKIE_NAME, token.expose_secret().to_owned()))
-        .path("/") // apple cookie to all URLs on the server
-        .http_only(true) // prevent JavaScript from accessing the cookie
-        .same_site(SameSite::Lax) // send cookie with "same-site" requests, and with "cross-site" top-level navigations.
-        .build();
+    COOKIE_CONFIG.build(JWT_COOKIE_NAME, token.expose_secret().to_owned(), *TOKEN_TTL_SECONDS)
+}
 
-    cookie
+// Create cookie with a new refresh token, starting the refresh token family of the session
+#[tracing::instrument(name = "Generate refresh cookie", skip_all)]
+pub async fn generate_refresh_cookie(
+    email: &Email,
+    session_id: &str,
+    session_version: u64,
+    refresh_token_store: RefreshTokenStoreType,
+) -> Result<Cookie<'static>> {
+    let token = generate_refresh_token(email, session_id, session_version, refresh_token_store).await?;
+    Ok(create_refresh_cookie(token))
+}
+
+// Create and store a new refresh token, starting the refresh token family of the session
+#[tracing::instrument(name = "Generate refresh token", skip_all)]
+async fn generate_refresh_token(
+    email: &Email,
+    session_id: &str,
+    session_version: u64,
+    refresh_token_store: RefreshTokenStoreType,
+) -> Result<RefreshToken> {
+    let token = RefreshToken::default();
+
+    refresh_token_store
+        .write()
+        .await
+        .add_token(token.clone(), RefreshTokenEntry::new(email.clone(), session_id.to_owned(), session_version))
+        .await
+        .wrap_err("Failed to store refresh token")?;
+
+    Ok(token)
+}
+
+// Create cookie and set the value to the passed-in refresh token
+#[tracing::instrument(name = "Create refresh cookie", skip_all)]
+pub fn create_refresh_cookie(token: RefreshToken) -> Cookie<'static> {
+    COOKIE_CONFIG.build(
+        REFRESH_COOKIE_NAME,
+        token.as_ref().expose_secret().to_owned(),
+        *REFRESH_TOKEN_TTL_SECONDS,
+    )
+}
+
+// Attributes of the auth and refresh cookies, configured at startup
+#[derive(Debug, Clone, PartialEq)]
+pub struct CookieConfig {
+    pub path: String,
+    // Set to the parent domain to share the cookies with its subdomains
+    pub domain: Option<String>,
+    // Only send the cookies over HTTPS
+    pub secure: bool,
+    pub same_site: SameSite,
+}
+
+impl Default for CookieConfig {
+    fn default() -> Self {
+        Self {
+            path: "/".to_owned(), // apply cookie to all URLs on the server
+            domain: None,
+            secure: false,
+            same_site: SameSite::Lax, // send cookie with "same-site" requests, and with "cross-site" top-level navigations.
+        }
+    }
+}
+
+impl CookieConfig {
+    pub fn new(path: String, domain: Option<String>, secure: bool, same_site: SameSite) -> Result<Self> {
+        // Browsers reject SameSite=None cookies that aren't Secure
+        if same_site == SameSite::None && !secure {
+            return Err(eyre!("SameSite=None cookies must be Secure"));
+        }
+        Ok(Self { path, domain, secure, same_site })
+    }
+
+    // Create a cookie that expires along with the token it carries
+    pub fn build(&self, name: &'static str, value: String, max_age_seconds: i64) -> Cookie<'static> {
+        let mut cookie = Cookie::build((name, value))
+            .path(self.path.clone())
+            .http_only(true) // prevent JavaScript from accessing the cookie
+            .secure(self.secure)
+            .same_site(self.same_site)
+            .max_age(time::Duration::seconds(max_age_seconds))
+            .build();
+
+        if let Some(domain) = &self.domain {
+            cookie.set_domain(domain.clone());
+        }
+
+        cookie
+    }
+
+    // Create the cookie to pass to `CookieJar::remove`. Browsers only remove a cookie
+    // when the removal is sent with the same path and domain the cookie was set with.
+    pub fn removal(&self, name: &'static str) -> Cookie<'static> {
+        self.build(name, String::new(), 0)
+    }
 }
 
 #[derive(Debug)]
@@ -34,19 +234,20 @@ pub enum GenerateTokenError {
     UnexpectedError,
 }
 
-// This value determines how long the JWT auth token is valid for
-pub const TOKEN_TTL_SECONDS: i64 = 600; // 10 minutes
-
-// Create JWT auth token
+// Create JWT auth token for a session, telling when and how the user last authenticated
 #[tracing::instrument(name = "Generate auth token", skip_all)]
-fn generate_auth_token(email: &Email) -> Result<Secret<String>> {
-    let delta = chrono::Duration::try_seconds(TOKEN_TTL_SECONDS)
-        .wrap_err("Failed to create 10 minute time delta")?;
+pub fn generate_auth_token(
+    session: &Session,
+    roles: &[String],
+    session_version: u64,
+) -> Result<Secret<String>> {
+    let delta = chrono::Duration::try_seconds(*TOKEN_TTL_SECONDS)
+        .wrap_err("Failed to create token TTL time delta")?;
 
     // Create JWT expiration time
     let exp = Utc::now()
         .checked_add_signed(delta)
-        .ok_or(eyre!("Failed to add 10 minutes to current time"))?
+        .ok_or(eyre!("Failed to add token TTL to current time"))?
         .timestamp();
 
     // Cast exp to a usize, which is what Claims expects
@@ -54,19 +255,177 @@ fn generate_auth_token(email: &Email) -> Result<Secret<String>> {
         .try_into()
         .wrap_err(format!("Failed to cast exp time to usize. exp time: {}", exp))?;
 
-    let sub = email.as_ref().expose_secret().to_owned();
+    let iat: usize = Utc::now()
+        .timestamp()
+        .try_into()
+        .wrap_err("Failed to cast iat time to usize")?;
+
+    let sub = session.email.as_ref().expose_secret().to_owned();
+
+    let auth_time: usize = session
+        .auth_time
+        .try_into()
+        .wrap_err("Failed to cast auth_time to usize")?;
+
+    let claims = Claims {
+        sub,
+        exp,
+        iss: JWT_ISSUER.to_owned(),
+        aud: JWT_AUDIENCE.to_owned(),
+        iat,
+        nbf: iat,
+        jti: uuid::Uuid::new_v4().to_string(),
+        sid: session.id.clone(),
+        session_version,
+        scope: None,
+        azp: None,
+        roles: roles.to_vec(),
+        auth_time,
+        amr: session.amr_claim(),
+    };
+
+    create_token(&claims)
+}
+
+// Create the access token an OAuth client gets for a user with the authorization code grant.
+// It is bound to the client and limited to the granted scopes, so it can't stand in for the
+// user's own session tokens, and is meant for the auth service's UserInfo endpoint.
+#[tracing::instrument(name = "Generate client access token", skip_all)]
+pub fn generate_client_access_token(
+    session: &Session,
+    client_id: &str,
+    scope: &str,
+    session_version: u64,
+) -> Result<Secret<String>> {
+    let iat = Utc::now().timestamp();
+    let exp = iat + *TOKEN_TTL_SECONDS;
+
+    let iat: usize = iat.try_into().wrap_err("Failed to cast iat time to usize")?;
+
+    let claims = Claims {
+        sub: session.email.as_ref().expose_secret().to_owned(),
+        exp: exp.try_into().wrap_err("Failed to cast exp time to usize")?,
+        iss: JWT_ISSUER.to_owned(),
+        aud: JWT_ISSUER.to_owned(),
+        iat,
+        nbf: iat,
+        jti: uuid::Uuid::new_v4().to_string(),
+        sid: session.id.clone(),
+        session_version,
+        scope: Some(scope.to_owned()),
+        azp: Some(client_id.to_owned()),
+        roles: vec![],
+        auth_time: 0,
+        amr: vec![],
+    };
+
+    create_token(&claims)
+}
+
+// Create a token a service client gets for itself with the client credentials grant.
+// It isn't tied to a user session, so it can only be revoked by banning it.
+#[tracing::instrument(name = "Generate service token", skip_all)]
+pub fn generate_service_token(client_id: &str, scope: &str) -> Result<Secret<String>> {
+    let iat = Utc::now().timestamp();
+    let exp = iat + *TOKEN_TTL_SECONDS;
+
+    let iat: usize = iat.try_into().wrap_err("Failed to cast iat time to usize")?;
 
-    let claims = Claims { sub, exp };
+    let claims = ServiceClaims {
+        sub: client_id.to_owned(),
+        exp: exp.try_into().wrap_err("Failed to cast exp time to usize")?,
+        iss: JWT_ISSUER.to_owned(),
+        aud: JWT_AUDIENCE.to_owned(),
+        iat,
+        nbf: iat,
+        jti: uuid::Uuid::new_v4().to_string(),
+        client_id: client_id.to_owned(),
+        scope: scope.to_owned(),
+    };
 
     create_token(&claims)
 }
 
+// Tokens are either issued to a user for one of their sessions, or to a service client.
+// Users' API keys are accepted in their place.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ValidatedToken {
+    User(Claims),
+    Service(ServiceClaims),
+    #[serde(skip)]
+    ApiKey(ApiKeyEntry),
+}
+
+impl ValidatedToken {
+    // Only the user's own tokens can act on behalf of them. Tokens they granted an OAuth
+    // client are limited to the client's scopes.
+    pub fn into_user(self) -> Result<Claims> {
+        match self {
+            ValidatedToken::User(claims) if claims.azp.is_some() || claims.scope.is_some() => {
+                Err(eyre!("Token was issued to an OAuth client"))
+            }
+            ValidatedToken::User(claims) => Ok(claims),
+            ValidatedToken::Service(_) => Err(eyre!("Token was issued to a service, not a user")),
+            ValidatedToken::ApiKey(_) => Err(eyre!("API keys can't act on behalf of a user session")),
+        }
+    }
+
+    // Accepts the user's own tokens, and tokens of OAuth clients that were granted the scope
+    pub fn into_user_with_scope(self, scope: &str) -> Result<Claims> {
+        match self {
+            ValidatedToken::User(claims) if claims.azp.is_some() => match claims.has_scope(scope) {
+                true => Ok(claims),
+                false => Err(eyre!("Token was not granted the {} scope", scope)),
+            },
+            token => token.into_user(),
+        }
+    }
+
+    pub fn jti(&self) -> &str {
+        match self {
+            ValidatedToken::User(claims) => &claims.jti,
+            ValidatedToken::Service(claims) => &claims.jti,
+            ValidatedToken::ApiKey(entry) => &entry.id,
+        }
+    }
+}
+
 #[tracing::instrument(name = "Validate token", skip_all)]
 pub async fn validate_token(
     token: &Secret<String>,
     banned_token_store: BannedTokenStoreType,
-) -> Result<Claims> {
-    match banned_token_store.read().await.contains_token(token).await {
+    session_version_store: SessionVersionStoreType,
+    session_store: SessionStoreType,
+    api_key_store: ApiKeyStoreType,
+) -> Result<ValidatedToken> {
+    if ApiKey::is_api_key(token) {
+        return validate_api_key(token, api_key_store)
+            .await
+            .map(ValidatedToken::ApiKey);
+    }
+
+    // Pick the key named by the token's kid header, which may be one that was retired recently
+    let header = decode_header(token.expose_secret()).wrap_err("Failed to decode token header")?;
+    let key = JWT_KEY_SET.validation_key(header.kid.as_deref(), header.alg)?;
+
+    // Only accept tokens minted by this deployment for its own audience
+    let mut validation = Validation::new(key.algorithm());
+    validation.set_issuer(&[JWT_ISSUER.as_str()]);
+    // Tokens of OAuth clients are meant for the auth service itself
+    validation.set_audience(&[JWT_AUDIENCE.as_str(), JWT_ISSUER.as_str()]);
+    validation.set_required_spec_claims(&["exp", "nbf", "iss", "aud", "sub"]);
+    validation.validate_nbf = true;
+
+    let token = decode::<ValidatedToken>(
+        token.expose_secret(),
+        key.decoding_key(),
+        &validation,
+    )
+    .map(|data| data.claims)
+    .wrap_err("Failed to decode token")?;
+
+    match banned_token_store.read().await.contains_token(token.jti()).await {
         Ok(value) => {
             if value {
                 return Err(eyre!("Token is banned"));
@@ -75,31 +434,241 @@ pub async fn validate_token(
         Err(e) => return Err(e.into()),
     }
 
-    decode::<Claims>(
-        token.expose_secret(),
-        &DecodingKey::from_secret(JWT_SECRET.expose_secret().as_bytes()),
-        &Validation::default(),
-    )
-    .map(|data| data.claims)
-    .wrap_err("Failed to decode token")
+    let claims = match token {
+        ValidatedToken::User(claims) => claims,
+        ValidatedToken::Service(_) | ValidatedToken::ApiKey(_) => return Ok(token),
+    };
+
+    // Tokens issued before the user last logged out everywhere are no longer valid
+    let email = Email::parse(Secret::new(claims.sub.clone()))?;
+    let session_version = session_version_store
+        .read()
+        .await
+        .get_version(&email)
+        .await?;
+
+    if claims.session_version < session_version {
+        return Err(eyre!("Token session version is outdated"));
+    }
+
+    // Fails once the session has been revoked. Only refreshing the token records that the
+    // session is still used, so validating tokens doesn't have to wait for a write lock.
+    session_store
+        .read()
+        .await
+        .get_session(&claims.sid)
+        .await
+        .wrap_err("Token session is not active")?;
+
+    Ok(ValidatedToken::User(claims))
+}
+
+// API keys are looked up instead of decoded, and stay valid until they expire or are revoked
+#[tracing::instrument(name = "Validate API key", skip_all)]
+async fn validate_api_key(
+    token: &Secret<String>,
+    api_key_store: ApiKeyStoreType,
+) -> Result<ApiKeyEntry> {
+    let key = ApiKey::parse(token.clone())?;
+
+    let entry = api_key_store
+        .read()
+        .await
+        .get_key(&key)
+        .await
+        .wrap_err("API key is not active")?;
+
+    if entry.is_expired() {
+        return Err(eyre!("API key has expired"));
+    }
+
+    api_key_store
+        .write()
+        .await
+        .touch_key(&entry.id)
+        .await
+        .wrap_err("Failed to record API key use")?;
+
+    Ok(entry)
+}
+
+// Validates tokens against the service's own stores, for the routes guarded by `AuthLayer`
+#[derive(Clone)]
+pub struct StoreTokenValidator {
+    banned_token_store: BannedTokenStoreType,
+    session_version_store: SessionVersionStoreType,
+    session_store: SessionStoreType,
+    api_key_store: ApiKeyStoreType,
+    client_scope: Option<&'static str>,
+}
+
+impl StoreTokenValidator {
+    pub fn new(state: &AppState) -> Self {
+        Self {
+            banned_token_store: state.banned_token_store.clone(),
+            session_version_store: state.session_version_store.clone(),
+            session_store: state.session_store.clone(),
+            api_key_store: state.api_key_store.clone(),
+            client_scope: None,
+        }
+    }
+
+    // Also accept the tokens of OAuth clients that were granted the scope
+    pub fn accept_client_scope(mut self, scope: &'static str) -> Self {
+        self.client_scope = Some(scope);
+        self
+    }
 }
 
-// Create JWT auth token by encoding claims using the JWT secret
+#[async_trait::async_trait]
+impl TokenValidator for StoreTokenValidator {
+    async fn validate(&self, token: &Secret<String>) -> Result<AuthenticatedUser, AuthError> {
+        validate_token(
+            token,
+            self.banned_token_store.clone(),
+            self.session_version_store.clone(),
+            self.session_store.clone(),
+            self.api_key_store.clone(),
+        )
+        .await
+        .and_then(|token| match self.client_scope {
+            Some(scope) => token.into_user_with_scope(scope),
+            None => token.into_user(),
+        })
+        .map(AuthenticatedUser::from)
+        .map_err(|_| AuthError::InvalidToken)
+    }
+}
+
+impl From<Claims> for AuthenticatedUser {
+    fn from(claims: Claims) -> Self {
+        Self {
+            subject: claims.sub,
+            roles: claims.roles,
+            scopes: claims
+                .scope
+                .map(|scope| scope.split_whitespace().map(str::to_owned).collect())
+                .unwrap_or_default(),
+            token_id: Some(claims.jti),
+            session_id: Some(claims.sid),
+            expires_at: Some(claims.exp),
+            client_id: claims.azp,
+            auth_time: Some(claims.auth_time),
+            amr: claims.amr,
+        }
+    }
+}
+
+// Create an OpenID Connect ID token, telling the client who the user is
+#[tracing::instrument(name = "Generate ID token", skip_all)]
+pub fn generate_id_token(
+    email: &Email,
+    client_id: &str,
+    scope: &str,
+    nonce: Option<String>,
+    auth_time: i64,
+) -> Result<Secret<String>> {
+    let iat = Utc::now().timestamp();
+    let exp = iat + *TOKEN_TTL_SECONDS;
+
+    let sub = email.as_ref().expose_secret().to_owned();
+    let email = scope
+        .split_whitespace()
+        .any(|scope| scope == "email")
+        .then(|| sub.clone());
+
+    let claims = IdTokenClaims {
+        iss: JWT_ISSUER.to_owned(),
+        sub,
+        aud: client_id.to_owned(),
+        exp: exp.try_into().wrap_err("Failed to cast exp time to usize")?,
+        iat: iat.try_into().wrap_err("Failed to cast iat time to usize")?,
+        auth_time: auth_time.try_into().wrap_err("Failed to cast auth_time to usize")?,
+        nonce,
+        email,
+    };
+
+    create_token(&claims)
+}
+
+// Create a JWT by encoding claims using the current signing key
 #[tracing::instrument(name = "Create token", skip_all)]
-fn create_token(claims: &Claims) -> Result<Secret<String>> {
-    encode(
-        &jsonwebtoken::Header::default(),
-        &claims,
-        &EncodingKey::from_secret(JWT_SECRET.expose_secret().as_bytes()),
-    )
-    .map(Secret::new)
-    .wrap_err("Failed to create token")
+fn create_token(claims: &impl Serialize) -> Result<Secret<String>> {
+    let key = JWT_KEY_SET.signing_key()?;
+
+    let mut header = Header::new(key.algorithm());
+    header.kid = key.kid().map(str::to_owned);
+
+    encode(&header, claims, key.encoding_key())
+        .map(Secret::new)
+        .wrap_err("Failed to create token")
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,
     pub exp: usize,
+    pub iss: String,
+    pub aud: String,
+    pub iat: usize,
+    pub nbf: usize,
+    pub jti: String,
+    pub sid: String,
+    pub session_version: u64,
+    // Space-delimited list of the scopes the token was granted, if it is restricted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    // The OAuth client the user granted the token to, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub azp: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roles: Vec<String>,
+    // When and how the user last proved who they are. Tokens issued before these were added
+    // count as authenticated long ago.
+    #[serde(default)]
+    pub auth_time: usize,
+    #[serde(default)]
+    pub amr: Vec<String>,
+}
+
+impl Claims {
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scope
+            .as_deref()
+            .is_some_and(|scopes| scopes.split_whitespace().any(|granted| granted == scope))
+    }
+}
+
+// Claims of a token issued to a service client. The `client_id` claim (RFC 9068) tells
+// them apart from user tokens.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ServiceClaims {
+    pub sub: String,
+    pub exp: usize,
+    pub iss: String,
+    pub aud: String,
+    pub iat: usize,
+    pub nbf: usize,
+    pub jti: String,
+    pub client_id: String,
+    // Space-delimited list of the scopes the client was granted
+    pub scope: String,
+}
+
+// Claims of an ID token. Unlike auth tokens, ID tokens are meant for the client itself,
+// which is their audience.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub sub: String,
+    pub aud: String,
+    pub exp: usize,
+    pub iat: usize,
+    pub auth_time: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
 }
 
 #[cfg(test)]
@@ -107,13 +676,69 @@ mod tests {
     
     use std::sync::Arc;
     use tokio::sync::RwLock;
-    use crate::services::data_stores::hashset_banned_token_store::HashsetBannedTokenStore;
+    use crate::domain::data_stores::RefreshTokenStore;
+    use crate::services::data_stores::{
+        hashmap_api_key_store::HashmapApiKeyStore,
+        hashmap_refresh_token_store::HashmapRefreshTokenStore,
+        hashmap_session_store::HashmapSessionStore,
+        hashmap_session_version_store::HashmapSessionVersionStore,
+        hashset_banned_token_store::HashsetBannedTokenStore,
+    };
+    use crate::domain::AuthMethod;
+    use crate::utils::constants::CSRF_COOKIE_NAME;
     use super::*;
 
+    struct Stores {
+        banned_token_store: BannedTokenStoreType,
+        session_version_store: SessionVersionStoreType,
+        session_store: SessionStoreType,
+        api_key_store: ApiKeyStoreType,
+    }
+
+    impl Stores {
+        fn new() -> Self {
+            Self {
+                banned_token_store: Arc::new(RwLock::new(HashsetBannedTokenStore::default())),
+                session_version_store: Arc::new(RwLock::new(HashmapSessionVersionStore::default())),
+                session_store: Arc::new(RwLock::new(HashmapSessionStore::default())),
+                api_key_store: Arc::new(RwLock::new(HashmapApiKeyStore::default())),
+            }
+        }
+
+        // Register a session for the user
+        async fn session(&self, email: &Email) -> Session {
+            let session = new_session(email);
+            self.session_store.write().await.add_session(session.clone()).await.unwrap();
+            session
+        }
+
+        async fn validate(&self, token: &Secret<String>) -> Result<Claims> {
+            self.validate_any(token).await?.into_user()
+        }
+
+        async fn validate_any(&self, token: &Secret<String>) -> Result<ValidatedToken> {
+            validate_token(
+                token,
+                self.banned_token_store.clone(),
+                self.session_version_store.clone(),
+                self.session_store.clone(),
+                self.api_key_store.clone(),
+            )
+            .await
+        }
+    }
+
+    fn email() -> Email {
+        Email::parse(Secret::new("test@example.com".to_owned())).unwrap()
+    }
+
+    fn new_session(email: &Email) -> Session {
+        Session::new(email.clone(), "127.0.0.1".to_owned(), None, vec![AuthMethod::Password])
+    }
+
     #[tokio::test]
     async fn test_generate_auth_cookie() {
-        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
-        let cookie = generate_auth_cookie(&email).unwrap();
+        let cookie = generate_auth_cookie(&new_session(&email()), &[], 0).unwrap();
         assert_eq!(cookie.name(), JWT_COOKIE_NAME);
         assert_eq!(cookie.value().split('.').count(), 3);
         assert_eq!(cookie.path(), Some("/"));
@@ -130,22 +755,95 @@ mod tests {
         assert_eq!(cookie.path(), Some("/"));
         assert_eq!(cookie.http_only(), Some(true));
         assert_eq!(cookie.same_site(), Some(SameSite::Lax));
+        assert_eq!(cookie.max_age(), Some(time::Duration::seconds(*TOKEN_TTL_SECONDS)));
+    }
+
+    #[test]
+    fn test_cookie_config_build() {
+        let config = CookieConfig::new(
+            "/auth".to_owned(),
+            Some("example.com".to_owned()),
+            true,
+            SameSite::Strict,
+        )
+        .unwrap();
+
+        let cookie = config.build(JWT_COOKIE_NAME, "token".to_owned(), 60);
+        assert_eq!(cookie.value(), "token");
+        assert_eq!(cookie.path(), Some("/auth"));
+        assert_eq!(cookie.domain(), Some("example.com"));
+        assert_eq!(cookie.secure(), Some(true));
+        assert_eq!(cookie.http_only(), Some(true));
+        assert_eq!(cookie.same_site(), Some(SameSite::Strict));
+        assert_eq!(cookie.max_age(), Some(time::Duration::seconds(60)));
+
+        let removal = config.removal(JWT_COOKIE_NAME);
+        assert_eq!(removal.value(), "");
+        assert_eq!(removal.path(), Some("/auth"));
+        assert_eq!(removal.domain(), Some("example.com"));
+        assert_eq!(removal.max_age(), Some(time::Duration::ZERO));
+    }
+
+    #[test]
+    fn test_cookie_config_rejects_insecure_same_site_none() {
+        let result = CookieConfig::new("/".to_owned(), None, false, SameSite::None);
+        assert!(result.is_err());
+
+        let result = CookieConfig::new("/".to_owned(), None, true, SameSite::None);
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_generate_refresh_cookie() {
+        let email = email();
+        let refresh_token_store = Arc::new(RwLock::new(HashmapRefreshTokenStore::default()));
+        let cookie = generate_refresh_cookie(&email, "session", 0, refresh_token_store.clone())
+            .await
+            .unwrap();
+        assert_eq!(cookie.name(), REFRESH_COOKIE_NAME);
+        assert_eq!(cookie.http_only(), Some(true));
+
+        let token = RefreshToken::parse(Secret::new(cookie.value().to_owned())).unwrap();
+        let entry = refresh_token_store.read().await.get_token(&token).await.unwrap();
+        assert_eq!(entry.email, email);
+        assert_eq!(entry.family_id, "session");
+        assert!(!entry.used);
     }
 
     #[tokio::test]
     async fn test_generate_auth_token() {
-        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
-        let result = generate_auth_token(&email).unwrap();
+        let result = generate_auth_token(&new_session(&email()), &[], 0).unwrap();
         assert_eq!(result.expose_secret().split('.').count(), 3);
     }
 
+    #[tokio::test]
+    async fn test_validate_client_access_token() {
+        let stores = Stores::new();
+        let session = stores.session(&email()).await;
+        let token = generate_client_access_token(&session, "client", "openid email", 0).unwrap();
+
+        let claims = stores.validate_any(&token).await.unwrap().into_user_with_scope("openid").unwrap();
+        assert_eq!(claims.azp.as_deref(), Some("client"));
+        assert!(claims.roles.is_empty());
+        assert_eq!(claims.auth_time, 0);
+
+        // Not accepted where the user's own tokens are expected, or without the scope
+        assert!(stores.validate(&token).await.is_err());
+        assert!(stores.validate_any(&token).await.unwrap().into_user_with_scope("profile").is_err());
+    }
+
     #[tokio::test]
     async fn test_validate_token_with_valid_token() {
-        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
-        let token = generate_auth_token(&email).unwrap();
-        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
-        let result = validate_token(&token, banned_token_store).await.unwrap();
+        let stores = Stores::new();
+        let session = stores.session(&email()).await;
+        let token = generate_auth_token(&session, &[], 0).unwrap();
+        let result = stores.validate(&token).await.unwrap();
         assert_eq!(result.sub, "test@example.com");
+        assert_eq!(result.sid, session.id);
+        assert_eq!(result.scope, None);
+        assert!(result.roles.is_empty());
+        assert_eq!(result.auth_time, session.auth_time as usize);
+        assert_eq!(result.amr, vec!["pwd"]);
 
         let exp = Utc::now()
             .checked_add_signed(chrono::Duration::try_minutes(9).expect("valid duration"))
@@ -155,11 +853,243 @@ mod tests {
         assert!(result.exp > exp as usize);
     }
 
+    #[tokio::test]
+    async fn test_validate_token_with_roles() {
+        let stores = Stores::new();
+        let session = stores.session(&email()).await;
+        let roles = vec!["admin".to_owned()];
+        let token = generate_auth_token(&session, &roles, 0).unwrap();
+        let result = stores.validate(&token).await.unwrap();
+        assert_eq!(result.roles, roles);
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_sets_registered_claims() {
+        let stores = Stores::new();
+        let session = stores.session(&email()).await;
+        let first = stores
+            .validate(&generate_auth_token(&session, &[], 0).unwrap())
+            .await
+            .unwrap();
+        let second = stores
+            .validate(&generate_auth_token(&session, &[], 0).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(first.iss, *JWT_ISSUER);
+        assert_eq!(first.aud, *JWT_AUDIENCE);
+        assert!(first.iat <= first.nbf && first.nbf < first.exp);
+        assert_ne!(first.jti, second.jti);
+    }
+
+    fn claims(email: &str, session_id: &str) -> Claims {
+        let now = Utc::now().timestamp() as usize;
+        Claims {
+            sub: email.to_owned(),
+            exp: now + *TOKEN_TTL_SECONDS as usize,
+            iss: JWT_ISSUER.to_owned(),
+            aud: JWT_AUDIENCE.to_owned(),
+            iat: now,
+            nbf: now,
+            jti: uuid::Uuid::new_v4().to_string(),
+            sid: session_id.to_owned(),
+            session_version: 0,
+            scope: None,
+            azp: None,
+            roles: vec![],
+            auth_time: now,
+            amr: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_with_wrong_audience() {
+        let stores = Stores::new();
+        let session = stores.session(&email()).await;
+        let claims = Claims {
+            aud: "another-app".to_owned(),
+            ..claims("test@example.com", &session.id)
+        };
+        let token = create_token(&claims).unwrap();
+        let result = stores.validate(&token).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_with_wrong_issuer() {
+        let stores = Stores::new();
+        let session = stores.session(&email()).await;
+        let claims = Claims {
+            iss: "another-issuer".to_owned(),
+            ..claims("test@example.com", &session.id)
+        };
+        let token = create_token(&claims).unwrap();
+        let result = stores.validate(&token).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_used_before_nbf() {
+        let stores = Stores::new();
+        let session = stores.session(&email()).await;
+        let claims = claims("test@example.com", &session.id);
+        let claims = Claims {
+            nbf: claims.iat + 300,
+            ..claims
+        };
+        let token = create_token(&claims).unwrap();
+        let result = stores.validate(&token).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_with_banned_token() {
+        let stores = Stores::new();
+        let session = stores.session(&email()).await;
+        let token = generate_auth_token(&session, &[], 0).unwrap();
+        let claims = stores.validate(&token).await.unwrap();
+
+        stores
+            .banned_token_store
+            .write()
+            .await
+            .add_token(claims.jti, claims.exp)
+            .await
+            .unwrap();
+
+        let result = stores.validate(&token).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_tells_service_tokens_apart() {
+        let stores = Stores::new();
+        let token = generate_service_token("client", "reports:read").unwrap();
+
+        match stores.validate_any(&token).await.unwrap() {
+            ValidatedToken::Service(claims) => {
+                assert_eq!(claims.sub, "client");
+                assert_eq!(claims.client_id, "client");
+                assert_eq!(claims.scope, "reports:read");
+            }
+            _ => panic!("Service token was not validated as a service token"),
+        }
+
+        // Service tokens can't act on behalf of a user
+        assert!(stores.validate(&token).await.is_err());
+
+        let session = stores.session(&email()).await;
+        let token = generate_auth_token(&session, &[], 0).unwrap();
+        assert!(matches!(
+            stores.validate_any(&token).await.unwrap(),
+            ValidatedToken::User(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_accepts_api_keys() {
+        let stores = Stores::new();
+        let key = ApiKey::default();
+        let entry = ApiKeyEntry::new(email(), "ci".to_owned(), vec!["read".to_owned()], None).unwrap();
+        stores.api_key_store.write().await.add_key(&key, entry.clone()).await.unwrap();
+
+        match stores.validate_any(key.as_ref()).await.unwrap() {
+            ValidatedToken::ApiKey(validated) => {
+                assert_eq!(validated.id, entry.id);
+                assert_eq!(validated.scopes, vec!["read".to_owned()]);
+            }
+            _ => panic!("API key was not validated as an API key"),
+        }
+
+        // Using the key is recorded
+        let stored = stores.api_key_store.read().await.get_key(&key).await.unwrap();
+        assert!(stored.last_used_at.is_some());
+
+        // API keys can't act on behalf of a user session
+        assert!(stores.validate(key.as_ref()).await.is_err());
+
+        assert!(stores.validate_any(ApiKey::default().as_ref()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_rejects_expired_api_keys() {
+        let stores = Stores::new();
+        let key = ApiKey::default();
+        let mut entry = ApiKeyEntry::new(email(), "ci".to_owned(), vec![], Some(60)).unwrap();
+        entry.expires_at = Some(Utc::now().timestamp() - 1);
+        stores.api_key_store.write().await.add_key(&key, entry).await.unwrap();
+
+        assert!(stores.validate_any(key.as_ref()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_with_outdated_session_version() {
+        let stores = Stores::new();
+        let session = stores.session(&email()).await;
+        let token = generate_auth_token(&session, &[], 0).unwrap();
+
+        let version = stores
+            .session_version_store
+            .write()
+            .await
+            .increment_version(&email())
+            .await
+            .unwrap();
+
+        let result = stores.validate(&token).await;
+        assert!(result.is_err());
+
+        let token = generate_auth_token(&session, &[], version).unwrap();
+        let result = stores.validate(&token).await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_with_revoked_session() {
+        let stores = Stores::new();
+        let session = stores.session(&email()).await;
+        let token = generate_auth_token(&session, &[], 0).unwrap();
+
+        assert!(stores.validate(&token).await.is_ok());
+
+        stores
+            .session_store
+            .write()
+            .await
+            .remove_session(&email(), &session.id)
+            .await
+            .unwrap();
+
+        let result = stores.validate(&token).await;
+        assert!(result.is_err());
+    }
+
     #[tokio::test]
     async fn test_validate_token_with_invalid_token() {
         let token = Secret::new("invalid_token".to_owned());
-        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
-        let result = validate_token(&token, banned_token_store).await;
+        let result = Stores::new().validate(&token).await;
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_deliver_session_tokens() {
+        let tokens = || SessionTokens {
+            auth_token: Secret::new("auth_token".to_owned()),
+            refresh_token: RefreshToken::default(),
+        };
+
+        let (jar, body) = tokens().deliver(TokenDelivery::Cookie, CookieJar::new());
+        assert!(body.is_none());
+        assert_eq!(jar.get(JWT_COOKIE_NAME).unwrap().value(), "auth_token");
+        assert!(jar.get(REFRESH_COOKIE_NAME).is_some());
+        assert!(jar.get(CSRF_COOKIE_NAME).is_some());
+
+        let (jar, body) = tokens().deliver(TokenDelivery::Body, CookieJar::new());
+        let body = body.unwrap();
+        assert!(jar.get(JWT_COOKIE_NAME).is_none());
+        assert!(jar.get(CSRF_COOKIE_NAME).is_none());
+        assert_eq!(body.access_token, "auth_token");
+        assert_eq!(body.token_type, "Bearer");
+        assert_eq!(body.expires_in, *TOKEN_TTL_SECONDS);
+    }
 }
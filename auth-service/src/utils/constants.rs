@@ -1,16 +1,84 @@
 use::dotenvy::dotenv;
+use base64::{engine::general_purpose::STANDARD, Engine};
 use lazy_static::lazy_static;
 use secrecy::Secret;
 use std::env as std_env;
 
+use axum_extra::extract::cookie::SameSite;
+
+use crate::domain::{ClientCredentials, OidcProviderConfig, RelyingParty, TotpCipher};
+use crate::services::http_sms_client::SmsProviderConfig;
+use super::{
+    auth::CookieConfig,
+    jwt_keys::{JwtKey, JwtKeySet},
+};
+
 pub const JWT_COOKIE_NAME: &str = "jwt";
+pub const REFRESH_COOKIE_NAME: &str = "refresh_token";
+pub const CSRF_COOKIE_NAME: &str = "csrf_token";
+pub const CSRF_HEADER_NAME: &str = "x-csrf-token";
+pub const OIDC_STATE_COOKIE_NAME: &str = "oidc_state";
+pub const MAGIC_LINK_COOKIE_NAME: &str = "magic_link_binding";
+pub const TRUSTED_DEVICE_COOKIE_NAME: &str = "trusted_device";
 pub const DEFAULT_REDIS_HOSTNAME: &str = "127.0.0.1";
+pub const DEFAULT_JWT_ISSUER: &str = "auth-service";
+pub const DEFAULT_JWT_AUDIENCE: &str = "app-service";
+// Where clients reach the service, used to advertise the OpenID Connect endpoints
+pub const DEFAULT_PUBLIC_URL: &str = "http://localhost:3000";
+// Shown to users when they create a passkey
+pub const WEBAUTHN_RP_NAME: &str = "Auth Service";
+// Shown next to the account in authenticator apps
+pub const TOTP_ISSUER: &str = "Auth Service";
+// How long a JWT auth token is valid for
+pub const DEFAULT_TOKEN_TTL_SECONDS: i64 = 600; // 10 minutes
+// How long a refresh token can be traded for a new JWT auth token
+pub const DEFAULT_REFRESH_TOKEN_TTL_SECONDS: i64 = 1_209_600; // 14 days
+// How long a login link sent by email can be used
+pub const DEFAULT_MAGIC_LINK_TTL_SECONDS: i64 = 900; // 15 minutes
+// How many wrong codes a login attempt may get before the user has to log in again
+pub const DEFAULT_TWO_FA_MAX_ATTEMPTS: u32 = 5;
+// How often and how many times a login attempt can get its 2FA code sent again
+pub const DEFAULT_TWO_FA_RESEND_COOLDOWN_SECONDS: i64 = 30;
+pub const DEFAULT_TWO_FA_MAX_RESENDS: u32 = 3;
+// How many logins of a user can wait for their 2FA code at once, from different devices
+pub const DEFAULT_TWO_FA_MAX_PENDING_ATTEMPTS: u32 = 5;
+// How long a browser the user trusted skips 2FA
+pub const DEFAULT_TRUSTED_DEVICE_TTL_SECONDS: i64 = 2_592_000; // 30 days
+// How recently users must have authenticated to use sensitive routes, such as changing
+// their password
+pub const DEFAULT_REAUTHENTICATION_MAX_AGE_SECONDS: i64 = 300; // 5 minutes
+// How many wrong passwords and codes a session can give to reauthenticate before it's
+// logged out
+pub const DEFAULT_REAUTHENTICATION_MAX_ATTEMPTS: u32 = 5;
 
 lazy_static! {
     pub static ref JWT_SECRET: Secret<String> = set_token();
+    pub static ref JWT_KEY_SET: JwtKeySet = set_jwt_key_set();
+    pub static ref JWT_ISSUER: String = set_jwt_issuer();
+    pub static ref JWT_AUDIENCE: String = set_jwt_audience();
+    pub static ref PUBLIC_URL: String = set_public_url();
+    pub static ref TOKEN_TTL_SECONDS: i64 = set_ttl(env::TOKEN_TTL_SECONDS_ENV_VAR, DEFAULT_TOKEN_TTL_SECONDS);
+    pub static ref REFRESH_TOKEN_TTL_SECONDS: i64 = set_ttl(env::REFRESH_TOKEN_TTL_SECONDS_ENV_VAR, DEFAULT_REFRESH_TOKEN_TTL_SECONDS);
+    pub static ref COOKIE_CONFIG: CookieConfig = set_cookie_config();
     pub static ref DATABASE_URL: Secret<String> = set_database_url();
     pub static ref REDIS_HOST_NAME: String = set_redis_host();
     pub static ref POSTMARK_AUTH_TOKEN: Secret<String> = set_postmark_auth_token();
+    pub static ref INTROSPECTION_CLIENT: Option<ClientCredentials> = set_introspection_client();
+    pub static ref CSRF_EXEMPT_BEARER: bool = set_csrf_exempt_bearer();
+    pub static ref OIDC_PROVIDERS: Vec<OidcProviderConfig> = set_oidc_providers();
+    pub static ref RELYING_PARTY: RelyingParty = set_relying_party();
+    pub static ref TOTP_CIPHER: TotpCipher = set_totp_cipher();
+    pub static ref SMS_PROVIDER: Option<SmsProviderConfig> = set_sms_provider();
+    pub static ref MAGIC_LINK_TTL_SECONDS: i64 = set_ttl(env::MAGIC_LINK_TTL_SECONDS_ENV_VAR, DEFAULT_MAGIC_LINK_TTL_SECONDS);
+    pub static ref MAGIC_LINK_KEY: Secret<Vec<u8>> = set_signing_key(env::MAGIC_LINK_KEY_ENV_VAR);
+    pub static ref TRUSTED_DEVICE_TTL_SECONDS: i64 = set_ttl(env::TRUSTED_DEVICE_TTL_SECONDS_ENV_VAR, DEFAULT_TRUSTED_DEVICE_TTL_SECONDS);
+    pub static ref TWO_FA_MAX_ATTEMPTS: u32 = set_limit(env::TWO_FA_MAX_ATTEMPTS_ENV_VAR, DEFAULT_TWO_FA_MAX_ATTEMPTS);
+    pub static ref TWO_FA_RESEND_COOLDOWN_SECONDS: i64 = set_ttl(env::TWO_FA_RESEND_COOLDOWN_SECONDS_ENV_VAR, DEFAULT_TWO_FA_RESEND_COOLDOWN_SECONDS);
+    pub static ref TWO_FA_MAX_RESENDS: u32 = set_limit(env::TWO_FA_MAX_RESENDS_ENV_VAR, DEFAULT_TWO_FA_MAX_RESENDS);
+    pub static ref TWO_FA_MAX_PENDING_ATTEMPTS: u32 = set_limit(env::TWO_FA_MAX_PENDING_ATTEMPTS_ENV_VAR, DEFAULT_TWO_FA_MAX_PENDING_ATTEMPTS);
+    pub static ref TRUSTED_DEVICE_KEY: Secret<Vec<u8>> = set_signing_key(env::TRUSTED_DEVICE_KEY_ENV_VAR);
+    pub static ref REAUTHENTICATION_MAX_AGE_SECONDS: i64 = set_ttl(env::REAUTHENTICATION_MAX_AGE_SECONDS_ENV_VAR, DEFAULT_REAUTHENTICATION_MAX_AGE_SECONDS);
+    pub static ref REAUTHENTICATION_MAX_ATTEMPTS: u32 = set_limit(env::REAUTHENTICATION_MAX_ATTEMPTS_ENV_VAR, DEFAULT_REAUTHENTICATION_MAX_ATTEMPTS);
 }
 
 fn set_token() -> Secret<String> {
@@ -22,6 +90,89 @@ fn set_token() -> Secret<String> {
     Secret::new(secret)
 }
 
+// Asymmetric keys are loaded from a manifest when JWT_KEYS_FILE is set,
+// otherwise tokens are signed with HS256 using JWT_SECRET.
+fn set_jwt_key_set() -> JwtKeySet {
+    dotenv().ok();
+    match std_env::var(env::JWT_KEYS_FILE_ENV_VAR) {
+        Ok(path) if !path.is_empty() => {
+            JwtKeySet::from_manifest(&path).expect("Failed to load JWT keys from JWT_KEYS_FILE.")
+        }
+        _ => JwtKeySet::new(vec![JwtKey::hmac(&JWT_SECRET)]).expect("Failed to create JWT key set."),
+    }
+}
+
+fn set_jwt_issuer() -> String {
+    dotenv().ok();
+    std_env::var(env::JWT_ISSUER_ENV_VAR).unwrap_or(DEFAULT_JWT_ISSUER.to_owned())
+}
+
+fn set_jwt_audience() -> String {
+    dotenv().ok();
+    std_env::var(env::JWT_AUDIENCE_ENV_VAR).unwrap_or(DEFAULT_JWT_AUDIENCE.to_owned())
+}
+
+fn set_public_url() -> String {
+    dotenv().ok();
+    std_env::var(env::PUBLIC_URL_ENV_VAR)
+        .ok()
+        .filter(|url| !url.is_empty())
+        .unwrap_or(DEFAULT_PUBLIC_URL.to_owned())
+        .trim_end_matches('/')
+        .to_owned()
+}
+
+fn set_ttl(name: &str, default: i64) -> i64 {
+    dotenv().ok();
+    match std_env::var(name) {
+        Ok(value) if !value.is_empty() => match value.parse() {
+            Ok(seconds) if seconds > 0 => seconds,
+            _ => panic!("{} must be a positive number of seconds.", name),
+        },
+        _ => default,
+    }
+}
+
+fn set_limit(name: &str, default: u32) -> u32 {
+    dotenv().ok();
+    match std_env::var(name) {
+        Ok(value) if !value.is_empty() => match value.parse() {
+            Ok(limit) if limit > 0 => limit,
+            _ => panic!("{} must be a positive number.", name),
+        },
+        _ => default,
+    }
+}
+
+// Defaults suit local development. Production sets COOKIE_SECURE and COOKIE_DOMAIN.
+fn set_cookie_config() -> CookieConfig {
+    dotenv().ok();
+    let default = CookieConfig::default();
+    let var = |name: &str| std_env::var(name).ok().filter(|value| !value.is_empty());
+
+    let path = var(env::COOKIE_PATH_ENV_VAR).unwrap_or(default.path);
+    let domain = var(env::COOKIE_DOMAIN_ENV_VAR);
+
+    let secure = match var(env::COOKIE_SECURE_ENV_VAR) {
+        Some(value) => value
+            .parse()
+            .expect("COOKIE_SECURE must be either true or false."),
+        None => default.secure,
+    };
+
+    let same_site = match var(env::COOKIE_SAME_SITE_ENV_VAR).map(|value| value.to_lowercase()) {
+        Some(value) => match value.as_str() {
+            "strict" => SameSite::Strict,
+            "lax" => SameSite::Lax,
+            "none" => SameSite::None,
+            _ => panic!("COOKIE_SAME_SITE must be one of strict, lax or none."),
+        },
+        None => default.same_site,
+    };
+
+    CookieConfig::new(path, domain, secure, same_site).expect("Invalid cookie configuration.")
+}
+
 fn set_database_url() -> Secret<String> {
     dotenv().ok(); // Load environment variables
     let secret = std_env::var(env::DATABASE_URL_ENV_VAR).expect("DATABASE_URL must be set.");
@@ -43,11 +194,136 @@ fn set_postmark_auth_token() -> Secret<String> {
     )
 }
 
+// Without a provider, texts are only logged, which is enough for local development
+fn set_sms_provider() -> Option<SmsProviderConfig> {
+    dotenv().ok();
+    let base_url = std_env::var(env::SMS_API_URL_ENV_VAR).ok().filter(|v| !v.is_empty())?;
+    let authorization_token = std_env::var(env::SMS_API_TOKEN_ENV_VAR).expect("SMS_API_TOKEN must be set with SMS_API_URL.");
+    let sender = std_env::var(env::SMS_SENDER_ENV_VAR).expect("SMS_SENDER must be set with SMS_API_URL.");
+    Some(SmsProviderConfig { base_url, sender, authorization_token: Secret::new(authorization_token) })
+}
+
+// Token verification is open to any client unless both variables are set
+fn set_introspection_client() -> Option<ClientCredentials> {
+    dotenv().ok();
+    let client_id = std_env::var(env::INTROSPECTION_CLIENT_ID_ENV_VAR).ok().filter(|v| !v.is_empty());
+    let client_secret = std_env::var(env::INTROSPECTION_CLIENT_SECRET_ENV_VAR).ok().filter(|v| !v.is_empty());
+    match (client_id, client_secret) {
+        (Some(client_id), Some(client_secret)) => {
+            Some(ClientCredentials::new(client_id, Secret::new(client_secret)))
+        }
+        (None, None) => None,
+        _ => panic!("INTROSPECTION_CLIENT_ID and INTROSPECTION_CLIENT_SECRET must be set together."),
+    }
+}
+
+// Requests authenticated with a bearer token can't be forged by another site, so they skip
+// CSRF checks unless CSRF_EXEMPT_BEARER is set to false
+fn set_csrf_exempt_bearer() -> bool {
+    dotenv().ok();
+    match std_env::var(env::CSRF_EXEMPT_BEARER_ENV_VAR) {
+        Ok(value) if !value.is_empty() => value
+            .parse()
+            .expect("CSRF_EXEMPT_BEARER must be either true or false."),
+        _ => true,
+    }
+}
+
+// Users can only log in with external providers listed in OIDC_PROVIDERS_FILE
+fn set_oidc_providers() -> Vec<OidcProviderConfig> {
+    dotenv().ok();
+    match std_env::var(env::OIDC_PROVIDERS_FILE_ENV_VAR) {
+        Ok(path) if !path.is_empty() => OidcProviderConfig::from_manifest(&path)
+            .expect("Failed to load OIDC providers from OIDC_PROVIDERS_FILE."),
+        _ => Vec::new(),
+    }
+}
+
+// Passkeys are bound to the host of PUBLIC_URL unless WEBAUTHN_RP_ID and WEBAUTHN_ORIGIN are set
+fn set_relying_party() -> RelyingParty {
+    dotenv().ok();
+    let var = |name: &str| std_env::var(name).ok().filter(|value| !value.is_empty());
+
+    let origin = var(env::WEBAUTHN_ORIGIN_ENV_VAR).unwrap_or(PUBLIC_URL.to_owned());
+    let id = var(env::WEBAUTHN_RP_ID_ENV_VAR).unwrap_or_else(|| {
+        url::Url::parse(&origin)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_owned))
+            .expect("WEBAUTHN_RP_ID must be set when the origin has no host.")
+    });
+
+    RelyingParty {
+        id,
+        name: WEBAUTHN_RP_NAME.to_owned(),
+        origin: origin.trim_end_matches('/').to_owned(),
+    }
+}
+
+// TOTP secrets are encrypted with TOTP_ENCRYPTION_KEY, a base64 encoded 32 byte key. Without
+// one, a key is derived from JWT_SECRET so that development setups work out of the box.
+// Secrets of authenticator apps are encrypted with a dedicated key, so a leaked JWT_SECRET
+// doesn't also reveal them
+fn set_totp_cipher() -> TotpCipher {
+    let key = decode_key(env::TOTP_ENCRYPTION_KEY_ENV_VAR);
+    TotpCipher::new(&key).expect("TOTP_ENCRYPTION_KEY must be 32 bytes.")
+}
+
+// Keys that sign login links and trusted device cookies. Each use has its own key, for the
+// same reason.
+fn set_signing_key(var: &str) -> Secret<Vec<u8>> {
+    let key = decode_key(var);
+    if key.len() < 32 {
+        panic!("{} must be at least 32 bytes.", var);
+    }
+    Secret::new(key)
+}
+
+fn decode_key(var: &str) -> Vec<u8> {
+    dotenv().ok();
+    let key = std_env::var(var).unwrap_or_else(|_| panic!("{} must be set.", var));
+    if key.is_empty() {
+        panic!("{} must not be empty.", var);
+    }
+    STANDARD
+        .decode(key)
+        .unwrap_or_else(|_| panic!("{} must be base64 encoded.", var))
+}
+
 pub mod env {
+    pub const COOKIE_DOMAIN_ENV_VAR: &str = "COOKIE_DOMAIN";
+    pub const COOKIE_PATH_ENV_VAR: &str = "COOKIE_PATH";
+    pub const COOKIE_SAME_SITE_ENV_VAR: &str = "COOKIE_SAME_SITE";
+    pub const COOKIE_SECURE_ENV_VAR: &str = "COOKIE_SECURE";
+    pub const CSRF_EXEMPT_BEARER_ENV_VAR: &str = "CSRF_EXEMPT_BEARER";
     pub const DATABASE_URL_ENV_VAR: &str = "DATABASE_URL";
+    pub const INTROSPECTION_CLIENT_ID_ENV_VAR: &str = "INTROSPECTION_CLIENT_ID";
+    pub const INTROSPECTION_CLIENT_SECRET_ENV_VAR: &str = "INTROSPECTION_CLIENT_SECRET";
+    pub const JWT_AUDIENCE_ENV_VAR: &str = "JWT_AUDIENCE";
+    pub const JWT_ISSUER_ENV_VAR: &str = "JWT_ISSUER";
+    pub const JWT_KEYS_FILE_ENV_VAR: &str = "JWT_KEYS_FILE";
     pub const JWT_SECRET_ENV_VAR: &str = "JWT_SECRET";
+    pub const MAGIC_LINK_KEY_ENV_VAR: &str = "MAGIC_LINK_KEY";
+    pub const MAGIC_LINK_TTL_SECONDS_ENV_VAR: &str = "MAGIC_LINK_TTL_SECONDS";
+    pub const OIDC_PROVIDERS_FILE_ENV_VAR: &str = "OIDC_PROVIDERS_FILE";
     pub const POSTMARK_AUTH_TOKEN_ENV_VAR: &str = "POSTMARK_AUTH_TOKEN";
+    pub const PUBLIC_URL_ENV_VAR: &str = "PUBLIC_URL";
+    pub const REAUTHENTICATION_MAX_AGE_SECONDS_ENV_VAR: &str = "REAUTHENTICATION_MAX_AGE_SECONDS";
+    pub const REAUTHENTICATION_MAX_ATTEMPTS_ENV_VAR: &str = "REAUTHENTICATION_MAX_ATTEMPTS";
     pub const REDIS_HOST_NAME_ENV_VAR: &str = "REDIS_HOST_NAME";
+    pub const REFRESH_TOKEN_TTL_SECONDS_ENV_VAR: &str = "REFRESH_TOKEN_TTL_SECONDS";
+    pub const SMS_API_TOKEN_ENV_VAR: &str = "SMS_API_TOKEN";
+    pub const SMS_API_URL_ENV_VAR: &str = "SMS_API_URL";
+    pub const SMS_SENDER_ENV_VAR: &str = "SMS_SENDER";
+    pub const TOKEN_TTL_SECONDS_ENV_VAR: &str = "TOKEN_TTL_SECONDS";
+    pub const TOTP_ENCRYPTION_KEY_ENV_VAR: &str = "TOTP_ENCRYPTION_KEY";
+    pub const TRUSTED_DEVICE_KEY_ENV_VAR: &str = "TRUSTED_DEVICE_KEY";
+    pub const TRUSTED_DEVICE_TTL_SECONDS_ENV_VAR: &str = "TRUSTED_DEVICE_TTL_SECONDS";
+    pub const TWO_FA_MAX_ATTEMPTS_ENV_VAR: &str = "TWO_FA_MAX_ATTEMPTS";
+    pub const TWO_FA_MAX_PENDING_ATTEMPTS_ENV_VAR: &str = "TWO_FA_MAX_PENDING_ATTEMPTS";
+    pub const TWO_FA_MAX_RESENDS_ENV_VAR: &str = "TWO_FA_MAX_RESENDS";
+    pub const TWO_FA_RESEND_COOLDOWN_SECONDS_ENV_VAR: &str = "TWO_FA_RESEND_COOLDOWN_SECONDS";
+    pub const WEBAUTHN_ORIGIN_ENV_VAR: &str = "WEBAUTHN_ORIGIN";
+    pub const WEBAUTHN_RP_ID_ENV_VAR: &str = "WEBAUTHN_RP_ID";
 }
 
 pub mod prod {
@@ -60,14 +336,34 @@ pub mod prod {
         pub const SENDER: &str = "bogdan@codeiron.io";
         pub const TIMEOUT: Duration = std::time::Duration::from_secs(10);
     }
+    pub mod oidc_client {
+        use std::time::Duration;
+
+        pub const TIMEOUT: Duration = std::time::Duration::from_secs(10);
+    }
+    pub mod sms_client {
+        use std::time::Duration;
+
+        pub const TIMEOUT: Duration = std::time::Duration::from_secs(10);
+    }
 }
 
 pub mod test {
     pub const APP_ADDRESS: &str = "127.0.0.1:0";
+    pub mod two_fa {
+        // Short enough for tests to wait out
+        pub const RESEND_COOLDOWN_SECONDS: i64 = 1;
+    }
     pub mod email_client {
         use std::time::Duration;
 
         pub const SENDER: &str = "test@email.com";
         pub const TIMEOUT: Duration = std::time::Duration::from_millis(200);
     }
+    pub mod sms_client {
+        use std::time::Duration;
+
+        pub const SENDER: &str = "+15005550006";
+        pub const TIMEOUT: Duration = std::time::Duration::from_millis(200);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,123 @@
+use auth_middleware::bearer_token;
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, Method},
+    middleware::Next,
+    response::Response,
+};
+use axum_extra::extract::{cookie::Cookie, CookieJar};
+use secrecy::{ExposeSecret, Secret};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, CsrfToken},
+};
+use super::constants::{
+    COOKIE_CONFIG, CSRF_COOKIE_NAME, CSRF_HEADER_NAME, JWT_COOKIE_NAME, REFRESH_COOKIE_NAME,
+    REFRESH_TOKEN_TTL_SECONDS,
+};
+
+// Create the cookie that carries a new CSRF token. Unlike the auth cookies, scripts of
+// the site must be able to read it, to repeat the token in the X-CSRF-Token header.
+pub fn create_csrf_cookie() -> Cookie<'static> {
+    let token = CsrfToken::default();
+    let mut cookie = COOKIE_CONFIG.build(
+        CSRF_COOKIE_NAME,
+        token.as_ref().expose_secret().to_owned(),
+        *REFRESH_TOKEN_TTL_SECONDS,
+    );
+    cookie.set_http_only(false);
+    cookie
+}
+
+// Double-submit CSRF protection for mutating routes. Browsers attach cookies to
+// cross-site requests, but another site can't read the CSRF cookie to set the header.
+#[tracing::instrument(name = "Verify CSRF token", skip_all)]
+pub async fn verify_csrf_token(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AuthAPIError> {
+    if !requires_csrf_token(request.method(), request.headers(), state.csrf_exempt_bearer) {
+        return Ok(next.run(request).await);
+    }
+
+    let cookie_token = CookieJar::from_headers(request.headers())
+        .get(CSRF_COOKIE_NAME)
+        .map(|cookie| CsrfToken::new(Secret::new(cookie.value().to_owned())))
+        .ok_or(AuthAPIError::InvalidCsrfToken)?;
+
+    let header_token = request
+        .headers()
+        .get(CSRF_HEADER_NAME)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| CsrfToken::new(Secret::new(value.to_owned())))
+        .ok_or(AuthAPIError::InvalidCsrfToken)?;
+
+    if !cookie_token.matches(&header_token) {
+        return Err(AuthAPIError::InvalidCsrfToken);
+    }
+
+    Ok(next.run(request).await)
+}
+
+// Only requests a browser authenticates by attaching cookies can be forged
+fn requires_csrf_token(method: &Method, headers: &HeaderMap, exempt_bearer: bool) -> bool {
+    if method.is_safe() {
+        return false;
+    }
+
+    if exempt_bearer && bearer_token(headers).is_some() {
+        return false;
+    }
+
+    let jar = CookieJar::from_headers(headers);
+    jar.get(JWT_COOKIE_NAME).is_some() || jar.get(REFRESH_COOKIE_NAME).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::header;
+    use super::*;
+
+    fn headers(cookie: Option<&str>, authorization: Option<&str>) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Some(cookie) = cookie {
+            headers.insert(header::COOKIE, cookie.parse().unwrap());
+        }
+        if let Some(authorization) = authorization {
+            headers.insert(header::AUTHORIZATION, authorization.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn test_create_csrf_cookie() {
+        let cookie = create_csrf_cookie();
+        assert_eq!(cookie.name(), CSRF_COOKIE_NAME);
+        assert!(!cookie.value().is_empty());
+        assert_eq!(cookie.http_only(), Some(false));
+        assert_ne!(cookie.value(), create_csrf_cookie().value());
+    }
+
+    #[test]
+    fn test_requires_csrf_token_for_cookie_authenticated_requests() {
+        let cookies = headers(Some("jwt=token"), None);
+        assert!(requires_csrf_token(&Method::POST, &cookies, true));
+        assert!(requires_csrf_token(&Method::DELETE, &cookies, true));
+        assert!(!requires_csrf_token(&Method::GET, &cookies, true));
+
+        let refresh_cookie = headers(Some("refresh_token=token"), None);
+        assert!(requires_csrf_token(&Method::POST, &refresh_cookie, true));
+
+        let no_cookies = headers(None, None);
+        assert!(!requires_csrf_token(&Method::POST, &no_cookies, true));
+    }
+
+    #[test]
+    fn test_requires_csrf_token_with_bearer_token() {
+        let bearer = headers(Some("jwt=token"), Some("Bearer token"));
+        assert!(!requires_csrf_token(&Method::POST, &bearer, true));
+        assert!(requires_csrf_token(&Method::POST, &bearer, false));
+    }
+}
@@ -0,0 +1,293 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::Utc;
+use color_eyre::eyre::{eyre, Context, ContextCompat, Result};
+use jsonwebtoken::{
+    jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm,
+        OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+    },
+    Algorithm, DecodingKey, EncodingKey,
+};
+use rsa::{pkcs8::DecodePrivateKey, traits::PublicKeyParts, RsaPrivateKey};
+use secrecy::{ExposeSecret, Secret};
+use serde::Deserialize;
+
+use super::constants::TOKEN_TTL_SECONDS;
+
+// A key used to sign and validate JWT auth tokens
+#[derive(Clone)]
+pub struct JwtKey {
+    kid: Option<String>,
+    algorithm: Algorithm,
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+    // Public part of an asymmetric key, published at /.well-known/jwks.json
+    jwk: Option<Jwk>,
+    // Unix timestamp from which the key is used for signing
+    active_from: i64,
+    // Unix timestamp from which the key is no longer used for signing
+    retire_at: Option<i64>,
+}
+
+impl JwtKey {
+    pub fn hmac(secret: &Secret<String>) -> Self {
+        Self {
+            kid: None,
+            algorithm: Algorithm::HS256,
+            encoding_key: EncodingKey::from_secret(secret.expose_secret().as_bytes()),
+            decoding_key: DecodingKey::from_secret(secret.expose_secret().as_bytes()),
+            jwk: None,
+            active_from: 0,
+            retire_at: None,
+        }
+    }
+
+    // Create a key from a PKCS#8 PEM encoded private key
+    pub fn from_pem(
+        kid: String,
+        algorithm: Algorithm,
+        private_key_pem: &Secret<String>,
+        active_from: i64,
+        retire_at: Option<i64>,
+    ) -> Result<Self> {
+        let pem = private_key_pem.expose_secret();
+
+        let (encoding_key, parameters, key_algorithm) = match algorithm {
+            Algorithm::RS256 => {
+                let private_key = RsaPrivateKey::from_pkcs8_pem(pem)
+                    .wrap_err("Failed to parse RSA private key")?;
+                let parameters = AlgorithmParameters::RSA(RSAKeyParameters {
+                    key_type: RSAKeyType::RSA,
+                    n: URL_SAFE_NO_PAD.encode(private_key.n().to_bytes_be()),
+                    e: URL_SAFE_NO_PAD.encode(private_key.e().to_bytes_be()),
+                });
+                let encoding_key = EncodingKey::from_rsa_pem(pem.as_bytes())
+                    .wrap_err("Failed to create RSA encoding key")?;
+                (encoding_key, parameters, KeyAlgorithm::RS256)
+            }
+            Algorithm::EdDSA => {
+                let signing_key = ed25519_dalek::SigningKey::from_pkcs8_pem(pem)
+                    .wrap_err("Failed to parse Ed25519 private key")?;
+                let parameters = AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                    key_type: OctetKeyPairType::OctetKeyPair,
+                    curve: EllipticCurve::Ed25519,
+                    x: URL_SAFE_NO_PAD.encode(signing_key.verifying_key().to_bytes()),
+                });
+                let encoding_key = EncodingKey::from_ed_pem(pem.as_bytes())
+                    .wrap_err("Failed to create Ed25519 encoding key")?;
+                (encoding_key, parameters, KeyAlgorithm::EdDSA)
+            }
+            _ => return Err(eyre!("Unsupported JWT signing algorithm: {:?}", algorithm)),
+        };
+
+        let jwk = Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(key_algorithm),
+                key_id: Some(kid.clone()),
+                ..Default::default()
+            },
+            algorithm: parameters,
+        };
+
+        let decoding_key = DecodingKey::from_jwk(&jwk).wrap_err("Failed to create decoding key")?;
+
+        Ok(Self {
+            kid: Some(kid),
+            algorithm,
+            encoding_key,
+            decoding_key,
+            jwk: Some(jwk),
+            active_from,
+            retire_at,
+        })
+    }
+
+    pub fn kid(&self) -> Option<&str> {
+        self.kid.as_deref()
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    pub fn encoding_key(&self) -> &EncodingKey {
+        &self.encoding_key
+    }
+
+    pub fn decoding_key(&self) -> &DecodingKey {
+        &self.decoding_key
+    }
+
+    fn is_signing_at(&self, now: i64) -> bool {
+        match self.retire_at {
+            Some(retire_at) => self.active_from <= now && now < retire_at,
+            None => self.active_from <= now,
+        }
+    }
+
+    // Tokens signed right before retirement stay valid until they expire
+    fn is_validating_at(&self, now: i64) -> bool {
+        match self.retire_at {
+            Some(retire_at) => now < retire_at + *TOKEN_TTL_SECONDS,
+            None => true,
+        }
+    }
+}
+
+// The set of keys known to the service, along with their rotation schedule
+#[derive(Clone)]
+pub struct JwtKeySet {
+    keys: Vec<JwtKey>,
+}
+
+impl JwtKeySet {
+    pub fn new(keys: Vec<JwtKey>) -> Result<Self> {
+        if keys.is_empty() {
+            return Err(eyre!("At least one JWT key must be configured"));
+        }
+        Ok(Self { keys })
+    }
+
+    // Load the keys listed in a JSON manifest. Private key paths are relative to the manifest.
+    pub fn from_manifest(path: &str) -> Result<Self> {
+        let manifest = std::fs::read_to_string(path)
+            .wrap_err(format!("Failed to read JWT key manifest: {}", path))?;
+        let entries: Vec<JwtKeyManifestEntry> = serde_json::from_str(&manifest)
+            .wrap_err("Failed to parse JWT key manifest")?;
+
+        let base_dir = std::path::Path::new(path)
+            .parent()
+            .wrap_err("Failed to get JWT key manifest directory")?;
+
+        let keys = entries
+            .into_iter()
+            .map(|entry| {
+                let private_key_path = base_dir.join(&entry.private_key_path);
+                let private_key_pem = std::fs::read_to_string(&private_key_path)
+                    .map(Secret::new)
+                    .wrap_err(format!("Failed to read private key for kid {}", entry.kid))?;
+                JwtKey::from_pem(
+                    entry.kid,
+                    entry.algorithm,
+                    &private_key_pem,
+                    entry.active_from,
+                    entry.retire_at,
+                )
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Self::new(keys)
+    }
+
+    // The most recently activated key that hasn't been retired yet
+    pub fn signing_key(&self) -> Result<&JwtKey> {
+        let now = Utc::now().timestamp();
+        self.keys
+            .iter()
+            .filter(|key| key.is_signing_at(now))
+            .max_by_key(|key| key.active_from)
+            .wrap_err("No active JWT signing key")
+    }
+
+    pub fn validation_key(&self, kid: Option<&str>, algorithm: Algorithm) -> Result<&JwtKey> {
+        let now = Utc::now().timestamp();
+        self.keys
+            .iter()
+            .find(|key| key.kid() == kid && key.algorithm == algorithm && key.is_validating_at(now))
+            .wrap_err("No JWT key matches the token header")
+    }
+
+    // Public keys that are scheduled, active or still validating
+    pub fn jwk_set(&self) -> JwkSet {
+        let now = Utc::now().timestamp();
+        JwkSet {
+            keys: self
+                .keys
+                .iter()
+                .filter(|key| key.is_validating_at(now))
+                .filter_map(|key| key.jwk.clone())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct JwtKeyManifestEntry {
+    kid: String,
+    algorithm: Algorithm,
+    private_key_path: String,
+    active_from: i64,
+    retire_at: Option<i64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rsa_pem() -> Secret<String> {
+        Secret::new(include_str!("../../tests/fixtures/rs256_private_key.pem").to_owned())
+    }
+
+    fn ed25519_pem() -> Secret<String> {
+        Secret::new(include_str!("../../tests/fixtures/ed25519_private_key.pem").to_owned())
+    }
+
+    #[test]
+    fn test_from_pem_rs256() {
+        let key = JwtKey::from_pem("rsa".to_owned(), Algorithm::RS256, &rsa_pem(), 0, None).unwrap();
+        let jwk = key.jwk.unwrap();
+        assert_eq!(jwk.common.key_id.as_deref(), Some("rsa"));
+        assert!(matches!(jwk.algorithm, AlgorithmParameters::RSA(_)));
+    }
+
+    #[test]
+    fn test_from_pem_eddsa() {
+        let key = JwtKey::from_pem("ed".to_owned(), Algorithm::EdDSA, &ed25519_pem(), 0, None).unwrap();
+        let jwk = key.jwk.unwrap();
+        assert_eq!(jwk.common.key_id.as_deref(), Some("ed"));
+        assert!(matches!(jwk.algorithm, AlgorithmParameters::OctetKeyPair(_)));
+    }
+
+    #[test]
+    fn test_from_pem_rejects_mismatched_algorithm() {
+        assert!(JwtKey::from_pem("rsa".to_owned(), Algorithm::EdDSA, &rsa_pem(), 0, None).is_err());
+        assert!(JwtKey::from_pem("rsa".to_owned(), Algorithm::HS256, &rsa_pem(), 0, None).is_err());
+    }
+
+    #[test]
+    fn test_signing_key_follows_rotation_schedule() {
+        let now = Utc::now().timestamp();
+        let retired = JwtKey::from_pem("old".to_owned(), Algorithm::RS256, &rsa_pem(), now - 200, Some(now - 100)).unwrap();
+        let active = JwtKey::from_pem("current".to_owned(), Algorithm::EdDSA, &ed25519_pem(), now - 100, None).unwrap();
+        let scheduled = JwtKey::from_pem("next".to_owned(), Algorithm::EdDSA, &ed25519_pem(), now + 100, None).unwrap();
+
+        let key_set = JwtKeySet::new(vec![retired, active, scheduled]).unwrap();
+
+        assert_eq!(key_set.signing_key().unwrap().kid(), Some("current"));
+        // The retired key still validates tokens it signed before retirement
+        assert!(key_set.validation_key(Some("old"), Algorithm::RS256).is_ok());
+        assert!(key_set.validation_key(Some("old"), Algorithm::HS256).is_err());
+        assert!(key_set.validation_key(Some("unknown"), Algorithm::RS256).is_err());
+        assert_eq!(key_set.jwk_set().keys.len(), 3);
+    }
+
+    #[test]
+    fn test_retired_key_stops_validating_after_token_ttl() {
+        let now = Utc::now().timestamp();
+        let expired = JwtKey::from_pem(
+            "expired".to_owned(),
+            Algorithm::RS256,
+            &rsa_pem(),
+            now - 2 * *TOKEN_TTL_SECONDS,
+            Some(now - *TOKEN_TTL_SECONDS - 1),
+        )
+        .unwrap();
+        let active = JwtKey::from_pem("current".to_owned(), Algorithm::EdDSA, &ed25519_pem(), now - 100, None).unwrap();
+
+        let key_set = JwtKeySet::new(vec![expired, active]).unwrap();
+
+        assert!(key_set.validation_key(Some("expired"), Algorithm::RS256).is_err());
+        assert_eq!(key_set.jwk_set().keys.len(), 1);
+    }
+}
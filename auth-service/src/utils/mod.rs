@@ -1,3 +1,5 @@
 pub mod auth;
 pub mod constants;
+pub mod csrf;
+pub mod jwt_keys;
 pub mod tracing;
\ No newline at end of file
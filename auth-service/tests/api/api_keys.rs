@@ -0,0 +1,208 @@
+use auth_service::{
+    routes::{ApiKeysResponse, CreateApiKeyResponse, IntrospectionResponse},
+    ErrorResponse,
+};
+use test_helpers::api_test;
+use crate::helpers::{get_random_email, TestApp};
+
+async fn sign_up_and_log_in(app: &TestApp) -> String {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    random_email
+}
+
+async fn create_api_key(app: &TestApp, body: &serde_json::Value) -> CreateApiKeyResponse {
+    let response = app.post_api_key(body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    response
+        .json::<CreateApiKeyResponse>()
+        .await
+        .expect("Could not deserialize response body to CreateApiKeyResponse")
+}
+
+async fn introspect(app: &TestApp, token: &str) -> IntrospectionResponse {
+    let verify_token_body = serde_json::json!({
+        "token": token,
+        "introspect": true,
+    });
+
+    let response = app.post_verify_token(&verify_token_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<IntrospectionResponse>()
+        .await
+        .expect("Could not deserialize response body to IntrospectionResponse")
+}
+
+#[api_test]
+async fn should_create_api_key_accepted_at_verify_token() {
+    let email = sign_up_and_log_in(&app).await;
+
+    let created = create_api_key(
+        &app,
+        &serde_json::json!({
+            "name": "deploy script",
+            "scopes": ["reports:read"],
+            "expiresIn": 3600,
+        }),
+    )
+    .await;
+
+    assert!(created.key.starts_with("ak_"));
+    assert_eq!(created.api_key.name, "deploy script");
+    assert_eq!(created.api_key.expires_at, Some(created.api_key.created_at + 3600));
+    assert_eq!(created.api_key.last_used_at, None);
+
+    let introspection = introspect(&app, &created.key).await;
+
+    assert!(introspection.active);
+    assert_eq!(introspection.sub, Some(email));
+    assert_eq!(introspection.jti, Some(created.api_key.id.clone()));
+    assert_eq!(introspection.scope.as_deref(), Some("reports:read"));
+    assert_eq!(introspection.roles, None);
+
+    // API keys can't be used to manage the account, nor pass for a user's token without
+    // introspection
+    let response = app
+        .post_verify_token_with_bearer(&serde_json::json!({}), &created.key)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.get_sessions_with_bearer(&created.key).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_list_api_keys_without_secrets() {
+    sign_up_and_log_in(&app).await;
+
+    let created = create_api_key(&app, &serde_json::json!({ "name": "ci" })).await;
+    create_api_key(&app, &serde_json::json!({ "name": "backups" })).await;
+
+    assert!(introspect(&app, &created.key).await.active);
+
+    let response = app.get_api_keys().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = response.text().await.expect("Failed to read response body");
+    assert!(!body.contains(&created.key));
+
+    let api_keys = serde_json::from_str::<ApiKeysResponse>(&body)
+        .expect("Could not deserialize response body to ApiKeysResponse")
+        .api_keys;
+
+    assert_eq!(api_keys.len(), 2);
+
+    let used = api_keys
+        .iter()
+        .find(|api_key| api_key.id == created.api_key.id)
+        .expect("Created API key not found");
+
+    assert!(used.last_used_at.is_some());
+    assert_eq!(used.expires_at, None);
+    assert!(used.scopes.is_empty());
+}
+
+#[api_test]
+async fn should_revoke_api_key() {
+    sign_up_and_log_in(&app).await;
+
+    let created = create_api_key(&app, &serde_json::json!({ "name": "ci" })).await;
+
+    let response = app.delete_api_key(&created.api_key.id).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert!(!introspect(&app, &created.key).await.active);
+
+    let response = app.delete_api_key(&created.api_key.id).await;
+
+    assert_eq!(response.status().as_u16(), 404);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "API key not found".to_owned()
+    );
+}
+
+#[api_test]
+async fn should_not_revoke_api_keys_of_other_users() {
+    sign_up_and_log_in(&app).await;
+
+    let created = create_api_key(&app, &serde_json::json!({ "name": "ci" })).await;
+
+    sign_up_and_log_in(&app).await;
+
+    let response = app.delete_api_key(&created.api_key.id).await;
+
+    assert_eq!(response.status().as_u16(), 404);
+
+    assert!(introspect(&app, &created.key).await.active);
+}
+
+#[api_test]
+async fn should_return_400_if_invalid_input() {
+    sign_up_and_log_in(&app).await;
+
+    let test_cases = [
+        serde_json::json!({ "name": "" }),
+        serde_json::json!({ "name": "ci", "scopes": ["bad\"scope"] }),
+        serde_json::json!({ "name": "ci", "expiresIn": 0 }),
+    ];
+
+    for test_case in test_cases.iter() {
+        let response = app.post_api_key(test_case).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for input: {:?}",
+            test_case
+        );
+
+        assert_eq!(
+            response
+                .json::<ErrorResponse>()
+                .await
+                .expect("Could not deserialize response body to ErrorResponse")
+                .error,
+            "Invalid API key request".to_owned()
+        );
+    }
+}
+
+#[api_test]
+async fn should_return_401_if_not_logged_in() {
+    let response = app.get_api_keys().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
@@ -0,0 +1,129 @@
+use auth_service::{utils::constants::JWT_COOKIE_NAME, ErrorResponse};
+use test_helpers::api_test;
+use crate::helpers::{get_random_email, TestApp};
+
+#[api_test]
+async fn should_return_200_and_invalidate_other_sessions() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let old_token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "currentPassword": "password123",
+            "newPassword": "newpassword123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let new_token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": old_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": new_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": random_email,
+            "password": "newpassword123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_return_401_if_incorrect_current_password() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": random_email,
+            "password": "password123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "currentPassword": "wrongpassword",
+            "newPassword": "newpassword123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Invalid credentials".to_owned()
+    );
+}
+
+#[api_test]
+async fn should_return_401_if_jwt_cookie_missing() {
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "currentPassword": "password123",
+            "newPassword": "newpassword123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
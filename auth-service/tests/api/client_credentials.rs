@@ -0,0 +1,246 @@
+use auth_service::{
+    domain::{ClientCredentials, ClientSecret, Email, OAuthClient, Password, User, INTROSPECT_SCOPE},
+    routes::{IntrospectionResponse, OAuthTokenResponse, RegisterClientResponse},
+    ErrorResponse, OAuthErrorResponse,
+};
+use secrecy::{ExposeSecret, Secret};
+use test_helpers::api_test;
+use crate::helpers::{get_random_email, TestApp};
+
+async fn register_service_client(app: &TestApp, scopes: &[&str]) -> (String, String) {
+    let scopes = scopes.iter().map(|scope| scope.to_string()).collect();
+    let client = OAuthClient::service("reports".to_owned(), scopes).expect("Failed to create client");
+    let client_secret = ClientSecret::default();
+
+    let credentials = (
+        client.client_id.clone(),
+        client_secret.as_ref().expose_secret().to_owned(),
+    );
+
+    app.oauth_client_store
+        .write()
+        .await
+        .add_client(client, Some(client_secret))
+        .await
+        .expect("Failed to add client");
+
+    credentials
+}
+
+fn client_credentials_form(scope: Option<&str>) -> Vec<(&'static str, String)> {
+    let mut form = vec![("grant_type", "client_credentials".to_owned())];
+    if let Some(scope) = scope {
+        form.push(("scope", scope.to_owned()));
+    }
+    form
+}
+
+async fn assert_oauth_error(response: reqwest::Response, status: u16, error: &str) {
+    assert_eq!(response.status().as_u16(), status);
+
+    assert_eq!(
+        response
+            .json::<OAuthErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to OAuthErrorResponse")
+            .error,
+        error
+    );
+}
+
+#[api_test]
+async fn should_issue_service_token_for_client_credentials() {
+    let (client_id, client_secret) = register_service_client(&app, &["reports:read", "reports:write"]).await;
+
+    let response = app
+        .post_token_with_basic_auth(&client_credentials_form(Some("reports:read")), &client_id, &client_secret)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let tokens = response
+        .json::<OAuthTokenResponse>()
+        .await
+        .expect("Could not deserialize response body to OAuthTokenResponse");
+
+    assert_eq!(tokens.scope, "reports:read");
+    assert!(tokens.refresh_token.is_none());
+    assert!(tokens.id_token.is_none());
+
+    let verify_token_body = serde_json::json!({
+        "token": tokens.access_token,
+        "introspect": true,
+    });
+
+    let response = app.post_verify_token(&verify_token_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let introspection = response
+        .json::<IntrospectionResponse>()
+        .await
+        .expect("Could not deserialize response body to IntrospectionResponse");
+
+    assert!(introspection.active);
+    assert_eq!(introspection.sub, Some(client_id.clone()));
+    assert_eq!(introspection.client_id, Some(client_id));
+    assert_eq!(introspection.scope.as_deref(), Some("reports:read"));
+    assert_eq!(introspection.roles, None);
+
+    // Service tokens can't act on behalf of a user, nor pass for a user's token without
+    // introspection
+    let response = app.get_sessions_with_bearer(&tokens.access_token).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": tokens.access_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_grant_all_registered_scopes_by_default() {
+    let (client_id, client_secret) = register_service_client(&app, &["reports:read", "reports:write"]).await;
+
+    let form = [
+        ("grant_type", "client_credentials"),
+        ("client_id", &client_id),
+        ("client_secret", &client_secret),
+    ];
+
+    let response = app.post_token(&form).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let tokens = response
+        .json::<OAuthTokenResponse>()
+        .await
+        .expect("Could not deserialize response body to OAuthTokenResponse");
+
+    assert_eq!(tokens.scope, "reports:read reports:write");
+}
+
+#[api_test]
+async fn should_reject_invalid_client_credentials_requests() {
+    let (client_id, client_secret) = register_service_client(&app, &["reports:read"]).await;
+
+    let response = app
+        .post_token_with_basic_auth(&client_credentials_form(None), &client_id, "wrong_secret")
+        .await;
+
+    assert_oauth_error(response, 401, "invalid_client").await;
+
+    let response = app
+        .post_token_with_basic_auth(&client_credentials_form(Some("reports:write")), &client_id, &client_secret)
+        .await;
+
+    assert_oauth_error(response, 400, "invalid_scope").await;
+
+    // Clients that sign users in can't get tokens for themselves
+    let client = OAuthClient::new(
+        "app".to_owned(),
+        vec!["https://app.example.com/callback".to_owned()],
+        false,
+    )
+    .unwrap();
+    let public_client_id = client.client_id.clone();
+    app.oauth_client_store.write().await.add_client(client, None).await.unwrap();
+
+    let mut form = client_credentials_form(None);
+    form.push(("client_id", public_client_id));
+
+    let response = app.post_token(&form).await;
+
+    assert_oauth_error(response, 400, "unauthorized_client").await;
+}
+
+#[tokio::test]
+async fn should_accept_service_clients_with_introspect_scope_at_verify_token() {
+    let client = ClientCredentials::new("app-service".to_owned(), Secret::new("secret".to_owned()));
+    let mut app = TestApp::new_with(|app_state| app_state.with_introspection_client(client)).await;
+
+    let verify_token_body = serde_json::json!({
+        "token": "invalid_token",
+        "introspect": true,
+    });
+
+    let (client_id, client_secret) = register_service_client(&app, &[INTROSPECT_SCOPE]).await;
+
+    let response = app
+        .post_verify_token_with_basic_auth(&verify_token_body, &client_id, &client_secret)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let test_cases = [
+        register_service_client(&app, &["reports:read"]).await,
+        (client_id, "wrong_secret".to_owned()),
+    ];
+
+    for (client_id, client_secret) in test_cases {
+        let response = app
+            .post_verify_token_with_basic_auth(&verify_token_body, &client_id, &client_secret)
+            .await;
+
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    app.clean_up().await;
+}
+
+#[api_test]
+async fn should_register_service_client_as_admin() {
+    let body = serde_json::json!({
+        "name": "reports",
+        "scopes": ["reports:read"],
+    });
+
+    let admin_email = get_random_email();
+    let mut admin = User::new(
+        Email::parse(Secret::new(admin_email.clone())).unwrap(),
+        Password::parse(Secret::new("password123".to_owned())).unwrap(),
+        false,
+    );
+    admin.roles = vec!["admin".to_owned()];
+    app.user_store.write().await.add_user(admin).await.unwrap();
+
+    let login_body = serde_json::json!({
+        "email": admin_email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_service_client(&serde_json::json!({ "name": "reports", "scopes": [] })).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Invalid scope".to_owned()
+    );
+
+    let response = app.post_service_client(&body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let registered = response
+        .json::<RegisterClientResponse>()
+        .await
+        .expect("Could not deserialize response body to RegisterClientResponse");
+
+    let client_secret = registered.client_secret.expect("No client secret found");
+
+    let response = app
+        .post_token_with_basic_auth(&client_credentials_form(None), &registered.client_id, &client_secret)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
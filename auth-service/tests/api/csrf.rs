@@ -0,0 +1,144 @@
+use auth_service::{
+    utils::constants::{CSRF_COOKIE_NAME, CSRF_HEADER_NAME, JWT_COOKIE_NAME},
+    ErrorResponse,
+};
+use test_helpers::api_test;
+use crate::helpers::{get_random_email, TestApp};
+
+async fn signup_and_login(app: &TestApp) -> reqwest::Response {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+}
+
+async fn assert_invalid_csrf_token(response: reqwest::Response) {
+    assert_eq!(response.status().as_u16(), 403);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Invalid CSRF token".to_owned()
+    );
+}
+
+#[api_test]
+async fn should_set_csrf_cookie_readable_by_scripts_on_login() {
+    let response = signup_and_login(&app).await;
+
+    let csrf_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == CSRF_COOKIE_NAME)
+        .expect("No CSRF cookie found");
+
+    assert!(!csrf_cookie.value().is_empty());
+    assert!(!csrf_cookie.http_only());
+    assert_eq!(csrf_cookie.path(), Some("/"));
+}
+
+#[api_test]
+async fn should_return_403_if_csrf_token_missing_or_wrong() {
+    signup_and_login(&app).await;
+
+    let test_cases = [None, Some("wrong_token")];
+
+    for csrf_token in test_cases {
+        let mut request = app.http_client.post(format!("{}/logout", &app.address));
+
+        if let Some(csrf_token) = csrf_token {
+            request = request.header(CSRF_HEADER_NAME, csrf_token);
+        }
+
+        let response = request.send().await.expect("Failed to execute request.");
+
+        assert_invalid_csrf_token(response).await;
+    }
+
+    // The session is untouched by the forged requests
+    let response = app.post_logout().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let csrf_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == CSRF_COOKIE_NAME)
+        .expect("No CSRF cookie found");
+
+    assert!(csrf_cookie.value().is_empty());
+}
+
+#[api_test]
+async fn should_return_403_if_refresh_without_csrf_token() {
+    signup_and_login(&app).await;
+
+    let response = app
+        .http_client
+        .post(format!("{}/refresh", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_invalid_csrf_token(response).await;
+
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_not_require_csrf_token_with_bearer_token() {
+    let response = signup_and_login(&app).await;
+
+    let token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    // The cookies are still sent along, but can't be what authenticates the request
+    let response = app.post_logout_with_bearer(&token).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_require_csrf_token_with_bearer_token_if_configured() {
+    let mut app = TestApp::new_with(|app_state| app_state.with_csrf_exempt_bearer(false)).await;
+
+    let response = signup_and_login(&app).await;
+
+    let token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    let response = app.post_logout_with_bearer(&token).await;
+
+    assert_invalid_csrf_token(response).await;
+
+    app.clean_up().await;
+}
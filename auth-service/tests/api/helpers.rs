@@ -1,5 +1,8 @@
 use std::sync::Arc;
-use reqwest::cookie::Jar;
+use reqwest::{
+    cookie::{CookieStore, Jar},
+    RequestBuilder, Url,
+};
 use sqlx::{
     postgres::{PgConnectOptions, PgPoolOptions},
     Connection, Executor, PgConnection, PgPool,
@@ -11,26 +14,62 @@ use tokio::sync::RwLock;
 use uuid::Uuid;
 use wiremock::MockServer;
 use auth_service::{
-    app_state::{AppState, BannedTokenStoreType, TwoFACodeStoreType},
+    app_state::{
+        ApiKeyStoreType, AppState, BannedTokenStoreType, ExternalIdentityStoreType,
+        MagicLinkStoreType, OAuthClientStoreType, PasskeyStoreType, PhoneVerificationStoreType, RecoveryCodeStoreType,
+        RefreshTokenStoreType, SessionStoreType, SessionVersionStoreType, TotpStoreType,
+        TrustedDeviceStoreType, TwoFACodeStoreType, UserStoreType,
+    },
     get_postgres_pool, get_redis_client,
     services::data_stores::{
+        PostgresApiKeyStore,
+        PostgresExternalIdentityStore,
+        PostgresOAuthClientStore,
+        PostgresPasskeyStore,
+        PostgresRecoveryCodeStore,
+        PostgresTotpStore,
+        RedisAuthorizationCodeStore,
         RedisTwoFACodeStore,
         RedisBannedTokenStore,
+        RedisOidcLoginStore,
+        RedisPasskeyChallengeStore,
+        RedisMagicLinkStore,
+        RedisPhoneVerificationStore,
+        RedisRefreshTokenStore,
+        RedisSessionStore,
+        RedisSessionVersionStore,
+        RedisTrustedDeviceStore,
         PostgresUserStore,
     },
-    utils::constants::{test, DATABASE_URL, DEFAULT_REDIS_HOSTNAME},
+    utils::constants::{
+        test, CSRF_COOKIE_NAME, CSRF_HEADER_NAME, DATABASE_URL, DEFAULT_REDIS_HOSTNAME, TOTP_CIPHER,
+    },
     Application,
 };
 use auth_service::domain::Email;
-use auth_service::services::postmark_email_client::PostmarkEmailClient;
+use auth_service::services::{http_sms_client::HttpSmsClient, postmark_email_client::PostmarkEmailClient};
 
 pub struct TestApp {
     pub address: String,
     pub cookie_jar: Arc<Jar>, // Atomic reference counter
+    pub user_store: UserStoreType,
     pub banned_token_store: BannedTokenStoreType,
+    pub refresh_token_store: RefreshTokenStoreType,
+    pub session_version_store: SessionVersionStoreType,
+    pub session_store: SessionStoreType,
     pub two_fa_code_store: TwoFACodeStoreType,
+    pub oauth_client_store: OAuthClientStoreType,
+    pub api_key_store: ApiKeyStoreType,
+    pub external_identity_store: ExternalIdentityStoreType,
+    pub passkey_store: PasskeyStoreType,
+    pub totp_store: TotpStoreType,
+    pub recovery_code_store: RecoveryCodeStoreType,
+    pub phone_verification_store: PhoneVerificationStoreType,
+    pub magic_link_store: MagicLinkStoreType,
+    pub trusted_device_store: TrustedDeviceStoreType,
     pub http_client: reqwest::Client,
     pub email_server: MockServer,
+    pub sms_server: MockServer,
     pub db_name:String,
     pub clean_up_called: bool,
 }
@@ -38,27 +77,65 @@ pub struct TestApp {
 impl TestApp {
 
     pub async fn new() -> Self {
+        Self::new_with(|app_state| app_state).await
+    }
+
+    // Build the app with a customized state, for tests that need a non-default configuration
+    pub async fn new_with(configure: impl FnOnce(AppState) -> AppState) -> Self {
         
         let db_name = Uuid::new_v4().to_string();
         let pg_pool = configure_postgresql(&db_name).await;
-        let user_store = Arc::new(RwLock::new(PostgresUserStore::new(pg_pool)));
+        let user_store: UserStoreType = Arc::new(RwLock::new(PostgresUserStore::new(pg_pool.clone())));
+        let oauth_client_store: OAuthClientStoreType = Arc::new(RwLock::new(PostgresOAuthClientStore::new(pg_pool.clone())));
+        let api_key_store: ApiKeyStoreType = Arc::new(RwLock::new(PostgresApiKeyStore::new(pg_pool.clone())));
+        let external_identity_store: ExternalIdentityStoreType = Arc::new(RwLock::new(PostgresExternalIdentityStore::new(pg_pool.clone())));
+        let passkey_store: PasskeyStoreType = Arc::new(RwLock::new(PostgresPasskeyStore::new(pg_pool.clone())));
+        let recovery_code_store: RecoveryCodeStoreType = Arc::new(RwLock::new(PostgresRecoveryCodeStore::new(pg_pool.clone())));
+        let totp_store: TotpStoreType = Arc::new(RwLock::new(PostgresTotpStore::new(pg_pool, TOTP_CIPHER.clone())));
 
         let redis_connection = Arc::new(RwLock::new(configure_redis()));
         let banned_token_store = Arc::new(RwLock::new(RedisBannedTokenStore::new(redis_connection.clone(),)));
-        let two_fa_code_store = Arc::new(RwLock::new(RedisTwoFACodeStore::new(redis_connection)));
+        let refresh_token_store = Arc::new(RwLock::new(RedisRefreshTokenStore::new(redis_connection.clone())));
+        let session_version_store = Arc::new(RwLock::new(RedisSessionVersionStore::new(redis_connection.clone())));
+        let session_store = Arc::new(RwLock::new(RedisSessionStore::new(redis_connection.clone())));
+        let two_fa_code_store = Arc::new(RwLock::new(
+            RedisTwoFACodeStore::new(redis_connection.clone())
+                .with_resend_cooldown(test::two_fa::RESEND_COOLDOWN_SECONDS),
+        ));
+        let authorization_code_store = Arc::new(RwLock::new(RedisAuthorizationCodeStore::new(redis_connection.clone())));
+        let oidc_login_store = Arc::new(RwLock::new(RedisOidcLoginStore::new(redis_connection.clone())));
+        let passkey_challenge_store = Arc::new(RwLock::new(RedisPasskeyChallengeStore::new(redis_connection.clone())));
+        let phone_verification_store: PhoneVerificationStoreType = Arc::new(RwLock::new(RedisPhoneVerificationStore::new(redis_connection.clone())));
+        let magic_link_store: MagicLinkStoreType = Arc::new(RwLock::new(RedisMagicLinkStore::new(redis_connection.clone())));
+        let trusted_device_store: TrustedDeviceStoreType = Arc::new(RwLock::new(RedisTrustedDeviceStore::new(redis_connection)));
 
         let email_server = MockServer::start().await;
         let base_url = email_server.uri();
         let email_client = Arc::new(configure_postmark_email_client(base_url));
 
+        let sms_server = MockServer::start().await;
+        let sms_client = Arc::new(configure_sms_client(sms_server.uri()));
+
         let app_state = AppState::new(
-            user_store,
+            user_store.clone(),
             banned_token_store.clone(),
+            refresh_token_store.clone(),
+            session_version_store.clone(),
+            session_store.clone(),
             two_fa_code_store.clone(),
             email_client,
-        );
-
-        let app = Application::build(app_state, test::APP_ADDRESS)
+        )
+        .with_oauth_stores(oauth_client_store.clone(), authorization_code_store)
+        .with_api_key_store(api_key_store.clone())
+        .with_oidc_stores(oidc_login_store, external_identity_store.clone())
+        .with_passkey_stores(passkey_store.clone(), passkey_challenge_store)
+        .with_totp_store(totp_store.clone())
+        .with_recovery_code_store(recovery_code_store.clone())
+        .with_sms(sms_client, phone_verification_store.clone())
+        .with_magic_link_store(magic_link_store.clone())
+        .with_trusted_device_store(trusted_device_store.clone());
+
+        let app = Application::build(configure(app_state), test::APP_ADDRESS)
             .await
             .expect("Failed to build app");
 
@@ -70,8 +147,10 @@ impl TestApp {
         let _ = tokio::spawn(app.run());
 
         let cookie_jar = Arc::new(Jar::default());
+        // Redirects are not followed, so tests can inspect where the OAuth endpoints send users
         let http_client = reqwest::Client::builder()
             .cookie_provider(cookie_jar.clone())
+            .redirect(reqwest::redirect::Policy::none())
             .build()
             .unwrap();
 
@@ -79,19 +158,61 @@ impl TestApp {
         Self {
             address,
             cookie_jar,
+            user_store,
             banned_token_store,
+            refresh_token_store,
+            session_version_store,
+            session_store,
             two_fa_code_store,
+            oauth_client_store,
+            api_key_store,
+            external_identity_store,
+            passkey_store,
+            totp_store,
+            recovery_code_store,
+            phone_verification_store,
+            magic_link_store,
+            trusted_device_store,
             http_client,
             email_server,
+            sms_server,
             db_name,
             clean_up_called: false,
         }
     
     }
 
+    // The CSRF token set at login, as the page's scripts would read it
+    pub fn csrf_token(&self) -> Option<String> {
+        let url = Url::parse(&self.address).expect("Failed to parse URL");
+        let cookies = self.cookie_jar.cookies(&url)?;
+        cookies
+            .to_str()
+            .ok()?
+            .split("; ")
+            .find_map(|cookie| cookie.strip_prefix(&format!("{}=", CSRF_COOKIE_NAME)))
+            .map(str::to_owned)
+    }
+
+    // Repeat the CSRF token in a header, like a browser would for requests authenticated by cookies
+    fn with_csrf_token(&self, request: RequestBuilder) -> RequestBuilder {
+        match self.csrf_token() {
+            Some(token) => request.header(CSRF_HEADER_NAME, token),
+            None => request,
+        }
+    }
+
     pub async fn get_root(&self) -> reqwest::Response {
         self.http_client
-            .get(&format!("{}/", &self.address))
+            .get(format!("{}/", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_jwks(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/.well-known/jwks.json", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -100,7 +221,7 @@ impl TestApp {
     pub async fn post_signup<Body>(&self, body: &Body) -> reqwest::Response
         where Body: serde::Serialize {
             self.http_client
-                .post(&format!("{}/signup", &self.address))
+                .post(format!("{}/signup", &self.address))
                 .json(body)
                 .send()
                 .await
@@ -110,7 +231,7 @@ impl TestApp {
     pub async fn post_login<Body>(&self, body: &Body) -> reqwest::Response
         where Body: serde::Serialize {
             self.http_client
-                .post(&format!("{}/login", &self.address))
+                .post(format!("{}/login", &self.address))
                 .json(body)
                 .send()
                 .await
@@ -118,8 +239,139 @@ impl TestApp {
     }
 
     pub async fn post_logout(&self) -> reqwest::Response {
+        let request = self
+            .http_client
+            .post(format!("{}/logout", &self.address));
+
+        self.with_csrf_token(request)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_logout_with_bearer(&self, token: &str) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/logout", &self.address))
+            .bearer_auth(token)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_logout_all(&self) -> reqwest::Response {
+        let request = self
+            .http_client
+            .post(format!("{}/logout-all", &self.address));
+
+        self.with_csrf_token(request)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_change_password<Body>(&self, body: &Body) -> reqwest::Response
+    where Body: serde::Serialize {
+        let request = self
+            .http_client
+            .post(format!("{}/change-password", &self.address))
+            .json(body);
+
+        self.with_csrf_token(request)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_reauthenticate<Body>(&self, body: &Body) -> reqwest::Response
+    where Body: serde::Serialize {
+        let request = self
+            .http_client
+            .post(format!("{}/reauthenticate", &self.address))
+            .json(body);
+
+        self.with_csrf_token(request)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_reauthenticate_send_2fa(&self) -> reqwest::Response {
+        let request = self
+            .http_client
+            .post(format!("{}/reauthenticate/send-2fa", &self.address));
+
+        self.with_csrf_token(request)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_refresh(&self) -> reqwest::Response {
+        let request = self
+            .http_client
+            .post(format!("{}/refresh", &self.address));
+
+        self.with_csrf_token(request)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_refresh_with_body<Body>(&self, body: &Body) -> reqwest::Response
+    where Body: serde::Serialize {
+        let request = self
+            .http_client
+            .post(format!("{}/refresh", &self.address))
+            .json(body);
+
+        self.with_csrf_token(request)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_sessions(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/sessions", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_sessions_with_bearer(&self, token: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/sessions", &self.address))
+            .bearer_auth(token)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_session(&self, id: &str) -> reqwest::Response {
+        let request = self
+            .http_client
+            .delete(format!("{}/sessions/{}", &self.address, id));
+
+        self.with_csrf_token(request)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_trusted_devices(&self) -> reqwest::Response {
         self.http_client
-            .post(&format!("{}/logout", &self.address))
+            .get(format!("{}/trusted-devices", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_trusted_device(&self, id: &str) -> reqwest::Response {
+        let request = self
+            .http_client
+            .delete(format!("{}/trusted-devices/{}", &self.address, id));
+
+        self.with_csrf_token(request)
             .send()
             .await
             .expect("Failed to execute request.")
@@ -128,7 +380,17 @@ impl TestApp {
     pub async fn post_verify_2fa<Body>(&self, body: &Body) -> reqwest::Response 
         where Body: serde::Serialize {
         self.http_client
-            .post(&format!("{}/verify-2fa", &self.address))
+            .post(format!("{}/verify-2fa", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_resend_2fa<Body>(&self, body: &Body) -> reqwest::Response
+    where Body: serde::Serialize {
+        self.http_client
+            .post(format!("{}/resend-2fa", &self.address))
             .json(body)
             .send()
             .await
@@ -138,13 +400,306 @@ impl TestApp {
     pub async fn post_verify_token<Body>(&self, body: &Body) -> reqwest::Response
     where Body: serde::Serialize {
         self.http_client
-            .post(&format!("{}/verify-token", &self.address))
+            .post(format!("{}/verify-token", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_verify_token_with_bearer<Body>(&self, body: &Body, token: &str) -> reqwest::Response
+    where Body: serde::Serialize {
+        self.http_client
+            .post(format!("{}/verify-token", &self.address))
+            .json(body)
+            .bearer_auth(token)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_openid_configuration(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/.well-known/openid-configuration", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_authorize<Query>(&self, query: &Query) -> reqwest::Response
+    where Query: serde::Serialize {
+        self.http_client
+            .get(format!("{}/authorize", &self.address))
+            .query(query)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_token<Form>(&self, form: &Form) -> reqwest::Response
+    where Form: serde::Serialize {
+        self.http_client
+            .post(format!("{}/token", &self.address))
+            .form(form)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_token_with_basic_auth<Form>(
+        &self,
+        form: &Form,
+        client_id: &str,
+        client_secret: &str,
+    ) -> reqwest::Response
+    where Form: serde::Serialize {
+        self.http_client
+            .post(format!("{}/token", &self.address))
+            .basic_auth(client_id, Some(client_secret))
+            .form(form)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_userinfo_with_bearer(&self, token: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/userinfo", &self.address))
+            .bearer_auth(token)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_oauth_client<Body>(&self, body: &Body) -> reqwest::Response
+    where Body: serde::Serialize {
+        let request = self
+            .http_client
+            .post(format!("{}/oauth/clients", &self.address))
+            .json(body);
+
+        self.with_csrf_token(request)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_service_client<Body>(&self, body: &Body) -> reqwest::Response
+    where Body: serde::Serialize {
+        let request = self
+            .http_client
+            .post(format!("{}/oauth/service-clients", &self.address))
+            .json(body);
+
+        self.with_csrf_token(request)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_verify_token_with_basic_auth<Body>(
+        &self,
+        body: &Body,
+        client_id: &str,
+        client_secret: &str,
+    ) -> reqwest::Response
+    where Body: serde::Serialize {
+        self.http_client
+            .post(format!("{}/verify-token", &self.address))
+            .basic_auth(client_id, Some(client_secret))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_api_key<Body>(&self, body: &Body) -> reqwest::Response
+    where Body: serde::Serialize {
+        let request = self
+            .http_client
+            .post(format!("{}/api-keys", &self.address))
+            .json(body);
+
+        self.with_csrf_token(request)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_api_keys(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/api-keys", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_api_key(&self, id: &str) -> reqwest::Response {
+        let request = self
+            .http_client
+            .delete(format!("{}/api-keys/{}", &self.address, id));
+
+        self.with_csrf_token(request)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_oidc_login(&self, provider: &str, return_to: Option<&str>) -> reqwest::Response {
+        let mut request = self
+            .http_client
+            .get(format!("{}/oidc/{}/login", &self.address, provider));
+
+        if let Some(return_to) = return_to {
+            request = request.query(&[("return_to", return_to)]);
+        }
+
+        request
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_oidc_link(&self, provider: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/oidc/{}/link", &self.address, provider))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_oidc_callback<Query>(&self, provider: &str, query: &Query) -> reqwest::Response
+    where Query: serde::Serialize {
+        self.http_client
+            .get(format!("{}/oidc/{}/callback", &self.address, provider))
+            .query(query)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_magic_link<Body>(&self, body: &Body) -> reqwest::Response
+    where Body: serde::Serialize {
+        self.http_client
+            .post(format!("{}/magic-link", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_magic_link_verify(&self, token: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/magic-link/verify", &self.address))
+            .query(&[("token", token)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_passkey_registration_start(&self) -> reqwest::Response {
+        let request = self
+            .http_client
+            .post(format!("{}/passkeys/register/start", &self.address));
+
+        self.with_csrf_token(request)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_passkey_registration_finish<Body>(&self, body: &Body) -> reqwest::Response
+    where Body: serde::Serialize {
+        let request = self
+            .http_client
+            .post(format!("{}/passkeys/register/finish", &self.address))
+            .json(body);
+
+        self.with_csrf_token(request)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_passkey_login_start(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/passkeys/login/start", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_passkey_login_finish<Body>(&self, body: &Body) -> reqwest::Response
+    where Body: serde::Serialize {
+        self.http_client
+            .post(format!("{}/passkeys/login/finish", &self.address))
             .json(body)
             .send()
             .await
             .expect("Failed to execute request.")
     }
 
+    pub async fn post_totp_enroll(&self) -> reqwest::Response {
+        let request = self
+            .http_client
+            .post(format!("{}/2fa/totp/enroll", &self.address));
+
+        self.with_csrf_token(request)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_totp_confirm<Body>(&self, body: &Body) -> reqwest::Response
+    where Body: serde::Serialize {
+        let request = self
+            .http_client
+            .post(format!("{}/2fa/totp/confirm", &self.address))
+            .json(body);
+
+        self.with_csrf_token(request)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_recovery_codes(&self) -> reqwest::Response {
+        let request = self
+            .http_client
+            .post(format!("{}/2fa/recovery-codes", &self.address));
+
+        self.with_csrf_token(request)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_sms_enroll<Body>(&self, body: &Body) -> reqwest::Response
+    where Body: serde::Serialize {
+        let request = self
+            .http_client
+            .post(format!("{}/2fa/sms/enroll", &self.address))
+            .json(body);
+
+        self.with_csrf_token(request)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_sms_confirm<Body>(&self, body: &Body) -> reqwest::Response
+    where Body: serde::Serialize {
+        let request = self
+            .http_client
+            .post(format!("{}/2fa/sms/confirm", &self.address))
+            .json(body);
+
+        self.with_csrf_token(request)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn clean_up(&mut self) {
         if self.clean_up_called {
             return;
@@ -245,7 +800,7 @@ async fn delete_database(db_name: &str) {
         .expect("Failed to drop the database.");
 }
 
-fn configure_redis() -> redis::Connection {
+pub fn configure_redis() -> redis::Connection {
     let redis_hostname = DEFAULT_REDIS_HOSTNAME.to_owned();
 
     get_redis_client(redis_hostname)
@@ -265,4 +820,18 @@ fn configure_postmark_email_client(base_url: String) -> PostmarkEmailClient {
         .expect("Failed to build HTTP client");
 
     PostmarkEmailClient::new(base_url, sender, postmark_auth_token, http_client)
-}
\ No newline at end of file
+}
+
+fn configure_sms_client(base_url: String) -> HttpSmsClient {
+    let http_client = Client::builder()
+        .timeout(test::sms_client::TIMEOUT)
+        .build()
+        .expect("Failed to build HTTP client");
+
+    HttpSmsClient::new(
+        base_url,
+        test::sms_client::SENDER.to_owned(),
+        Secret::new("sms_token".to_owned()),
+        http_client,
+    )
+}
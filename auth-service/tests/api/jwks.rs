@@ -0,0 +1,18 @@
+use jsonwebtoken::jwk::JwkSet;
+use test_helpers::api_test;
+use crate::helpers::TestApp;
+
+#[api_test]
+async fn should_return_200_with_jwk_set() {
+    let response = app.get_jwks().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let jwk_set = response
+        .json::<JwkSet>()
+        .await
+        .expect("Could not deserialize response body to JwkSet");
+
+    // The test configuration signs with HS256, whose secret is never published
+    assert!(jwk_set.keys.is_empty());
+}
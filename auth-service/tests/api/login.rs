@@ -2,9 +2,12 @@ use secrecy::{ExposeSecret, Secret};
 use test_helpers::api_test;
 use crate::helpers::{get_random_email, TestApp};
 use auth_service::{
-    domain::Email,
+    domain::LoginAttemptId,
     routes::TwoFactorAuthResponse,
-    utils::constants::JWT_COOKIE_NAME,
+    utils::{
+        auth::TokenResponse,
+        constants::{JWT_COOKIE_NAME, TOKEN_TTL_SECONDS},
+    },
     ErrorResponse,
 };
 use wiremock::matchers::{method, path};
@@ -39,6 +42,10 @@ async fn should_return_200_if_valid_credentials_and_2fa_disabled() {
         .expect("No auth cookie found");
 
     assert!(!auth_cookie.value().is_empty());
+    assert_eq!(
+        auth_cookie.max_age(),
+        Some(std::time::Duration::from_secs(*TOKEN_TTL_SECONDS as u64))
+    );
 }
 
 #[api_test]
@@ -81,14 +88,11 @@ async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
     let two_fa_code_store = app.two_fa_code_store.read().await;
 
     let code_tuple = two_fa_code_store
-        .get_code(&Email::parse(Secret::new(random_email)).unwrap())
+        .get_code(&LoginAttemptId::parse(Secret::new(json_body.login_attempt_id)).unwrap())
         .await
         .expect("Failed to get 2FA code");
 
-    assert_eq!(
-        code_tuple.0.as_ref().expose_secret(),
-        &json_body.login_attempt_id
-    );
+    assert_eq!(code_tuple.0.as_ref().expose_secret(), &random_email);
 }
 
 #[api_test]
@@ -218,4 +222,43 @@ async fn should_return_422_if_malformed_credentials() {
             test_case
         );
     }
-}
\ No newline at end of file
+}
+
+#[api_test]
+async fn should_return_tokens_in_body_if_requested() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "tokenDelivery": "body"
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.cookies().next().is_none());
+
+    let json_body = response
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse");
+
+    assert_eq!(json_body.token_type, "Bearer");
+    assert!(!json_body.refresh_token.is_empty());
+
+    // The returned token authenticates requests on its own
+    let response = app.get_sessions_with_bearer(&json_body.access_token).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
@@ -1,4 +1,10 @@
-use auth_service::{utils::constants::JWT_COOKIE_NAME, ErrorResponse};
+use auth_service::{
+    utils::{
+        auth::{validate_token, TokenResponse, ValidatedToken},
+        constants::{CSRF_COOKIE_NAME, JWT_COOKIE_NAME},
+    },
+    ErrorResponse,
+};
 use reqwest::Url;
 use secrecy::Secret;
 use test_helpers::api_test;
@@ -39,6 +45,17 @@ async fn should_return_200_if_valid_jwt_cookie() {
 
     let token = Secret::new(auth_cookie.value().to_owned());
 
+    let claims = validate_token(
+        &token,
+        app.banned_token_store.clone(),
+        app.session_version_store.clone(),
+        app.session_store.clone(),
+        app.api_key_store.clone(),
+    )
+    .await
+    .and_then(ValidatedToken::into_user)
+    .expect("Failed to validate token");
+
     let response = app.post_logout().await;
 
     assert_eq!(response.status().as_u16(), 200);
@@ -49,10 +66,15 @@ async fn should_return_200_if_valid_jwt_cookie() {
         .expect("No auth cookie found");
 
     assert!(auth_cookie.value().is_empty());
+    // Removal must match the attributes the cookie was set with
+    assert_eq!(auth_cookie.path(), Some("/"));
+    assert!(auth_cookie.http_only());
+    assert!(auth_cookie.same_site_lax());
+    assert_eq!(auth_cookie.max_age(), Some(std::time::Duration::ZERO));
 
     let banned_token_store = app.banned_token_store.read().await;
     let contains_token = banned_token_store
-        .contains_token(&token)
+        .contains_token(&claims.jti)
         .await
         .expect("Failed to check if token is banned");
 
@@ -60,13 +82,18 @@ async fn should_return_200_if_valid_jwt_cookie() {
 }
 
 #[api_test]
-async fn should_return_400_if_jwt_cookie_missing() {
+async fn should_return_401_if_jwt_cookie_missing() {
     let response = app.post_logout().await;
 
     assert_eq!(
         response.status().as_u16(),
-        400,
-        "The API did not return a 400 BAD REQUEST",
+        401,
+        "The API did not return a 401 UNAUTHORIZED",
+    );
+
+    assert_eq!(
+        response.headers().get("www-authenticate").map(|value| value.to_str().unwrap()),
+        Some("Bearer")
     );
 
     let auth_cookie = response
@@ -86,7 +113,7 @@ async fn should_return_400_if_jwt_cookie_missing() {
 }
 
 #[api_test]
-async fn should_return_400_if_logout_called_twice_in_a_row() {
+async fn should_return_401_if_logout_called_twice_in_a_row() {
     let random_email = get_random_email();
 
     let signup_body = serde_json::json!({
@@ -126,7 +153,7 @@ async fn should_return_400_if_logout_called_twice_in_a_row() {
     assert!(auth_cookie.value().is_empty());
 
     let response = app.post_logout().await;
-    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(response.status().as_u16(), 401);
 
     assert_eq!(
         response
@@ -148,6 +175,10 @@ async fn should_return_401_if_invalid_token() {
         ),
         &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
     );
+    app.cookie_jar.add_cookie_str(
+        &format!("{}=csrf; SameSite=Lax; Path=/", CSRF_COOKIE_NAME),
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
 
     let response = app.post_logout().await;
 
@@ -167,4 +198,43 @@ async fn should_return_401_if_invalid_token() {
             .error,
         "Invalid auth token".to_owned()
     );
-}
\ No newline at end of file
+}
+
+#[api_test]
+async fn should_return_200_if_valid_bearer_token() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "tokenDelivery": "body"
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = response
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse")
+        .access_token;
+
+    let response = app.post_logout_with_bearer(&token).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_logout_with_bearer(&token).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
@@ -0,0 +1,132 @@
+use auth_service::{
+    utils::constants::{CSRF_COOKIE_NAME, JWT_COOKIE_NAME, REFRESH_COOKIE_NAME},
+    ErrorResponse,
+};
+use reqwest::Url;
+use test_helpers::api_test;
+use crate::helpers::{get_random_email, TestApp};
+
+#[api_test]
+async fn should_return_200_and_invalidate_all_sessions() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    // A session on another device
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let other_token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    let other_refresh_token = response
+        .cookies()
+        .find(|cookie| cookie.name() == REFRESH_COOKIE_NAME)
+        .expect("No refresh cookie found")
+        .value()
+        .to_owned();
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_logout_all().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    assert!(auth_cookie.value().is_empty());
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": other_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    // The other device can't get a new auth token either
+    let url = Url::parse(&app.address).expect("Failed to parse URL");
+    app.cookie_jar.add_cookie_str(
+        &format!("{}={}; HttpOnly; SameSite=Lax; Path=/", REFRESH_COOKIE_NAME, other_refresh_token),
+        &url,
+    );
+    app.cookie_jar.add_cookie_str(&format!("{}=csrf; SameSite=Lax; Path=/", CSRF_COOKIE_NAME), &url);
+
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    // New logins are unaffected
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_return_401_if_jwt_cookie_missing() {
+    let response = app.post_logout_all().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Missing auth token".to_owned()
+    );
+}
+
+#[api_test]
+async fn should_return_401_if_invalid_token() {
+    app.cookie_jar.add_cookie_str(
+        &format!(
+            "{}=invalid; HttpOnly; SameSite=Lax; Secure; Path=/",
+            JWT_COOKIE_NAME
+        ),
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+    app.cookie_jar.add_cookie_str(
+        &format!("{}=csrf; SameSite=Lax; Path=/", CSRF_COOKIE_NAME),
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+
+    let response = app.post_logout_all().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
@@ -0,0 +1,209 @@
+use auth_service::{
+    domain::{BrowserBinding, Email, MagicLink, MagicLinkToken},
+    routes::TwoFactorAuthResponse,
+    utils::constants::{JWT_COOKIE_NAME, MAGIC_LINK_COOKIE_NAME, MAGIC_LINK_KEY},
+    ErrorResponse,
+};
+use reqwest::Url;
+use secrecy::{ExposeSecret, Secret};
+use test_helpers::api_test;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+use crate::helpers::{get_random_email, TestApp};
+
+async fn sign_up(app: &TestApp, requires_2fa: bool) -> String {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": requires_2fa
+    });
+
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    random_email
+}
+
+async fn mount_email_server(app: &TestApp, expected_emails: u64) {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(expected_emails)
+        .mount(&app.email_server)
+        .await;
+}
+
+async fn request_link(app: &TestApp, email: &str) {
+    let response = app.post_magic_link(&serde_json::json!({ "email": email })).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+// The signed token of the most recent login link emailed
+async fn last_link_token(app: &TestApp) -> String {
+    let requests = app.email_server.received_requests().await.unwrap();
+
+    requests
+        .iter()
+        .rev()
+        .filter_map(|request| serde_json::from_slice::<serde_json::Value>(&request.body).ok())
+        .filter(|body| body["Subject"] == "Your login link")
+        .find_map(|body| {
+            let text = body["TextBody"].as_str()?.to_owned();
+            let start = text.find("token=")? + "token=".len();
+            text[start..].split_whitespace().next().map(str::to_owned)
+        })
+        .expect("No login link was emailed")
+}
+
+#[api_test]
+async fn should_log_in_with_magic_link() {
+    let email = sign_up(&app, false).await;
+    mount_email_server(&app, 2).await;
+
+    // An earlier link keeps working when another one is requested in the same browser
+    request_link(&app, &email).await;
+    let token = last_link_token(&app).await;
+    request_link(&app, &email).await;
+
+    let response = app.get_magic_link_verify(&token).await;
+
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(response.headers()["location"], "/");
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+    assert!(!auth_cookie.value().is_empty());
+}
+
+#[api_test]
+async fn should_return_401_if_link_is_reused() {
+    let email = sign_up(&app, false).await;
+    mount_email_server(&app, 1).await;
+
+    request_link(&app, &email).await;
+    let token = last_link_token(&app).await;
+
+    assert_eq!(app.get_magic_link_verify(&token).await.status().as_u16(), 303);
+
+    let response = app.get_magic_link_verify(&token).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(
+        response.json::<ErrorResponse>().await.unwrap().error,
+        "Invalid magic link"
+    );
+}
+
+#[api_test]
+async fn should_return_401_if_link_is_opened_in_another_browser() {
+    let email = sign_up(&app, false).await;
+    mount_email_server(&app, 1).await;
+
+    request_link(&app, &email).await;
+    let token = last_link_token(&app).await;
+
+    let other_browser = reqwest::Client::builder()
+        .cookie_store(true)
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+
+    let response = other_browser
+        .get(format!("{}/magic-link/verify", &app.address))
+        .query(&[("token", &token)])
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 401);
+    assert!(response.cookies().all(|cookie| cookie.name() != JWT_COOKIE_NAME));
+
+    // Opening it elsewhere used the link up
+    assert_eq!(app.get_magic_link_verify(&token).await.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_return_401_if_link_is_forged() {
+    let email = sign_up(&app, false).await;
+    mount_email_server(&app, 1).await;
+
+    request_link(&app, &email).await;
+    let token = last_link_token(&app).await;
+    let (id, _) = token.split_once('.').unwrap();
+    let unsigned = MagicLinkToken::default();
+
+    for forged in [id.to_owned(), format!("{}.{}", id, "00".repeat(32)), unsigned.as_ref().expose_secret().to_owned()] {
+        assert_eq!(app.get_magic_link_verify(&forged).await.status().as_u16(), 401, "{}", forged);
+    }
+
+    // The real link was never touched
+    assert_eq!(app.get_magic_link_verify(&token).await.status().as_u16(), 303);
+}
+
+#[api_test]
+async fn should_return_401_if_link_expired() {
+    let email = sign_up(&app, false).await;
+
+    let binding = BrowserBinding::default();
+    let token = MagicLinkToken::default();
+    let link = MagicLink::new(Email::parse(Secret::new(email)).unwrap(), &binding, 0);
+
+    app.magic_link_store
+        .write()
+        .await
+        .add_link(token.clone(), link)
+        .await
+        .unwrap();
+
+    app.cookie_jar.add_cookie_str(
+        &format!("{}={}", MAGIC_LINK_COOKIE_NAME, binding.as_ref().expose_secret()),
+        &Url::parse(&app.address).unwrap(),
+    );
+
+    let signed = token.sign(MAGIC_LINK_KEY.expose_secret());
+    let response = app.get_magic_link_verify(signed.expose_secret()).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_not_email_unknown_users() {
+    mount_email_server(&app, 0).await;
+
+    request_link(&app, &get_random_email()).await;
+}
+
+#[api_test]
+async fn should_return_400_if_email_is_invalid() {
+    let response = app.post_magic_link(&serde_json::json!({ "email": "invalid_email" })).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[api_test]
+async fn should_still_require_second_factor() {
+    let email = sign_up(&app, true).await;
+    // The login link, then the 2FA code
+    mount_email_server(&app, 2).await;
+
+    request_link(&app, &email).await;
+    let token = last_link_token(&app).await;
+
+    let response = app.get_magic_link_verify(&token).await;
+
+    assert_eq!(response.status().as_u16(), 206);
+    assert!(response.cookies().all(|cookie| cookie.name() != JWT_COOKIE_NAME));
+
+    let response_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+
+    assert_eq!(response_body.message, "2FA required");
+}
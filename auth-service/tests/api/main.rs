@@ -1,7 +1,25 @@
+mod api_keys;
+mod change_password;
+mod client_credentials;
+mod csrf;
 mod helpers;
+mod jwks;
 mod login;
 mod logout;
+mod logout_all;
+mod magic_link;
+mod oauth;
+mod oidc;
+mod passkeys;
+mod reauthenticate;
+mod recovery_codes;
+mod refresh;
+mod resend_2fa;
 mod root;
+mod sessions;
 mod signup;
+mod sms;
+mod totp;
+mod trusted_devices;
 mod verify_2fa;
 mod verify_token;
\ No newline at end of file
@@ -0,0 +1,450 @@
+use auth_service::{
+    domain::{ClientSecret, Email, OAuthClient, Password, User},
+    routes::{
+        IntrospectionResponse, OAuthTokenResponse, OpenIdConfiguration, RegisterClientResponse,
+        SessionsResponse, UserInfoResponse,
+    },
+    utils::{auth::IdTokenClaims, constants::JWT_ISSUER},
+    ErrorResponse, OAuthErrorResponse,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use reqwest::{header::LOCATION, Url};
+use secrecy::{ExposeSecret, Secret};
+use test_helpers::api_test;
+use crate::helpers::{get_random_email, TestApp};
+
+const REDIRECT_URI: &str = "https://app.example.com/callback";
+// Example from RFC 7636 appendix B
+const CODE_VERIFIER: &str = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+const CODE_CHALLENGE: &str = "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM";
+
+async fn register_client(app: &TestApp, confidential: bool) -> (OAuthClient, Option<ClientSecret>) {
+    let client = OAuthClient::new("app".to_owned(), vec![REDIRECT_URI.to_owned()], confidential)
+        .expect("Failed to create client");
+    let client_secret = confidential.then(ClientSecret::default);
+
+    app.oauth_client_store
+        .write()
+        .await
+        .add_client(client.clone(), client_secret.clone())
+        .await
+        .expect("Failed to add client");
+
+    (client, client_secret)
+}
+
+async fn signup_and_login(app: &TestApp) -> String {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    random_email
+}
+
+fn authorize_query(client_id: &str) -> Vec<(&'static str, String)> {
+    vec![
+        ("response_type", "code".to_owned()),
+        ("client_id", client_id.to_owned()),
+        ("redirect_uri", REDIRECT_URI.to_owned()),
+        ("scope", "openid email".to_owned()),
+        ("state", "af0ifjsldkj".to_owned()),
+        ("nonce", "n-0S6_WzA2Mj".to_owned()),
+        ("code_challenge", CODE_CHALLENGE.to_owned()),
+        ("code_challenge_method", "S256".to_owned()),
+    ]
+}
+
+// Follow the redirect back to the client and read its query parameters
+fn redirect_params(response: &reqwest::Response) -> Vec<(String, String)> {
+    let location = response
+        .headers()
+        .get(LOCATION)
+        .expect("No Location header found")
+        .to_str()
+        .unwrap();
+
+    let url = Url::parse(location).expect("Location is not an absolute URL");
+    assert!(location.starts_with(REDIRECT_URI), "{}", location);
+
+    url.query_pairs().into_owned().collect()
+}
+
+fn param<'a>(params: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    params
+        .iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.as_str())
+}
+
+async fn get_code(app: &TestApp, client_id: &str) -> String {
+    let response = app.get_authorize(&authorize_query(client_id)).await;
+
+    assert_eq!(response.status().as_u16(), 303);
+
+    let params = redirect_params(&response);
+
+    assert_eq!(param(&params, "state"), Some("af0ifjsldkj"));
+
+    param(&params, "code").expect("No code found").to_owned()
+}
+
+fn token_form(client_id: &str, code: &str, code_verifier: &str) -> Vec<(&'static str, String)> {
+    vec![
+        ("grant_type", "authorization_code".to_owned()),
+        ("code", code.to_owned()),
+        ("redirect_uri", REDIRECT_URI.to_owned()),
+        ("code_verifier", code_verifier.to_owned()),
+        ("client_id", client_id.to_owned()),
+    ]
+}
+
+async fn assert_oauth_error(response: reqwest::Response, status: u16, error: &str) {
+    assert_eq!(response.status().as_u16(), status);
+
+    assert_eq!(
+        response
+            .json::<OAuthErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to OAuthErrorResponse")
+            .error,
+        error
+    );
+}
+
+#[api_test]
+async fn should_return_openid_configuration() {
+    let response = app.get_openid_configuration().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let configuration = response
+        .json::<OpenIdConfiguration>()
+        .await
+        .expect("Could not deserialize response body to OpenIdConfiguration");
+
+    assert_eq!(configuration.issuer, *JWT_ISSUER);
+    assert!(configuration.authorization_endpoint.ends_with("/authorize"));
+    assert!(configuration.token_endpoint.ends_with("/token"));
+    assert!(configuration.jwks_uri.ends_with("/.well-known/jwks.json"));
+    assert_eq!(configuration.code_challenge_methods_supported, vec!["S256"]);
+}
+
+#[api_test]
+async fn should_return_400_for_unknown_client_or_redirect_uri() {
+    let (client, _) = register_client(&app, false).await;
+
+    let mut unregistered_redirect = authorize_query(&client.client_id);
+    unregistered_redirect[2].1 = "https://evil.example.com/callback".to_owned();
+
+    let test_cases = [authorize_query("unknown"), unregistered_redirect];
+
+    for query in test_cases {
+        let response = app.get_authorize(&query).await;
+
+        assert!(response.headers().get(LOCATION).is_none());
+        assert_oauth_error(response, 400, "invalid_request").await;
+    }
+}
+
+#[api_test]
+async fn should_redirect_errors_to_client() {
+    let (client, _) = register_client(&app, false).await;
+
+    let mut unsupported_response_type = authorize_query(&client.client_id);
+    unsupported_response_type[0].1 = "token".to_owned();
+
+    let mut invalid_scope = authorize_query(&client.client_id);
+    invalid_scope[3].1 = "openid admin".to_owned();
+
+    let mut missing_pkce = authorize_query(&client.client_id);
+    missing_pkce.retain(|(name, _)| !name.starts_with("code_challenge"));
+
+    let test_cases = [
+        (unsupported_response_type, "unsupported_response_type"),
+        (invalid_scope, "invalid_scope"),
+        (missing_pkce, "invalid_request"),
+    ];
+
+    for (query, error) in test_cases {
+        let response = app.get_authorize(&query).await;
+
+        assert_eq!(response.status().as_u16(), 303);
+
+        let params = redirect_params(&response);
+
+        assert_eq!(param(&params, "error"), Some(error));
+        assert_eq!(param(&params, "state"), Some("af0ifjsldkj"));
+    }
+}
+
+#[api_test]
+async fn should_redirect_to_login_without_session() {
+    let (client, _) = register_client(&app, false).await;
+
+    let response = app.get_authorize(&authorize_query(&client.client_id)).await;
+
+    assert_eq!(response.status().as_u16(), 303);
+
+    let location = response
+        .headers()
+        .get(LOCATION)
+        .expect("No Location header found")
+        .to_str()
+        .unwrap();
+
+    assert!(location.starts_with("/?return_to=%2Fauthorize%3F"), "{}", location);
+}
+
+#[api_test]
+async fn should_issue_tokens_for_authorization_code() {
+    let (client, _) = register_client(&app, false).await;
+    let email = signup_and_login(&app).await;
+
+    let code = get_code(&app, &client.client_id).await;
+
+    let response = app
+        .post_token(&token_form(&client.client_id, &code, CODE_VERIFIER))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers().get("cache-control").map(|value| value.to_str().unwrap()),
+        Some("no-store")
+    );
+
+    let tokens = response
+        .json::<OAuthTokenResponse>()
+        .await
+        .expect("Could not deserialize response body to OAuthTokenResponse");
+
+    assert_eq!(tokens.token_type, "Bearer");
+    assert_eq!(tokens.scope, "openid email");
+    assert!(tokens.refresh_token.is_none());
+
+    let id_token = tokens.id_token.expect("No ID token found");
+    let payload = id_token.split('.').nth(1).expect("ID token is not a JWT");
+    let claims: IdTokenClaims = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).unwrap())
+        .expect("Could not deserialize ID token claims");
+
+    assert_eq!(claims.aud, client.client_id);
+    assert_eq!(claims.sub, email);
+    assert_eq!(claims.email, Some(email.clone()));
+    assert_eq!(claims.nonce.as_deref(), Some("n-0S6_WzA2Mj"));
+
+    // The access token is accepted by the UserInfo endpoint
+    let response = app.get_userinfo_with_bearer(&tokens.access_token).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let userinfo = response
+        .json::<UserInfoResponse>()
+        .await
+        .expect("Could not deserialize response body to UserInfoResponse");
+
+    assert_eq!(userinfo.email, email);
+}
+
+#[api_test]
+async fn should_not_accept_client_access_token_on_account_routes() {
+    let (client, _) = register_client(&app, false).await;
+    signup_and_login(&app).await;
+
+    let code = get_code(&app, &client.client_id).await;
+
+    let tokens = app
+        .post_token(&token_form(&client.client_id, &code, CODE_VERIFIER))
+        .await
+        .json::<OAuthTokenResponse>()
+        .await
+        .expect("Could not deserialize response body to OAuthTokenResponse");
+
+    let response = app.get_sessions_with_bearer(&tokens.access_token).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Introspection tells services which client the token was granted to
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": tokens.access_token, "introspect": true }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let introspection = response
+        .json::<IntrospectionResponse>()
+        .await
+        .expect("Could not deserialize response body to IntrospectionResponse");
+
+    assert!(introspection.active);
+    assert_eq!(introspection.client_id, Some(client.client_id));
+    assert_eq!(introspection.scope.as_deref(), Some("openid email"));
+
+    // The user sees the client's session next to their own
+    let response = app.get_sessions().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.json::<SessionsResponse>().await.unwrap().sessions.len(), 2);
+}
+
+#[api_test]
+async fn should_return_invalid_grant_if_code_verifier_wrong_or_code_reused() {
+    let (client, _) = register_client(&app, false).await;
+    signup_and_login(&app).await;
+
+    let code = get_code(&app, &client.client_id).await;
+
+    let wrong_verifier = CODE_VERIFIER.replace('d', "e");
+    let response = app
+        .post_token(&token_form(&client.client_id, &code, &wrong_verifier))
+        .await;
+
+    assert_oauth_error(response, 400, "invalid_grant").await;
+
+    // A failed attempt uses up the code
+    let response = app
+        .post_token(&token_form(&client.client_id, &code, CODE_VERIFIER))
+        .await;
+
+    assert_oauth_error(response, 400, "invalid_grant").await;
+
+    let code = get_code(&app, &client.client_id).await;
+    let form = token_form(&client.client_id, &code, CODE_VERIFIER);
+
+    let response = app.post_token(&form).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_token(&form).await;
+
+    assert_oauth_error(response, 400, "invalid_grant").await;
+}
+
+#[api_test]
+async fn should_require_secret_of_confidential_client() {
+    let (client, client_secret) = register_client(&app, true).await;
+    let client_secret = client_secret.unwrap();
+    signup_and_login(&app).await;
+
+    let code = get_code(&app, &client.client_id).await;
+    let form = token_form(&client.client_id, &code, CODE_VERIFIER);
+
+    let response = app.post_token(&form).await;
+
+    assert_oauth_error(response, 401, "invalid_client").await;
+
+    let response = app
+        .post_token_with_basic_auth(&form, &client.client_id, "wrong_secret")
+        .await;
+
+    assert_oauth_error(response, 401, "invalid_client").await;
+
+    let response = app
+        .post_token_with_basic_auth(
+            &form,
+            &client.client_id,
+            client_secret.as_ref().expose_secret(),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_return_unsupported_grant_type() {
+    let (client, _) = register_client(&app, false).await;
+
+    let mut form = token_form(&client.client_id, "code", CODE_VERIFIER);
+    form[0].1 = "password".to_owned();
+
+    let response = app.post_token(&form).await;
+
+    assert_oauth_error(response, 400, "unsupported_grant_type").await;
+}
+
+#[api_test]
+async fn should_only_let_admins_register_clients() {
+    let body = serde_json::json!({
+        "name": "app",
+        "redirectUris": [REDIRECT_URI],
+        "confidential": true
+    });
+
+    signup_and_login(&app).await;
+
+    let response = app.post_oauth_client(&body).await;
+
+    assert_eq!(response.status().as_u16(), 403);
+
+    let admin_email = get_random_email();
+    let mut admin = User::new(
+        Email::parse(Secret::new(admin_email.clone())).unwrap(),
+        Password::parse(Secret::new("password123".to_owned())).unwrap(),
+        false,
+    );
+    admin.roles = vec!["admin".to_owned()];
+    app.user_store.write().await.add_user(admin).await.unwrap();
+
+    let login_body = serde_json::json!({
+        "email": admin_email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let invalid_body = serde_json::json!({
+        "name": "app",
+        "redirectUris": ["not a uri"],
+    });
+
+    let response = app.post_oauth_client(&invalid_body).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Invalid redirect URI".to_owned()
+    );
+
+    let response = app.post_oauth_client(&body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let registered = response
+        .json::<RegisterClientResponse>()
+        .await
+        .expect("Could not deserialize response body to RegisterClientResponse");
+
+    assert!(registered.client_secret.is_some());
+
+    let client = app
+        .oauth_client_store
+        .read()
+        .await
+        .get_client(&registered.client_id)
+        .await
+        .expect("Client was not stored");
+
+    assert!(client.confidential);
+    assert_eq!(client.redirect_uris, vec![REDIRECT_URI.to_owned()]);
+}
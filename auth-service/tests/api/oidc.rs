@@ -0,0 +1,480 @@
+use auth_service::{
+    domain::{Email, ExternalIdentity, OidcProviderConfig},
+    routes::TwoFactorAuthResponse,
+    services::oidc_client::OidcClient,
+    utils::{
+        constants::JWT_COOKIE_NAME,
+        jwt_keys::{JwtKey, JwtKeySet},
+    },
+    ErrorResponse,
+};
+use jsonwebtoken::{encode, Algorithm, Header};
+use reqwest::Url;
+use secrecy::Secret;
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+use crate::helpers::{get_random_email, TestApp};
+
+const PROVIDER: &str = "corp";
+const CLIENT_ID: &str = "auth-service";
+
+// A stand-in for an external OpenID Connect provider
+struct Provider {
+    server: MockServer,
+    key: JwtKey,
+}
+
+fn provider_key(kid: &str) -> JwtKey {
+    JwtKey::from_pem(
+        kid.to_owned(),
+        Algorithm::RS256,
+        &Secret::new(include_str!("../fixtures/rs256_private_key.pem").to_owned()),
+        0,
+        None,
+    )
+    .unwrap()
+}
+
+impl Provider {
+    async fn start() -> Self {
+        let server = MockServer::start().await;
+        let key = provider_key("provider");
+
+        Mock::given(method("GET"))
+            .and(path("/.well-known/openid-configuration"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "issuer": server.uri(),
+                "authorization_endpoint": format!("{}/authorize", server.uri()),
+                "token_endpoint": format!("{}/token", server.uri()),
+                "jwks_uri": format!("{}/jwks", server.uri()),
+            })))
+            .mount(&server)
+            .await;
+
+        let jwk_set = JwtKeySet::new(vec![key.clone()]).unwrap().jwk_set();
+        Mock::given(method("GET"))
+            .and(path("/jwks"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(jwk_set))
+            .mount(&server)
+            .await;
+
+        Self { server, key }
+    }
+
+    fn client(&self) -> OidcClient {
+        let config = OidcProviderConfig {
+            name: PROVIDER.to_owned(),
+            issuer: self.server.uri(),
+            client_id: CLIENT_ID.to_owned(),
+            client_secret: Secret::new("provider-secret".to_owned()),
+            scopes: vec!["openid".to_owned(), "email".to_owned()],
+        };
+        OidcClient::new(config, reqwest::Client::new())
+    }
+
+    // Answer the next code exchange with an ID token carrying these claims
+    async fn issue_id_token(&self, claims: serde_json::Value) {
+        self.issue_id_token_signed_by(&self.key, claims).await;
+    }
+
+    async fn issue_id_token_signed_by(&self, key: &JwtKey, claims: serde_json::Value) {
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = key.kid().map(str::to_owned);
+        let id_token = encode(&header, &claims, key.encoding_key()).unwrap();
+
+        Mock::given(method("POST"))
+            .and(path("/token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "access_token": "provider-access-token",
+                "token_type": "Bearer",
+                "id_token": id_token,
+            })))
+            .up_to_n_times(1)
+            .mount(&self.server)
+            .await;
+    }
+
+    // Publish another signing key next to the current one
+    async fn rotate_key(&self, kid: &str) -> JwtKey {
+        let key = provider_key(kid);
+        let jwk_set = JwtKeySet::new(vec![self.key.clone(), key.clone()]).unwrap().jwk_set();
+
+        Mock::given(method("GET"))
+            .and(path("/jwks"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(jwk_set))
+            .with_priority(1)
+            .mount(&self.server)
+            .await;
+
+        key
+    }
+
+    async fn requests_to(&self, request_path: &str) -> usize {
+        let requests = self.server.received_requests().await.unwrap();
+        requests.iter().filter(|request| request.url.path() == request_path).count()
+    }
+
+    fn claims(&self, nonce: &str, subject: &str, email: &str) -> serde_json::Value {
+        serde_json::json!({
+            "iss": self.server.uri(),
+            "aud": CLIENT_ID,
+            "sub": subject,
+            "exp": chrono::Utc::now().timestamp() + 600,
+            "nonce": nonce,
+            "email": email,
+            "email_verified": true,
+        })
+    }
+}
+
+// Return the state and nonce the provider was sent by a started login or link
+fn provider_params(response: &reqwest::Response) -> (String, String) {
+    assert_eq!(response.status().as_u16(), 303);
+
+    let location = response.headers()["location"].to_str().unwrap();
+    let url = Url::parse(location).unwrap();
+    let param = |name: &str| {
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+            .unwrap()
+    };
+
+    assert_eq!(param("client_id"), CLIENT_ID);
+    assert_eq!(param("code_challenge_method"), "S256");
+
+    (param("state"), param("nonce"))
+}
+
+async fn start_login(app: &TestApp, return_to: Option<&str>) -> (String, String) {
+    provider_params(&app.get_oidc_login(PROVIDER, return_to).await)
+}
+
+#[tokio::test]
+async fn should_log_in_new_user_with_external_provider() {
+    let provider = Provider::start().await;
+    let client = provider.client();
+    let mut app = TestApp::new_with(|app_state| app_state.with_oidc_provider(client)).await;
+
+    let (state, nonce) = start_login(&app, Some("/app")).await;
+    let email = get_random_email();
+    provider.issue_id_token(provider.claims(&nonce, "user-1", &email)).await;
+
+    let response = app
+        .get_oidc_callback(PROVIDER, &[("code", "provider-code"), ("state", &state)])
+        .await;
+
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(response.headers()["location"], "/app");
+    assert!(response.cookies().any(|cookie| cookie.name() == JWT_COOKIE_NAME));
+
+    let email = Email::parse(Secret::new(email)).unwrap();
+    assert!(app.user_store.read().await.get_user(&email).await.is_ok());
+    let identity = app.external_identity_store.read().await.get_identity(PROVIDER, "user-1").await;
+    assert_eq!(identity.unwrap().email, email);
+
+    assert_eq!(app.get_sessions().await.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_fetch_provider_metadata_and_keys_once() {
+    let provider = Provider::start().await;
+    let client = provider.client();
+    let mut app = TestApp::new_with(|app_state| app_state.with_oidc_provider(client)).await;
+    let email = get_random_email();
+
+    for _ in 0..2 {
+        let (state, nonce) = start_login(&app, None).await;
+        provider.issue_id_token(provider.claims(&nonce, "user-1", &email)).await;
+
+        let response = app
+            .get_oidc_callback(PROVIDER, &[("code", "provider-code"), ("state", &state)])
+            .await;
+
+        assert_eq!(response.status().as_u16(), 303);
+    }
+
+    assert_eq!(provider.requests_to("/.well-known/openid-configuration").await, 1);
+    assert_eq!(provider.requests_to("/jwks").await, 1);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_fetch_keys_again_when_provider_rotates_them() {
+    let provider = Provider::start().await;
+    let client = provider.client();
+    let mut app = TestApp::new_with(|app_state| app_state.with_oidc_provider(client)).await;
+    let email = get_random_email();
+
+    let (state, nonce) = start_login(&app, None).await;
+    provider.issue_id_token(provider.claims(&nonce, "user-1", &email)).await;
+
+    let response = app
+        .get_oidc_callback(PROVIDER, &[("code", "provider-code"), ("state", &state)])
+        .await;
+
+    assert_eq!(response.status().as_u16(), 303);
+
+    let key = provider.rotate_key("provider-2").await;
+    let (state, nonce) = start_login(&app, None).await;
+    provider.issue_id_token_signed_by(&key, provider.claims(&nonce, "user-1", &email)).await;
+
+    let response = app
+        .get_oidc_callback(PROVIDER, &[("code", "provider-code"), ("state", &state)])
+        .await;
+
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(provider.requests_to("/jwks").await, 2);
+
+    app.clean_up().await;
+}
+
+async fn sign_up(app: &TestApp, requires_2fa: bool) -> String {
+    let email = get_random_email();
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": requires_2fa
+    });
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    email
+}
+
+#[tokio::test]
+async fn should_return_409_if_user_exists_but_did_not_link_provider() {
+    let provider = Provider::start().await;
+    let client = provider.client();
+    let mut app = TestApp::new_with(|app_state| app_state.with_oidc_provider(client)).await;
+
+    let email = sign_up(&app, false).await;
+
+    let (state, nonce) = start_login(&app, None).await;
+    provider.issue_id_token(provider.claims(&nonce, "user-2", &email)).await;
+
+    let response = app
+        .get_oidc_callback(PROVIDER, &[("code", "provider-code"), ("state", &state)])
+        .await;
+
+    assert_eq!(response.status().as_u16(), 409);
+    assert!(response.cookies().all(|cookie| cookie.name() != JWT_COOKIE_NAME));
+
+    let identity = app.external_identity_store.read().await.get_identity(PROVIDER, "user-2").await;
+    assert!(identity.is_err());
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_link_provider_to_logged_in_user() {
+    let provider = Provider::start().await;
+    let client = provider.client();
+    let mut app = TestApp::new_with(|app_state| app_state.with_oidc_provider(client)).await;
+
+    let email = sign_up(&app, false).await;
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "password123"
+    });
+    assert_eq!(app.post_login(&login_body).await.status().as_u16(), 200);
+
+    // The provider's account may use another email
+    let (state, nonce) = provider_params(&app.get_oidc_link(PROVIDER).await);
+    provider.issue_id_token(provider.claims(&nonce, "user-2", &get_random_email())).await;
+
+    let response = app
+        .get_oidc_callback(PROVIDER, &[("code", "provider-code"), ("state", &state)])
+        .await;
+
+    assert_eq!(response.status().as_u16(), 303);
+
+    let identity = app.external_identity_store.read().await.get_identity(PROVIDER, "user-2").await;
+    assert_eq!(identity.unwrap().email, Email::parse(Secret::new(email)).unwrap());
+
+    // From then on, the provider logs the user in
+    let (state, nonce) = start_login(&app, None).await;
+    provider.issue_id_token(provider.claims(&nonce, "user-2", "other@example.com")).await;
+
+    let response = app
+        .get_oidc_callback(PROVIDER, &[("code", "provider-code"), ("state", &state)])
+        .await;
+
+    assert_eq!(response.status().as_u16(), 303);
+    assert!(response.cookies().any(|cookie| cookie.name() == JWT_COOKIE_NAME));
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_linking_without_session() {
+    let provider = Provider::start().await;
+    let client = provider.client();
+    let mut app = TestApp::new_with(|app_state| app_state.with_oidc_provider(client)).await;
+
+    assert_eq!(app.get_oidc_link(PROVIDER).await.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_require_second_factor_of_users_with_2fa() {
+    let provider = Provider::start().await;
+    let client = provider.client();
+    let mut app = TestApp::new_with(|app_state| app_state.with_oidc_provider(client)).await;
+
+    let email = sign_up(&app, true).await;
+    let identity = ExternalIdentity {
+        provider: PROVIDER.to_owned(),
+        subject: "user-3".to_owned(),
+        email: Email::parse(Secret::new(email.clone())).unwrap(),
+    };
+    app.external_identity_store.write().await.add_identity(identity).await.unwrap();
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let (state, nonce) = start_login(&app, None).await;
+    provider.issue_id_token(provider.claims(&nonce, "user-3", &email)).await;
+
+    let response = app
+        .get_oidc_callback(PROVIDER, &[("code", "provider-code"), ("state", &state)])
+        .await;
+
+    assert_eq!(response.status().as_u16(), 206);
+    assert!(response.cookies().all(|cookie| cookie.name() != JWT_COOKIE_NAME));
+
+    let response_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+
+    assert_eq!(response_body.message, "2FA required");
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_state_does_not_match() {
+    let provider = Provider::start().await;
+    let client = provider.client();
+    let mut app = TestApp::new_with(|app_state| app_state.with_oidc_provider(client)).await;
+
+    let (state, nonce) = start_login(&app, None).await;
+    provider.issue_id_token(provider.claims(&nonce, "user-1", &get_random_email())).await;
+
+    let forged_state = "a".repeat(state.len());
+    let response = app
+        .get_oidc_callback(PROVIDER, &[("code", "provider-code"), ("state", &forged_state)])
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(
+        response.json::<ErrorResponse>().await.unwrap().error,
+        "External login failed"
+    );
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_callback_is_replayed() {
+    let provider = Provider::start().await;
+    let client = provider.client();
+    let mut app = TestApp::new_with(|app_state| app_state.with_oidc_provider(client)).await;
+
+    let (state, nonce) = start_login(&app, None).await;
+    provider.issue_id_token(provider.claims(&nonce, "user-1", &get_random_email())).await;
+
+    let query = [("code", "provider-code"), ("state", state.as_str())];
+    assert_eq!(app.get_oidc_callback(PROVIDER, &query).await.status().as_u16(), 303);
+    assert_eq!(app.get_oidc_callback(PROVIDER, &query).await.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_nonce_does_not_match() {
+    let provider = Provider::start().await;
+    let client = provider.client();
+    let mut app = TestApp::new_with(|app_state| app_state.with_oidc_provider(client)).await;
+
+    let (state, _) = start_login(&app, None).await;
+    provider.issue_id_token(provider.claims("another-nonce", "user-1", &get_random_email())).await;
+
+    let response = app
+        .get_oidc_callback(PROVIDER, &[("code", "provider-code"), ("state", &state)])
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    assert!(!response.cookies().any(|cookie| cookie.name() == JWT_COOKIE_NAME));
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_id_token_is_for_another_client() {
+    let provider = Provider::start().await;
+    let client = provider.client();
+    let mut app = TestApp::new_with(|app_state| app_state.with_oidc_provider(client)).await;
+
+    let (state, nonce) = start_login(&app, None).await;
+    let mut claims = provider.claims(&nonce, "user-1", &get_random_email());
+    claims["aud"] = serde_json::json!("another-client");
+    provider.issue_id_token(claims).await;
+
+    let response = app
+        .get_oidc_callback(PROVIDER, &[("code", "provider-code"), ("state", &state)])
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_email_is_not_verified() {
+    let provider = Provider::start().await;
+    let client = provider.client();
+    let mut app = TestApp::new_with(|app_state| app_state.with_oidc_provider(client)).await;
+
+    let (state, nonce) = start_login(&app, None).await;
+    let email = get_random_email();
+    let mut claims = provider.claims(&nonce, "user-1", &email);
+    claims["email_verified"] = serde_json::json!(false);
+    provider.issue_id_token(claims).await;
+
+    let response = app
+        .get_oidc_callback(PROVIDER, &[("code", "provider-code"), ("state", &state)])
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let email = Email::parse(Secret::new(email)).unwrap();
+    assert!(app.user_store.read().await.get_user(&email).await.is_err());
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_404_for_unknown_provider() {
+    let mut app = TestApp::new().await;
+
+    let response = app.get_oidc_login("unknown", None).await;
+
+    assert_eq!(response.status().as_u16(), 404);
+    assert_eq!(
+        response.json::<ErrorResponse>().await.unwrap().error,
+        "Unknown identity provider"
+    );
+
+    app.clean_up().await;
+}
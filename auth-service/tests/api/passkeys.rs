@@ -0,0 +1,376 @@
+use auth_service::{
+    domain::{Email, LoginAttemptId, Passkey, TwoFAMethod},
+    routes::{PasskeyAuthResponse, PasskeyCreationOptions, PasskeyOptionsResponse, PasskeyRequestOptions, PasskeyResponse},
+    utils::constants::{JWT_COOKIE_NAME, RELYING_PARTY},
+    ErrorResponse,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ciborium::value::Value;
+use p256::ecdsa::{signature::Signer, Signature, SigningKey};
+use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
+use test_helpers::api_test;
+use wiremock::{matchers::path, Mock, ResponseTemplate};
+use crate::helpers::{get_random_email, TestApp};
+
+const USER_PRESENT: u8 = 0x01;
+const USER_VERIFIED: u8 = 0x04;
+const ATTESTED_CREDENTIAL: u8 = 0x40;
+
+// A software stand-in for a security key or platform authenticator
+struct Authenticator {
+    key: SigningKey,
+    credential_id: Vec<u8>,
+    sign_count: u32,
+    origin: String,
+}
+
+impl Authenticator {
+    fn new() -> Self {
+        Self {
+            key: SigningKey::random(&mut rand::thread_rng()),
+            credential_id: uuid::Uuid::new_v4().as_bytes().to_vec(),
+            sign_count: 0,
+            origin: RELYING_PARTY.origin.clone(),
+        }
+    }
+
+    fn id(&self) -> String {
+        URL_SAFE_NO_PAD.encode(&self.credential_id)
+    }
+
+    // The passkey as the service would have stored it after registration
+    fn passkey(&self, email: &str) -> Passkey {
+        Passkey {
+            id: self.id(),
+            email: Email::parse(Secret::new(email.to_owned())).unwrap(),
+            name: "Security key".to_owned(),
+            public_key: self.key.verifying_key().to_encoded_point(false).as_bytes().to_vec(),
+            sign_count: self.sign_count,
+            created_at: 0,
+            last_used_at: None,
+        }
+    }
+
+    fn create(&self, options: &PasskeyCreationOptions) -> serde_json::Value {
+        let point = self.key.verifying_key().to_encoded_point(false);
+        let cose_key = cbor(&Value::Map(vec![
+            (Value::from(1), Value::from(2)),
+            (Value::from(3), Value::from(-7)),
+            (Value::from(-1), Value::from(1)),
+            (Value::from(-2), Value::Bytes(point.x().unwrap().to_vec())),
+            (Value::from(-3), Value::Bytes(point.y().unwrap().to_vec())),
+        ]));
+
+        let auth_data = [
+            self.authenticator_data(&options.rp.id, USER_PRESENT | USER_VERIFIED | ATTESTED_CREDENTIAL),
+            vec![0; 16],
+            (self.credential_id.len() as u16).to_be_bytes().to_vec(),
+            self.credential_id.clone(),
+            cose_key,
+        ]
+        .concat();
+
+        let attestation_object = cbor(&Value::Map(vec![
+            (Value::from("fmt"), Value::from("none")),
+            (Value::from("attStmt"), Value::Map(vec![])),
+            (Value::from("authData"), Value::Bytes(auth_data)),
+        ]));
+
+        serde_json::json!({
+            "id": self.id(),
+            "name": "Security key",
+            "response": {
+                "clientDataJSON": URL_SAFE_NO_PAD.encode(self.client_data("webauthn.create", &options.challenge)),
+                "attestationObject": URL_SAFE_NO_PAD.encode(attestation_object),
+            }
+        })
+    }
+
+    fn get(&mut self, options: &PasskeyRequestOptions, flags: u8) -> serde_json::Value {
+        self.sign_count += 1;
+
+        let client_data = self.client_data("webauthn.get", &options.challenge);
+        let auth_data = self.authenticator_data(&options.rp_id, flags);
+        let signed_data = [&auth_data[..], &Sha256::digest(&client_data)[..]].concat();
+        let signature: Signature = self.key.sign(&signed_data);
+
+        serde_json::json!({
+            "id": self.id(),
+            "response": {
+                "clientDataJSON": URL_SAFE_NO_PAD.encode(client_data),
+                "authenticatorData": URL_SAFE_NO_PAD.encode(auth_data),
+                "signature": URL_SAFE_NO_PAD.encode(signature.to_der().as_bytes()),
+            }
+        })
+    }
+
+    fn client_data(&self, ceremony_type: &str, challenge: &str) -> Vec<u8> {
+        serde_json::to_vec(&serde_json::json!({
+            "type": ceremony_type,
+            "challenge": challenge,
+            "origin": self.origin,
+        }))
+        .unwrap()
+    }
+
+    fn authenticator_data(&self, rp_id: &str, flags: u8) -> Vec<u8> {
+        [&Sha256::digest(rp_id.as_bytes())[..], &[flags], &self.sign_count.to_be_bytes()].concat()
+    }
+}
+
+fn cbor(value: &Value) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    ciborium::ser::into_writer(value, &mut bytes).unwrap();
+    bytes
+}
+
+async fn sign_up(app: &TestApp, requires_2fa: bool) -> String {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": requires_2fa
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    random_email
+}
+
+async fn log_in(app: &TestApp, email: &str) -> reqwest::Response {
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+    });
+
+    app.post_login(&login_body).await
+}
+
+async fn start_registration(app: &TestApp) -> PasskeyCreationOptions {
+    let response = app.post_passkey_registration_start().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<PasskeyOptionsResponse<PasskeyCreationOptions>>()
+        .await
+        .expect("Could not deserialize response body to PasskeyCreationOptions")
+        .public_key
+}
+
+async fn start_login(app: &TestApp) -> PasskeyRequestOptions {
+    let response = app.post_passkey_login_start().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<PasskeyOptionsResponse<PasskeyRequestOptions>>()
+        .await
+        .expect("Could not deserialize response body to PasskeyRequestOptions")
+        .public_key
+}
+
+// Log in with the password of a user who has the authenticator's passkey, and return the
+// passkey challenge
+async fn log_in_with_passkey_required(app: &TestApp, authenticator: &Authenticator) -> (String, PasskeyRequestOptions) {
+    let (email, body) = log_in_with_passkey_or_code(app, authenticator).await;
+
+    (email, body.public_key)
+}
+
+// Users with a passkey also get a code of their 2FA method, in case it isn't at hand
+async fn log_in_with_passkey_or_code(app: &TestApp, authenticator: &Authenticator) -> (String, PasskeyAuthResponse) {
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let email = sign_up(app, true).await;
+    app.passkey_store
+        .write()
+        .await
+        .add_passkey(authenticator.passkey(&email))
+        .await
+        .unwrap();
+
+    let response = log_in(app, &email).await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let body = response
+        .json::<PasskeyAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to PasskeyAuthResponse");
+
+    assert_eq!(body.message, "Passkey required");
+    assert_eq!(body.two_fa_method, TwoFAMethod::Email);
+
+    (email, body)
+}
+
+#[api_test]
+async fn should_register_passkey_and_log_in_without_password() {
+    let email = sign_up(&app, false).await;
+    assert_eq!(log_in(&app, &email).await.status().as_u16(), 200);
+
+    let options = start_registration(&app).await;
+    assert_eq!(options.rp.id, RELYING_PARTY.id);
+    assert!(options.exclude_credentials.is_empty());
+
+    let mut authenticator = Authenticator::new();
+    let response = app.post_passkey_registration_finish(&authenticator.create(&options)).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let passkey = response
+        .json::<PasskeyResponse>()
+        .await
+        .expect("Could not deserialize response body to PasskeyResponse");
+    assert_eq!(passkey.id, authenticator.id());
+    assert_eq!(passkey.name, "Security key");
+
+    // Registering it again is prevented
+    let options = start_registration(&app).await;
+    assert_eq!(options.exclude_credentials[0].id, authenticator.id());
+
+    assert_eq!(app.post_logout().await.status().as_u16(), 200);
+
+    let options = start_login(&app).await;
+    assert_eq!(options.user_verification, "required");
+
+    let response = app
+        .post_passkey_login_finish(&authenticator.get(&options, USER_PRESENT | USER_VERIFIED))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.cookies().any(|cookie| cookie.name() == JWT_COOKIE_NAME));
+
+    let stored = app.passkey_store.read().await.get_passkey(&authenticator.id()).await.unwrap();
+    assert_eq!(stored.sign_count, 1);
+    assert!(stored.last_used_at.is_some());
+}
+
+#[api_test]
+async fn should_require_passkey_as_second_factor() {
+    let mut authenticator = Authenticator::new();
+    let (_, options) = log_in_with_passkey_required(&app, &authenticator).await;
+
+    assert_eq!(options.allow_credentials[0].id, authenticator.id());
+
+    // A second factor only needs the user's presence
+    let response = app.post_passkey_login_finish(&authenticator.get(&options, USER_PRESENT)).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.cookies().any(|cookie| cookie.name() == JWT_COOKIE_NAME));
+}
+
+#[api_test]
+async fn should_accept_2fa_code_instead_of_passkey() {
+    let authenticator = Authenticator::new();
+    let (email, body) = log_in_with_passkey_or_code(&app, &authenticator).await;
+
+    let login_attempt_id = LoginAttemptId::parse(Secret::new(body.login_attempt_id.clone())).unwrap();
+    let (_, code) = app.two_fa_code_store.read().await.get_code(&login_attempt_id).await.unwrap();
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": body.login_attempt_id,
+            "2FACode": code.as_ref().expose_secret()
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.cookies().any(|cookie| cookie.name() == JWT_COOKIE_NAME));
+}
+
+#[api_test]
+async fn should_return_401_if_assertion_is_replayed() {
+    let mut authenticator = Authenticator::new();
+    let (_, options) = log_in_with_passkey_required(&app, &authenticator).await;
+
+    let body = authenticator.get(&options, USER_PRESENT);
+
+    assert_eq!(app.post_passkey_login_finish(&body).await.status().as_u16(), 200);
+
+    let response = app.post_passkey_login_finish(&body).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_return_401_if_passkey_belongs_to_another_user() {
+    let authenticator = Authenticator::new();
+    let (_, options) = log_in_with_passkey_required(&app, &authenticator).await;
+
+    let mut other_authenticator = Authenticator::new();
+    let other_email = sign_up(&app, false).await;
+    app.passkey_store
+        .write()
+        .await
+        .add_passkey(other_authenticator.passkey(&other_email))
+        .await
+        .unwrap();
+
+    let response = app
+        .post_passkey_login_finish(&other_authenticator.get(&options, USER_PRESENT))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_return_401_if_sign_count_did_not_increase() {
+    let mut authenticator = Authenticator::new();
+    authenticator.sign_count = 10;
+    let (_, options) = log_in_with_passkey_required(&app, &authenticator).await;
+
+    // A clone of the authenticator that fell behind the original
+    authenticator.sign_count = 5;
+    let response = app.post_passkey_login_finish(&authenticator.get(&options, USER_PRESENT)).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_return_401_if_passwordless_login_is_not_user_verified() {
+    let mut authenticator = Authenticator::new();
+    let email = sign_up(&app, false).await;
+    app.passkey_store
+        .write()
+        .await
+        .add_passkey(authenticator.passkey(&email))
+        .await
+        .unwrap();
+
+    let options = start_login(&app).await;
+    let response = app.post_passkey_login_finish(&authenticator.get(&options, USER_PRESENT)).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_return_400_if_registration_comes_from_another_origin() {
+    let email = sign_up(&app, false).await;
+    assert_eq!(log_in(&app, &email).await.status().as_u16(), 200);
+
+    let options = start_registration(&app).await;
+
+    let mut authenticator = Authenticator::new();
+    authenticator.origin = "https://phishing.example.com".to_owned();
+    let response = app.post_passkey_registration_finish(&authenticator.create(&options)).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(
+        response.json::<ErrorResponse>().await.unwrap().error,
+        "Invalid passkey"
+    );
+
+    let email = Email::parse(Secret::new(email)).unwrap();
+    assert!(app.passkey_store.read().await.get_passkeys(&email).await.unwrap().is_empty());
+}
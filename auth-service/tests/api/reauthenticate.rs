@@ -0,0 +1,310 @@
+use auth_service::{
+    domain::{Email, LoginAttemptId},
+    routes::{IntrospectionResponse, TwoFactorAuthResponse},
+    utils::constants::{JWT_COOKIE_NAME, REAUTHENTICATION_MAX_ATTEMPTS},
+    ErrorResponse,
+};
+use secrecy::{ExposeSecret, Secret};
+use test_helpers::api_test;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+use crate::helpers::{get_random_email, TestApp};
+
+async fn sign_up(app: &TestApp, requires_2fa: bool) -> String {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": requires_2fa
+    });
+
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    random_email
+}
+
+async fn mount_email_server(app: &TestApp, expected_emails: u64) {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(expected_emails)
+        .mount(&app.email_server)
+        .await;
+}
+
+async fn current_code(app: &TestApp, login_attempt_id: &str) -> String {
+    let (_, code) = app
+        .two_fa_code_store
+        .read()
+        .await
+        .get_code(&LoginAttemptId::parse(Secret::new(login_attempt_id.to_owned())).unwrap())
+        .await
+        .unwrap();
+
+    code.as_ref().expose_secret().to_owned()
+}
+
+async fn log_in(app: &TestApp, email: &str) {
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+async fn log_in_with_2fa(app: &TestApp, email: &str) {
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": current_code(app, &login_attempt_id).await
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+// Pretends the user logged in an hour ago, then refreshes so the auth cookie says so
+async fn authenticate_long_ago(app: &TestApp, email: &str) {
+    let email = Email::parse(Secret::new(email.to_owned())).unwrap();
+    let mut session_store = app.session_store.write().await;
+
+    for mut session in session_store.get_sessions(&email).await.unwrap() {
+        session.auth_time -= 3600;
+        session_store.add_session(session).await.unwrap();
+    }
+
+    drop(session_store);
+
+    assert_eq!(app.post_refresh().await.status().as_u16(), 200);
+}
+
+async fn change_password(app: &TestApp) -> reqwest::Response {
+    app.post_change_password(&serde_json::json!({
+        "currentPassword": "password123",
+        "newPassword": "newpassword123"
+    }))
+    .await
+}
+
+async fn introspect(app: &TestApp, response: &reqwest::Response) -> IntrospectionResponse {
+    let token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    app.post_verify_token(&serde_json::json!({ "token": token, "introspect": true }))
+        .await
+        .json::<IntrospectionResponse>()
+        .await
+        .expect("Could not deserialize response body to IntrospectionResponse")
+}
+
+#[api_test]
+async fn should_return_401_on_sensitive_routes_if_authenticated_long_ago() {
+    let email = sign_up(&app, false).await;
+    log_in(&app, &email).await;
+    authenticate_long_ago(&app, &email).await;
+
+    let response = change_password(&app).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    assert!(response.headers()["www-authenticate"]
+        .to_str()
+        .unwrap()
+        .contains("insufficient_user_authentication"));
+    assert_eq!(
+        response.json::<ErrorResponse>().await.unwrap().error,
+        "Reauthentication required"
+    );
+
+    let responses = [
+        app.post_api_key(&serde_json::json!({ "name": "deploy script" })).await,
+        app.post_totp_enroll().await,
+        app.post_sms_enroll(&serde_json::json!({ "phoneNumber": "+15555550100" })).await,
+        app.post_recovery_codes().await,
+        app.post_passkey_registration_start().await,
+        app.delete_trusted_device("unknown").await,
+    ];
+
+    for response in responses {
+        assert_eq!(response.status().as_u16(), 401, "{}", response.url());
+    }
+
+    // Other routes still accept the session
+    assert_eq!(app.get_api_keys().await.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_allow_sensitive_routes_after_reauthenticating_with_password() {
+    let email = sign_up(&app, false).await;
+    log_in(&app, &email).await;
+    authenticate_long_ago(&app, &email).await;
+
+    let response = app
+        .post_reauthenticate(&serde_json::json!({ "password": "password123" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let claims = introspect(&app, &response).await;
+
+    assert_eq!(claims.amr, Some(vec!["pwd".to_owned()]));
+
+    // The new auth token keeps the session, so a refresh doesn't undo the reauthentication
+    assert_eq!(app.post_refresh().await.status().as_u16(), 200);
+    assert_eq!(change_password(&app).await.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_reauthenticate_with_emailed_2fa_code() {
+    let email = sign_up(&app, true).await;
+    // The login code, then the reauthentication code
+    mount_email_server(&app, 2).await;
+    log_in_with_2fa(&app, &email).await;
+    authenticate_long_ago(&app, &email).await;
+
+    let response = app.post_reauthenticate_send_2fa().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+
+    let response = app
+        .post_reauthenticate(&serde_json::json!({
+            "password": "password123",
+            "loginAttemptId": login_attempt_id,
+            "2FACode": current_code(&app, &login_attempt_id).await
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let claims = introspect(&app, &response).await;
+
+    assert_eq!(
+        claims.amr,
+        Some(vec!["pwd".to_owned(), "otp".to_owned(), "mfa".to_owned()])
+    );
+
+    let response = app.post_api_key(&serde_json::json!({ "name": "deploy script" })).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+}
+
+#[api_test]
+async fn should_return_401_if_credentials_are_incorrect() {
+    let email = sign_up(&app, true).await;
+    mount_email_server(&app, 2).await;
+    log_in_with_2fa(&app, &email).await;
+    authenticate_long_ago(&app, &email).await;
+
+    let response = app
+        .post_reauthenticate(&serde_json::json!({ "password": "wrongpassword" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let login_attempt_id = app
+        .post_reauthenticate_send_2fa()
+        .await
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+
+    let code = current_code(&app, &login_attempt_id).await;
+    let wrong_code = if code == "000000" { "111111" } else { "000000" };
+
+    let response = app
+        .post_reauthenticate(&serde_json::json!({
+            "loginAttemptId": login_attempt_id,
+            "2FACode": wrong_code
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    assert_eq!(change_password(&app).await.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_log_out_session_after_too_many_failed_attempts() {
+    let email = sign_up(&app, false).await;
+    log_in(&app, &email).await;
+    authenticate_long_ago(&app, &email).await;
+
+    let wrong_password = serde_json::json!({ "password": "wrongpassword" });
+
+    for _ in 1..*REAUTHENTICATION_MAX_ATTEMPTS {
+        assert_eq!(app.post_reauthenticate(&wrong_password).await.status().as_u16(), 401);
+    }
+
+    let response = app.post_reauthenticate(&wrong_password).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(
+        response.json::<ErrorResponse>().await.unwrap().error,
+        "Too many failed attempts, log in again"
+    );
+
+    // The right password comes too late, the session is gone
+    let response = app
+        .post_reauthenticate(&serde_json::json!({ "password": "password123" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(app.post_refresh().await.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_return_400_if_no_credentials_are_given() {
+    let email = sign_up(&app, false).await;
+    log_in(&app, &email).await;
+
+    let test_cases = [
+        serde_json::json!({}),
+        serde_json::json!({ "password": "short" }),
+        serde_json::json!({ "2FACode": "123456" }),
+    ];
+
+    for body in test_cases {
+        assert_eq!(app.post_reauthenticate(&body).await.status().as_u16(), 400, "{}", body);
+    }
+}
+
+#[api_test]
+async fn should_return_401_if_not_logged_in() {
+    let response = app
+        .post_reauthenticate(&serde_json::json!({ "password": "password123" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
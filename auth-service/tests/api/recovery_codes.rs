@@ -0,0 +1,166 @@
+use auth_service::{
+    domain::Email,
+    routes::{RecoveryCodesResponse, SignupResponse, TwoFactorAuthResponse},
+    utils::constants::JWT_COOKIE_NAME,
+};
+use secrecy::Secret;
+use test_helpers::api_test;
+use wiremock::{
+    matchers::{body_string_contains, method, path},
+    Mock, ResponseTemplate,
+};
+use crate::helpers::{get_random_email, TestApp};
+
+async fn sign_up_with_2fa(app: &TestApp, email: &str) -> Vec<String> {
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": true
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    response
+        .json::<SignupResponse>()
+        .await
+        .expect("Could not deserialize response body to SignupResponse")
+        .recovery_codes
+        .expect("No recovery codes in response")
+}
+
+// Login codes are still emailed to the user, even when they end up using a recovery code
+async fn mount_email_server(app: &TestApp) {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+}
+
+async fn verify_2fa_with(app: &TestApp, email: &str, code: &str) -> reqwest::Response {
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "password123"
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let response_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+
+    app.post_verify_2fa(&serde_json::json!({
+        "email": email,
+        "loginAttemptId": response_body.login_attempt_id,
+        "2FACode": code
+    }))
+    .await
+}
+
+#[api_test]
+async fn should_log_in_with_recovery_code_only_once() {
+    let random_email = get_random_email();
+    let codes = sign_up_with_2fa(&app, &random_email).await;
+
+    assert_eq!(codes.len(), 10);
+    assert!(codes.iter().all(|code| code.len() == 19));
+
+    // The user is told a recovery code was used, mounted first so it takes precedence
+    Mock::given(path("/email"))
+        .and(body_string_contains("Recovery code used"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    mount_email_server(&app).await;
+
+    let response = verify_2fa_with(&app, &random_email, &codes[0]).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.cookies().any(|cookie| cookie.name() == JWT_COOKIE_NAME));
+
+    let remaining = app
+        .recovery_code_store
+        .read()
+        .await
+        .count_codes(&Email::parse(Secret::new(random_email.clone())).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(remaining, 9);
+
+    let response = verify_2fa_with(&app, &random_email, &codes[0]).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_accept_recovery_codes_in_any_case_without_dashes() {
+    let random_email = get_random_email();
+    let codes = sign_up_with_2fa(&app, &random_email).await;
+    mount_email_server(&app).await;
+
+    let code = codes[3].replace('-', "").to_uppercase();
+    let response = verify_2fa_with(&app, &random_email, &code).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_invalidate_old_codes_when_regenerating() {
+    let random_email = get_random_email();
+    let old_codes = sign_up_with_2fa(&app, &random_email).await;
+    mount_email_server(&app).await;
+
+    let response = verify_2fa_with(&app, &random_email, &old_codes[0]).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_recovery_codes().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let new_codes = response
+        .json::<RecoveryCodesResponse>()
+        .await
+        .expect("Could not deserialize response body to RecoveryCodesResponse")
+        .recovery_codes;
+
+    assert_eq!(new_codes.len(), 10);
+    assert!(new_codes.iter().all(|code| !old_codes.contains(code)));
+
+    let response = verify_2fa_with(&app, &random_email, &old_codes[1]).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = verify_2fa_with(&app, &random_email, &new_codes[0]).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_not_return_codes_without_2fa() {
+    let signup_body = serde_json::json!({
+        "email": get_random_email(),
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response_body = response
+        .json::<SignupResponse>()
+        .await
+        .expect("Could not deserialize response body to SignupResponse");
+
+    assert_eq!(response_body.recovery_codes, None);
+}
+
+#[api_test]
+async fn should_return_401_if_not_logged_in() {
+    let response = app.post_recovery_codes().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
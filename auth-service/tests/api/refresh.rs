@@ -0,0 +1,278 @@
+use auth_service::{
+    domain::RefreshToken,
+    utils::{
+        auth::TokenResponse,
+        constants::{CSRF_COOKIE_NAME, JWT_COOKIE_NAME, REFRESH_COOKIE_NAME},
+    },
+    ErrorResponse,
+};
+use redis::Commands;
+use reqwest::Url;
+use secrecy::Secret;
+use test_helpers::api_test;
+use crate::helpers::{configure_redis, get_random_email, TestApp};
+
+#[api_test]
+async fn should_return_200_and_rotate_refresh_token() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let refresh_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == REFRESH_COOKIE_NAME)
+        .expect("No refresh cookie found");
+
+    let old_refresh_token = refresh_cookie.value().to_owned();
+
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    assert!(!auth_cookie.value().is_empty());
+
+    let refresh_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == REFRESH_COOKIE_NAME)
+        .expect("No refresh cookie found");
+
+    assert!(!refresh_cookie.value().is_empty());
+    assert_ne!(refresh_cookie.value(), old_refresh_token);
+
+    let old_entry = app
+        .refresh_token_store
+        .read()
+        .await
+        .get_token(&RefreshToken::parse(Secret::new(old_refresh_token)).unwrap())
+        .await
+        .expect("Failed to get refresh token");
+
+    assert!(old_entry.used);
+
+    // The rotated token can be used in turn
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_only_store_hash_of_refresh_token() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let refresh_token = response
+        .cookies()
+        .find(|cookie| cookie.name() == REFRESH_COOKIE_NAME)
+        .expect("No refresh cookie found")
+        .value()
+        .to_owned();
+
+    let hash = RefreshToken::parse(Secret::new(refresh_token.clone())).unwrap().hash();
+
+    let mut redis = configure_redis();
+    let stored_as_token: bool = redis.exists(format!("refresh_token:{}", refresh_token)).unwrap();
+    let stored_as_hash: bool = redis.exists(format!("refresh_token:{}", hash)).unwrap();
+
+    assert!(!stored_as_token);
+    assert!(stored_as_hash);
+}
+
+#[api_test]
+async fn should_return_400_if_refresh_cookie_missing() {
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Missing auth token".to_owned()
+    );
+}
+
+#[api_test]
+async fn should_return_401_if_invalid_refresh_token() {
+    app.cookie_jar.add_cookie_str(
+        &format!(
+            "{}=invalid; HttpOnly; SameSite=Lax; Secure; Path=/",
+            REFRESH_COOKIE_NAME
+        ),
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+    app.cookie_jar.add_cookie_str(
+        &format!("{}=csrf; SameSite=Lax; Path=/", CSRF_COOKIE_NAME),
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Invalid auth token".to_owned()
+    );
+}
+
+#[api_test]
+async fn should_revoke_token_family_if_refresh_token_reused() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let old_refresh_token = response
+        .cookies()
+        .find(|cookie| cookie.name() == REFRESH_COOKIE_NAME)
+        .expect("No refresh cookie found")
+        .value()
+        .to_owned();
+
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let new_refresh_token = response
+        .cookies()
+        .find(|cookie| cookie.name() == REFRESH_COOKIE_NAME)
+        .expect("No refresh cookie found")
+        .value()
+        .to_owned();
+
+    // Replay the token that was already rotated
+    let url = Url::parse(&app.address).expect("Failed to parse URL");
+    app.cookie_jar.add_cookie_str(
+        &format!("{}={}; HttpOnly; SameSite=Lax; Path=/", REFRESH_COOKIE_NAME, old_refresh_token),
+        &url,
+    );
+
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    // The session is logged out, so its auth token is no longer accepted either
+    assert_eq!(app.get_sessions().await.status().as_u16(), 401);
+
+    // The newest token of the family is revoked as well
+    app.cookie_jar.add_cookie_str(
+        &format!("{}={}; HttpOnly; SameSite=Lax; Path=/", REFRESH_COOKIE_NAME, new_refresh_token),
+        &url,
+    );
+
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_return_tokens_in_body_if_refresh_token_in_body() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "tokenDelivery": "body"
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let old_tokens = response
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse");
+
+    let refresh_body = serde_json::json!({
+        "refreshToken": old_tokens.refresh_token,
+    });
+
+    let response = app.post_refresh_with_body(&refresh_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.cookies().next().is_none());
+
+    let new_tokens = response
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse");
+
+    assert_ne!(new_tokens.refresh_token, old_tokens.refresh_token);
+
+    let response = app.get_sessions_with_bearer(&new_tokens.access_token).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
@@ -0,0 +1,182 @@
+use std::time::Duration;
+use auth_service::{
+    domain::LoginAttemptId,
+    routes::TwoFactorAuthResponse,
+    utils::constants::{test, JWT_COOKIE_NAME, TWO_FA_MAX_RESENDS},
+    ErrorResponse,
+};
+use secrecy::{ExposeSecret, Secret};
+use test_helpers::api_test;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+use crate::helpers::{get_random_email, TestApp};
+
+// Signs up a user with 2FA and logs in, returning the email and login attempt id
+async fn start_login(app: &TestApp, expected_emails: u64) -> (String, String) {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": true
+    });
+
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(expected_emails)
+        .mount(&app.email_server)
+        .await;
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123"
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+
+    (random_email, login_attempt_id)
+}
+
+async fn current_code(app: &TestApp, login_attempt_id: &str) -> String {
+    let (_, code) = app
+        .two_fa_code_store
+        .read()
+        .await
+        .get_code(&LoginAttemptId::parse(Secret::new(login_attempt_id.to_owned())).unwrap())
+        .await
+        .unwrap();
+
+    code.as_ref().expose_secret().to_owned()
+}
+
+async fn wait_for_cooldown() {
+    tokio::time::sleep(Duration::from_millis(test::two_fa::RESEND_COOLDOWN_SECONDS as u64 * 1000 + 100)).await;
+}
+
+#[api_test]
+async fn should_send_fresh_code_for_the_same_attempt() {
+    let (email, login_attempt_id) = start_login(&app, 2).await;
+    let old_code = current_code(&app, &login_attempt_id).await;
+
+    wait_for_cooldown().await;
+
+    let response = app
+        .post_resend_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let new_code = current_code(&app, &login_attempt_id).await;
+    let verify = |code: &str| {
+        serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code
+        })
+    };
+
+    // Unless the same code happened to be drawn twice, the old one no longer works
+    if old_code != new_code {
+        assert_eq!(app.post_verify_2fa(&verify(&old_code)).await.status().as_u16(), 401);
+    }
+
+    let response = app.post_verify_2fa(&verify(&new_code)).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.cookies().any(|cookie| cookie.name() == JWT_COOKIE_NAME));
+}
+
+#[api_test]
+async fn should_return_429_with_retry_after_if_resent_too_soon() {
+    let (email, login_attempt_id) = start_login(&app, 1).await;
+
+    let response = app
+        .post_resend_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 429);
+
+    let retry_after: i64 = response.headers()["retry-after"]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(retry_after > 0 && retry_after <= test::two_fa::RESEND_COOLDOWN_SECONDS);
+
+    assert_eq!(
+        response.json::<ErrorResponse>().await.unwrap().error,
+        "Code was sent too recently"
+    );
+}
+
+#[api_test]
+async fn should_return_429_after_too_many_resends() {
+    let (email, login_attempt_id) = start_login(&app, 1 + *TWO_FA_MAX_RESENDS as u64).await;
+
+    let request_body = serde_json::json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id
+    });
+
+    for _ in 0..*TWO_FA_MAX_RESENDS {
+        wait_for_cooldown().await;
+        assert_eq!(app.post_resend_2fa(&request_body).await.status().as_u16(), 200);
+    }
+
+    wait_for_cooldown().await;
+    let response = app.post_resend_2fa(&request_body).await;
+
+    assert_eq!(response.status().as_u16(), 429);
+    assert!(response.headers().get("retry-after").is_none());
+    assert_eq!(
+        response.json::<ErrorResponse>().await.unwrap().error,
+        "Too many codes sent, log in again"
+    );
+}
+
+#[api_test]
+async fn should_return_401_if_login_attempt_is_unknown() {
+    let (email, _) = start_login(&app, 1).await;
+
+    wait_for_cooldown().await;
+
+    let response = app
+        .post_resend_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": LoginAttemptId::default().as_ref().expose_secret()
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_return_400_if_invalid_input() {
+    let test_cases = [
+        serde_json::json!({ "email": "invalid_email", "loginAttemptId": LoginAttemptId::default().as_ref().expose_secret() }),
+        serde_json::json!({ "email": get_random_email(), "loginAttemptId": "invalid_id" }),
+    ];
+
+    for body in test_cases {
+        assert_eq!(app.post_resend_2fa(&body).await.status().as_u16(), 400, "{}", body);
+    }
+}
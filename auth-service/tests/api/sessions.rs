@@ -0,0 +1,338 @@
+use auth_service::{
+    domain::Email,
+    routes::SessionsResponse,
+    utils::{auth::TokenResponse, constants::JWT_COOKIE_NAME},
+    ErrorResponse,
+};
+use redis::Commands;
+use secrecy::Secret;
+use test_helpers::api_test;
+use crate::helpers::{configure_redis, get_random_email, TestApp};
+
+#[api_test]
+async fn should_return_200_and_list_sessions() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .http_client
+        .post(format!("{}/login", &app.address))
+        .header("User-Agent", "test-agent")
+        .json(&login_body)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.get_sessions().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let sessions = response
+        .json::<SessionsResponse>()
+        .await
+        .expect("Could not deserialize response body to SessionsResponse")
+        .sessions;
+
+    assert_eq!(sessions.len(), 2);
+
+    let current = sessions
+        .iter()
+        .find(|session| session.current)
+        .expect("No current session found");
+
+    assert_eq!(current.ip, "127.0.0.1");
+    assert_eq!(current.user_agent.as_deref(), Some("test-agent"));
+    assert!(current.created_at <= current.last_seen);
+    assert_eq!(sessions.iter().filter(|session| session.current).count(), 1);
+}
+
+#[api_test]
+async fn should_return_200_and_revoke_other_session() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    // A session on another device
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let other_token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let sessions = app
+        .get_sessions()
+        .await
+        .json::<SessionsResponse>()
+        .await
+        .expect("Could not deserialize response body to SessionsResponse")
+        .sessions;
+
+    let other_session = sessions
+        .iter()
+        .find(|session| !session.current)
+        .expect("No other session found");
+
+    let response = app.delete_session(&other_session.id).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": other_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let sessions = app
+        .get_sessions()
+        .await
+        .json::<SessionsResponse>()
+        .await
+        .expect("Could not deserialize response body to SessionsResponse")
+        .sessions;
+
+    assert_eq!(sessions.len(), 1);
+    assert!(sessions[0].current);
+}
+
+#[api_test]
+async fn should_return_200_and_remove_cookies_if_current_session_revoked() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": random_email,
+            "password": "password123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let sessions = app
+        .get_sessions()
+        .await
+        .json::<SessionsResponse>()
+        .await
+        .expect("Could not deserialize response body to SessionsResponse")
+        .sessions;
+
+    let response = app.delete_session(&sessions[0].id).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    assert!(auth_cookie.value().is_empty());
+}
+
+#[api_test]
+async fn should_return_404_if_session_not_found() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": random_email,
+            "password": "password123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.delete_session("unknown").await;
+
+    assert_eq!(response.status().as_u16(), 404);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Session not found".to_owned()
+    );
+}
+
+#[api_test]
+async fn should_return_401_if_jwt_cookie_missing() {
+    let response = app.get_sessions().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.delete_session("unknown").await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_prefer_bearer_token_over_jwt_cookie() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    // The first session is carried by the cookie
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    // The second one by a bearer token
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "tokenDelivery": "body"
+    });
+
+    let response = app
+        .http_client
+        .post(format!("{}/login", &app.address))
+        .header("User-Agent", "bearer-agent")
+        .json(&login_body)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = response
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse")
+        .access_token;
+
+    let response = app.get_sessions_with_bearer(&token).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let sessions = response
+        .json::<SessionsResponse>()
+        .await
+        .expect("Could not deserialize response body to SessionsResponse")
+        .sessions;
+
+    let current = sessions
+        .iter()
+        .find(|session| session.current)
+        .expect("No current session found");
+
+    assert_eq!(current.user_agent.as_deref(), Some("bearer-agent"));
+}
+
+#[api_test]
+async fn should_keep_user_sessions_while_session_is_used() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let email = Email::parse(Secret::new(random_email.clone())).unwrap();
+    let user_key = format!("user_sessions:{}", random_email);
+
+    // The set of the user's sessions is about to expire, while their session is still used
+    let mut redis = configure_redis();
+    let _: () = redis.expire(&user_key, 60).unwrap();
+
+    let mut session_store = app.session_store.write().await;
+    let session = session_store.get_sessions(&email).await.unwrap().remove(0);
+    session_store.touch_session(&session.id).await.unwrap();
+    drop(session_store);
+
+    let ttl: i64 = redis.ttl(&user_key).unwrap();
+    assert!(ttl > 60, "{}", ttl);
+
+    let response = app.get_sessions().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.json::<SessionsResponse>().await.unwrap().sessions.len(), 1);
+}
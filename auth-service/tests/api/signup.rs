@@ -16,17 +16,14 @@ async fn should_return_201_if_valid_input() {
 
     assert_eq!(response.status().as_u16(), 201);
 
-    let expected_response = SignupResponse {
-        message: "User created successfully!".to_owned(),
-    };
-
-    assert_eq!(
-        response
-            .json::<SignupResponse>()
-            .await
-            .expect("Could not deserialize response body to UserBody"),
-        expected_response
-    );
+    let response_body = response
+        .json::<SignupResponse>()
+        .await
+        .expect("Could not deserialize response body to SignupResponse");
+
+    assert_eq!(response_body.message, "User created successfully!");
+    // Users who turned on 2FA get their recovery codes right away
+    assert_eq!(response_body.recovery_codes.map(|codes| codes.len()), Some(10));
 }
 
 #[api_test]
@@ -0,0 +1,223 @@
+use auth_service::{
+    domain::{Email, LoginAttemptId, PhoneNumber, TwoFAMethod},
+    routes::{RecoveryCodesResponse, TwoFactorAuthResponse},
+    utils::constants::JWT_COOKIE_NAME,
+    ErrorResponse,
+};
+use secrecy::{ExposeSecret, Secret};
+use test_helpers::api_test;
+use wiremock::{
+    matchers::{body_partial_json, method, path},
+    Mock, ResponseTemplate,
+};
+use crate::helpers::{get_random_email, TestApp};
+
+const PHONE_NUMBER: &str = "+14155552671";
+
+async fn sign_up_and_log_in(app: &TestApp) -> String {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    assert_eq!(app.post_login(&login_body).await.status().as_u16(), 200);
+
+    random_email
+}
+
+async fn mount_sms_server(app: &TestApp, expected_texts: u64) {
+    Mock::given(path("/sms"))
+        .and(method("POST"))
+        .and(body_partial_json(serde_json::json!({ "to": PHONE_NUMBER })))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(expected_texts)
+        .mount(&app.sms_server)
+        .await;
+}
+
+// The code texted to confirm the phone number
+async fn verification_code(app: &TestApp, email: &str) -> String {
+    let (_, code) = app
+        .phone_verification_store
+        .read()
+        .await
+        .get_verification(&Email::parse(Secret::new(email.to_owned())).unwrap())
+        .await
+        .expect("No phone verification found");
+
+    code.as_ref().expose_secret().to_owned()
+}
+
+#[api_test]
+async fn should_enroll_phone_and_text_login_codes() {
+    let email = sign_up_and_log_in(&app).await;
+
+    // One text to confirm the number, one for the login
+    mount_sms_server(&app, 2).await;
+
+    // No code is emailed to users who chose SMS
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_sms_enroll(&serde_json::json!({ "phoneNumber": "+1 (415) 555-2671" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let code = verification_code(&app, &email).await;
+    let response = app.post_sms_confirm(&serde_json::json!({ "code": code })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let recovery_codes = response
+        .json::<RecoveryCodesResponse>()
+        .await
+        .expect("Could not deserialize response body to RecoveryCodesResponse")
+        .recovery_codes;
+    assert_eq!(recovery_codes.len(), 10);
+
+    let email_address = Email::parse(Secret::new(email.clone())).unwrap();
+    let user = app
+        .user_store
+        .read()
+        .await
+        .get_user(&email_address)
+        .await
+        .unwrap();
+    assert!(user.requires_2fa);
+    assert_eq!(user.two_fa_method, TwoFAMethod::Sms);
+    assert_eq!(user.phone_number, Some(PhoneNumber::parse(Secret::new(PHONE_NUMBER.to_owned())).unwrap()));
+
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let response_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+    assert_eq!(response_body.two_fa_method, TwoFAMethod::Sms);
+
+    let (_, code) = app
+        .two_fa_code_store
+        .read()
+        .await
+        .get_code(&LoginAttemptId::parse(Secret::new(response_body.login_attempt_id.clone())).unwrap())
+        .await
+        .unwrap();
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": response_body.login_attempt_id,
+            "2FACode": code.as_ref().expose_secret()
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.cookies().any(|cookie| cookie.name() == JWT_COOKIE_NAME));
+}
+
+#[api_test]
+async fn should_return_400_if_phone_number_is_invalid() {
+    sign_up_and_log_in(&app).await;
+    mount_sms_server(&app, 0).await;
+
+    for phone_number in ["", "4155552671", "+1415555267a"] {
+        let response = app
+            .post_sms_enroll(&serde_json::json!({ "phoneNumber": phone_number }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 400);
+        assert_eq!(
+            response.json::<ErrorResponse>().await.unwrap().error,
+            "Invalid phone number"
+        );
+    }
+}
+
+#[api_test]
+async fn should_return_400_if_confirmation_code_is_wrong() {
+    let email = sign_up_and_log_in(&app).await;
+    mount_sms_server(&app, 1).await;
+
+    let response = app
+        .post_sms_enroll(&serde_json::json!({ "phoneNumber": PHONE_NUMBER }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let code = verification_code(&app, &email).await;
+    let wrong_code = if code == "000000" { "111111" } else { "000000" };
+
+    for code in [wrong_code, "12345"] {
+        let response = app.post_sms_confirm(&serde_json::json!({ "code": code })).await;
+
+        assert_eq!(response.status().as_u16(), 400);
+        assert_eq!(
+            response.json::<ErrorResponse>().await.unwrap().error,
+            "Invalid verification code"
+        );
+    }
+
+    let user = app
+        .user_store
+        .read()
+        .await
+        .get_user(&Email::parse(Secret::new(email)).unwrap())
+        .await
+        .unwrap();
+    assert!(!user.requires_2fa);
+    assert_eq!(user.two_fa_method, TwoFAMethod::Email);
+    assert_eq!(user.phone_number, None);
+}
+
+#[api_test]
+async fn should_return_404_if_confirming_without_enrollment() {
+    sign_up_and_log_in(&app).await;
+
+    let response = app.post_sms_confirm(&serde_json::json!({ "code": "123456" })).await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[api_test]
+async fn should_return_500_if_sms_provider_fails() {
+    sign_up_and_log_in(&app).await;
+
+    Mock::given(path("/sms"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(1)
+        .mount(&app.sms_server)
+        .await;
+
+    let response = app
+        .post_sms_enroll(&serde_json::json!({ "phoneNumber": PHONE_NUMBER }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 500);
+}
+
+#[api_test]
+async fn should_return_401_if_not_logged_in() {
+    let response = app
+        .post_sms_enroll(&serde_json::json!({ "phoneNumber": PHONE_NUMBER }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
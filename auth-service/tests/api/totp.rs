@@ -0,0 +1,301 @@
+use auth_service::{
+    domain::{Email, TwoFAMethod},
+    routes::{RecoveryCodesResponse, TotpEnrollmentResponse, TwoFactorAuthResponse},
+    utils::constants::JWT_COOKIE_NAME,
+    ErrorResponse,
+};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use secrecy::Secret;
+use sha1::Sha1;
+use test_helpers::api_test;
+use wiremock::{matchers::path, Mock, ResponseTemplate};
+use crate::helpers::{get_random_email, TestApp};
+
+// A stand-in for the user's authenticator app
+struct Authenticator {
+    secret: Vec<u8>,
+}
+
+impl Authenticator {
+    fn new(enrollment: &TotpEnrollmentResponse) -> Self {
+        let secret = base32::decode(base32::Alphabet::RFC4648 { padding: false }, &enrollment.secret)
+            .expect("Secret is not base32 encoded");
+        Self { secret }
+    }
+
+    fn current_step() -> u64 {
+        Utc::now().timestamp() as u64 / 30
+    }
+
+    // The code shown during a time step
+    fn code(&self, step: u64) -> String {
+        let mut mac = Hmac::<Sha1>::new_from_slice(&self.secret).unwrap();
+        mac.update(&step.to_be_bytes());
+        let hash = mac.finalize().into_bytes();
+
+        let offset = (hash[19] & 0x0f) as usize;
+        let binary = u32::from_be_bytes(hash[offset..offset + 4].try_into().unwrap()) & 0x7fff_ffff;
+        format!("{:06}", binary % 1_000_000)
+    }
+}
+
+async fn sign_up_and_log_in(app: &TestApp) -> String {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+    assert_eq!(log_in(app, &random_email).await.status().as_u16(), 200);
+
+    random_email
+}
+
+async fn log_in(app: &TestApp, email: &str) -> reqwest::Response {
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+    });
+
+    app.post_login(&login_body).await
+}
+
+async fn enroll(app: &TestApp) -> TotpEnrollmentResponse {
+    let response = app.post_totp_enroll().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<TotpEnrollmentResponse>()
+        .await
+        .expect("Could not deserialize response body to TotpEnrollmentResponse")
+}
+
+// Enroll an authenticator app and confirm it with the current code. Returns the time step
+// of that code.
+async fn enable_totp(app: &TestApp) -> (Authenticator, u64) {
+    let authenticator = Authenticator::new(&enroll(app).await);
+    let step = Authenticator::current_step();
+
+    let response = app
+        .post_totp_confirm(&serde_json::json!({ "code": authenticator.code(step) }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    (authenticator, step)
+}
+
+async fn verify_2fa(app: &TestApp, email: &str, code: &str) -> reqwest::Response {
+    let response = log_in(app, email).await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let response_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+
+    assert_eq!(response_body.two_fa_method, TwoFAMethod::Totp);
+
+    app.post_verify_2fa(&serde_json::json!({
+        "email": email,
+        "loginAttemptId": response_body.login_attempt_id,
+        "2FACode": code
+    }))
+    .await
+}
+
+#[api_test]
+async fn should_enroll_authenticator_app_and_require_its_codes() {
+    let email = sign_up_and_log_in(&app).await;
+
+    let enrollment = enroll(&app).await;
+    assert!(enrollment.otpauth_uri.starts_with("otpauth://totp/"));
+    assert!(enrollment.otpauth_uri.contains(&format!("secret={}", enrollment.secret)));
+
+    let authenticator = Authenticator::new(&enrollment);
+    let step = Authenticator::current_step();
+    let response = app
+        .post_totp_confirm(&serde_json::json!({ "code": authenticator.code(step) }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let email_address = Email::parse(Secret::new(email.clone())).unwrap();
+    let stored = app.totp_store.read().await.get_enrollment(&email_address).await.unwrap();
+    assert!(stored.confirmed);
+    assert_eq!(stored.last_used_step, Some(step));
+
+    let user = app
+        .user_store
+        .read()
+        .await
+        .get_user(&email_address)
+        .await
+        .unwrap();
+    assert!(user.requires_2fa);
+    assert_eq!(user.two_fa_method, TwoFAMethod::Totp);
+
+    // No code is emailed to users with an authenticator app
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // The current code was used up by the confirmation, the next one is accepted for drift
+    let response = verify_2fa(&app, &email, &authenticator.code(step + 1)).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.cookies().any(|cookie| cookie.name() == JWT_COOKIE_NAME));
+}
+
+#[api_test]
+async fn should_accept_recovery_code_instead_of_totp_code() {
+    let email = sign_up_and_log_in(&app).await;
+    let authenticator = Authenticator::new(&enroll(&app).await);
+
+    let response = app
+        .post_totp_confirm(&serde_json::json!({ "code": authenticator.code(Authenticator::current_step()) }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let recovery_codes = response
+        .json::<RecoveryCodesResponse>()
+        .await
+        .expect("Could not deserialize response body to RecoveryCodesResponse")
+        .recovery_codes;
+
+    assert_eq!(recovery_codes.len(), 10);
+
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = verify_2fa(&app, &email, &recovery_codes[0]).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_return_401_if_totp_code_is_reused() {
+    let email = sign_up_and_log_in(&app).await;
+    let (authenticator, step) = enable_totp(&app).await;
+
+    let response = verify_2fa(&app, &email, &authenticator.code(step)).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = verify_2fa(&app, &email, &authenticator.code(step + 1)).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = verify_2fa(&app, &email, &authenticator.code(step + 1)).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Codes of earlier steps can't be used after a later one either
+    let response = verify_2fa(&app, &email, &authenticator.code(step - 1)).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_return_401_if_totp_code_is_outside_allowed_drift() {
+    let email = sign_up_and_log_in(&app).await;
+    let (authenticator, step) = enable_totp(&app).await;
+
+    let response = verify_2fa(&app, &email, &authenticator.code(step + 3)).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_return_400_if_confirmation_code_is_wrong() {
+    let email = sign_up_and_log_in(&app).await;
+    let authenticator = Authenticator::new(&enroll(&app).await);
+
+    for code in [authenticator.code(Authenticator::current_step() + 5), "12345".to_owned()] {
+        let response = app
+            .post_totp_confirm(&serde_json::json!({ "code": code }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 400);
+        assert_eq!(
+            response.json::<ErrorResponse>().await.unwrap().error,
+            "Invalid TOTP code"
+        );
+    }
+
+    let user = app
+        .user_store
+        .read()
+        .await
+        .get_user(&Email::parse(Secret::new(email)).unwrap())
+        .await
+        .unwrap();
+    assert!(!user.requires_2fa);
+    assert_eq!(user.two_fa_method, TwoFAMethod::Email);
+}
+
+#[api_test]
+async fn should_return_404_if_confirming_without_enrollment() {
+    sign_up_and_log_in(&app).await;
+
+    let response = app
+        .post_totp_confirm(&serde_json::json!({ "code": "123456" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[api_test]
+async fn should_return_409_if_totp_is_already_enabled() {
+    sign_up_and_log_in(&app).await;
+    let (authenticator, step) = enable_totp(&app).await;
+
+    let response = app.post_totp_enroll().await;
+    assert_eq!(response.status().as_u16(), 409);
+
+    let response = app
+        .post_totp_confirm(&serde_json::json!({ "code": authenticator.code(step + 1) }))
+        .await;
+    assert_eq!(response.status().as_u16(), 409);
+}
+
+#[api_test]
+async fn should_return_400_if_code_is_resent() {
+    let email = sign_up_and_log_in(&app).await;
+    enable_totp(&app).await;
+
+    let login_attempt_id = log_in(&app, &email)
+        .await
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+
+    let response = app
+        .post_resend_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(
+        response.json::<ErrorResponse>().await.unwrap().error,
+        "Codes come from the authenticator app"
+    );
+}
+
+#[api_test]
+async fn should_return_401_if_not_logged_in() {
+    let response = app.post_totp_enroll().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
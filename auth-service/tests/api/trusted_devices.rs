@@ -0,0 +1,204 @@
+use auth_service::{
+    domain::{Email, LoginAttemptId, TrustedDevice},
+    routes::{TrustedDevicesResponse, TwoFactorAuthResponse},
+    utils::constants::{JWT_COOKIE_NAME, TRUSTED_DEVICE_COOKIE_NAME},
+};
+use reqwest::Url;
+use secrecy::{ExposeSecret, Secret};
+use test_helpers::api_test;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+use crate::helpers::{get_random_email, TestApp};
+
+async fn sign_up_with_2fa(app: &TestApp) -> String {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": true
+    });
+
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    random_email
+}
+
+async fn mount_email_server(app: &TestApp, expected_emails: u64) {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(expected_emails)
+        .mount(&app.email_server)
+        .await;
+}
+
+async fn post_login(app: &TestApp, email: &str, password: &str) -> reqwest::Response {
+    app.post_login(&serde_json::json!({
+        "email": email,
+        "password": password
+    }))
+    .await
+}
+
+// Logs in with the emailed code, optionally trusting the browser
+async fn log_in_with_2fa(app: &TestApp, email: &str, remember_device: bool) -> reqwest::Response {
+    let response = post_login(app, email, "password123").await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let response_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+
+    let (_, code) = app
+        .two_fa_code_store
+        .read()
+        .await
+        .get_code(&LoginAttemptId::parse(Secret::new(response_body.login_attempt_id.clone())).unwrap())
+        .await
+        .unwrap();
+
+    app.post_verify_2fa(&serde_json::json!({
+        "email": email,
+        "loginAttemptId": response_body.login_attempt_id,
+        "2FACode": code.as_ref().expose_secret(),
+        "rememberDevice": remember_device
+    }))
+    .await
+}
+
+async fn get_trusted_devices(app: &TestApp) -> TrustedDevicesResponse {
+    let response = app.get_trusted_devices().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<TrustedDevicesResponse>()
+        .await
+        .expect("Could not deserialize response body to TrustedDevicesResponse")
+}
+
+#[api_test]
+async fn should_skip_2fa_on_trusted_device() {
+    let email = sign_up_with_2fa(&app).await;
+    // Only the first login needs a code
+    mount_email_server(&app, 1).await;
+
+    let response = log_in_with_2fa(&app, &email, true).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.cookies().any(|cookie| cookie.name() == TRUSTED_DEVICE_COOKIE_NAME));
+
+    let response = post_login(&app, &email, "password123").await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.cookies().any(|cookie| cookie.name() == JWT_COOKIE_NAME));
+}
+
+#[api_test]
+async fn should_require_2fa_if_device_was_not_remembered() {
+    let email = sign_up_with_2fa(&app).await;
+    mount_email_server(&app, 2).await;
+
+    let response = log_in_with_2fa(&app, &email, false).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.cookies().all(|cookie| cookie.name() != TRUSTED_DEVICE_COOKIE_NAME));
+
+    assert_eq!(post_login(&app, &email, "password123").await.status().as_u16(), 206);
+}
+
+#[api_test]
+async fn should_list_and_revoke_trusted_devices() {
+    let email = sign_up_with_2fa(&app).await;
+    mount_email_server(&app, 2).await;
+
+    assert_eq!(log_in_with_2fa(&app, &email, true).await.status().as_u16(), 200);
+
+    let devices = get_trusted_devices(&app).await.devices;
+
+    assert_eq!(devices.len(), 1);
+    assert!(devices[0].current);
+
+    let response = app.delete_trusted_device(&devices[0].id).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(get_trusted_devices(&app).await.devices.is_empty());
+
+    assert_eq!(post_login(&app, &email, "password123").await.status().as_u16(), 206);
+}
+
+#[api_test]
+async fn should_forget_trusted_devices_when_password_changes() {
+    let email = sign_up_with_2fa(&app).await;
+    mount_email_server(&app, 2).await;
+
+    assert_eq!(log_in_with_2fa(&app, &email, true).await.status().as_u16(), 200);
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "currentPassword": "password123",
+            "newPassword": "newpassword123"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(get_trusted_devices(&app).await.devices.is_empty());
+
+    assert_eq!(post_login(&app, &email, "newpassword123").await.status().as_u16(), 206);
+}
+
+#[api_test]
+async fn should_only_trust_device_for_the_user_who_trusted_it() {
+    let email = sign_up_with_2fa(&app).await;
+    let other_email = sign_up_with_2fa(&app).await;
+    mount_email_server(&app, 2).await;
+
+    assert_eq!(log_in_with_2fa(&app, &email, true).await.status().as_u16(), 200);
+
+    assert_eq!(post_login(&app, &other_email, "password123").await.status().as_u16(), 206);
+}
+
+#[api_test]
+async fn should_require_2fa_if_cookie_is_forged() {
+    let email = sign_up_with_2fa(&app).await;
+    mount_email_server(&app, 1).await;
+
+    let device = TrustedDevice::new(
+        Email::parse(Secret::new(email.clone())).unwrap(),
+        "127.0.0.1".to_owned(),
+        None,
+        60,
+    );
+    let id = device.id.clone();
+
+    app.trusted_device_store
+        .write()
+        .await
+        .add_device(device)
+        .await
+        .unwrap();
+
+    app.cookie_jar.add_cookie_str(
+        &format!("{}={}.{}", TRUSTED_DEVICE_COOKIE_NAME, id, "00".repeat(32)),
+        &Url::parse(&app.address).unwrap(),
+    );
+
+    assert_eq!(post_login(&app, &email, "password123").await.status().as_u16(), 206);
+}
+
+#[api_test]
+async fn should_return_404_if_device_does_not_exist() {
+    let email = sign_up_with_2fa(&app).await;
+    mount_email_server(&app, 1).await;
+
+    assert_eq!(log_in_with_2fa(&app, &email, false).await.status().as_u16(), 200);
+
+    let response = app.delete_trusted_device("unknown").await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
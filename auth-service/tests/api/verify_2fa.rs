@@ -1,7 +1,7 @@
 use auth_service::{
-    domain::{Email, LoginAttemptId, TwoFACode},
+    domain::{LoginAttemptId, TwoFACode},
     routes::TwoFactorAuthResponse,
-    utils::constants::JWT_COOKIE_NAME,
+    utils::constants::{JWT_COOKIE_NAME, TWO_FA_MAX_ATTEMPTS, TWO_FA_MAX_PENDING_ATTEMPTS},
     ErrorResponse,
 };
 use secrecy::{ExposeSecret, Secret};
@@ -56,7 +56,7 @@ async fn should_return_200_if_correct_code() {
         .two_fa_code_store
         .read()
         .await
-        .get_code(&Email::parse(Secret::new(random_email.clone())).unwrap())
+        .get_code(&LoginAttemptId::parse(Secret::new(login_attempt_id.clone())).unwrap())
         .await
         .unwrap();
 
@@ -178,7 +178,7 @@ async fn should_return_401_if_incorrect_credentials() {
         .two_fa_code_store
         .read()
         .await
-        .get_code(&Email::parse(Secret::new(random_email.clone())).unwrap())
+        .get_code(&LoginAttemptId::parse(Secret::new(login_attempt_id.clone())).unwrap())
         .await
         .unwrap();
 
@@ -237,7 +237,7 @@ async fn should_return_401_if_incorrect_credentials() {
 }
 
 #[api_test]
-async fn should_return_401_if_old_code() {
+async fn should_invalidate_code_after_too_many_failed_attempts() {
     let random_email = get_random_email();
 
     let signup_body = serde_json::json!({
@@ -253,12 +253,10 @@ async fn should_return_401_if_old_code() {
     Mock::given(path("/email"))
         .and(method("POST"))
         .respond_with(ResponseTemplate::new(200))
-        .expect(2)
+        .expect(1)
         .mount(&app.email_server)
         .await;
 
-    // First login call
-
     let login_body = serde_json::json!({
         "email": random_email,
         "password": "password123"
@@ -268,43 +266,187 @@ async fn should_return_401_if_old_code() {
 
     assert_eq!(response.status().as_u16(), 206);
 
-    let response_body = response
+    let login_attempt_id = response
         .json::<TwoFactorAuthResponse>()
         .await
-        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
 
-    assert_eq!(response_body.message, "2FA required".to_owned());
-    assert!(!response_body.login_attempt_id.is_empty());
+    let (_, code) = app
+        .two_fa_code_store
+        .read()
+        .await
+        .get_code(&LoginAttemptId::parse(Secret::new(login_attempt_id.clone())).unwrap())
+        .await
+        .unwrap();
 
-    let login_attempt_id = response_body.login_attempt_id;
+    let code = code.as_ref().expose_secret().to_owned();
+    let wrong_code = if code == "000000" { "111111" } else { "000000" };
 
-    let code_tuple = app
+    let mut test_cases = vec![(wrong_code, "Invalid credentials"); *TWO_FA_MAX_ATTEMPTS as usize - 1];
+    test_cases.push((wrong_code, "Too many failed attempts, log in again"));
+    // The right code doesn't help anymore
+    test_cases.push((code.as_str(), "Invalid credentials"));
+
+    for (code, error) in test_cases {
+        let request_body = serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code
+        });
+
+        let response = app.post_verify_2fa(&request_body).await;
+
+        assert_eq!(response.status().as_u16(), 401);
+        assert_eq!(
+            response
+                .json::<ErrorResponse>()
+                .await
+                .expect("Could not deserialize response body to ErrorResponse")
+                .error,
+            error
+        );
+    }
+}
+
+// Logs in and returns the login attempt id with its code
+async fn start_login(app: &TestApp, email: &str) -> (String, String) {
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+
+    let (_, code) = app
         .two_fa_code_store
         .read()
         .await
-        .get_code(&Email::parse(Secret::new(random_email.clone())).unwrap())
+        .get_code(&LoginAttemptId::parse(Secret::new(login_attempt_id.clone())).unwrap())
         .await
         .unwrap();
 
-    let code = code_tuple.1.as_ref();
+    (login_attempt_id, code.as_ref().expose_secret().to_owned())
+}
 
-    // Second login call
+#[api_test]
+async fn should_keep_concurrent_login_attempts_independent() {
+    let random_email = get_random_email();
 
-    let response = app.post_login(&login_body).await;
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": true
+    });
 
-    assert_eq!(response.status().as_u16(), 206);
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
 
-    // 2FA attempt with old login_attempt_id and code
+    // Say from a laptop, then from a phone
+    let (laptop_attempt_id, laptop_code) = start_login(&app, &random_email).await;
+    let (phone_attempt_id, phone_code) = start_login(&app, &random_email).await;
 
+    // Codes only work for their own attempt
+    if laptop_code != phone_code {
+        let request_body = serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": laptop_attempt_id,
+            "2FACode": phone_code
+        });
+
+        assert_eq!(app.post_verify_2fa(&request_body).await.status().as_u16(), 401);
+    }
+
+    for (login_attempt_id, code) in [(laptop_attempt_id, laptop_code), (phone_attempt_id, phone_code)] {
+        let request_body = serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code
+        });
+
+        assert_eq!(app.post_verify_2fa(&request_body).await.status().as_u16(), 200);
+    }
+}
+
+#[api_test]
+async fn should_return_401_if_too_many_newer_login_attempts() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": true
+    });
+
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1 + *TWO_FA_MAX_PENDING_ATTEMPTS as u64)
+        .mount(&app.email_server)
+        .await;
+
+    let (login_attempt_id, code) = start_login(&app, &random_email).await;
+
+    for _ in 0..*TWO_FA_MAX_PENDING_ATTEMPTS {
+        start_login(&app, &random_email).await;
+    }
+
+    // The oldest attempt was dropped to make room for the newest
     let request_body = serde_json::json!({
         "email": random_email,
         "loginAttemptId": login_attempt_id,
-        "2FACode": code.expose_secret()
+        "2FACode": code
     });
 
-    let response = app.post_verify_2fa(&request_body).await;
+    assert_eq!(app.post_verify_2fa(&request_body).await.status().as_u16(), 401);
+}
 
-    assert_eq!(response.status().as_u16(), 401);
+#[api_test]
+async fn should_return_401_if_login_attempt_belongs_to_another_user() {
+    let (email, other_email) = (get_random_email(), get_random_email());
+
+    for email in [&email, &other_email] {
+        let signup_body = serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": true
+        });
+
+        assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+    }
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let (login_attempt_id, code) = start_login(&app, &email).await;
+
+    let request_body = serde_json::json!({
+        "email": other_email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": code
+    });
+
+    assert_eq!(app.post_verify_2fa(&request_body).await.status().as_u16(), 401);
 }
 
 #[api_test]
@@ -351,7 +493,7 @@ async fn should_return_401_if_same_code_twice() {
         .two_fa_code_store
         .read()
         .await
-        .get_code(&Email::parse(Secret::new(random_email.clone())).unwrap())
+        .get_code(&LoginAttemptId::parse(Secret::new(login_attempt_id.clone())).unwrap())
         .await
         .unwrap();
 
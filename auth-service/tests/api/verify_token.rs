@@ -1,7 +1,10 @@
 use auth_service::{
-    utils::constants::JWT_COOKIE_NAME,
+    domain::ClientCredentials,
+    routes::IntrospectionResponse,
+    utils::{auth::TokenResponse, constants::JWT_COOKIE_NAME},
     ErrorResponse
 };
+use secrecy::Secret;
 use crate::helpers::{get_random_email, TestApp};
 use test_helpers::api_test;
 
@@ -132,11 +135,233 @@ async fn should_return_422_if_malformed_input() {
         serde_json::json!({
             "token": true,
         }),
-        serde_json::json!({}),
+        serde_json::json!({
+            "token": "token",
+            "introspect": "yes",
+        }),
     ];
 
     for test_case in test_cases {
         let response = app.post_verify_token(&test_case).await;
         assert_eq!(response.status().as_u16(), 422);
     }
-}
\ No newline at end of file
+}
+
+#[api_test]
+async fn should_return_400_if_token_missing() {
+    let response = app.post_verify_token(&serde_json::json!({})).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Missing auth token".to_owned()
+    );
+}
+
+#[api_test]
+async fn should_return_200_if_valid_bearer_token() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "tokenDelivery": "body"
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = response
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse")
+        .access_token;
+
+    let response = app
+        .post_verify_token_with_bearer(&serde_json::json!({}), &token)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    // A token in the body is what gets verified, whatever the header says
+    let verify_token_body = serde_json::json!({
+        "token": "invalid_token",
+    });
+
+    let response = app
+        .post_verify_token_with_bearer(&verify_token_body, &token)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+#[api_test]
+async fn should_return_introspection_response_if_active_token() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    let response = app
+        .post_verify_token(&serde_json::json!({
+            "token": token,
+            "introspect": true,
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = response
+        .json::<IntrospectionResponse>()
+        .await
+        .expect("Could not deserialize response body to IntrospectionResponse");
+
+    assert!(body.active);
+    assert_eq!(body.sub, Some(random_email));
+    assert!(body.iat.expect("No iat") < body.exp.expect("No exp"));
+    assert_eq!(body.scope, None);
+    assert_eq!(body.roles, Some(vec![]));
+}
+
+#[api_test]
+async fn should_return_inactive_introspection_response_if_invalid_token() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": random_email,
+            "password": "password123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let banned_token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    let response = app.post_logout().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    for token in ["", "invalid_token", &banned_token] {
+        let response = app
+            .post_verify_token(&serde_json::json!({
+                "token": token,
+                "introspect": true,
+            }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 200);
+
+        // Every inactive token gets the exact same answer
+        assert_eq!(
+            response
+                .json::<serde_json::Value>()
+                .await
+                .expect("Could not deserialize response body"),
+            serde_json::json!({ "active": false })
+        );
+    }
+}
+
+#[tokio::test]
+async fn should_require_client_credentials_if_configured() {
+    let client = ClientCredentials::new("app-service".to_owned(), Secret::new("secret".to_owned()));
+    let mut app = TestApp::new_with(|app_state| app_state.with_introspection_client(client)).await;
+
+    let verify_token_body = serde_json::json!({
+        "token": "invalid_token",
+        "introspect": true,
+    });
+
+    let test_cases = [None, Some("wrong_secret")];
+
+    for secret in test_cases {
+        let mut request = app
+            .http_client
+            .post(format!("{}/verify-token", &app.address))
+            .json(&verify_token_body);
+
+        if let Some(secret) = secret {
+            request = request.basic_auth("app-service", Some(secret));
+        }
+
+        let response = request.send().await.expect("Failed to execute request.");
+
+        assert_eq!(response.status().as_u16(), 401);
+
+        assert_eq!(
+            response
+                .json::<ErrorResponse>()
+                .await
+                .expect("Could not deserialize response body to ErrorResponse")
+                .error,
+            "Invalid client credentials".to_owned()
+        );
+    }
+
+    let response = app
+        .http_client
+        .post(format!("{}/verify-token", &app.address))
+        .basic_auth("app-service", Some("secret"))
+        .json(&verify_token_body)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}